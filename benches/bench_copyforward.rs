@@ -1,5 +1,5 @@
 use copyforward::{Config, CopyForward, approximate, exact};
-use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 
@@ -112,5 +112,46 @@ fn bench_algorithms(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_algorithms);
+/// A post of `words` random lowercase words, sharing little text with others.
+fn make_random_post(rng: &mut impl Rng, words: usize) -> String {
+    let mut s = String::new();
+    for _ in 0..words {
+        let len = rng.gen_range(3..9);
+        s.extend((0..len).map(|_| rng.gen_range(b'a'..=b'z') as char));
+        s.push(' ');
+    }
+    s
+}
+
+fn bench_push_message(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push_message");
+
+    // The same reply pushed onto threads of growing length should cost about
+    // the same: the index of the thread is kept from the build. The first
+    // push grows the k-mer table, sized for the build, once; it is left out.
+    let mut rng = ChaCha8Rng::seed_from_u64(42);
+    let msgs: Vec<String> = (0..1000).map(|_| make_random_post(&mut rng, 50)).collect();
+    let reply = format!("> {}\nAgreed, ship it.", msgs[0]);
+    for &history in [10usize, 100, 1000].iter() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("exact_history{}", history)),
+            &msgs[..history],
+            |b, m| {
+                b.iter_batched_ref(
+                    || {
+                        let mut cf = exact(m, Config::default());
+                        cf.push_message("First reply.");
+                        cf
+                    },
+                    |cf| cf.push_message(&reply).len(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_algorithms, bench_push_message);
 criterion_main!(benches);
@@ -0,0 +1,224 @@
+"""Type stubs for the copyforward extension module."""
+
+from collections.abc import Callable, Iterable, Iterator, Sequence
+from typing import Any, Literal, TypedDict, Unpack, overload
+
+import numpy as np
+import numpy.typing as npt
+
+class CopyForwardError(ValueError):
+    """Base class for errors raised by copyforward."""
+
+class ConfigError(CopyForwardError):
+    """Invalid algorithm configuration or tokenizer."""
+
+class Config:
+    """Validated algorithm settings, mirroring the Rust `Config`."""
+
+    @property
+    def min_match_len(self) -> int: ...
+    @property
+    def lookback(self) -> int | None: ...
+    @property
+    def cap_len(self) -> int: ...
+    @property
+    def ncap(self) -> int: ...
+    @property
+    def stop_phrases(self) -> list[str]: ...
+    @property
+    def min_indexed_len(self) -> int: ...
+    @property
+    def kmer_density(self) -> float: ...
+    @property
+    def skip_referenced_regions(self) -> bool: ...
+    def __init__(
+        self,
+        *,
+        min_match_len: int = 4,
+        lookback: int | None = None,
+        cap_len: int = 64,
+        ncap: int = 64,
+        stop_phrases: Sequence[str] | None = None,
+        min_indexed_len: int = 0,
+        kmer_density: float = 0.5,
+        skip_referenced_regions: bool = False,
+    ) -> None: ...
+
+ConfigSettings = TypedDict(
+    "ConfigSettings",
+    {
+        "min_match_len": int,
+        "lookback": int | None,
+        "cap_len": int,
+        "ncap": int,
+        "stop_phrases": Sequence[str] | None,
+        "min_indexed_len": int,
+        "kmer_density": float,
+        "skip_referenced_regions": bool,
+    },
+    total=False,
+)
+
+class PyLiteralSegment:
+    """Literal text that appears directly in a compressed message."""
+
+    @property
+    def text(self) -> str: ...
+    def __init__(self, text: str) -> None: ...
+
+class PyReferenceSegment:
+    """Reference to `len` bytes of message `message` starting at byte `start`.
+
+    `char_start` and `char_len` give the same span in characters, which index
+    Python strings; they are `None` when the source text is no longer kept.
+    """
+
+    @property
+    def message(self) -> int: ...
+    @property
+    def start(self) -> int: ...
+    @property
+    def len(self) -> int: ...
+    @property
+    def char_start(self) -> int | None: ...
+    @property
+    def char_len(self) -> int | None: ...
+    def __init__(self, message: int, start: int, len: int) -> None: ...
+
+class PyLiteralTokens:
+    """Literal token ids that appear directly in a compressed message."""
+
+    @property
+    def tokens(self) -> list[int]: ...
+    def __init__(self, tokens: Sequence[int]) -> None: ...
+    def as_numpy(self) -> npt.NDArray[np.uint32]: ...
+
+class PyReferenceTokens:
+    """Reference to `len` tokens of message `message` starting at token `start`."""
+
+    @property
+    def message(self) -> int: ...
+    @property
+    def start(self) -> int: ...
+    @property
+    def len(self) -> int: ...
+    def __init__(self, message: int, start: int, len: int) -> None: ...
+
+TextSegment = PyLiteralSegment | PyReferenceSegment
+TokenSegment = PyLiteralTokens | PyReferenceTokens
+
+class CopyForwardText:
+    """Copy-forward compression of text messages."""
+
+    @classmethod
+    def from_texts(
+        cls,
+        messages: Iterable[str | None],
+        *,
+        exact_mode: bool = True,
+        config: Config | None = None,
+        progress: Callable[[int, int], bool | None] | None = None,
+        progress_every: int = 1000,
+        **keywords: Unpack[ConfigSettings],
+    ) -> CopyForwardText: ...
+    def segments(self) -> list[list[TextSegment]]: ...
+    def render(self, replacement: str) -> list[str | None]: ...
+    def compression_ratio(self) -> float: ...
+    def metadata(self) -> dict[str, Any] | None: ...
+    def to_json(self) -> str: ...
+    @classmethod
+    def from_json(cls, data: str) -> CopyForwardText: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes) -> CopyForwardText: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, idx: int) -> list[TextSegment]: ...
+    def __iter__(self) -> Iterator[list[TextSegment]]: ...
+
+class CopyForwardStream:
+    """Incremental copy-forward over a stream, referencing the last `window` messages."""
+
+    def __init__(self, window: int, *, config: Config | None = None) -> None: ...
+    def add_message(self, message: str) -> list[TextSegment]: ...
+    def message(self, idx: int) -> str | None: ...
+    def __len__(self) -> int: ...
+    def to_json(self) -> str: ...
+    @classmethod
+    def from_json(cls, data: str) -> CopyForwardStream: ...
+
+class CopyForwardTokens:
+    """Copy-forward compression of token id sequences."""
+
+    @classmethod
+    def from_tokens(
+        cls,
+        messages: Iterable[Sequence[int] | npt.NDArray[np.uint32] | None],
+        *,
+        exact_mode: bool = True,
+        config: Config | None = None,
+        progress: Callable[[int, int], bool | None] | None = None,
+        progress_every: int = 1000,
+        **keywords: Unpack[ConfigSettings],
+    ) -> CopyForwardTokens: ...
+    @classmethod
+    def from_texts_with_tokenizer(
+        cls,
+        messages: Iterable[str | None],
+        tokenizer: str,
+        *,
+        exact_mode: bool = True,
+        config: Config | None = None,
+        progress: Callable[[int, int], bool | None] | None = None,
+        progress_every: int = 1000,
+        **keywords: Unpack[ConfigSettings],
+    ) -> CopyForwardTokens: ...
+    def segments(self) -> list[list[TokenSegment]]: ...
+    def metadata(self) -> dict[str, Any] | None: ...
+    def to_json(self) -> str: ...
+    @classmethod
+    def from_json(cls, data: str) -> CopyForwardTokens: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, idx: int) -> list[TokenSegment]: ...
+    def __iter__(self) -> Iterator[list[TokenSegment]]: ...
+    @overload
+    def render(
+        self,
+        replacement: Sequence[int] | npt.NDArray[np.uint32],
+        *,
+        as_numpy: Literal[False] = False,
+    ) -> list[list[int]]: ...
+    @overload
+    def render(
+        self,
+        replacement: Sequence[int] | npt.NDArray[np.uint32],
+        *,
+        as_numpy: Literal[True],
+    ) -> list[npt.NDArray[np.uint32]]: ...
+    def render_texts(self, replacement: str) -> list[str]: ...
+
+CorpusStats = TypedDict(
+    "CorpusStats",
+    {
+        "messages": int,
+        "original_bytes": int,
+        "literal_bytes": int,
+        "references": int,
+        "compression_ratio": float,
+    },
+)
+
+def compress_corpus(
+    threads: Iterable[Iterable[str | None]] | Any,
+    *,
+    exact_mode: bool = True,
+    config: Config | None = None,
+    **keywords: Unpack[ConfigSettings],
+) -> tuple[list[CopyForwardText], CorpusStats]: ...
+
+def minhash_signatures(
+    messages: Iterable[str | None],
+    *,
+    num_perm: int = 128,
+    seed: int = 1,
+    shingle_size: int | None = None,
+) -> list[list[int]]: ...
@@ -0,0 +1,332 @@
+//! Random-access archives of a compressed thread.
+//!
+//! [`crate::codec`] encodes a whole segmentation as one stream, so reading
+//! any message means decoding all of them. An archive stores the same
+//! per-message entries followed by an index of their sizes, so
+//! [`Archive::message`] decodes only the message asked for and the messages
+//! its references copy from, transitively, and verifies each against its
+//! checksum.
+//!
+//! Because the index follows the entries, [`Archive::append`] and
+//! [`Archive::push_segments`] add messages without touching earlier entries:
+//! only the index and footer are rewritten, so a growing thread can be kept
+//! in one file that is truncated at [`Archive::entries_len`] and extended.
+//!
+//! Layout (varints are LEB128, as in the codec):
+//!
+//! ```text
+//! "CFAR" version:u8 metadata                               (as in the codec)
+//!   per message: checksum:u32le segment_count segments...   (as in the codec)
+//!   index: per message, entry size in bytes (varint)
+//!   footer: index_start:u64le message_count:u64le
+//! ```
+//!
+//! # Example
+//! ```
+//! use copyforward::archive::Archive;
+//! use copyforward::{exact, Config, CopyForward};
+//!
+//! let messages = ["Hello world", "Hello world today", "> Hello world today\nIndeed"];
+//! let archive = Archive::new(&exact(&messages, Config::default()).segments());
+//! let reopened = Archive::from_bytes(archive.into_bytes()).unwrap();
+//! assert_eq!(reopened.message(2).unwrap(), messages[2]);
+//! ```
+
+use crate::codec::{
+    DecodeError, Entry, Reader, checksum, decode_entry, lossy_texts, put_message, put_varint,
+};
+use crate::core::{CopyForward, Segment};
+use crate::metadata::{Metadata, put_metadata, read_metadata};
+#[cfg(feature = "exact")]
+use crate::{Config, MessageLike, exact};
+use std::collections::BTreeMap;
+
+const MAGIC: &[u8; 4] = b"CFAR";
+/// Current archive version written by [`Archive::new`].
+pub const VERSION: u8 = 1;
+
+const FOOTER_LEN: usize = 16;
+
+/// An encoded thread supporting per-message decoding; see the
+/// [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    bytes: Vec<u8>,
+    /// Start of each message entry, plus the start of the index; the first
+    /// is the end of the header.
+    offsets: Vec<usize>,
+}
+
+impl Archive {
+    /// Encode a segmentation as an archive, with checksums of the text the
+    /// segments reconstruct.
+    pub fn new(segments: &[Vec<Segment>]) -> Archive {
+        let texts = lossy_texts(segments);
+        Archive::encode(segments, None, |i| checksum(&texts[i]))
+    }
+
+    /// Encode the segments of `model` as an archive, with checksums of its
+    /// original messages and its [`Metadata`]; see
+    /// [`crate::codec::encode_model`].
+    pub fn from_model(model: &dyn CopyForward) -> Archive {
+        Archive::encode(&model.segments(), model.metadata().as_ref(), |i| {
+            checksum(model.message(i))
+        })
+    }
+
+    fn encode(
+        segments: &[Vec<Segment>],
+        metadata: Option<&Metadata>,
+        checksum_of: impl Fn(usize) -> u32,
+    ) -> Archive {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        put_metadata(&mut bytes, metadata);
+        let mut offsets = vec![bytes.len()];
+        for (i, segs) in segments.iter().enumerate() {
+            put_message(&mut bytes, i, checksum_of(i), segs);
+            offsets.push(bytes.len());
+        }
+        let mut archive = Archive { bytes, offsets };
+        archive.write_index();
+        archive
+    }
+
+    /// Open an archive written by [`Archive::new`], checking its header and
+    /// index. Message entries are only decoded when read.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Archive, DecodeError> {
+        if bytes.get(..MAGIC.len()) != Some(MAGIC.as_slice()) {
+            return Err(DecodeError::BadMagic);
+        }
+        let header_len = Archive::header(&bytes)?.0;
+        let footer = bytes
+            .len()
+            .checked_sub(FOOTER_LEN)
+            .filter(|&f| f >= header_len)
+            .ok_or(DecodeError::Truncated)?;
+        let word = |at: usize| {
+            let raw = u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+            usize::try_from(raw).map_err(|_| DecodeError::Overflow)
+        };
+        let (index_start, count) = (word(footer)?, word(footer + 8)?);
+        if !(header_len..=footer).contains(&index_start) {
+            return Err(DecodeError::InvalidIndex);
+        }
+        let mut reader = Reader::new(&bytes[index_start..footer]);
+        // Every index entry takes at least one byte, which bounds the allocation.
+        let mut offsets = Vec::with_capacity(count.min(footer - index_start) + 1);
+        offsets.push(header_len);
+        for _ in 0..count {
+            let size = reader.varint().map_err(|_| DecodeError::InvalidIndex)?;
+            let end = (offsets[offsets.len() - 1])
+                .checked_add(size)
+                .filter(|&end| end <= index_start)
+                .ok_or(DecodeError::InvalidIndex)?;
+            offsets.push(end);
+        }
+        if reader.pos != footer - index_start || offsets[count] != index_start {
+            return Err(DecodeError::InvalidIndex);
+        }
+        Ok(Archive { bytes, offsets })
+    }
+
+    /// Length of the header of `bytes` and the metadata it records.
+    fn header(bytes: &[u8]) -> Result<(usize, Option<Metadata>), DecodeError> {
+        let mut reader = Reader::new(bytes);
+        reader.bytes(MAGIC.len())?;
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let metadata = read_metadata(&mut reader)?;
+        Ok((reader.pos, metadata))
+    }
+
+    /// Crate version, algorithm and config recorded by
+    /// [`from_model`](Archive::from_model). Messages appended later are not
+    /// described by it.
+    pub fn metadata(&self) -> Option<Metadata> {
+        Archive::header(&self.bytes[..self.offsets[0]])
+            .expect("header was checked when the archive was opened")
+            .1
+    }
+
+    /// The encoded archive.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Consume the archive, returning its encoding.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Number of messages.
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Whether the archive holds no messages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decode the segments of message `idx` alone, without verifying them;
+    /// [`message`](Archive::message) checks them against their checksum.
+    ///
+    /// # Panics
+    /// If `idx >= self.len()`.
+    pub fn segments_for(&self, idx: usize) -> Result<Vec<Segment>, DecodeError> {
+        Ok(self.entry(idx)?.segments)
+    }
+
+    /// Decode the segments of every message, verifying every checksum.
+    pub fn segments(&self) -> Result<Vec<Vec<Segment>>, DecodeError> {
+        Ok(self
+            .decode_all()?
+            .into_iter()
+            .map(|(segs, _)| segs)
+            .collect())
+    }
+
+    /// Reconstruct the text of message `idx`, decoding only the messages on
+    /// its reference chains and verifying their checksums.
+    ///
+    /// # Panics
+    /// If `idx >= self.len()`.
+    pub fn message(&self, idx: usize) -> Result<String, DecodeError> {
+        let mut texts = self.chain_texts(vec![idx])?;
+        Ok(texts.remove(&idx).unwrap())
+    }
+
+    /// Reconstruct the text of every message, verifying every checksum.
+    pub fn messages(&self) -> Result<Vec<String>, DecodeError> {
+        Ok(self
+            .decode_all()?
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect())
+    }
+
+    /// Bytes of the header and message entries, which appending leaves
+    /// unchanged; everything after them is the index and footer.
+    pub fn entries_len(&self) -> usize {
+        self.offsets[self.len()]
+    }
+
+    /// Append a message given by its segments, returning its index.
+    ///
+    /// The messages it references are decoded and verified to compute its
+    /// checksum.
+    ///
+    /// # Panics
+    /// If a reference points at a later message.
+    pub fn push_segments(&mut self, segments: &[Segment]) -> Result<usize, DecodeError> {
+        let idx = self.len();
+        let sources: Vec<usize> = segments
+            .iter()
+            .filter_map(|seg| match seg {
+                Segment::Reference { message_idx, .. } => Some(*message_idx),
+                Segment::Literal(_) => None,
+            })
+            .collect();
+        assert!(
+            sources.iter().all(|&m| m <= idx),
+            "reference to a later message"
+        );
+        let texts = self.chain_texts(sources.into_iter().filter(|&m| m < idx).collect())?;
+        let entry = Entry {
+            checksum: None,
+            segments: segments.to_vec(),
+        };
+        let text = entry.text(idx, |i| texts[&i].as_str())?;
+        self.push_entry(segments, checksum(&text));
+        Ok(idx)
+    }
+
+    /// Segment `messages` with [`exact()`](crate::exact) against the
+    /// archived messages and each other, and append them.
+    ///
+    /// Archived messages are decoded to be matched against but their entries
+    /// are kept as they are.
+    #[cfg(feature = "exact")]
+    pub fn append<M: MessageLike>(
+        &mut self,
+        messages: &[M],
+        config: Config,
+    ) -> Result<(), DecodeError> {
+        let archived = self.messages()?;
+        let all: Vec<Option<&str>> = archived
+            .iter()
+            .map(|m| Some(m.as_str()))
+            .chain(messages.iter().map(MessageLike::as_message))
+            .collect();
+        let model = exact(&all, config);
+        for idx in archived.len()..all.len() {
+            self.push_entry(model.segments_for(idx), checksum(model.message(idx)));
+        }
+        Ok(())
+    }
+
+    /// Decode the entry of message `idx`.
+    fn entry(&self, idx: usize) -> Result<Entry, DecodeError> {
+        assert!(idx < self.len(), "message index out of range");
+        decode_entry(&self.bytes[self.offsets[idx]..self.offsets[idx + 1]], idx)
+    }
+
+    /// Segments and verified text of every message.
+    fn decode_all(&self) -> Result<Vec<(Vec<Segment>, String)>, DecodeError> {
+        let mut out: Vec<(Vec<Segment>, String)> = Vec::with_capacity(self.len());
+        for m in 0..self.len() {
+            let entry = self.entry(m)?;
+            let text = entry.text(m, |i| out[i].1.as_str())?;
+            out.push((entry.segments, text));
+        }
+        Ok(out)
+    }
+
+    /// Verified texts of `roots` and every message their references reach.
+    fn chain_texts(&self, mut pending: Vec<usize>) -> Result<BTreeMap<usize, String>, DecodeError> {
+        let mut decoded = BTreeMap::new();
+        while let Some(m) = pending.pop() {
+            if decoded.contains_key(&m) {
+                continue;
+            }
+            let entry = self.entry(m)?;
+            pending.extend(entry.segments.iter().filter_map(|seg| match seg {
+                Segment::Reference { message_idx, .. } => Some(*message_idx),
+                Segment::Literal(_) => None,
+            }));
+            decoded.insert(m, entry);
+        }
+        // Sources are earlier messages, so ascending order resolves them first.
+        let mut texts: BTreeMap<usize, String> = BTreeMap::new();
+        for (m, entry) in decoded {
+            let text = entry.text(m, |i| texts[&i].as_str())?;
+            texts.insert(m, text);
+        }
+        Ok(texts)
+    }
+
+    /// Append an entry after the last one and rewrite the index.
+    fn push_entry(&mut self, segments: &[Segment], checksum: u32) {
+        let idx = self.len();
+        self.bytes.truncate(self.entries_len());
+        put_message(&mut self.bytes, idx, checksum, segments);
+        self.offsets.push(self.bytes.len());
+        self.write_index();
+    }
+
+    /// Append the index of the entries in `offsets`, then the footer.
+    fn write_index(&mut self) {
+        let index_start = self.bytes.len();
+        for pair in self.offsets.windows(2) {
+            put_varint(&mut self.bytes, pair[1] - pair[0]);
+        }
+        self.bytes
+            .extend_from_slice(&(index_start as u64).to_le_bytes());
+        self.bytes
+            .extend_from_slice(&(self.len() as u64).to_le_bytes());
+    }
+}
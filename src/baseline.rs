@@ -0,0 +1,196 @@
+//! Comparison against general-purpose compressors (requires the `baseline`
+//! feature).
+//!
+//! [`compare`] compresses one corpus four ways and reports the size and time
+//! of each, so deployments can be justified with numbers and end-to-end
+//! regressions caught:
+//!
+//! * `copyforward`: the binary segment encoding of [`crate::codec`];
+//! * `copyforward+zstd`: that encoding compressed with zstd;
+//! * `zstd` and `gzip`: the raw message text compressed directly.
+//!
+//! Every method sees the whole corpus as one stream. Raw text is framed with
+//! the same length prefixes the codec uses, so no method is charged for
+//! message boundaries the others get for free.
+//!
+//! # Example
+//! ```
+//! use copyforward::Thread;
+//! use copyforward::baseline::{compare, BaselineOptions};
+//!
+//! let threads = [Thread::new("t", vec!["Hello world".into(), "> Hello world\nHi!".into()])];
+//! let report = compare(&threads, &BaselineOptions::default()).unwrap();
+//! assert_eq!(report.original_bytes, 28);
+//! assert_eq!(report.results.len(), 4);
+//! println!("{report}");
+//! ```
+
+use crate::codec::{encode_segments, put_varint};
+use crate::core::{Config, CopyForward};
+use crate::corpus::Thread;
+use crate::{approximate, exact};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Settings for [`compare`].
+#[derive(Debug, Clone)]
+pub struct BaselineOptions {
+    /// Configuration for the copyforward methods.
+    pub config: Config,
+    /// Use [`approximate()`](crate::approximate()) instead of
+    /// [`exact()`](crate::exact()).
+    pub approximate: bool,
+    /// zstd compression level.
+    ///
+    /// **Default:** 3
+    pub zstd_level: i32,
+    /// gzip compression level, 0–9.
+    ///
+    /// **Default:** 6
+    pub gzip_level: u32,
+}
+
+impl Default for BaselineOptions {
+    fn default() -> Self {
+        BaselineOptions {
+            config: Config::default(),
+            approximate: false,
+            zstd_level: 3,
+            gzip_level: 6,
+        }
+    }
+}
+
+/// Size and time of one compression method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// Method name, such as `"copyforward+zstd"`.
+    pub method: &'static str,
+    /// Compressed size in bytes.
+    pub bytes: usize,
+    /// Wall-clock time to compress the whole corpus.
+    pub elapsed: Duration,
+}
+
+/// Result of [`compare`]; its `Display` form is a tab-separated table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// Number of threads compressed.
+    pub threads: usize,
+    /// Number of messages across all threads.
+    pub messages: usize,
+    /// Total bytes of message text.
+    pub original_bytes: usize,
+    /// One entry per method, copyforward methods first.
+    pub results: Vec<Measurement>,
+}
+
+impl Report {
+    /// The measurement for `method`, if it was run.
+    pub fn get(&self, method: &str) -> Option<&Measurement> {
+        self.results.iter().find(|m| m.method == method)
+    }
+
+    /// Compressed size of `measurement` relative to the original text.
+    ///
+    /// Returns 1.0 for empty input.
+    pub fn ratio(&self, measurement: &Measurement) -> f64 {
+        if self.original_bytes == 0 {
+            1.0
+        } else {
+            measurement.bytes as f64 / self.original_bytes as f64
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "method\tbytes\tratio\tseconds")?;
+        writeln!(f, "original\t{}\t1.000\t0.000", self.original_bytes)?;
+        for m in &self.results {
+            writeln!(
+                f,
+                "{}\t{}\t{:.3}\t{:.3}",
+                m.method,
+                m.bytes,
+                self.ratio(m),
+                m.elapsed.as_secs_f64()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compress `threads` with copyforward, copyforward followed by zstd, zstd
+/// and gzip, and measure each.
+pub fn compare(threads: &[Thread], options: &BaselineOptions) -> io::Result<Report> {
+    let started = Instant::now();
+    let mut encoded = Vec::new();
+    for thread in threads {
+        let segments = if options.approximate {
+            approximate(&thread.messages, options.config.clone()).segments()
+        } else {
+            exact(&thread.messages, options.config.clone()).segments()
+        };
+        encoded.extend(encode_segments(&segments));
+    }
+    let segmented = started.elapsed();
+    let (zstd_encoded, zstd_encoded_time) =
+        timed(|| zstd::encode_all(&encoded[..], options.zstd_level))?;
+
+    let mut raw = Vec::new();
+    let mut messages = 0;
+    let mut original_bytes = 0;
+    for thread in threads {
+        put_varint(&mut raw, thread.messages.len());
+        for message in &thread.messages {
+            put_varint(&mut raw, message.len());
+            raw.extend_from_slice(message.as_bytes());
+            messages += 1;
+            original_bytes += message.len();
+        }
+    }
+    let (zstd_raw, zstd_raw_time) = timed(|| zstd::encode_all(&raw[..], options.zstd_level))?;
+    let (gzip_raw, gzip_raw_time) = timed(|| {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(options.gzip_level));
+        encoder.write_all(&raw)?;
+        encoder.finish()
+    })?;
+
+    Ok(Report {
+        threads: threads.len(),
+        messages,
+        original_bytes,
+        results: vec![
+            Measurement {
+                method: "copyforward",
+                bytes: encoded.len(),
+                elapsed: segmented,
+            },
+            Measurement {
+                method: "copyforward+zstd",
+                bytes: zstd_encoded.len(),
+                elapsed: segmented + zstd_encoded_time,
+            },
+            Measurement {
+                method: "zstd",
+                bytes: zstd_raw.len(),
+                elapsed: zstd_raw_time,
+            },
+            Measurement {
+                method: "gzip",
+                bytes: gzip_raw.len(),
+                elapsed: gzip_raw_time,
+            },
+        ],
+    })
+}
+
+fn timed<T>(f: impl FnOnce() -> io::Result<T>) -> io::Result<(T, Duration)> {
+    let started = Instant::now();
+    let value = f()?;
+    Ok((value, started.elapsed()))
+}
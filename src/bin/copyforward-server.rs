@@ -0,0 +1,60 @@
+//! HTTP front end for copyforward; see [`copyforward::server`] for the endpoints.
+
+use copyforward::daemon::Daemon;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: copyforward-server [--addr <HOST:PORT> | --stdio]
+
+Serves POST /segment, POST /render and GET /health with JSON bodies.
+
+Options:
+  --addr <HOST:PORT>      Address to listen on (default 127.0.0.1:8080)
+  --stdio                 Read newline-delimited JSON commands on stdin instead of serving HTTP
+  -h, --help              Print this help
+";
+
+fn main() -> ExitCode {
+    let mut addr = "127.0.0.1:8080".to_string();
+    let mut stdio = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--addr" => match args.next() {
+                Some(value) => addr = value,
+                None => {
+                    eprintln!("error: --addr requires a value\n\n{USAGE}");
+                    return ExitCode::from(2);
+                }
+            },
+            "--stdio" => stdio = true,
+            "-h" | "--help" => {
+                print!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                eprintln!("error: unexpected argument {arg}\n\n{USAGE}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+    if stdio {
+        let stdin = std::io::stdin().lock();
+        let stdout = std::io::stdout().lock();
+        return match Daemon::new().run(stdin, stdout) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    eprintln!("copyforward-server listening on http://{addr}");
+    match copyforward::server::serve(&addr) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {addr}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Boilerplate detection across a corpus.
+//!
+//! Finds text repeated in more than [`BoilerplateOptions::threshold`] distinct
+//! documents (or threads): footers, legal disclaimers, canned replies. Every
+//! k-mer of [`BoilerplateOptions::min_len`] characters is counted once per
+//! document; positions covered by a frequent k-mer are merged into maximal
+//! spans, which double as a removal mask.
+//!
+//! # Example
+//! ```
+//! use copyforward::boilerplate::{analyze, BoilerplateOptions};
+//!
+//! let footer = "\n-- \nThis message is confidential.";
+//! let docs: Vec<String> = ["Hi Ann", "Lunch?", "Done."]
+//!     .iter()
+//!     .map(|body| format!("{body}{footer}"))
+//!     .collect();
+//! let opts = BoilerplateOptions { min_len: 12, threshold: 2 };
+//! let report = analyze(&docs, &opts);
+//! assert_eq!(report.spans[0].text, footer);
+//! assert_eq!(report.strip(&docs), ["Hi Ann", "Lunch?", "Done."]);
+//! ```
+
+use crate::corpus::Thread;
+use crate::hashing::{prefix_hashes_u32, range_hash};
+use crate::{MessageLike, compute_offsets, normalize};
+use ahash::AHashMap as HashMap;
+use std::ops::Range;
+
+/// Thresholds for boilerplate detection.
+#[derive(Debug, Clone)]
+pub struct BoilerplateOptions {
+    /// Minimum span length in characters.
+    ///
+    /// **Default:** 32
+    pub min_len: usize,
+    /// A span is boilerplate when it occurs in more than this many distinct
+    /// documents (or threads, for [`analyze_threads`]).
+    ///
+    /// **Default:** 2
+    pub threshold: usize,
+}
+
+impl Default for BoilerplateOptions {
+    fn default() -> Self {
+        BoilerplateOptions {
+            min_len: 32,
+            threshold: 2,
+        }
+    }
+}
+
+/// A distinct boilerplate span and where it occurs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Boilerplate {
+    pub text: String,
+    /// Number of times the exact span was found.
+    pub occurrences: usize,
+    /// Number of distinct documents (or threads) containing the exact span.
+    pub documents: usize,
+    /// `text.len() * occurrences`.
+    pub total_bytes: usize,
+}
+
+/// Result of a boilerplate analysis.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BoilerplateReport {
+    /// Distinct spans ranked by `total_bytes`, largest first.
+    pub spans: Vec<Boilerplate>,
+    /// Removal mask: sorted, non-overlapping byte ranges of boilerplate per document.
+    pub ranges: Vec<Vec<Range<usize>>>,
+}
+
+impl BoilerplateReport {
+    /// Return the documents with all boilerplate ranges removed.
+    ///
+    /// `documents` must be the input the report was computed from.
+    pub fn strip<M: MessageLike>(&self, documents: &[M]) -> Vec<String> {
+        documents
+            .iter()
+            .zip(&self.ranges)
+            .map(|(doc, ranges)| {
+                let text = doc.as_message().unwrap_or("");
+                let mut out = String::with_capacity(text.len());
+                let mut pos = 0;
+                for r in ranges {
+                    out.push_str(&text[pos..r.start]);
+                    pos = r.end;
+                }
+                out.push_str(&text[pos..]);
+                out
+            })
+            .collect()
+    }
+}
+
+/// Find boilerplate repeated across distinct documents.
+pub fn analyze<M: MessageLike>(documents: &[M], options: &BoilerplateOptions) -> BoilerplateReport {
+    let texts: Vec<&str> = documents
+        .iter()
+        .map(|d| d.as_message().unwrap_or(""))
+        .collect();
+    let groups: Vec<usize> = (0..texts.len()).collect();
+    analyze_grouped(&texts, &groups, options)
+}
+
+/// Find boilerplate repeated across distinct threads.
+///
+/// Repeats within a single thread (quoting) do not count towards the
+/// threshold. `ranges` has one entry per message, in thread order.
+pub fn analyze_threads(threads: &[Thread], options: &BoilerplateOptions) -> BoilerplateReport {
+    let mut texts = Vec::new();
+    let mut groups = Vec::new();
+    for (g, thread) in threads.iter().enumerate() {
+        for m in &thread.messages {
+            texts.push(m.as_str());
+            groups.push(g);
+        }
+    }
+    analyze_grouped(&texts, &groups, options)
+}
+
+/// `groups` must be non-decreasing so each group's documents are contiguous.
+fn analyze_grouped(
+    texts: &[&str],
+    groups: &[usize],
+    options: &BoilerplateOptions,
+) -> BoilerplateReport {
+    let k = options.min_len.max(1);
+    let codes: Vec<Vec<u32>> = texts.iter().map(|t| normalize::string_to_u32s(t)).collect();
+    let kmers: Vec<Vec<u64>> = codes
+        .iter()
+        .map(|c| {
+            if c.len() < k {
+                return Vec::new();
+            }
+            let (h, p) = prefix_hashes_u32(c, 257);
+            (0..=c.len() - k)
+                .map(|s| range_hash(&h, &p, s, s + k))
+                .collect()
+        })
+        .collect();
+
+    // Distinct-group count per k-mer; groups arrive in order.
+    let mut counts: HashMap<u64, (usize, usize)> = HashMap::new();
+    for (hashes, &g) in kmers.iter().zip(groups) {
+        for &h in hashes {
+            let entry = counts.entry(h).or_insert((usize::MAX, 0));
+            if entry.0 != g {
+                *entry = (g, entry.1 + 1);
+            }
+        }
+    }
+
+    let mut ranges = Vec::with_capacity(texts.len());
+    for (text, hashes) in texts.iter().zip(&kmers) {
+        let offsets = compute_offsets(text);
+        let mut spans: Vec<Range<usize>> = Vec::new();
+        for (s, h) in hashes.iter().enumerate() {
+            if counts[h].1 <= options.threshold {
+                continue;
+            }
+            match spans.last_mut() {
+                Some(last) if s <= last.end => last.end = s + k,
+                _ => spans.push(s..s + k),
+            }
+        }
+        ranges.push(
+            spans
+                .into_iter()
+                .map(|r| offsets[r.start]..offsets[r.end])
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    let mut by_text: HashMap<&str, (usize, usize, usize)> = HashMap::new();
+    for ((text, doc_ranges), &g) in texts.iter().zip(&ranges).zip(groups) {
+        for r in doc_ranges {
+            let entry = by_text
+                .entry(&text[r.clone()])
+                .or_insert((0, usize::MAX, 0));
+            entry.0 += 1;
+            if entry.1 != g {
+                entry.1 = g;
+                entry.2 += 1;
+            }
+        }
+    }
+    let mut spans: Vec<Boilerplate> = by_text
+        .into_iter()
+        .map(|(text, (occurrences, _, documents))| Boilerplate {
+            text: text.to_string(),
+            occurrences,
+            documents,
+            total_bytes: text.len() * occurrences,
+        })
+        .collect();
+    spans.sort_by(|a, b| {
+        b.total_bytes
+            .cmp(&a.total_bytes)
+            .then_with(|| a.text.cmp(&b.text))
+    });
+
+    BoilerplateReport { spans, ranges }
+}
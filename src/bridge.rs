@@ -0,0 +1,243 @@
+//! Bridging short literals between co-linear references.
+//!
+//! Re-wrapped quotes fragment badly: every changed line break splits the
+//! quote into two references with a one-character literal between them.
+//! Both functions here merge such a run when the literal is at most `max_gap`
+//! bytes and stands in for at most `max_gap` bytes of the source:
+//!
+//! - [`bridge_literals`] (exact mode) merges only when the literal equals the
+//!   source text it replaces byte for byte, into one plain reference.
+//! - [`bridge_fuzzy`] (approximate mode) also merges when the two differ in
+//!   whitespace only (runs of whitespace compare equal to each other, but never
+//!   to no whitespace at all), into one [`FuzzySegment::Fuzzy`] reference that
+//!   records the message's own text for the differing bytes.
+//!
+//! Either way every message still renders byte for byte as before, so later
+//! references into a bridged message keep pointing at the right text.
+//!
+//! # Example
+//! ```
+//! use copyforward::bridge::{FuzzySegment, Patch, bridge_fuzzy};
+//! use copyforward::{exact, Config, Segment};
+//!
+//! let messages = [
+//!     "The build is green and ready to ship today.",
+//!     "Quote: The build is green and\nready to ship today.",
+//! ];
+//! let model = exact(&messages, Config::default());
+//! let bridged = bridge_fuzzy(&model, 4);
+//! assert_eq!(
+//!     bridged[1],
+//!     [
+//!         FuzzySegment::Exact(Segment::Literal("Quote: ".into())),
+//!         FuzzySegment::Fuzzy {
+//!             message_idx: 0,
+//!             start: 0,
+//!             len: messages[0].len(),
+//!             patches: vec![Patch { offset: 22, len: 1, text: "\n".into() }],
+//!         },
+//!     ]
+//! );
+//! ```
+
+use crate::core::{CopyForward, Segment};
+use crate::literal::LiteralText;
+
+/// A segment of [`bridge_fuzzy`] output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FuzzySegment {
+    /// A segment of the model, unchanged or merged with references whose gaps
+    /// matched exactly.
+    Exact(Segment),
+    /// `len` bytes of message `message_idx` from byte `start`, with each of
+    /// `patches` replacing a few of them by the text the message has instead.
+    Fuzzy {
+        /// Index of the referenced message.
+        message_idx: usize,
+        /// Byte offset where the referenced substring starts.
+        start: usize,
+        /// Length in bytes of the referenced substring.
+        len: usize,
+        /// Differences from the referenced substring, in order.
+        patches: Vec<Patch>,
+    },
+}
+
+/// Bytes of a [`FuzzySegment::Fuzzy`] reference that the message spells
+/// differently from its source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Patch {
+    /// Offset of the replaced bytes from the start of the referenced substring.
+    pub offset: usize,
+    /// Number of source bytes replaced.
+    pub len: usize,
+    /// Text the message has in their place.
+    pub text: LiteralText,
+}
+
+impl FuzzySegment {
+    /// Plain segments rendering the same text: for a fuzzy reference, the
+    /// references between its patches and each patch as a literal.
+    pub fn into_segments(self) -> Vec<Segment> {
+        let (message_idx, start, len, patches) = match self {
+            FuzzySegment::Exact(seg) => return vec![seg],
+            FuzzySegment::Fuzzy {
+                message_idx,
+                start,
+                len,
+                patches,
+            } => (message_idx, start, len, patches),
+        };
+        let reference = |from: usize, to: usize| Segment::Reference {
+            message_idx,
+            start: from,
+            len: to - from,
+        };
+        let mut out = Vec::with_capacity(2 * patches.len() + 1);
+        let mut pos = start;
+        for patch in patches {
+            if start + patch.offset > pos {
+                out.push(reference(pos, start + patch.offset));
+            }
+            pos = start + patch.offset + patch.len;
+            if !patch.text.is_empty() {
+                out.push(Segment::Literal(patch.text));
+            }
+        }
+        if start + len > pos {
+            out.push(reference(pos, start + len));
+        }
+        out
+    }
+
+    /// Length in bytes of the text this segment renders to.
+    pub fn rendered_len(&self) -> usize {
+        match self {
+            FuzzySegment::Exact(Segment::Literal(s)) => s.len(),
+            FuzzySegment::Exact(Segment::Reference { len, .. }) => *len,
+            FuzzySegment::Fuzzy { len, patches, .. } => patches
+                .iter()
+                .fold(*len, |total, p| total - p.len + p.text.len()),
+        }
+    }
+
+    /// Source, start, length and patches of a reference.
+    fn span(&self) -> Option<(usize, usize, usize, &[Patch])> {
+        match self {
+            &FuzzySegment::Exact(Segment::Reference {
+                message_idx,
+                start,
+                len,
+            }) => Some((message_idx, start, len, &[])),
+            FuzzySegment::Fuzzy {
+                message_idx,
+                start,
+                len,
+                patches,
+            } => Some((*message_idx, *start, *len, patches)),
+            FuzzySegment::Exact(Segment::Literal(_)) => None,
+        }
+    }
+}
+
+/// Segments of every message in `model`, with literals of at most `max_gap`
+/// bytes between two references to consecutive parts of one source merged
+/// away when they equal the skipped source text.
+pub fn bridge_literals(model: &dyn CopyForward, max_gap: usize) -> Vec<Vec<Segment>> {
+    (0..model.len())
+        .map(|idx| {
+            bridge_message(model, idx, max_gap, |literal, gap| literal == gap)
+                .into_iter()
+                .flat_map(FuzzySegment::into_segments)
+                .collect()
+        })
+        .collect()
+}
+
+/// [`bridge_literals`], also merging literals that match the skipped source
+/// text up to whitespace, into fuzzy references that keep the literal.
+pub fn bridge_fuzzy(model: &dyn CopyForward, max_gap: usize) -> Vec<Vec<FuzzySegment>> {
+    (0..model.len())
+        .map(|idx| bridge_message(model, idx, max_gap, same_up_to_whitespace))
+        .collect()
+}
+
+fn bridge_message(
+    model: &dyn CopyForward,
+    idx: usize,
+    max_gap: usize,
+    matches: impl Fn(&str, &str) -> bool,
+) -> Vec<FuzzySegment> {
+    let mut out: Vec<FuzzySegment> = Vec::with_capacity(model.segments_for(idx).len());
+    for seg in model.segments_for(idx) {
+        if let &Segment::Reference {
+            message_idx,
+            start,
+            len,
+        } = seg
+            && let [.., prev, FuzzySegment::Exact(Segment::Literal(literal))] = out.as_slice()
+            && let Some((prev_idx, prev_start, prev_len, patches)) = prev.span()
+            && prev_idx == message_idx
+            && literal.len() <= max_gap
+            && let gap_start = prev_start + prev_len
+            && gap_start <= start
+            && start - gap_start <= max_gap
+            && let gap = &model.message(message_idx)[gap_start..start]
+            && matches(literal, gap)
+            // A self-reference must still end before the text it renders to.
+            && (message_idx != idx
+                || start + len <= out[..out.len() - 2].iter().map(FuzzySegment::rendered_len).sum())
+        {
+            let mut patches = patches.to_vec();
+            if literal.as_str() != gap {
+                patches.push(Patch {
+                    offset: gap_start - prev_start,
+                    len: gap.len(),
+                    text: literal.clone(),
+                });
+            }
+            let len = start + len - prev_start;
+            let merged = if patches.is_empty() {
+                FuzzySegment::Exact(Segment::Reference {
+                    message_idx,
+                    start: prev_start,
+                    len,
+                })
+            } else {
+                FuzzySegment::Fuzzy {
+                    message_idx,
+                    start: prev_start,
+                    len,
+                    patches,
+                }
+            };
+            out.truncate(out.len() - 2);
+            out.push(merged);
+            continue;
+        }
+        out.push(FuzzySegment::Exact(seg.clone()));
+    }
+    out
+}
+
+/// Whether `a` and `b` are equal once every whitespace run is collapsed to a
+/// single space.
+fn same_up_to_whitespace(a: &str, b: &str) -> bool {
+    fn collapsed(s: &str) -> impl Iterator<Item = char> + '_ {
+        let mut in_space = false;
+        s.chars().filter_map(move |c| {
+            if c.is_whitespace() {
+                let first = !in_space;
+                in_space = true;
+                first.then_some(' ')
+            } else {
+                in_space = false;
+                Some(c)
+            }
+        })
+    }
+    collapsed(a).eq(collapsed(b))
+}
@@ -0,0 +1,101 @@
+//! Fluent construction of a compression run.
+//!
+//! [`Config`] is a plain struct, so every new setting is a new field that
+//! struct literals without `..Config::default()` must name. A
+//! [`CopyForwardBuilder`] starts from the defaults and sets only what is
+//! asked for, and bundles the [`Algorithm`] and the round-trip check of
+//! [`compress_verified`](crate::compress_verified) with the config.
+//!
+//! # Example
+//! ```
+//! use copyforward::{Algorithm, CopyForward, CopyForwardBuilder};
+//!
+//! let messages = ["Hello world, how are you?", "> Hello world, how are you?\nFine."];
+//! let compressed = CopyForwardBuilder::new()
+//!     .algorithm(Algorithm::Exact)
+//!     .min_match_len(8)
+//!     .lookback(16)
+//!     .verify(true)
+//!     .build(&messages)
+//!     .unwrap();
+//! assert_eq!(compressed.render_with_static("[REF]")[1], "> [REF]\nFine.");
+//! ```
+
+use crate::core::{Config, CopyForwardError};
+use crate::{Algorithm, Compressed, MessageLike, compress_verified, try_compress};
+
+/// Settings for one compression run; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct CopyForwardBuilder {
+    algorithm: Algorithm,
+    config: Config,
+    verify: bool,
+}
+
+impl Default for CopyForwardBuilder {
+    /// The first algorithm in [`Algorithm::ALL`], [`Config::default`] and no
+    /// verification.
+    fn default() -> Self {
+        CopyForwardBuilder {
+            algorithm: Algorithm::ALL[0],
+            config: Config::default(),
+            verify: false,
+        }
+    }
+}
+
+impl CopyForwardBuilder {
+    /// A builder with the [default](CopyForwardBuilder::default) settings.
+    pub fn new() -> CopyForwardBuilder {
+        CopyForwardBuilder::default()
+    }
+
+    /// Algorithm to run.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> CopyForwardBuilder {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Replace the whole config, for settings without a setter here.
+    pub fn config(mut self, config: Config) -> CopyForwardBuilder {
+        self.config = config;
+        self
+    }
+
+    /// Set [`Config::min_match_len`].
+    pub fn min_match_len(mut self, min_match_len: usize) -> CopyForwardBuilder {
+        self.config.min_match_len = min_match_len;
+        self
+    }
+
+    /// Set [`Config::lookback`] to `lookback` messages.
+    pub fn lookback(mut self, lookback: usize) -> CopyForwardBuilder {
+        self.config.lookback = Some(lookback);
+        self
+    }
+
+    /// Set [`Config::cap_len`].
+    pub fn cap_len(mut self, cap_len: usize) -> CopyForwardBuilder {
+        self.config.cap_len = cap_len;
+        self
+    }
+
+    /// Check that every message renders back to its input, as
+    /// [`compress_verified`](crate::compress_verified) does.
+    pub fn verify(mut self, verify: bool) -> CopyForwardBuilder {
+        self.verify = verify;
+        self
+    }
+
+    /// Compress `messages` with these settings, after
+    /// [`Config::validate`] and, when [`verify`](CopyForwardBuilder::verify)
+    /// is set, [`CopyForward::verify`](crate::CopyForward::verify).
+    pub fn build<M: MessageLike>(&self, messages: &[M]) -> Result<Compressed, CopyForwardError> {
+        let config = self.config.clone();
+        if self.verify {
+            compress_verified(messages, self.algorithm, config)
+        } else {
+            try_compress(messages, self.algorithm, config)
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Copy-forward over raw bytes, for input that is not valid UTF-8.
+//!
+//! Logs and mail bodies often carry stray Latin-1 or truncated multi-byte
+//! sequences, which the `&str` constructors cannot take. The functions here
+//! run the same engines with every byte as one unit, so
+//! [`Config::min_match_len`] and [`Config::cap_len`] count bytes, literals
+//! hold `Vec<u8>` and references are byte spans that need not fall on `char`
+//! boundaries. As in text mode, `None` messages keep their index and yield no
+//! segments.
+//!
+//! # Example
+//! ```
+//! use copyforward::{exact_bytes, ByteSegment, Config, CopyForwardBytes};
+//!
+//! let messages: [&[u8]; 2] = [b"caf\xe9 au lait", b"> caf\xe9 au lait\nyes"];
+//! let compressed = exact_bytes(&messages, Config::default());
+//! assert_eq!(
+//!     compressed.segments_for(1)[1],
+//!     ByteSegment::Reference { message_idx: 0, start: 0, len: 12 }
+//! );
+//! assert_eq!(compressed.render_with_static(b"[REF]")[1], b"> [REF]\nyes");
+//! ```
+
+#![cfg_attr(
+    not(any(feature = "exact", feature = "approximate", feature = "optimal")),
+    allow(dead_code)
+)]
+
+use crate::core::{Config, TokenSegment};
+use crate::metadata::Metadata;
+
+/// Trait for types that can be used as byte inputs, supporting both regular
+/// byte strings and `None` values.
+pub trait BytesLike {
+    fn as_message_bytes(&self) -> Option<&[u8]>;
+}
+
+impl BytesLike for &[u8] {
+    fn as_message_bytes(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+}
+
+impl BytesLike for Option<&[u8]> {
+    fn as_message_bytes(&self) -> Option<&[u8]> {
+        *self
+    }
+}
+
+impl BytesLike for Vec<u8> {
+    fn as_message_bytes(&self) -> Option<&[u8]> {
+        Some(self.as_slice())
+    }
+}
+
+impl BytesLike for Option<Vec<u8>> {
+    fn as_message_bytes(&self) -> Option<&[u8]> {
+        self.as_deref()
+    }
+}
+
+/// A segment of a byte message: literal bytes, or a byte span of an earlier
+/// message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ByteSegment {
+    /// Bytes stored as-is.
+    Literal(Vec<u8>),
+    /// `len` bytes of message `message_idx` starting at byte `start`.
+    Reference {
+        message_idx: usize,
+        start: usize,
+        len: usize,
+    },
+}
+
+/// Replacer passed as a trait object to [`CopyForwardBytes::render_with_dyn`].
+pub type DynByteReplacer<'r> = dyn FnMut(usize, usize, usize, &[u8]) -> Vec<u8> + 'r;
+
+/// Copy-forward interface for byte messages; the byte counterpart of
+/// [`CopyForward`](crate::CopyForward).
+pub trait CopyForwardBytes: Send + Sync {
+    /// Get the compressed byte segment representation.
+    fn segments(&self) -> Vec<Vec<ByteSegment>>;
+
+    /// Byte segments of message `idx`, borrowed from the model.
+    fn segments_for(&self, idx: usize) -> &[ByteSegment];
+
+    /// Original bytes of message `idx`; empty for `None` inputs.
+    fn message(&self, idx: usize) -> &[u8];
+
+    /// Number of messages, `None` inputs included.
+    fn len(&self) -> usize;
+
+    /// Whether the model holds no messages.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Crate version, algorithm and config that produced the segments; see
+    /// [`CopyForward::metadata`](crate::CopyForward::metadata).
+    fn metadata(&self) -> Option<Metadata> {
+        None
+    }
+
+    /// Render byte messages by calling a replacer for each reference.
+    fn render_with<F>(&self, replacer: F) -> Vec<Vec<u8>>
+    where
+        F: FnMut(usize, usize, usize, &[u8]) -> Vec<u8>,
+        Self: Sized;
+
+    /// Object-safe [`render_with`](CopyForwardBytes::render_with), for use
+    /// behind `Box<dyn CopyForwardBytes>`.
+    fn render_with_dyn(&self, replacer: &mut DynByteReplacer<'_>) -> Vec<Vec<u8>>;
+
+    /// Render with a static replacement for all references.
+    fn render_with_static(&self, replacement: &[u8]) -> Vec<Vec<u8>> {
+        self.render_with_dyn(&mut |_, _, _, _| replacement.to_vec())
+    }
+}
+
+/// Result of [`exact_bytes`], [`approximate_bytes`] or [`optimal_bytes`].
+#[derive(Debug, Clone)]
+pub struct BytesModel {
+    /// Input messages; `None` entries are stored as empty.
+    originals: Vec<Vec<u8>>,
+    segments: Vec<Vec<ByteSegment>>,
+    metadata: Metadata,
+}
+
+impl BytesModel {
+    fn build<B, F>(messages: &[B], algorithm: &str, config: Config, engine: F) -> BytesModel
+    where
+        B: BytesLike,
+        F: FnOnce(&[Vec<u32>], &Config) -> Vec<Vec<TokenSegment>>,
+    {
+        let metadata = Metadata::new(algorithm, &config);
+        let opts: Vec<Option<&[u8]>> = messages.iter().map(|m| m.as_message_bytes()).collect();
+        let originals: Vec<Vec<u8>> = opts.iter().map(|o| o.unwrap_or(&[]).to_vec()).collect();
+        let valid_indices: Vec<usize> = (0..opts.len()).filter(|&i| opts[i].is_some()).collect();
+        let codes: Vec<Vec<u32>> = valid_indices
+            .iter()
+            .map(|&i| originals[i].iter().map(|&b| u32::from(b)).collect())
+            .collect();
+        // The engines compare stop phrases char by char; spelling each byte
+        // as the char of the same value makes them match byte by byte.
+        let stop_phrases = config
+            .stop_phrases
+            .iter()
+            .map(|p| p.bytes().map(char::from).collect())
+            .collect();
+        let config = Config {
+            stop_phrases,
+            ..config
+        };
+
+        let mut segments = vec![Vec::new(); originals.len()];
+        for (valid, segs) in engine(&codes, &config).into_iter().enumerate() {
+            segments[valid_indices[valid]] = segs
+                .into_iter()
+                .map(|seg| match seg {
+                    TokenSegment::Literal(toks) => {
+                        ByteSegment::Literal(toks.into_iter().map(|t| t as u8).collect())
+                    }
+                    TokenSegment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => ByteSegment::Reference {
+                        message_idx: valid_indices[message_idx],
+                        start,
+                        len,
+                    },
+                })
+                .collect();
+        }
+        BytesModel {
+            originals,
+            segments,
+            metadata,
+        }
+    }
+}
+
+impl CopyForwardBytes for BytesModel {
+    fn segments(&self) -> Vec<Vec<ByteSegment>> {
+        self.segments.clone()
+    }
+
+    fn segments_for(&self, idx: usize) -> &[ByteSegment] {
+        &self.segments[idx]
+    }
+
+    fn message(&self, idx: usize) -> &[u8] {
+        &self.originals[idx]
+    }
+
+    fn len(&self) -> usize {
+        self.originals.len()
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        Some(self.metadata.clone())
+    }
+
+    fn render_with<F>(&self, mut replacer: F) -> Vec<Vec<u8>>
+    where
+        F: FnMut(usize, usize, usize, &[u8]) -> Vec<u8>,
+    {
+        self.segments
+            .iter()
+            .map(|segs| {
+                let mut out = Vec::new();
+                for seg in segs {
+                    match seg {
+                        ByteSegment::Literal(bytes) => out.extend_from_slice(bytes),
+                        ByteSegment::Reference {
+                            message_idx,
+                            start,
+                            len,
+                        } => {
+                            let source = &self.originals[*message_idx][*start..*start + *len];
+                            out.extend(replacer(*message_idx, *start, *len, source));
+                        }
+                    }
+                }
+                out
+            })
+            .collect()
+    }
+
+    fn render_with_dyn(&self, replacer: &mut DynByteReplacer<'_>) -> Vec<Vec<u8>> {
+        self.render_with(replacer)
+    }
+}
+
+/// Byte-mode [`exact()`](crate::exact): one unit per byte.
+#[cfg(feature = "exact")]
+pub fn exact_bytes<B: BytesLike>(messages: &[B], config: Config) -> BytesModel {
+    use crate::engine::binary::compute_binary_segments;
+    BytesModel::build(messages, "exact", config, compute_binary_segments)
+}
+
+/// Byte-mode [`approximate()`](crate::approximate): one unit per byte.
+#[cfg(feature = "approximate")]
+pub fn approximate_bytes<B: BytesLike>(messages: &[B], config: Config) -> BytesModel {
+    use crate::engine::capped::compute_capped_segments;
+    BytesModel::build(messages, "approximate", config, compute_capped_segments)
+}
+
+/// Byte-mode [`optimal()`](crate::optimal): one unit per byte.
+#[cfg(feature = "optimal")]
+pub fn optimal_bytes<B: BytesLike>(messages: &[B], config: Config) -> BytesModel {
+    use crate::engine::optimal::compute_optimal_segments;
+    BytesModel::build(messages, "optimal", config, compute_optimal_segments)
+}
@@ -1,5 +1,6 @@
-use crate::core::{Config, CopyForwardTokens, TokenSegment};
+use crate::core::{Config, CopyForwardTokens, DynTokenReplacer, TokenSegment};
 use crate::engine::capped::compute_capped_segments;
+use crate::metadata::Metadata;
 
 /// Approximate hashed greedy (token-only): caps per-candidate extension to a
 /// fixed length and then coalesces adjacent references to consecutive source
@@ -28,6 +29,22 @@ impl CopyForwardTokens for CappedHashedGreedy {
         self.token_segs.clone()
     }
 
+    fn segments_for(&self, idx: usize) -> &[TokenSegment] {
+        &self.token_segs[idx]
+    }
+
+    fn message(&self, idx: usize) -> &[u32] {
+        &self.messages[idx]
+    }
+
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        Some(Metadata::new("approximate", &self.config))
+    }
+
     fn render_with<F>(&self, mut replacer: F) -> Vec<Vec<u32>>
     where
         F: FnMut(usize, usize, usize, &[u32]) -> Vec<u32>,
@@ -53,4 +70,8 @@ impl CopyForwardTokens for CappedHashedGreedy {
         }
         out
     }
+
+    fn render_with_dyn(&self, replacer: &mut DynTokenReplacer<'_>) -> Vec<Vec<u32>> {
+        self.render_with(replacer)
+    }
 }
@@ -0,0 +1,440 @@
+//! Compact binary encoding of segmentations.
+//!
+//! Layout (all integers are LEB128 varints):
+//!
+//! ```text
+//! "CFSG" version:u8 metadata message_count
+//!   per message: checksum:u32le segment_count
+//!     per segment: 0 len utf8-bytes             (literal)
+//!                | 1 distance start len         (reference)
+//! ```
+//!
+//! References store the distance back to the source message
+//! (`current - message_idx`, 0 for a
+//! [self-reference](crate::Config::self_references)) rather than the absolute
+//! index, which keeps typical quote-the-previous-message references to one
+//! byte.
+//!
+//! The checksum is the CRC-32 of the message text (see [`checksum`]).
+//! Decoding reconstructs every message and reports the first whose text does
+//! not match as [`DecodeError::ChecksumMismatch`], so corrupted storage is
+//! never silently rendered.
+//!
+//! `metadata` is a presence byte, followed when set by the
+//! [`Metadata`] of the run that produced the segments: crate version,
+//! algorithm and config. [`encode_model`] records it and [`decode_metadata`]
+//! reads it back.
+
+use crate::core::{CopyForward, Segment};
+use crate::metadata::{Metadata, put_metadata, read_metadata};
+use std::fmt;
+
+const MAGIC: &[u8; 4] = b"CFSG";
+/// Codec version written by [`encode_segments`], the only one decoded.
+pub const VERSION: u8 = 1;
+
+const TAG_LITERAL: u8 = 0;
+const TAG_REFERENCE: u8 = 1;
+
+/// Error returned by [`decode_segments`] for malformed input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Input does not start with the codec magic bytes.
+    BadMagic,
+    /// Input was written by an unknown codec version.
+    UnsupportedVersion(u8),
+    /// Input ended in the middle of a value.
+    Truncated,
+    /// A varint does not fit in `usize`.
+    Overflow,
+    /// A literal is not valid UTF-8.
+    InvalidUtf8,
+    /// Unknown segment tag.
+    InvalidTag(u8),
+    /// A reference points at a later message.
+    InvalidReference { message: usize },
+    /// Bytes remain after the last message.
+    TrailingBytes,
+    /// A reference covers text outside its source message.
+    ReferenceOutOfRange { message: usize },
+    /// An archive's index does not match its message entries.
+    InvalidIndex,
+    /// A message does not reconstruct to the text its checksum was taken of.
+    ChecksumMismatch { message: usize },
+    /// The metadata block is malformed.
+    InvalidMetadata,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not a copyforward segment encoding"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported codec version {v}"),
+            DecodeError::Truncated => write!(f, "unexpected end of input"),
+            DecodeError::Overflow => write!(f, "integer overflow"),
+            DecodeError::InvalidUtf8 => write!(f, "literal is not valid UTF-8"),
+            DecodeError::InvalidTag(t) => write!(f, "invalid segment tag {t}"),
+            DecodeError::InvalidReference { message } => {
+                write!(f, "message {message} references a later message")
+            }
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after last message"),
+            DecodeError::ReferenceOutOfRange { message } => {
+                write!(f, "message {message} references text outside its source")
+            }
+            DecodeError::InvalidIndex => write!(f, "archive index does not match its messages"),
+            DecodeError::ChecksumMismatch { message } => {
+                write!(f, "message {message} does not match its checksum")
+            }
+            DecodeError::InvalidMetadata => write!(f, "malformed metadata"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub(crate) fn put_varint(out: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+pub(crate) fn varint_len(mut v: usize) -> usize {
+    let mut n = 1;
+    while v >= 0x80 {
+        v >>= 7;
+        n += 1;
+    }
+    n
+}
+
+/// Bytes [`encode_segments`] spends on `seg` when it appears in message
+/// `message`.
+pub(crate) fn segment_size(message: usize, seg: &Segment) -> usize {
+    match seg {
+        Segment::Literal(s) => 1 + varint_len(s.len()) + s.len(),
+        Segment::Reference {
+            message_idx,
+            start,
+            len,
+        } => {
+            1 + varint_len(message.wrapping_sub(*message_idx))
+                + varint_len(*start)
+                + varint_len(*len)
+        }
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Reader { buf, pos: 0 }
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, DecodeError> {
+        let b = *self.buf.get(self.pos).ok_or(DecodeError::Truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<usize, DecodeError> {
+        let mut v: usize = 0;
+        let mut shift = 0u32;
+        loop {
+            let b = self.byte()?;
+            let bits = ((b & 0x7f) as usize)
+                .checked_shl(shift)
+                .filter(|x| x >> shift == (b & 0x7f) as usize)
+                .ok_or(DecodeError::Overflow)?;
+            v |= bits;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+            shift += 7;
+        }
+    }
+
+    pub(crate) fn bytes(&mut self, n: usize) -> Result<&[u8], DecodeError> {
+        let end = self.pos.checked_add(n).ok_or(DecodeError::Overflow)?;
+        let s = self.buf.get(self.pos..end).ok_or(DecodeError::Truncated)?;
+        self.pos = end;
+        Ok(s)
+    }
+
+    /// The entry of message `message`, written by [`put_message`].
+    fn entry(&mut self, message: usize) -> Result<Entry, DecodeError> {
+        let raw = self.bytes(4)?;
+        let checksum = Some(u32::from_le_bytes(raw.try_into().unwrap()));
+        let nsegs = self.varint()?;
+        let mut segs = Vec::with_capacity(nsegs.min(self.buf.len()));
+        for _ in 0..nsegs {
+            match self.byte()? {
+                TAG_LITERAL => {
+                    let len = self.varint()?;
+                    let s = std::str::from_utf8(self.bytes(len)?)
+                        .map_err(|_| DecodeError::InvalidUtf8)?;
+                    segs.push(Segment::Literal(s.into()));
+                }
+                TAG_REFERENCE => {
+                    let distance = self.varint()?;
+                    if distance > message {
+                        return Err(DecodeError::InvalidReference { message });
+                    }
+                    segs.push(Segment::Reference {
+                        message_idx: message - distance,
+                        start: self.varint()?,
+                        len: self.varint()?,
+                    });
+                }
+                tag => return Err(DecodeError::InvalidTag(tag)),
+            }
+        }
+        Ok(Entry {
+            checksum,
+            segments: segs,
+        })
+    }
+}
+
+/// Encode a segmentation into the compact binary format.
+///
+/// Each message's checksum is taken over the text its segments reconstruct,
+/// which detects later damage to the encoding. Use [`encode_model`] to also
+/// catch segments that do not reproduce the original messages.
+///
+/// # Example
+/// ```
+/// use copyforward::codec::{decode_segments, encode_segments};
+/// use copyforward::{exact, Config, CopyForward};
+///
+/// let segments = exact(&["Hello world", "Hello world today"], Config::default()).segments();
+/// let bytes = encode_segments(&segments);
+/// assert_eq!(decode_segments(&bytes).unwrap(), segments);
+/// ```
+pub fn encode_segments(segments: &[Vec<Segment>]) -> Vec<u8> {
+    let texts = lossy_texts(segments);
+    encode(segments, None, |i| checksum(&texts[i]))
+}
+
+/// Encode the segments of `model`, with checksums of its original messages
+/// and the model's [`Metadata`].
+///
+/// Decoding then fails with [`DecodeError::ChecksumMismatch`] if a segment
+/// does not reproduce its message, for example after an undetected hash
+/// collision, as well as on damage to the encoding.
+///
+/// # Example
+/// ```
+/// use copyforward::codec::{decode_messages, encode_model};
+/// use copyforward::{approximate, Config};
+///
+/// let messages = ["Hello world", "Hello world today"];
+/// let bytes = encode_model(&approximate(&messages, Config::default()));
+/// assert_eq!(decode_messages(&bytes).unwrap(), messages);
+/// ```
+pub fn encode_model(model: &dyn CopyForward) -> Vec<u8> {
+    encode(&model.segments(), model.metadata().as_ref(), |i| {
+        checksum(model.message(i))
+    })
+}
+
+fn encode(
+    segments: &[Vec<Segment>],
+    metadata: Option<&Metadata>,
+    checksum_of: impl Fn(usize) -> u32,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    put_metadata(&mut out, metadata);
+    put_varint(&mut out, segments.len());
+    for (i, segs) in segments.iter().enumerate() {
+        put_message(&mut out, i, checksum_of(i), segs);
+    }
+    out
+}
+
+/// CRC-32 (IEEE) of `text`, the checksum stored for each message.
+pub fn checksum(text: &str) -> u32 {
+    !text.bytes().fold(!0u32, |crc, b| {
+        CRC_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Append the entry of message `message`: its checksum, its segment count,
+/// then each segment.
+pub(crate) fn put_message(out: &mut Vec<u8>, message: usize, checksum: u32, segments: &[Segment]) {
+    out.extend_from_slice(&checksum.to_le_bytes());
+    put_varint(out, segments.len());
+    for seg in segments {
+        match seg {
+            Segment::Literal(s) => {
+                out.push(TAG_LITERAL);
+                put_varint(out, s.len());
+                out.extend_from_slice(s.as_bytes());
+            }
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                out.push(TAG_REFERENCE);
+                put_varint(out, message.wrapping_sub(*message_idx));
+                put_varint(out, *start);
+                put_varint(out, *len);
+            }
+        }
+    }
+}
+
+/// Decode a segmentation written by [`encode_segments`] or [`encode_model`],
+/// verifying every message's checksum.
+pub fn decode_segments(bytes: &[u8]) -> Result<Vec<Vec<Segment>>, DecodeError> {
+    Ok(decode(bytes)?.into_iter().map(|(segs, _)| segs).collect())
+}
+
+/// Decode and reconstruct the messages written by [`encode_segments`] or
+/// [`encode_model`], verifying every message's checksum.
+pub fn decode_messages(bytes: &[u8]) -> Result<Vec<String>, DecodeError> {
+    Ok(decode(bytes)?.into_iter().map(|(_, text)| text).collect())
+}
+
+/// Read the [`Metadata`] recorded by [`encode_model`], without decoding the
+/// messages. Encodings without metadata give `None`.
+pub fn decode_metadata(bytes: &[u8]) -> Result<Option<Metadata>, DecodeError> {
+    let mut r = Reader::new(bytes);
+    header(&mut r)
+}
+
+/// Check the magic bytes and version, and read the metadata block.
+fn header(r: &mut Reader) -> Result<Option<Metadata>, DecodeError> {
+    if r.bytes(MAGIC.len()).map_err(|_| DecodeError::BadMagic)? != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let version = r.byte()?;
+    if version != VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    read_metadata(r)
+}
+
+/// Segments and verified text of every message.
+fn decode(bytes: &[u8]) -> Result<Vec<(Vec<Segment>, String)>, DecodeError> {
+    let mut r = Reader::new(bytes);
+    header(&mut r)?;
+    let count = r.varint()?;
+    // Every message takes at least one byte, which bounds the allocation.
+    let mut entries = Vec::with_capacity(count.min(bytes.len()));
+    for message in 0..count {
+        entries.push(r.entry(message)?);
+    }
+    if r.pos != bytes.len() {
+        return Err(DecodeError::TrailingBytes);
+    }
+    let mut out: Vec<(Vec<Segment>, String)> = Vec::with_capacity(entries.len());
+    for (message, entry) in entries.into_iter().enumerate() {
+        let text = entry.text(message, |i| out[i].1.as_str())?;
+        out.push((entry.segments, text));
+    }
+    Ok(out)
+}
+
+/// A decoded message entry.
+pub(crate) struct Entry {
+    /// Checksum of the message text; none for an entry about to be written.
+    pub(crate) checksum: Option<u32>,
+    pub(crate) segments: Vec<Segment>,
+}
+
+impl Entry {
+    /// Reconstruct the text of message `message` given the text of each
+    /// message it references, and verify it against the checksum.
+    pub(crate) fn text<'a>(
+        &self,
+        message: usize,
+        source: impl Fn(usize) -> &'a str,
+    ) -> Result<String, DecodeError> {
+        let text = rebuild(&self.segments, message, source)
+            .ok_or(DecodeError::ReferenceOutOfRange { message })?;
+        match self.checksum {
+            Some(sum) if sum != checksum(&text) => Err(DecodeError::ChecksumMismatch { message }),
+            _ => Ok(text),
+        }
+    }
+}
+
+/// The text the `segments` of message `message` spell out, or `None` if a
+/// reference covers text outside its source.
+fn rebuild<'a>(
+    segments: &[Segment],
+    message: usize,
+    source: impl Fn(usize) -> &'a str,
+) -> Option<String> {
+    let mut text = String::new();
+    for seg in segments {
+        match seg {
+            Segment::Literal(s) => text.push_str(s),
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                let range = *start..start.checked_add(*len)?;
+                if *message_idx == message {
+                    text.get(range.clone())?;
+                    text.extend_from_within(range);
+                } else {
+                    text.push_str(source(*message_idx).get(range)?);
+                }
+            }
+        }
+    }
+    Some(text)
+}
+
+/// The text each message reconstructs to, empty where a reference is
+/// invalid; decoding rejects such messages before their checksum matters.
+pub(crate) fn lossy_texts(segments: &[Vec<Segment>]) -> Vec<String> {
+    let mut texts: Vec<String> = Vec::with_capacity(segments.len());
+    for (message, segs) in segments.iter().enumerate() {
+        let text =
+            rebuild(segs, message, |i| texts.get(i).map_or("", String::as_str)).unwrap_or_default();
+        texts.push(text);
+    }
+    texts
+}
+
+/// Decode an entry written by [`put_message`] that spans all of `bytes`.
+pub(crate) fn decode_entry(bytes: &[u8], message: usize) -> Result<Entry, DecodeError> {
+    let mut r = Reader::new(bytes);
+    let entry = r.entry(message)?;
+    if r.pos != bytes.len() {
+        return Err(DecodeError::TrailingBytes);
+    }
+    Ok(entry)
+}
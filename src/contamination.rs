@@ -0,0 +1,170 @@
+//! Contamination checking against a fixed reference corpus.
+//!
+//! Build a [`ContaminationIndex`] once from the reference set (for example
+//! evaluation benchmarks), then scan candidate documents for spans that also
+//! occur in it. Matching uses the same rolling k-mer hashes and binary-search
+//! extension as [`crate::exact()`], but the index is never extended with the
+//! scanned documents.
+//!
+//! # Example
+//! ```
+//! use copyforward::contamination::ContaminationIndex;
+//!
+//! let index = ContaminationIndex::new(&["What is the capital of France? Paris."], 12);
+//! let matches = index.scan("Q: What is the capital of France? A: Paris.");
+//! assert_eq!(matches.len(), 1);
+//! assert_eq!(matches[0].reference, 0);
+//! assert_eq!(matches[0].len, "What is the capital of France? ".len());
+//! ```
+
+use crate::hashing::{prefix_hashes_u32, range_hash};
+use crate::{MessageLike, compute_offsets, normalize};
+use ahash::AHashMap as HashMap;
+
+const BASE: u64 = 257;
+
+/// A span of a scanned document that also occurs in the reference corpus.
+///
+/// Offsets are in bytes, like [`crate::Segment::Reference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContaminationMatch {
+    /// Byte offset of the span in the scanned document.
+    pub start: usize,
+    /// Byte length of the span (identical in both documents).
+    pub len: usize,
+    /// Index of the reference document containing the span.
+    pub reference: usize,
+    /// Byte offset of the span in the reference document.
+    pub reference_start: usize,
+}
+
+struct Reference {
+    codes: Vec<u32>,
+    offsets: Vec<usize>,
+    prefix: (Vec<u64>, Vec<u64>),
+}
+
+/// Index of every k-mer of a reference corpus.
+pub struct ContaminationIndex {
+    k: usize,
+    references: Vec<Reference>,
+    table: HashMap<u64, Vec<(usize, usize)>>,
+}
+
+impl ContaminationIndex {
+    /// Index `references`, reporting matches of at least `min_match_len`
+    /// characters. `None` entries are indexed as empty documents so reference
+    /// indices stay aligned with the input.
+    pub fn new<M: MessageLike>(references: &[M], min_match_len: usize) -> ContaminationIndex {
+        let k = min_match_len.max(1);
+        let references: Vec<Reference> = references
+            .iter()
+            .map(|m| {
+                let text = m.as_message().unwrap_or("");
+                let codes = normalize::string_to_u32s(text);
+                let prefix = prefix_hashes_u32(&codes, BASE);
+                Reference {
+                    offsets: compute_offsets(text),
+                    codes,
+                    prefix,
+                }
+            })
+            .collect();
+        let mut table: HashMap<u64, Vec<(usize, usize)>> = HashMap::new();
+        for (idx, r) in references.iter().enumerate() {
+            if r.codes.len() >= k {
+                for start in 0..=(r.codes.len() - k) {
+                    let h = range_hash(&r.prefix.0, &r.prefix.1, start, start + k);
+                    table.entry(h).or_default().push((idx, start));
+                }
+            }
+        }
+        ContaminationIndex {
+            k,
+            references,
+            table,
+        }
+    }
+
+    /// Number of indexed reference documents.
+    pub fn len(&self) -> usize {
+        self.references.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+
+    /// Find non-overlapping spans of `document` that occur in the reference
+    /// corpus, scanning left to right and keeping the longest match at each
+    /// position.
+    pub fn scan(&self, document: &str) -> Vec<ContaminationMatch> {
+        let k = self.k;
+        let codes = normalize::string_to_u32s(document);
+        let offsets = compute_offsets(document);
+        let prefix = prefix_hashes_u32(&codes, BASE);
+        let mut matches = Vec::new();
+        let mut cursor = 0;
+        while cursor + k <= codes.len() {
+            let key = range_hash(&prefix.0, &prefix.1, cursor, cursor + k);
+            let mut best: Option<(usize, usize, usize)> = None;
+            for &(idx, ref_start) in self.table.get(&key).into_iter().flatten() {
+                let r = &self.references[idx];
+                if codes[cursor..cursor + k] != r.codes[ref_start..ref_start + k] {
+                    continue;
+                }
+                let len = extend(&prefix, &r.prefix, cursor, ref_start, k);
+                if best.is_none_or(|(best_len, _, _)| len > best_len) {
+                    best = Some((len, idx, ref_start));
+                }
+            }
+            match best {
+                Some((len, idx, ref_start)) => {
+                    let ref_offsets = &self.references[idx].offsets;
+                    matches.push(ContaminationMatch {
+                        start: offsets[cursor],
+                        len: offsets[cursor + len] - offsets[cursor],
+                        reference: idx,
+                        reference_start: ref_offsets[ref_start],
+                    });
+                    cursor += len;
+                }
+                None => cursor += 1,
+            }
+        }
+        matches
+    }
+
+    /// Scan each document; `None` entries yield no matches.
+    pub fn scan_all<M: MessageLike>(&self, documents: &[M]) -> Vec<Vec<ContaminationMatch>> {
+        documents
+            .iter()
+            .map(|d| d.as_message().map(|s| self.scan(s)).unwrap_or_default())
+            .collect()
+    }
+}
+
+/// Longest common extension of two positions, given that the first `initial`
+/// units already match.
+#[allow(clippy::manual_div_ceil)]
+fn extend(
+    cur: &(Vec<u64>, Vec<u64>),
+    reference: &(Vec<u64>, Vec<u64>),
+    cursor: usize,
+    ref_start: usize,
+    initial: usize,
+) -> usize {
+    let mut low = initial;
+    let mut high = std::cmp::min(cur.0.len() - 1 - cursor, reference.0.len() - 1 - ref_start);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        let h1 = range_hash(&cur.0, &cur.1, cursor, cursor + mid);
+        let h2 = range_hash(&reference.0, &reference.1, ref_start, ref_start + mid);
+        if h1 == h2 {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
@@ -1,3 +1,17 @@
+use crate::hashing::HashCache;
+use crate::literal::LiteralText;
+use crate::metadata::Metadata;
+use crate::render::{
+    Occurrence, Origin, SharedSpan, char_segments, locate_with, longest_common, occurrences_in,
+};
+use crate::trace::Trace;
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::sync::Arc;
+
 /// A segment of a compressed message - either literal text or a reference.
 ///
 /// Messages are compressed into sequences of segments. References point to
@@ -7,7 +21,7 @@
 /// ```
 /// use copyforward::Segment;
 ///
-/// let literal = Segment::Literal("Hello ".to_string());
+/// let literal = Segment::Literal("Hello ".into());
 /// let reference = Segment::Reference {
 ///     message_idx: 0,
 ///     start: 6,
@@ -15,30 +29,89 @@
 /// }; // Points to "world" in message 0
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Segment {
     /// Literal text that appears directly in the compressed message.
-    Literal(String),
+    ///
+    /// Short literals, typically the connective text between references, are
+    /// stored inline without a heap allocation; longer ones a model repeats
+    /// share one; see [`LiteralText`].
+    Literal(LiteralText),
     /// Reference to a substring of a previous message.
     ///
     /// Points to `messages[message_idx][start..start+len]`.
     Reference {
-        /// Index of the referenced message (must be < current message index).
+        /// Index of the referenced message: an earlier one, or the message
+        /// itself with [`Config::self_references`].
+        message_idx: usize,
+        /// Byte offset where the referenced substring starts.
+        start: usize,
+        /// Length in bytes of the referenced substring.
+        len: usize,
+    },
+}
+
+/// A [`Segment`] borrowed from a model, with the text it stands for.
+///
+/// Built by [`CopyForward::segment_refs`] without allocating: literals and
+/// the text of references both point into the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentRef<'a> {
+    /// Literal text stored in the compressed message.
+    Literal(&'a str),
+    /// Reference to `len` bytes of message `message_idx` from byte `start`.
+    Reference {
+        /// Index of the referenced message.
         message_idx: usize,
         /// Byte offset where the referenced substring starts.
         start: usize,
         /// Length in bytes of the referenced substring.
         len: usize,
+        /// The referenced substring.
+        text: &'a str,
     },
 }
 
+impl<'a> SegmentRef<'a> {
+    /// Text this segment contributes to its message.
+    pub fn text(&self) -> &'a str {
+        match *self {
+            SegmentRef::Literal(text) | SegmentRef::Reference { text, .. } => text,
+        }
+    }
+}
+
+/// One reference as seen by the replacer of
+/// [`CopyForward::render_with_context`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplaceContext<'a> {
+    /// Index of the message being rendered.
+    pub target: usize,
+    /// Byte offset in the rendered `target` message where the replacement
+    /// goes, after every earlier literal and replacement.
+    pub output_offset: usize,
+    /// Index of the referenced message.
+    pub message_idx: usize,
+    /// Byte offset where the referenced substring starts.
+    pub start: usize,
+    /// Length in bytes of the referenced substring.
+    pub len: usize,
+    /// The referenced substring.
+    pub text: &'a str,
+}
+
 /// A segment of a compressed token sequence (u32 token IDs).
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum TokenSegment {
     /// Literal token span that appears directly in the compressed message.
     Literal(Vec<u32>),
     /// Reference to a subspan of a previous tokenized message.
     Reference {
-        /// Index of the referenced message (must be < current message index).
+        /// Index of the referenced message: an earlier one, or the message
+        /// itself with [`Config::self_references`].
         message_idx: usize,
         /// Token index where the referenced subspan starts.
         start: usize,
@@ -47,6 +120,13 @@ pub enum TokenSegment {
     },
 }
 
+/// Replacer passed as a trait object to [`CopyForward::render_with_dyn`]:
+/// `(message_idx, start, len, referenced_text) -> replacement`.
+pub type DynReplacer<'r> = dyn FnMut(usize, usize, usize, &str) -> String + 'r;
+
+/// Replacer passed as a trait object to [`CopyForwardTokens::render_with_dyn`].
+pub type DynTokenReplacer<'r> = dyn FnMut(usize, usize, usize, &[u32]) -> Vec<u32> + 'r;
+
 /// Common interface for copy-forward compression algorithms.
 ///
 /// Implementations compress message sequences into [`Segment`] representations
@@ -66,7 +146,49 @@ pub enum TokenSegment {
 /// let original = compressed.render_with(|_, _, _, text| text.to_string());
 /// assert_eq!(original, messages);
 /// ```
-pub trait CopyForward {
+///
+/// The trait is object safe: generic methods require `Self: Sized`, and the
+/// `*_dyn` methods take the replacer as a trait object, so an algorithm can be
+/// chosen at runtime behind `Box<dyn CopyForward>`.
+///
+/// ```
+/// use copyforward::{approximate, exact, Config, CopyForward};
+///
+/// let messages = &["Hello world", "Hello world today"];
+/// let fast = true;
+/// let compressed: Box<dyn CopyForward> = if fast {
+///     Box::new(approximate(messages, Config::default()))
+/// } else {
+///     Box::new(exact(messages, Config::default()))
+/// };
+/// let rendered = compressed.render_with_dyn(&mut |_, _, _, text| text.to_string());
+/// assert_eq!(rendered, messages);
+/// assert_eq!(compressed.segments().len(), 2);
+/// ```
+///
+/// # Thread safety
+///
+/// A model is immutable once built: every method takes `&self`, reads only
+/// data computed during construction and takes no locks. Implementations
+/// must be `Send + Sync`, so one model, including a `dyn CopyForward`, can be
+/// shared behind an `Arc` and queried from many server threads at once.
+///
+/// ```
+/// use copyforward::{exact, Config, CopyForward};
+/// use std::sync::Arc;
+///
+/// let model: Arc<dyn CopyForward> = Arc::new(exact(&["Hi all", "> Hi all\nHello"], Config::default()));
+/// let workers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let model = Arc::clone(&model);
+///         std::thread::spawn(move || model.segments_for(1).len())
+///     })
+///     .collect();
+/// for worker in workers {
+///     assert_eq!(worker.join().unwrap(), 3);
+/// }
+/// ```
+pub trait CopyForward: Send + Sync {
     /// Get the compressed segment representation.
     ///
     /// Returns a vector where each element corresponds to one input message,
@@ -76,6 +198,219 @@ pub trait CopyForward {
     /// Vector of segment vectors, one per input message.
     fn segments(&self) -> Vec<Vec<Segment>>;
 
+    /// Segments of message `idx`, borrowed from the model.
+    ///
+    /// # Panics
+    /// If `idx >= self.len()`.
+    fn segments_for(&self, idx: usize) -> &[Segment];
+
+    /// Original text of message `idx` (`""` for `None` inputs).
+    ///
+    /// # Panics
+    /// If `idx >= self.len()`.
+    fn message(&self, idx: usize) -> &str;
+
+    /// Number of messages, including `None` entries.
+    fn len(&self) -> usize;
+
+    /// Whether the model holds no messages.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Crate version, algorithm and config that produced the segments, for
+    /// models built by an algorithm; `None` for segments from elsewhere, such
+    /// as decoded or hand-built ones.
+    fn metadata(&self) -> Option<Metadata> {
+        None
+    }
+
+    /// Segments covering bytes `range` of message `idx`.
+    ///
+    /// Segments straddling either end are split: literals are cut and
+    /// references narrowed to the matching part of their source, so the result
+    /// renders exactly `self.message(idx)[range]`.
+    ///
+    /// # Panics
+    /// If `range` is out of bounds or does not lie on `char` boundaries; see
+    /// [`try_slice_segments`](CopyForward::try_slice_segments).
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward, Segment};
+    ///
+    /// let compressed = exact(&["Hello world", "Hello world today"], Config::default());
+    /// assert_eq!(
+    ///     compressed.slice_segments(1, 6..14),
+    ///     [
+    ///         Segment::Reference { message_idx: 0, start: 6, len: 5 },
+    ///         Segment::Literal(" to".into()),
+    ///     ]
+    /// );
+    /// ```
+    fn slice_segments(&self, idx: usize, range: Range<usize>) -> Vec<Segment> {
+        self.try_slice_segments(idx, range.clone())
+            .unwrap_or_else(|_| {
+                panic!("byte range {range:?} is not a valid slice of message {idx}")
+            })
+    }
+
+    /// [`slice_segments`](CopyForward::slice_segments), returning an error
+    /// instead of panicking when `range` runs past the end of the message or
+    /// splits a character.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward, CopyForwardError};
+    ///
+    /// let compressed = exact(&["héllo"], Config::default());
+    /// assert_eq!(
+    ///     compressed.try_slice_segments(0, 0..2),
+    ///     Err(CopyForwardError::NotCharBoundary { message: 0, offset: 2 })
+    /// );
+    /// assert_eq!(
+    ///     compressed.try_slice_segments(0, 3..9),
+    ///     Err(CopyForwardError::OffsetOverflow { message: 0, offset: 9 })
+    /// );
+    /// ```
+    fn try_slice_segments(
+        &self,
+        idx: usize,
+        range: Range<usize>,
+    ) -> Result<Vec<Segment>, CopyForwardError> {
+        let text = self.message(idx);
+        check_offset(text, idx, range.end)?;
+        if range.start > range.end {
+            return Err(CopyForwardError::OffsetOverflow {
+                message: idx,
+                offset: range.start,
+            });
+        }
+        check_offset(text, idx, range.start)?;
+        Ok(slice_message(self.segments_for(idx), range))
+    }
+
+    /// Map byte `offset` of message `idx` to its producing segment and
+    /// ultimate source, as [`render::locate`](crate::render::locate) does.
+    fn locate(&self, idx: usize, offset: usize) -> Option<Origin> {
+        locate_with(
+            |i| (i < self.len()).then(|| self.segments_for(i)),
+            idx,
+            offset,
+        )
+    }
+
+    /// Segments of message `idx` with each reference's `start` and `len`
+    /// counted in characters (Unicode scalar values) of its source message
+    /// instead of bytes.
+    ///
+    /// These index Python strings directly; JavaScript strings, indexed by
+    /// UTF-16 unit, differ only for characters outside the Basic
+    /// Multilingual Plane.
+    ///
+    /// # Panics
+    /// If `idx >= self.len()`.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward, Segment};
+    ///
+    /// let compressed = exact(&["héllo wörld", "> héllo wörld"], Config::default());
+    /// let reference = Segment::Reference { message_idx: 0, start: 0, len: 13 };
+    /// assert_eq!(compressed.segments_for(1)[1], reference);
+    /// let reference = Segment::Reference { message_idx: 0, start: 0, len: 11 };
+    /// assert_eq!(compressed.char_segments_for(1)[1], reference);
+    /// ```
+    fn char_segments_for(&self, idx: usize) -> Vec<Segment> {
+        char_segments(self.segments_for(idx), |i| self.message(i))
+    }
+
+    /// Segments of message `idx` as borrowed [`SegmentRef`]s, each with the
+    /// text it stands for, without cloning any literal.
+    ///
+    /// # Panics
+    /// If `idx >= self.len()`.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward, SegmentRef};
+    ///
+    /// let compressed = exact(&["Hello world", "> Hello world"], Config::default());
+    /// let refs: Vec<SegmentRef> = compressed.segment_refs(1).collect();
+    /// assert_eq!(refs[0], SegmentRef::Literal("> "));
+    /// assert_eq!(refs[1].text(), "Hello world");
+    /// ```
+    fn segment_refs(&self, idx: usize) -> impl Iterator<Item = SegmentRef<'_>>
+    where
+        Self: Sized,
+    {
+        self.segments_for(idx).iter().map(|seg| match seg {
+            Segment::Literal(text) => SegmentRef::Literal(text),
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => SegmentRef::Reference {
+                message_idx: *message_idx,
+                start: *start,
+                len: *len,
+                text: &self.message(*message_idx)[*start..start + len],
+            },
+        })
+    }
+
+    /// Every occurrence of `query` across the thread, by message and then
+    /// position, overlapping ones included.
+    ///
+    /// Each message is scanned with a plain substring search, so the cost is
+    /// linear in the thread's text; no index is built. Each
+    /// [`Occurrence`] says whether it lies inside a reference, so the
+    /// unquoted ones show where the text was first written. An empty query
+    /// matches nothing.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let messages = ["Deploy on Friday", "> Deploy on Friday\nNo, Monday", "Monday then"];
+    /// let model = exact(&messages, Config::default());
+    /// let places = |query| {
+    ///     let found = model.find_occurrences(query);
+    ///     found.iter().map(|o| (o.message, o.span.clone(), o.quoted)).collect::<Vec<_>>()
+    /// };
+    /// assert_eq!(places("Friday"), [(0, 10..16, false), (1, 12..18, true)]);
+    /// assert_eq!(places("Monday"), [(1, 23..29, false), (2, 0..6, true)]);
+    /// ```
+    fn find_occurrences(&self, query: &str) -> Vec<Occurrence> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        (0..self.len())
+            .flat_map(|idx| occurrences_in(idx, self.message(idx), self.segments_for(idx), query))
+            .collect()
+    }
+
+    /// The longest spans messages `i` and `j` share, one per distinct text,
+    /// at its first occurrence in each and ordered by position in `i`.
+    ///
+    /// Lengths are counted in characters and found by binary search over
+    /// rolling hashes of both messages, so no index is rebuilt. Returns an
+    /// empty vector when the messages share no character.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let model = exact(&["red fish, blue fish", "one fish, two fish, red fish"], Config::default());
+    /// let shared = model.lcs(0, 1);
+    /// assert_eq!(shared.len(), 1);
+    /// assert_eq!(&model.message(0)[shared[0].first.clone()], "red fish");
+    /// assert_eq!(shared[0].second, 20..28);
+    /// ```
+    fn lcs(&self, i: usize, j: usize) -> Vec<SharedSpan> {
+        longest_common(self.message(i), self.message(j))
+    }
+
     /// Render messages by calling a replacer function for each reference.
     ///
     /// For each [`Segment::Reference`], calls `replacer(message_idx, start, len, referenced_text)`
@@ -88,7 +423,332 @@ pub trait CopyForward {
     /// Vector of rendered message strings.
     fn render_with<F>(&self, replacer: F) -> Vec<String>
     where
-        F: FnMut(usize, usize, usize, &str) -> String;
+        F: FnMut(usize, usize, usize, &str) -> String,
+        Self: Sized;
+
+    /// Check that every message renders back to its input with the identity
+    /// replacer.
+    ///
+    /// The engines compare text by hash, so a collision could leave a
+    /// reference naming text other than what it replaces. Call this before
+    /// storing segments to catch that instead of keeping corrupted output; it
+    /// costs one pass over every message. Each reference must also name an
+    /// earlier message, or text before it in its own message, so that the
+    /// segments rebuild without the originals as in
+    /// [`render::reconstruct`](crate::render::reconstruct). A reference past
+    /// the end of its source or splitting a character is reported as
+    /// [`CopyForwardError::OffsetOverflow`] or
+    /// [`CopyForwardError::NotCharBoundary`].
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{approximate, Config, CopyForward};
+    ///
+    /// let compressed = approximate(&["Hello world", "Hello world today"], Config::default());
+    /// assert_eq!(compressed.verify(), Ok(()));
+    /// ```
+    fn verify(&self) -> Result<(), CopyForwardError> {
+        let mut buf = String::new();
+        for idx in 0..self.len() {
+            buf.clear();
+            for seg in self.segments_for(idx) {
+                match seg {
+                    Segment::Literal(text) => buf.push_str(text),
+                    &Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => {
+                        if message_idx > idx {
+                            return Err(CopyForwardError::RoundTrip { message: idx });
+                        }
+                        let text = span_text(self.message(message_idx), message_idx, start, len)?;
+                        if message_idx < idx {
+                            buf.push_str(text);
+                        } else if buf.get(start..start + len).is_some() {
+                            buf.extend_from_within(start..start + len);
+                        } else {
+                            return Err(CopyForwardError::RoundTrip { message: idx });
+                        }
+                    }
+                }
+            }
+            if buf != self.message(idx) {
+                return Err(CopyForwardError::RoundTrip { message: idx });
+            }
+        }
+        Ok(())
+    }
+
+    /// Object-safe [`render_with`](CopyForward::render_with).
+    fn render_with_dyn(&self, replacer: &mut DynReplacer<'_>) -> Vec<String>;
+
+    /// Object-safe [`render_into`](CopyForward::render_into).
+    fn render_into_dyn(&self, idx: usize, buf: &mut String, replacer: &mut DynReplacer<'_>);
+
+    /// Render message `idx` like [`render_with`](CopyForward::render_with),
+    /// appending to `buf`.
+    ///
+    /// Reusing one buffer across calls avoids allocating a `String` per message.
+    ///
+    /// # Panics
+    /// If `idx` is not a message index.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let messages = &["Hello world", "Hello world today"];
+    /// let compressed = exact(messages, Config::default());
+    /// let mut buf = String::new();
+    /// for idx in 0..messages.len() {
+    ///     buf.clear();
+    ///     compressed.render_into(idx, &mut buf, |_, _, _, text| text.to_string());
+    ///     assert_eq!(buf, messages[idx]);
+    /// }
+    /// ```
+    fn render_into<F>(&self, idx: usize, buf: &mut String, replacer: F)
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+        Self: Sized;
+
+    /// Render only message `idx`, like one entry of
+    /// [`render_with`](CopyForward::render_with).
+    ///
+    /// Materializes just that message, with the replacer called only for its
+    /// references.
+    ///
+    /// # Panics
+    /// If `idx` is not a message index.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let compressed = exact(&["Hello world", "Hello world today"], Config::default());
+    /// assert_eq!(compressed.render_message(1, |m, _, _, _| format!("[#{m}]")), "[#0] today");
+    /// ```
+    fn render_message<F>(&self, idx: usize, replacer: F) -> String
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+        Self: Sized,
+    {
+        let mut buf = String::new();
+        self.render_into(idx, &mut buf, replacer);
+        buf
+    }
+
+    /// [`render_message`](CopyForward::render_message), returning an error
+    /// instead of panicking when a reference of message `idx` names a message
+    /// that does not exist, runs past the end of its source or splits a
+    /// character, as segments from a hand-written model might.
+    ///
+    /// # Panics
+    /// If `idx` is not a message index.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let compressed = exact(&["Hello world", "Hello world today"], Config::default());
+    /// let rendered = compressed.try_render_message(1, |m, _, _, _| format!("[#{m}]"));
+    /// assert_eq!(rendered.unwrap(), "[#0] today");
+    /// ```
+    fn try_render_message<F>(&self, idx: usize, replacer: F) -> Result<String, CopyForwardError>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+        Self: Sized,
+    {
+        for seg in self.segments_for(idx) {
+            if let &Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } = seg
+            {
+                if message_idx >= self.len() {
+                    return Err(CopyForwardError::OffsetOverflow {
+                        message: message_idx,
+                        offset: start,
+                    });
+                }
+                span_text(self.message(message_idx), message_idx, start, len)?;
+            }
+        }
+        Ok(self.render_message(idx, replacer))
+    }
+
+    /// [`render_with`](CopyForward::render_with) with a replacer that can
+    /// fail; the first error stops rendering and is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let compressed = exact(&["Hello world", "Hello world today"], Config::default());
+    /// let authors = ["alice"];
+    /// let rendered = compressed.try_render_with(|m, _, _, _| match authors.get(m) {
+    ///     Some(author) => Ok(format!("[{author}]")),
+    ///     None => Err(format!("no author for message {m}")),
+    /// });
+    /// assert_eq!(rendered.unwrap()[1], "[alice] today");
+    /// ```
+    fn try_render_with<F, E>(&self, mut replacer: F) -> Result<Vec<String>, E>
+    where
+        F: FnMut(usize, usize, usize, &str) -> Result<String, E>,
+        Self: Sized,
+    {
+        (0..self.len())
+            .map(|idx| {
+                let mut out = String::new();
+                walk_segments(
+                    idx,
+                    self.segments_for(idx),
+                    |m| self.message(m),
+                    &mut out,
+                    |r| replacer(r.message_idx, r.start, r.len, r.text),
+                )?;
+                Ok(out)
+            })
+            .collect()
+    }
+
+    /// [`render_with`](CopyForward::render_with) with a replacer that also
+    /// learns which message it renders into and where; see
+    /// [`ReplaceContext`].
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let compressed = exact(&["Hello world", "Hi", "> Hello world"], Config::default());
+    /// let rendered = compressed.render_with_context(|r| {
+    ///     format!("[quoted in reply #{} from #{}]", r.target, r.message_idx)
+    /// });
+    /// assert_eq!(rendered[2], "> [quoted in reply #2 from #0]");
+    /// ```
+    fn render_with_context<F>(&self, mut replacer: F) -> Vec<String>
+    where
+        F: FnMut(ReplaceContext<'_>) -> String,
+        Self: Sized,
+    {
+        (0..self.len())
+            .map(|target| {
+                let mut out = String::new();
+                let Ok(()) = walk_segments(
+                    target,
+                    self.segments_for(target),
+                    |m| self.message(m),
+                    &mut out,
+                    |r| Ok::<_, Infallible>(replacer(r)),
+                );
+                out
+            })
+            .collect()
+    }
+
+    /// Render every message into one buffer, appending to `buf`.
+    ///
+    /// Returns the byte range of each message within `buf`.
+    fn render_all_into<F>(&self, buf: &mut String, replacer: F) -> Vec<Range<usize>>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+        Self: Sized;
+
+    /// Render every message into `writer`, back to back as with
+    /// [`render_all_into`](CopyForward::render_all_into).
+    ///
+    /// Returns the byte range of each message within what was written. One
+    /// message is held in memory at a time, so exporting a large thread to a
+    /// file or socket needs no `Vec<String>`. Wrap unbuffered writers in a
+    /// [`std::io::BufWriter`].
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let compressed = exact(&["Hello world", "Hello world today"], Config::default());
+    /// let mut out = Vec::new();
+    /// let ranges = compressed.render_to(&mut out, |_, _, _, _| "[REF]".to_string()).unwrap();
+    /// assert_eq!(out, b"Hello world[REF] today");
+    /// assert_eq!(ranges, [0..11, 11..22]);
+    /// ```
+    fn render_to<W, F>(&self, mut writer: W, mut replacer: F) -> io::Result<Vec<Range<usize>>>
+    where
+        W: io::Write,
+        F: FnMut(usize, usize, usize, &str) -> String,
+        Self: Sized,
+    {
+        let mut buf = String::new();
+        let mut ranges = Vec::with_capacity(self.len());
+        let mut written = 0;
+        for idx in 0..self.len() {
+            buf.clear();
+            self.render_into(idx, &mut buf, &mut replacer);
+            writer.write_all(buf.as_bytes())?;
+            ranges.push(written..written + buf.len());
+            written += buf.len();
+        }
+        Ok(ranges)
+    }
+
+    /// Render messages without copying text that comes out unchanged.
+    ///
+    /// Like [`render_with`](CopyForward::render_with), but the replacer returns a
+    /// [`Cow`]. A message whose references all come back as
+    /// `Cow::Borrowed(text)` of the very slice passed in, including any message
+    /// without references, is borrowed from the stored input instead of being
+    /// rebuilt. The identity render of a mostly-novel thread allocates nothing.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward};
+    /// use std::borrow::Cow;
+    ///
+    /// let messages = &["Hello world", "Hello world today"];
+    /// let compressed = exact(messages, Config::default());
+    /// let rendered = compressed.render_cow(|_, _, _, text| Cow::Borrowed(text));
+    /// assert!(rendered.iter().all(|m| matches!(m, Cow::Borrowed(_))));
+    /// assert_eq!(rendered, messages);
+    /// ```
+    fn render_cow<'a, F>(&'a self, replacer: F) -> Vec<Cow<'a, str>>
+    where
+        F: FnMut(usize, usize, usize, &'a str) -> Cow<'a, str>,
+        Self: Sized;
+
+    /// Render messages in parallel on the rayon thread pool.
+    ///
+    /// Same output as [`render_with`](CopyForward::render_with); the replacer
+    /// is shared across threads, so it must be `Fn + Sync`. Requires the
+    /// `rayon` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "rayon")] {
+    /// use copyforward::{exact, Config, CopyForward};
+    ///
+    /// let messages = &["Hello world", "Hello world today"];
+    /// let compressed = exact(messages, Config::default());
+    /// let rendered = compressed.par_render_with(|m, _, _, _| format!("[#{m}]"));
+    /// assert_eq!(rendered[1], "[#0] today");
+    /// # }
+    /// ```
+    #[cfg(feature = "rayon")]
+    fn par_render_with<F>(&self, replacer: F) -> Vec<String>
+    where
+        F: Fn(usize, usize, usize, &str) -> String + Sync,
+        Self: Sized + Sync,
+    {
+        use rayon::prelude::*;
+        (0..self.len())
+            .into_par_iter()
+            .map(|idx| {
+                let mut buf = String::new();
+                self.render_into(idx, &mut buf, &replacer);
+                buf
+            })
+            .collect()
+    }
 
     /// Render with a static replacement string for all references.
     ///
@@ -104,23 +764,150 @@ pub trait CopyForward {
     /// let redacted = compressed.render_with_static("[REFERENCE]");
     /// ```
     fn render_with_static(&self, replacement: &str) -> Vec<String> {
-        self.render_with(|_, _, _, _| replacement.to_string())
+        self.render_with_dyn(&mut |_, _, _, _| replacement.to_string())
+    }
+}
+
+/// Render `segments`, the segments of message `target`, onto `buf`: literals
+/// are copied and each reference is replaced by what `replace` returns for it.
+/// `source` yields the text of a referenced message; output offsets count
+/// from where `target` starts in `buf`.
+pub(crate) fn walk_segments<'a, E>(
+    target: usize,
+    segments: &[Segment],
+    source: impl Fn(usize) -> &'a str,
+    buf: &mut String,
+    mut replace: impl FnMut(ReplaceContext<'a>) -> Result<String, E>,
+) -> Result<(), E> {
+    let base = buf.len();
+    for seg in segments {
+        match seg {
+            Segment::Literal(text) => buf.push_str(text),
+            &Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                let replacement = replace(ReplaceContext {
+                    target,
+                    output_offset: buf.len() - base,
+                    message_idx,
+                    start,
+                    len,
+                    text: &source(message_idx)[start..start + len],
+                })?;
+                buf.push_str(&replacement);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that byte `offset` of `text`, the text of message `message`, is in
+/// bounds and on a `char` boundary.
+fn check_offset(text: &str, message: usize, offset: usize) -> Result<(), CopyForwardError> {
+    if offset > text.len() {
+        Err(CopyForwardError::OffsetOverflow { message, offset })
+    } else if !text.is_char_boundary(offset) {
+        Err(CopyForwardError::NotCharBoundary { message, offset })
+    } else {
+        Ok(())
+    }
+}
+
+/// The `len` bytes of `text`, the text of message `message`, from byte
+/// `start`, or the error slicing them would panic with.
+fn span_text(
+    text: &str,
+    message: usize,
+    start: usize,
+    len: usize,
+) -> Result<&str, CopyForwardError> {
+    let end = start
+        .checked_add(len)
+        .ok_or(CopyForwardError::OffsetOverflow {
+            message,
+            offset: start,
+        })?;
+    check_offset(text, message, end)?;
+    check_offset(text, message, start)?;
+    Ok(&text[start..end])
+}
+
+/// Segments covering bytes `range` of the message made of `segments`, with
+/// boundary segments split. `range` must lie on `char` boundaries.
+pub(crate) fn slice_message(segments: &[Segment], range: Range<usize>) -> Vec<Segment> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    for seg in segments {
+        let seg_len = match seg {
+            Segment::Literal(s) => s.len(),
+            Segment::Reference { len, .. } => *len,
+        };
+        let (lo, hi) = (range.start.max(pos), range.end.min(pos + seg_len));
+        if lo < hi {
+            out.push(match seg {
+                Segment::Literal(s) => Segment::Literal(s[lo - pos..hi - pos].into()),
+                Segment::Reference {
+                    message_idx, start, ..
+                } => Segment::Reference {
+                    message_idx: *message_idx,
+                    start: start + (lo - pos),
+                    len: hi - lo,
+                },
+            });
+        }
+        pos += seg_len;
+        if pos >= range.end {
+            break;
+        }
     }
+    out
 }
 
 /// Copy-forward interface specialized for token sequences (u32 token IDs).
-pub trait CopyForwardTokens {
+///
+/// Like [`CopyForward`], implementations are immutable once built and must be
+/// `Send + Sync` so they can be queried concurrently.
+pub trait CopyForwardTokens: Send + Sync {
     /// Get the compressed token segment representation.
     fn segments(&self) -> Vec<Vec<TokenSegment>>;
 
+    /// Token segments of message `idx`, borrowed from the model.
+    ///
+    /// Indices count only the non-`None` inputs, like [`segments`](CopyForwardTokens::segments).
+    fn segments_for(&self, idx: usize) -> &[TokenSegment];
+
+    /// Original tokens of message `idx`.
+    fn message(&self, idx: usize) -> &[u32];
+
+    /// Number of (non-`None`) messages.
+    fn len(&self) -> usize;
+
+    /// Whether the model holds no messages.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Crate version, algorithm and config that produced the segments; see
+    /// [`CopyForward::metadata`].
+    fn metadata(&self) -> Option<Metadata> {
+        None
+    }
+
     /// Render token messages by calling a replacer for each reference.
     fn render_with<F>(&self, replacer: F) -> Vec<Vec<u32>>
     where
-        F: FnMut(usize, usize, usize, &[u32]) -> Vec<u32>;
+        F: FnMut(usize, usize, usize, &[u32]) -> Vec<u32>,
+        Self: Sized;
+
+    /// Object-safe [`render_with`](CopyForwardTokens::render_with), for use
+    /// behind `Box<dyn CopyForwardTokens>`.
+    fn render_with_dyn(&self, replacer: &mut DynTokenReplacer<'_>) -> Vec<Vec<u32>>;
 
     /// Render with a static replacement for all references.
     fn render_with_static(&self, replacement: &[u32]) -> Vec<Vec<u32>> {
-        self.render_with(|_, _, _, _| replacement.to_vec())
+        self.render_with_dyn(&mut |_, _, _, _| replacement.to_vec())
     }
 }
 
@@ -142,6 +929,8 @@ pub trait CopyForwardTokens {
 /// let compressed = exact(&["test"], config);
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Config {
     /// Minimum match length required to create a reference.
     ///
@@ -156,6 +945,9 @@ pub struct Config {
     ///
     /// `None` considers all previous messages. Limiting lookback can improve
     /// speed for very long message sequences at the cost of some compression.
+    /// K-mers of messages that leave the window are evicted from the index, so
+    /// its size is bounded by the window rather than the whole history. `None`
+    /// inputs do not count toward the window.
     ///
     /// **Default:** None (unlimited)
     pub lookback: Option<usize>,
@@ -175,6 +967,185 @@ pub struct Config {
     ///
     /// **Default:** 64 candidates
     pub ncap: usize,
+
+    /// Re-extend references backward before coalescing in approximate
+    /// algorithms.
+    ///
+    /// The capped first pass only finds a match where its whole `cap_len`
+    /// window agrees with one stored source, so a quote often starts late or
+    /// is split between two sources. With this set, each reference grows
+    /// backward over the preceding literal as far as its source matches, and
+    /// over a preceding reference it can cover entirely; the coalescing pass
+    /// then merges what became contiguous. Forward, every match already runs
+    /// as far as its source agrees. Ignored by [`crate::exact()`].
+    ///
+    /// **Default:** false
+    pub reextend: bool,
+
+    /// Phrases that are never indexed or emitted as references (text mode).
+    ///
+    /// Use this for greetings, sign-offs and signature delimiters that appear in
+    /// nearly every message: they would otherwise crowd the hash buckets and
+    /// show up as many tiny references. Occurrences always stay literal, and no
+    /// reference spans across them. Token APIs ignore this setting.
+    ///
+    /// **Default:** empty
+    pub stop_phrases: Vec<String>,
+
+    /// Expected distinct k-mers per indexed window, used to pre-size the
+    /// k-mer hash table (internal tuning).
+    ///
+    /// The table starts with room for this fraction of the windows that will
+    /// be indexed. Raise it toward 1.0 for novel text to avoid rehashing during
+    /// the build; lower it for highly repetitive threads to save memory.
+    ///
+    /// **Default:** 0.5
+    pub kmer_density: f64,
+
+    /// Skip indexing parts of a message that it took as references.
+    ///
+    /// Their text is already indexed where it first appeared, so deeply
+    /// quoted threads build a much smaller table. Matches that start inside
+    /// such a part then point at the original source instead, so with
+    /// [`lookback`](Config::lookback) they are lost once that source leaves
+    /// the window.
+    ///
+    /// **Default:** false
+    pub skip_referenced_regions: bool,
+
+    /// Messages shorter than this are neither indexed nor matched.
+    ///
+    /// One-line acknowledgements ("+1", "thanks") rarely yield useful
+    /// references but can dominate message counts; below this length a
+    /// message is emitted as a single literal and never becomes a reference
+    /// source. Measured like [`min_match_len`](Config::min_match_len).
+    ///
+    /// **Default:** 0 (every message takes part)
+    pub min_indexed_len: usize,
+
+    /// Collapse verbatim repeats of earlier messages before matching.
+    ///
+    /// Crossposts, resent notices and bot output repeat whole messages. With
+    /// this set, a message identical to an earlier one still in the
+    /// [`lookback`](Config::lookback) window becomes a single reference to it
+    /// without being searched, and its k-mers are never indexed, so repeats
+    /// cost neither matching time nor table space. Messages shorter than
+    /// [`min_match_len`](Config::min_match_len) are left alone, as are
+    /// repeats whose reference would exceed
+    /// [`max_ref_depth`](Config::max_ref_depth). Ignored by
+    /// [`RollingCopyForward`](crate::rolling::RollingCopyForward).
+    ///
+    /// **Default:** false
+    pub dedup_messages: bool,
+
+    /// Bucket messages by MinHash similarity so lookups only consult the
+    /// index partitions of plausibly related messages; see [`Lsh`].
+    ///
+    /// In wide, topic-diverse threads common k-mers otherwise fill buckets
+    /// with unrelated messages, and the candidate caps of both algorithms
+    /// are spent on them. Matches between messages that share no band are
+    /// lost. Ignored by [`RollingCopyForward`](crate::rolling::RollingCopyForward).
+    ///
+    /// **Default:** None (one partition)
+    pub lsh: Option<Lsh>,
+
+    /// Prefer sources with shallower reference chains among candidates whose
+    /// lengths are within this many units of each other.
+    ///
+    /// By default the longest candidate wins, which in deeply quoted threads
+    /// is often a quote of a quote. With a slack set, a match into the
+    /// original text beats a slightly longer one into a later quote of it, so
+    /// segments point closer to where text first appeared. Measured like
+    /// [`min_match_len`](Config::min_match_len). Ignored by
+    /// [`RollingCopyForward`](crate::rolling::RollingCopyForward).
+    ///
+    /// **Default:** None (longest match wins)
+    pub shallow_preference: Option<usize>,
+
+    /// Which source wins among candidates matching equally far; see
+    /// [`TieBreak`].
+    ///
+    /// Applied after length and
+    /// [`shallow_preference`](Config::shallow_preference). Ignored by
+    /// [`crate::optimal()`] and
+    /// [`RollingCopyForward`](crate::rolling::RollingCopyForward). The
+    /// approximate engine indexes each [`cap_len`](Config::cap_len) window
+    /// once per partition, so it only sees ties between the partitions of
+    /// [`lsh`](Config::lsh).
+    ///
+    /// **Default:** [`TieBreak::PreferEarliest`]
+    pub tie_break: TieBreak,
+
+    /// Longest reference chain a segment may start (`1` means references
+    /// only point at literal text).
+    ///
+    /// Storage that resolves references at read time pays one lookup per
+    /// level, so bounding the depth bounds resolution cost. A match that would
+    /// exceed the limit is split along its source's segments: parts that are
+    /// themselves references point at their sources directly, and parts that
+    /// still cannot be referenced within the limit, or whose sources fall
+    /// outside [`lookback`](Config::lookback), stay literal. `Some(0)`
+    /// emits no references at all. Ignored by
+    /// [`RollingCopyForward`](crate::rolling::RollingCopyForward).
+    ///
+    /// **Default:** None (unbounded)
+    pub max_ref_depth: Option<usize>,
+
+    /// Also match against the already segmented part of the message being
+    /// segmented, LZ77-style.
+    ///
+    /// Boilerplate repeated inside one message, such as a signature pasted
+    /// twice or a table of identical rows, then becomes references to the
+    /// message itself. Such a reference's span always ends at or before the
+    /// reference's own position. Unless [`tie_break`](Config::tie_break)
+    /// prefers the most recent source, cross-message sources win ties. Only
+    /// used by
+    /// [`crate::exact()`] and [`crate::exact_tokens()`], and not while
+    /// [`shallow_preference`](Config::shallow_preference) or
+    /// [`max_ref_depth`](Config::max_ref_depth) is set.
+    ///
+    /// **Default:** false
+    pub self_references: bool,
+
+    /// Spell out references that save fewer than this many bytes of encoded
+    /// size over their text.
+    ///
+    /// A short reference between long literals costs its message, offset and
+    /// length varints plus the segment breaks it introduces, which can exceed
+    /// the few characters it replaces. With this set, each reference whose
+    /// [`codec`](crate::codec) encoding, merged with the literals around it,
+    /// is not at least this much smaller than the merged text becomes a
+    /// literal. `Some(1)` drops every reference that does not pay for itself;
+    /// larger values also drop marginal ones. Only used by
+    /// [`crate::exact()`], [`crate::approximate()`], [`crate::optimal()`],
+    /// their `push_message` and [`crate::process_iter()`]; see also [`peephole::optimize`](crate::peephole::optimize)
+    /// for other models.
+    ///
+    /// **Default:** None (every match becomes a reference)
+    pub min_encoded_gain: Option<usize>,
+
+    /// Cache of per-message rolling and k-mer hashes shared across runs.
+    ///
+    /// Set this when compressing many threads that repeat whole messages, such
+    /// as forwarded boilerplate or crossposts; see [`HashCache`]. Not serialized.
+    ///
+    /// **Default:** None
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub hash_cache: Option<HashCache>,
+
+    /// Callback told how many messages are segmented, which can also cancel
+    /// the run; see [`Progress`]. Not serialized.
+    ///
+    /// **Default:** None
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub progress: Option<Progress>,
+
+    /// Collector of the engines' matching decisions, for tuning the other
+    /// settings; see [`Trace`]. Not serialized.
+    ///
+    /// **Default:** None
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub trace: Option<Trace>,
 }
 
 impl Default for Config {
@@ -185,6 +1156,261 @@ impl Default for Config {
             lookback: None,
             cap_len: 64,
             ncap: 64,
+            reextend: false,
+            stop_phrases: Vec::new(),
+            kmer_density: 0.5,
+            skip_referenced_regions: false,
+            min_indexed_len: 0,
+            dedup_messages: false,
+            lsh: None,
+            shallow_preference: None,
+            tie_break: TieBreak::PreferEarliest,
+            max_ref_depth: None,
+            self_references: false,
+            min_encoded_gain: None,
+            hash_cache: None,
+            progress: None,
+            trace: None,
+        }
+    }
+}
+
+impl Config {
+    /// Check for settings the engines accept but that cannot produce
+    /// references, such as a `min_match_len` of 0.
+    ///
+    /// The constructors take any `Config`; call this first, or use
+    /// [`crate::try_compress`], to reject such settings instead.
+    ///
+    /// ```
+    /// use copyforward::{Config, CopyForwardError};
+    ///
+    /// let config = Config { min_match_len: 0, ..Config::default() };
+    /// assert_eq!(
+    ///     config.validate(),
+    ///     Err(CopyForwardError::InvalidConfig {
+    ///         field: "min_match_len",
+    ///         requirement: "at least 1",
+    ///     })
+    /// );
+    /// assert!(Config::default().validate().is_ok());
+    /// ```
+    pub fn validate(&self) -> Result<(), CopyForwardError> {
+        let invalid =
+            |field, requirement| Err(CopyForwardError::InvalidConfig { field, requirement });
+        if self.min_match_len == 0 {
+            return invalid("min_match_len", "at least 1");
+        }
+        if self.cap_len == 0 {
+            return invalid("cap_len", "at least 1");
+        }
+        if self.ncap == 0 {
+            return invalid("ncap", "at least 1");
+        }
+        if !(self.kmer_density.is_finite() && self.kmer_density > 0.0) {
+            return invalid("kmer_density", "a positive finite number");
+        }
+        if let Some(lsh) = &self.lsh {
+            if lsh.bands == 0 {
+                return invalid("lsh.bands", "at least 1");
+            }
+            if lsh.rows == 0 {
+                return invalid("lsh.rows", "at least 1");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error from the fallible entry points: [`Config::validate`],
+/// [`CopyForward::verify`], [`CopyForward::try_slice_segments`],
+/// [`CopyForward::try_render_message`], [`crate::try_compress`],
+/// [`crate::compress_verified`] and the models' `try_with_config`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CopyForwardError {
+    /// A [`Config`] field holds a value the engines cannot use.
+    InvalidConfig {
+        /// Name of the field, e.g. `min_match_len`.
+        field: &'static str,
+        /// What the field must be, e.g. `at least 1`.
+        requirement: &'static str,
+    },
+    /// Message `message` does not render back to its input; see
+    /// [`CopyForward::verify`].
+    RoundTrip { message: usize },
+    /// Byte `offset` lies past the end of message `message`, which may not
+    /// exist, or past the end of a range it starts.
+    OffsetOverflow { message: usize, offset: usize },
+    /// Byte `offset` of message `message` falls inside a character.
+    NotCharBoundary { message: usize, offset: usize },
+    /// There were no messages to compress.
+    EmptyInput,
+}
+
+impl fmt::Display for CopyForwardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CopyForwardError::InvalidConfig { field, requirement } => {
+                write!(f, "{field} must be {requirement}")
+            }
+            CopyForwardError::RoundTrip { message } => {
+                write!(f, "message {message} does not render back to its input")
+            }
+            CopyForwardError::OffsetOverflow { message, offset } => {
+                write!(
+                    f,
+                    "byte offset {offset} is out of range for message {message}"
+                )
+            }
+            CopyForwardError::NotCharBoundary { message, offset } => {
+                write!(
+                    f,
+                    "byte offset {offset} of message {message} is not a char boundary"
+                )
+            }
+            CopyForwardError::EmptyInput => write!(f, "no messages to compress"),
+        }
+    }
+}
+
+impl std::error::Error for CopyForwardError {}
+
+/// MinHash banding for [`Config::lsh`].
+///
+/// Each message gets `bands * rows` MinHash values over its k-mers. Its k-mers
+/// are indexed once per band, in the partition named by that band's values,
+/// and a message is matched only against partitions it belongs to. Two
+/// messages whose k-mer sets have Jaccard similarity `s` share a partition
+/// with probability `1 - (1 - s^rows)^bands`. More bands raise recall but
+/// store every k-mer `bands` times; more rows make partitions stricter.
+///
+/// # Example
+/// ```
+/// use copyforward::{exact, Config, CopyForward, Lsh};
+///
+/// let config = Config {
+///     lsh: Some(Lsh::default()),
+///     ..Config::default()
+/// };
+/// let messages = ["Hello world", "Hello world, again"];
+/// let compressed = exact(&messages, config);
+/// assert_eq!(compressed.render_with(|_, _, _, text| text.to_string()), messages);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lsh {
+    /// Number of bands, and so of partitions per message.
+    ///
+    /// **Default:** 4
+    pub bands: usize,
+    /// MinHash values per band.
+    ///
+    /// **Default:** 1
+    pub rows: usize,
+}
+
+impl Default for Lsh {
+    fn default() -> Self {
+        Lsh { bands: 4, rows: 1 }
+    }
+}
+
+/// Choice among equally long candidate sources, set as [`Config::tie_break`].
+///
+/// Text repeated across a thread, such as a notice reposted or quoted by
+/// several replies, can be referenced from any of its copies. Pointing at the
+/// latest copy keeps references local to recent messages; keeping to the
+/// source of the previous reference gathers a reply's quotes in one message
+/// and lets consecutive matches merge into one reference.
+///
+/// # Example
+/// ```
+/// use copyforward::{exact, Config, CopyForward, Segment, TieBreak};
+///
+/// let config = Config {
+///     tie_break: TieBreak::PreferMostRecent,
+///     ..Config::default()
+/// };
+/// let messages = ["Hello world", "Hello world", "> Hello world"];
+/// let compressed = exact(&messages, config);
+/// assert_eq!(
+///     compressed.segments_for(2)[1],
+///     Segment::Reference { message_idx: 1, start: 0, len: 11 }
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TieBreak {
+    /// The earliest source, by message and then position.
+    #[default]
+    PreferEarliest,
+    /// The latest source, by message and then position.
+    PreferMostRecent,
+    /// A source continuing the previous reference of the message, then any
+    /// source in the same message as that reference, then the earliest.
+    PreferSameSourceAsPrevSegment,
+}
+
+/// Progress callback for long segmentation runs, set as [`Config::progress`].
+///
+/// The callback receives `(done, total)` message counts every `every`
+/// messages and after the last one. Returning `false` cancels the run: the
+/// remaining messages are emitted as single literals, so the result is still
+/// a valid segmentation that renders back to the input.
+///
+/// # Example
+/// ```
+/// use copyforward::{exact, Config, CopyForward, Progress, Segment};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let calls = Arc::new(AtomicUsize::new(0));
+/// let seen = Arc::clone(&calls);
+/// let config = Config {
+///     progress: Some(Progress::new(2, move |done, _total| {
+///         seen.fetch_add(1, Ordering::Relaxed);
+///         done < 2
+///     })),
+///     ..Config::default()
+/// };
+/// let messages = ["Hello world", "Hello world", "Hello world", "Hello world"];
+/// let compressed = exact(&messages, config);
+/// assert_eq!(calls.load(Ordering::Relaxed), 1);
+/// assert_eq!(compressed.segments_for(3), [Segment::Literal("Hello world".into())]);
+/// ```
+#[derive(Clone)]
+pub struct Progress {
+    every: usize,
+    callback: Arc<dyn Fn(usize, usize) -> bool + Send + Sync>,
+}
+
+impl Progress {
+    /// Call `callback(done, total)` every `every` messages (at least 1).
+    pub fn new<F>(every: usize, callback: F) -> Progress
+    where
+        F: Fn(usize, usize) -> bool + Send + Sync + 'static,
+    {
+        Progress {
+            every: every.max(1),
+            callback: Arc::new(callback),
+        }
+    }
+
+    /// Report `done` of `total` messages; false once the run should stop.
+    pub(crate) fn report(&self, done: usize, total: usize) -> bool {
+        if done.is_multiple_of(self.every) || done == total {
+            (self.callback)(done, total)
+        } else {
+            true
         }
     }
 }
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("every", &self.every)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,62 @@
+//! Corpus-level types shared by the input readers.
+//!
+//! Readers such as [`crate::maildir`] group raw input into [`Thread`]s, each of
+//! which is compressed independently with copy-forward; [`compress_threads`]
+//! does so for a whole corpus on all cores.
+
+/// An ordered sequence of messages that belong together (a mail thread, a
+/// support ticket, a forum topic).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    /// Identifier of the thread (for example the root `Message-ID`).
+    pub id: String,
+    /// Message bodies in thread order.
+    pub messages: Vec<String>,
+}
+
+impl Thread {
+    /// Create a thread from an id and its messages.
+    pub fn new(id: impl Into<String>, messages: Vec<String>) -> Self {
+        Thread {
+            id: id.into(),
+            messages,
+        }
+    }
+}
+
+/// Apply `compress` to every thread, spreading threads over all available
+/// cores. Results are in input order.
+///
+/// # Example
+/// ```
+/// use copyforward::corpus::compress_threads;
+/// use copyforward::{exact, Config, CopyForward, Stats};
+///
+/// let threads = vec![
+///     vec!["Hello world", "Hello world today"],
+///     vec!["Lunch at noon?", "Lunch at noon works"],
+/// ];
+/// let results = compress_threads(&threads, |t| exact(t, Config::default()));
+/// let total: Stats = results.iter().map(|r| Stats::from_segments(&r.segments())).sum();
+/// assert_eq!(total.messages, 4);
+/// ```
+pub fn compress_threads<T, R, F>(threads: &[T], compress: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = threads.len().div_ceil(workers).max(1);
+    let compress = &compress;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = threads
+            .chunks(chunk)
+            .map(|part| scope.spawn(move || part.iter().map(compress).collect::<Vec<R>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("compression thread panicked"))
+            .collect()
+    })
+}
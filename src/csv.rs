@@ -0,0 +1,155 @@
+//! CSV/TSV ingestion with column selection.
+//!
+//! Exported support tickets and forum dumps usually arrive as one row per
+//! message. [`read_threads`] picks the text column and, optionally, a
+//! thread-id column, and groups rows into [`Thread`]s in first-seen order.
+//! Quoting follows RFC 4180: fields may be wrapped in `"`, embedded quotes
+//! are doubled, and quoted fields may span lines.
+
+use crate::corpus::Thread;
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+/// Selects a column by header name or by 0-based position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    /// Column whose header matches this name exactly.
+    Name(String),
+    /// Column at this 0-based index.
+    Index(usize),
+}
+
+impl Column {
+    fn resolve(&self, headers: Option<&[String]>) -> io::Result<usize> {
+        match self {
+            Column::Index(i) => Ok(*i),
+            Column::Name(name) => headers
+                .and_then(|h| h.iter().position(|c| c == name))
+                .ok_or_else(|| invalid(format!("no column named '{name}'"))),
+        }
+    }
+}
+
+/// Options for [`read_threads`].
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Field delimiter, `b','` for CSV or `b'\t'` for TSV.
+    pub delimiter: u8,
+    /// Whether the first row holds column names.
+    pub has_headers: bool,
+    /// Column holding the message text.
+    pub text_column: Column,
+    /// Column holding the thread id; `None` puts every row in one thread.
+    pub thread_column: Option<Column>,
+}
+
+impl Default for CsvOptions {
+    /// Comma-delimited with a header row, text in column 0, no thread column.
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            has_headers: true,
+            text_column: Column::Index(0),
+            thread_column: None,
+        }
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Split delimited text into records of fields.
+///
+/// A trailing newline does not produce an empty record; `\r\n` line endings
+/// are accepted.
+pub fn parse_records(input: &str, delimiter: u8) -> io::Result<Vec<Vec<String>>> {
+    let delimiter = delimiter as char;
+    let mut records = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut chars = input.chars().peekable();
+    let mut in_quotes = false;
+    let mut line = 1usize;
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+                line += 1;
+            }
+            _ if c == delimiter => record.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(invalid(format!("unterminated quoted field at line {line}")));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Read delimited rows and group them into threads.
+///
+/// Without a thread column every row belongs to a single thread named `all`.
+/// Invalid UTF-8 is replaced lossily; a row missing a selected column is an
+/// [`io::ErrorKind::InvalidData`] error.
+pub fn read_threads<R: Read>(mut reader: R, options: &CsvOptions) -> io::Result<Vec<Thread>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut records = parse_records(&String::from_utf8_lossy(&bytes), options.delimiter)?;
+
+    let headers = if options.has_headers && !records.is_empty() {
+        Some(records.remove(0))
+    } else {
+        None
+    };
+    let text_idx = options.text_column.resolve(headers.as_deref())?;
+    let thread_idx = match &options.thread_column {
+        Some(col) => Some(col.resolve(headers.as_deref())?),
+        None => None,
+    };
+
+    let first_row = if headers.is_some() { 2 } else { 1 };
+    let mut threads: Vec<Thread> = Vec::new();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+    for (row, mut record) in records.into_iter().enumerate() {
+        let missing =
+            |idx: usize| invalid(format!("row {} has no column {}", row + first_row, idx));
+        let id = match thread_idx {
+            Some(idx) => record.get(idx).cloned().ok_or_else(|| missing(idx))?,
+            None => "all".to_string(),
+        };
+        let text = record
+            .get_mut(text_idx)
+            .map(std::mem::take)
+            .ok_or_else(|| missing(text_idx))?;
+        let t = *by_id.entry(id).or_insert_with_key(|id| {
+            threads.push(Thread::new(id.clone(), Vec::new()));
+            threads.len() - 1
+        });
+        threads[t].messages.push(text);
+    }
+    Ok(threads)
+}
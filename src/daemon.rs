@@ -0,0 +1,239 @@
+//! Long-running NDJSON service mode (requires the `server` feature).
+//!
+//! [`Daemon::run`] reads one JSON command per line and writes one JSON response
+//! per line, flushing after each, so a supervising process can drive it over
+//! stdin/stdout (`copyforward-server --stdio`). Commands are tagged by `cmd`:
+//!
+//! ```json
+//! {"cmd": "add_thread", "thread": "t1", "messages": ["Hello world"], "approximate": false, "config": {"min_match_len": 4}}
+//! {"cmd": "add_message", "thread": "t1", "message": "Hello world today"}
+//! {"cmd": "query", "thread": "t1"}
+//! {"cmd": "query"}
+//! {"cmd": "render", "thread": "t1", "replacement": "[...]"}
+//! ```
+//!
+//! - `add_thread` replaces the thread and responds with its `stats`.
+//! - `add_message` appends to a thread (created with default settings if new)
+//!   and responds with the new message's `index` and `segments`.
+//! - `query` responds with a thread's `segments`, `stats` and the `metadata`
+//!   that produced them, or without `thread` with all thread ids and the
+//!   total `stats`.
+//! - `render` responds with the thread's `messages`, optionally with every
+//!   reference replaced.
+//!
+//! Every response carries `"ok": true` or `"ok": false` with an `error`
+//! message, and echoes the request's `id` field if present.
+
+use crate::core::{Config, CopyForward, Segment};
+use crate::metadata::Metadata;
+use crate::stats::Stats;
+use crate::{approximate, exact};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Command {
+    AddThread {
+        thread: String,
+        messages: Vec<Option<String>>,
+        #[serde(default)]
+        approximate: bool,
+        #[serde(default)]
+        config: Box<Config>,
+    },
+    AddMessage {
+        thread: String,
+        message: Option<String>,
+    },
+    Query {
+        #[serde(default)]
+        thread: Option<String>,
+    },
+    Render {
+        thread: String,
+        #[serde(default)]
+        replacement: Option<String>,
+    },
+}
+
+#[derive(Debug, Default)]
+struct ThreadState {
+    messages: Vec<Option<String>>,
+    approximate: bool,
+    config: Config,
+    segments: Vec<Vec<Segment>>,
+}
+
+impl ThreadState {
+    fn recompute(&mut self) {
+        self.segments = if self.approximate {
+            approximate(&self.messages, self.config.clone()).segments()
+        } else {
+            exact(&self.messages, self.config.clone()).segments()
+        };
+    }
+
+    fn metadata(&self) -> Metadata {
+        let algorithm = if self.approximate {
+            "approximate"
+        } else {
+            "exact"
+        };
+        Metadata::new(algorithm, &self.config)
+    }
+}
+
+/// In-memory state of the NDJSON service: compressed threads by id.
+#[derive(Debug, Default)]
+pub struct Daemon {
+    threads: BTreeMap<String, ThreadState>,
+}
+
+impl Daemon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process one request line and return the response line (without newline).
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::daemon::Daemon;
+    ///
+    /// let mut daemon = Daemon::new();
+    /// daemon.handle_line(r#"{"cmd": "add_thread", "thread": "t", "messages": ["Hello world"]}"#);
+    /// let resp = daemon.handle_line(r#"{"cmd": "add_message", "thread": "t", "message": "Hello world!"}"#);
+    /// assert!(resp.contains(r#""ok":true"#));
+    /// ```
+    pub fn handle_line(&mut self, line: &str) -> String {
+        let mut resp = match serde_json::from_str::<Value>(line) {
+            Ok(request) => {
+                let id = request.get("id").cloned();
+                let mut resp = match serde_json::from_value::<Command>(request) {
+                    Ok(cmd) => self.execute(cmd),
+                    Err(e) => Err(e.to_string()),
+                }
+                .unwrap_or_else(|e| json!({ "ok": false, "error": e }));
+                if let Some(id) = id {
+                    resp["id"] = id;
+                }
+                resp
+            }
+            Err(e) => json!({ "ok": false, "error": e.to_string() }),
+        };
+        if resp.get("ok").is_none() {
+            resp["ok"] = Value::Bool(true);
+        }
+        resp.to_string()
+    }
+
+    fn execute(&mut self, cmd: Command) -> Result<Value, String> {
+        match cmd {
+            Command::AddThread {
+                thread,
+                messages,
+                approximate,
+                config,
+            } => {
+                let mut state = ThreadState {
+                    messages,
+                    approximate,
+                    config: *config,
+                    segments: Vec::new(),
+                };
+                state.recompute();
+                let stats = Stats::from_segments(&state.segments);
+                self.threads.insert(thread, state);
+                Ok(json!({ "stats": stats }))
+            }
+            Command::AddMessage { thread, message } => {
+                let state = self.threads.entry(thread).or_default();
+                state.messages.push(message);
+                state.recompute();
+                let index = state.segments.len() - 1;
+                Ok(json!({ "index": index, "segments": state.segments[index] }))
+            }
+            Command::Query {
+                thread: Some(thread),
+            } => {
+                let state = self.thread(&thread)?;
+                Ok(json!({
+                    "segments": state.segments,
+                    "stats": Stats::from_segments(&state.segments),
+                    "metadata": state.metadata(),
+                }))
+            }
+            Command::Query { thread: None } => {
+                let total: Stats = self
+                    .threads
+                    .values()
+                    .map(|state| Stats::from_segments(&state.segments))
+                    .sum();
+                let threads: Vec<&String> = self.threads.keys().collect();
+                Ok(json!({ "threads": threads, "stats": total }))
+            }
+            Command::Render {
+                thread,
+                replacement,
+            } => {
+                let state = self.thread(&thread)?;
+                let messages =
+                    render_segments(&state.messages, &state.segments, replacement.as_deref());
+                Ok(json!({ "messages": messages }))
+            }
+        }
+    }
+
+    fn thread(&self, thread: &str) -> Result<&ThreadState, String> {
+        self.threads
+            .get(thread)
+            .ok_or_else(|| format!("unknown thread '{thread}'"))
+    }
+
+    /// Serve requests from `input` until end of input, writing one response per line.
+    ///
+    /// Blank lines are ignored.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            writeln!(output, "{}", self.handle_line(&line))?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Render a thread's segments against its original messages, optionally
+/// replacing every reference with `replacement`.
+fn render_segments(
+    messages: &[Option<String>],
+    segments: &[Vec<Segment>],
+    replacement: Option<&str>,
+) -> Vec<String> {
+    segments
+        .iter()
+        .map(|segs| {
+            let mut out = String::new();
+            for seg in segs {
+                match seg {
+                    Segment::Literal(s) => out.push_str(s),
+                    Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => {
+                        let source = messages[*message_idx].as_deref().unwrap_or_default();
+                        out.push_str(replacement.unwrap_or(&source[*start..start + len]));
+                    }
+                }
+            }
+            out
+        })
+        .collect()
+}
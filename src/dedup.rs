@@ -0,0 +1,270 @@
+//! One-call deduplication of document collections for dataset building.
+//!
+//! Documents are processed in order; any span of at least
+//! [`DedupOptions::min_span_len`] characters that already appeared in an
+//! earlier document is removed or masked, so each piece of text survives only
+//! at its first occurrence.
+//!
+//! # Example
+//! ```
+//! use copyforward::dedup::{dedup, DedupOptions};
+//!
+//! let docs = [
+//!     "The quick brown fox jumps over the lazy dog.",
+//!     "> The quick brown fox jumps over the lazy dog.\nIndeed it does.",
+//! ];
+//! let opts = DedupOptions { min_span_len: 16, ..DedupOptions::default() };
+//! let out = dedup(&docs, &opts);
+//! assert_eq!(out[0].text, docs[0]);
+//! assert_eq!(out[1].text, "> \nIndeed it does.");
+//! ```
+//!
+//! Tokenized datasets can be deduplicated without detokenizing via
+//! [`dedup_tokens`], where lengths and spans are counted in tokens.
+
+use crate::core::{Config, CopyForward, CopyForwardTokens, Segment, TokenSegment};
+use crate::{MessageLike, TokenLike, approximate, approximate_tokens, exact, exact_tokens};
+use std::ops::Range;
+
+/// What to do with a duplicated span.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DedupMode {
+    /// Drop the span entirely.
+    #[default]
+    Remove,
+    /// Replace each maximal run of duplicated text with this string.
+    Mask(String),
+}
+
+/// Thresholds for [`dedup`].
+#[derive(Debug, Clone)]
+pub struct DedupOptions {
+    /// Minimum length, in characters, of a duplicated span to remove; shorter
+    /// repeats are kept.
+    ///
+    /// **Default:** 50
+    pub min_span_len: usize,
+    /// Drop a document entirely (empty `text`, `dropped: true`) when more than
+    /// this fraction of its bytes is duplicated. `1.0` never drops.
+    ///
+    /// **Default:** 1.0
+    pub max_duplicate_fraction: f64,
+    /// How duplicated spans are removed.
+    pub mode: DedupMode,
+    /// Use [`approximate()`] instead of [`exact()`] for large corpora.
+    pub approximate: bool,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        DedupOptions {
+            min_span_len: 50,
+            max_duplicate_fraction: 1.0,
+            mode: DedupMode::Remove,
+            approximate: false,
+        }
+    }
+}
+
+/// A document after deduplication.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DedupedDocument {
+    /// Text with duplicated spans removed or masked.
+    pub text: String,
+    /// Bytes of the original document that duplicate earlier documents.
+    pub duplicate_bytes: usize,
+    /// `duplicate_bytes` as a fraction of the original length (0.0 when empty).
+    pub duplicate_fraction: f64,
+    /// The document exceeded [`DedupOptions::max_duplicate_fraction`].
+    pub dropped: bool,
+}
+
+/// Deduplicate `documents` in order, returning one result per input.
+///
+/// `None` entries yield empty documents.
+pub fn dedup<M: MessageLike>(documents: &[M], options: &DedupOptions) -> Vec<DedupedDocument> {
+    let config = Config {
+        min_match_len: options.min_span_len.max(1),
+        ..Config::default()
+    };
+    let segments = if options.approximate {
+        approximate(documents, config).segments()
+    } else {
+        exact(documents, config).segments()
+    };
+    segments
+        .iter()
+        .map(|segs| dedup_document(segs, options))
+        .collect()
+}
+
+fn dedup_document(segments: &[Segment], options: &DedupOptions) -> DedupedDocument {
+    let mut text = String::new();
+    let mut total = 0;
+    let mut duplicate_bytes = 0;
+    let mut in_duplicate = false;
+    for seg in segments {
+        match seg {
+            Segment::Literal(s) => {
+                text.push_str(s);
+                total += s.len();
+                in_duplicate = false;
+            }
+            Segment::Reference { len, .. } => {
+                total += len;
+                duplicate_bytes += len;
+                if let DedupMode::Mask(mask) = &options.mode
+                    && !in_duplicate
+                {
+                    text.push_str(mask);
+                }
+                in_duplicate = true;
+            }
+        }
+    }
+    let duplicate_fraction = if total == 0 {
+        0.0
+    } else {
+        duplicate_bytes as f64 / total as f64
+    };
+    let dropped = duplicate_fraction > options.max_duplicate_fraction;
+    if dropped {
+        text.clear();
+    }
+    DedupedDocument {
+        text,
+        duplicate_bytes,
+        duplicate_fraction,
+        dropped,
+    }
+}
+
+/// Thresholds for [`dedup_tokens`]; lengths are in tokens.
+#[derive(Debug, Clone)]
+pub struct TokenDedupOptions {
+    /// Minimum length, in tokens, of a duplicated span to remove.
+    ///
+    /// **Default:** 32
+    pub min_span_len: usize,
+    /// Drop a document entirely when more than this fraction of its tokens is
+    /// duplicated. `1.0` never drops.
+    ///
+    /// **Default:** 1.0
+    pub max_duplicate_fraction: f64,
+    /// Replace each maximal run of duplicated tokens with these tokens instead
+    /// of removing it.
+    pub mask: Option<Vec<u32>>,
+    /// Use [`approximate_tokens()`] instead of [`exact_tokens()`].
+    pub approximate: bool,
+}
+
+impl Default for TokenDedupOptions {
+    fn default() -> Self {
+        TokenDedupOptions {
+            min_span_len: 32,
+            max_duplicate_fraction: 1.0,
+            mask: None,
+            approximate: false,
+        }
+    }
+}
+
+/// A token sequence after deduplication.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DedupedTokens {
+    /// Tokens with duplicated spans removed or masked.
+    pub tokens: Vec<u32>,
+    /// Duplicated token ranges of the original sequence, in order.
+    pub duplicate_spans: Vec<Range<usize>>,
+    /// Total tokens covered by `duplicate_spans`.
+    pub duplicate_tokens: usize,
+    /// `duplicate_tokens` as a fraction of the original length (0.0 when empty).
+    pub duplicate_fraction: f64,
+    /// The document exceeded [`TokenDedupOptions::max_duplicate_fraction`].
+    pub dropped: bool,
+}
+
+/// Deduplicate token-id sequences in order, returning one result per input.
+///
+/// Works directly on `u32` ids, so arrays from a tokenized dataset never need
+/// to be detokenized. `None` entries yield empty results.
+///
+/// # Example
+/// ```
+/// use copyforward::dedup::{dedup_tokens, TokenDedupOptions};
+///
+/// let docs: Vec<Vec<u32>> = vec![vec![1, 2, 3, 4, 5, 6], vec![7, 1, 2, 3, 4, 5, 6, 8]];
+/// let opts = TokenDedupOptions { min_span_len: 4, mask: Some(vec![0]), ..TokenDedupOptions::default() };
+/// let out = dedup_tokens(&docs, &opts);
+/// assert_eq!(out[1].tokens, [7, 0, 8]);
+/// assert_eq!(out[1].duplicate_spans, [1..7]);
+/// ```
+pub fn dedup_tokens<T: TokenLike>(
+    documents: &[T],
+    options: &TokenDedupOptions,
+) -> Vec<DedupedTokens> {
+    let config = Config {
+        min_match_len: options.min_span_len.max(1),
+        ..Config::default()
+    };
+    // The token engines skip `None` entries; realign their output afterwards.
+    let segments = if options.approximate {
+        approximate_tokens(documents, config).segments()
+    } else {
+        exact_tokens(documents, config).segments()
+    };
+    let mut segments = segments.iter();
+    documents
+        .iter()
+        .map(|doc| match doc.as_tokens() {
+            Some(_) => dedup_token_document(
+                segments.next().expect("one segmentation per document"),
+                options,
+            ),
+            None => dedup_token_document(&[], options),
+        })
+        .collect()
+}
+
+fn dedup_token_document(segments: &[TokenSegment], options: &TokenDedupOptions) -> DedupedTokens {
+    let mut tokens = Vec::new();
+    let mut duplicate_spans: Vec<Range<usize>> = Vec::new();
+    let mut pos = 0;
+    for seg in segments {
+        match seg {
+            TokenSegment::Literal(toks) => {
+                tokens.extend_from_slice(toks);
+                pos += toks.len();
+            }
+            TokenSegment::Reference { len, .. } => {
+                match duplicate_spans.last_mut() {
+                    Some(last) if last.end == pos => last.end += len,
+                    _ => {
+                        if let Some(mask) = &options.mask {
+                            tokens.extend_from_slice(mask);
+                        }
+                        duplicate_spans.push(pos..pos + len);
+                    }
+                }
+                pos += len;
+            }
+        }
+    }
+    let duplicate_tokens: usize = duplicate_spans.iter().map(|r| r.len()).sum();
+    let duplicate_fraction = if pos == 0 {
+        0.0
+    } else {
+        duplicate_tokens as f64 / pos as f64
+    };
+    let dropped = duplicate_fraction > options.max_duplicate_fraction;
+    if dropped {
+        tokens.clear();
+    }
+    DedupedTokens {
+        tokens,
+        duplicate_spans,
+        duplicate_tokens,
+        duplicate_fraction,
+        dropped,
+    }
+}
@@ -0,0 +1,209 @@
+//! Remapping references after a message is edited, without rerunning the
+//! matcher.
+//!
+//! Given an edit script for one source message, [`remap_after_edits`] rewrites
+//! the source's own segments to describe its new text and shifts every
+//! reference into it to the new offsets. References whose span an edit
+//! touched can no longer be expressed against the source, so they are
+//! materialized as literal text. Referencing messages render exactly as
+//! before; only the source changes. For large edits, recomputing gives better
+//! compression.
+//!
+//! # Example
+//! ```
+//! use copyforward::edit::{remap_after_edits, Edit};
+//! use copyforward::render::reconstruct;
+//! use copyforward::{exact, Config, CopyForward};
+//!
+//! let messages = ["Lunch at noon? Bring the slides.", "> Lunch at noon?\nYes. > Bring the slides."];
+//! let segments = exact(&messages, Config::default()).segments();
+//!
+//! let edits = [Edit::insert(0, "Hi! "), Edit::replace(9..13, "one")];
+//! let remapped = remap_after_edits(&segments, 0, &edits).unwrap();
+//! let texts = reconstruct(&remapped).unwrap();
+//! assert_eq!(texts[0], "Hi! Lunch at one? Bring the slides.");
+//! assert_eq!(texts[1], messages[1]);
+//! ```
+
+use crate::core::{Segment, slice_message};
+use crate::render::{ReconstructError, reconstruct};
+use std::fmt;
+use std::ops::Range;
+
+/// Replace bytes `range` of the source message with `text`.
+///
+/// Ranges are offsets into the message before any edit is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    /// Insert `text` at byte offset `at`.
+    pub fn insert(at: usize, text: impl Into<String>) -> Edit {
+        Edit {
+            range: at..at,
+            text: text.into(),
+        }
+    }
+
+    /// Delete bytes `range`.
+    pub fn delete(range: Range<usize>) -> Edit {
+        Edit {
+            range,
+            text: String::new(),
+        }
+    }
+
+    /// Replace bytes `range` with `text`.
+    pub fn replace(range: Range<usize>, text: impl Into<String>) -> Edit {
+        Edit {
+            range,
+            text: text.into(),
+        }
+    }
+
+    /// Whether this edit changes bytes strictly inside `span`, or removes any of them.
+    fn disturbs(&self, span: &Range<usize>) -> bool {
+        if self.range.is_empty() {
+            span.start < self.range.start && self.range.start < span.end
+        } else {
+            self.range.start < span.end && span.start < self.range.end
+        }
+    }
+}
+
+/// Error returned by [`remap_after_edits`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    /// The segments themselves are invalid.
+    Segments(ReconstructError),
+    /// The source index is not a message.
+    UnknownMessage(usize),
+    /// Edit `edit` lies outside the message, splits a UTF-8 character, or
+    /// overlaps or precedes the previous edit.
+    InvalidEdit { edit: usize },
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::Segments(e) => write!(f, "{e}"),
+            EditError::UnknownMessage(idx) => write!(f, "message {idx} does not exist"),
+            EditError::InvalidEdit { edit } => write!(
+                f,
+                "edit {edit} is out of bounds, splits a character, or is out of order"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl From<ReconstructError> for EditError {
+    fn from(e: ReconstructError) -> Self {
+        EditError::Segments(e)
+    }
+}
+
+/// Apply `edits` to message `source` and remap the segmentation to match.
+///
+/// `edits` must be sorted by position and must not overlap; insertions may
+/// share a position with the start of the following edit.
+pub fn remap_after_edits(
+    segments: &[Vec<Segment>],
+    source: usize,
+    edits: &[Edit],
+) -> Result<Vec<Vec<Segment>>, EditError> {
+    let texts = reconstruct(segments)?;
+    let original = texts.get(source).ok_or(EditError::UnknownMessage(source))?;
+    let mut prev_end = 0;
+    for (i, edit) in edits.iter().enumerate() {
+        let r = &edit.range;
+        if r.start < prev_end
+            || r.start > r.end
+            || !original.is_char_boundary(r.start)
+            || !original.is_char_boundary(r.end)
+        {
+            return Err(EditError::InvalidEdit { edit: i });
+        }
+        prev_end = r.end;
+    }
+
+    let mut out: Vec<Vec<Segment>> = segments.to_vec();
+
+    // Self-references of the source would point at shifted text, so they are
+    // spelled out first.
+    let own: Vec<Segment> = segments[source]
+        .iter()
+        .map(|seg| match seg {
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } if *message_idx == source => Segment::Literal(original[*start..start + len].into()),
+            other => other.clone(),
+        })
+        .collect();
+
+    // The source's new text: kept stretches of its old segments interleaved
+    // with the inserted text.
+    let mut edited = Vec::new();
+    let mut pos = 0;
+    for edit in edits {
+        edited.extend(slice_message(&own, pos..edit.range.start));
+        if !edit.text.is_empty() {
+            edited.push(Segment::Literal(edit.text.as_str().into()));
+        }
+        pos = edit.range.end;
+    }
+    edited.extend(slice_message(&own, pos..original.len()));
+    out[source] = merge_literals(edited);
+
+    for segs in out.iter_mut().skip(source + 1) {
+        let remapped = segs
+            .iter()
+            .map(|seg| match seg {
+                Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } if *message_idx == source => {
+                    let span = *start..start + len;
+                    if edits.iter().any(|e| e.disturbs(&span)) {
+                        Segment::Literal(original[span].into())
+                    } else {
+                        Segment::Reference {
+                            message_idx: source,
+                            start: shifted(*start, edits),
+                            len: *len,
+                        }
+                    }
+                }
+                other => other.clone(),
+            })
+            .collect();
+        *segs = merge_literals(remapped);
+    }
+    Ok(out)
+}
+
+/// New offset of an undisturbed position `pos` in the edited text.
+fn shifted(pos: usize, edits: &[Edit]) -> usize {
+    edits
+        .iter()
+        .filter(|e| e.range.end <= pos)
+        .fold(pos, |p, e| p + e.text.len() - e.range.len())
+}
+
+fn merge_literals(segments: Vec<Segment>) -> Vec<Segment> {
+    let mut out: Vec<Segment> = Vec::with_capacity(segments.len());
+    for seg in segments {
+        match (out.last_mut(), seg) {
+            (Some(Segment::Literal(prev)), Segment::Literal(s)) => prev.push_str(&s),
+            (_, seg) => out.push(seg),
+        }
+    }
+    out
+}
@@ -1,132 +1,291 @@
 use crate::core::{Config, TokenSegment};
-use crate::hashing::{prefix_hashes_u32, range_hash};
+use crate::engine::{
+    History, Resume, bounded_reference, evicted, extend_full, indexable_windows, last_reference,
+    partition_key, prefer, push_literal, report_progress, room, table_capacity, unsegmented,
+    within_depth,
+};
+use crate::trace::{Lookup, Outcome};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Compute token segments using binary-search extension over &[u32] messages.
 /// This mirrors HashedGreedyBinaryTokens::new logic but as a reusable engine.
 pub fn compute_binary_segments(messages: &[Vec<u32>], config: &Config) -> Vec<Vec<TokenSegment>> {
-    use std::collections::HashMap;
-    let mut inner: Vec<Vec<TokenSegment>> = Vec::with_capacity(messages.len());
-
-    let base: u64 = 257;
-    let prefixes: Vec<(Vec<u64>, Vec<u64>)> = messages
-        .iter()
-        .map(|m| prefix_hashes_u32(m, base))
-        .collect();
-
-    let k = config.min_match_len;
-    let total_kmers: usize = if k > 0 {
-        messages
-            .iter()
-            .map(|m| if m.len() >= k { m.len() - k + 1 } else { 0 })
-            .sum()
-    } else {
-        0
-    };
-    let mut table: HashMap<u64, Vec<(usize, usize)>> =
-        HashMap::with_capacity((total_kmers / 2).max(16));
-
-    fn insert_kmers(
-        table: &mut HashMap<u64, Vec<(usize, usize)>>,
-        messages: &[Vec<u32>],
-        prefixes: &[(Vec<u64>, Vec<u64>)],
-        j: usize,
-        k: usize,
-    ) {
-        if messages[j].len() >= k {
-            let (ref_h, ref_p) = &prefixes[j];
-            for start in 0..=(messages[j].len() - k) {
-                let h = range_hash(ref_h, ref_p, start, start + k);
-                table.entry(h).or_default().push((j, start));
-            }
+    compute_binary_segments_with(messages, config, &mut |_, _| true)
+}
+
+/// [`compute_binary_segments`], passing each message's final segments to
+/// `sink(index, segments)` as soon as they are computed. The sink returning
+/// false stops the run like a cancelling [`Config::progress`].
+pub fn compute_binary_segments_with(
+    messages: &[Vec<u32>],
+    config: &Config,
+    sink: &mut dyn FnMut(usize, &[TokenSegment]) -> bool,
+) -> Vec<Vec<TokenSegment>> {
+    let mut engine = BinaryEngine::new(Cow::Borrowed(messages), Cow::Borrowed(config));
+    for i in 0..messages.len() {
+        engine.step();
+        let inner = &mut engine.history.inner;
+        if !sink(i, &inner[i]) || !report_progress(config, i + 1, messages.len()) {
+            inner.extend(messages[i + 1..].iter().map(|m| unsegmented(m)));
+            break;
         }
     }
+    engine.history.inner
+}
 
-    #[allow(clippy::manual_div_ceil)]
-    fn extend_candidate(
-        pref_cur: &(Vec<u64>, Vec<u64>),
-        pref_prev: &(Vec<u64>, Vec<u64>),
-        cursor: usize,
-        ref_start: usize,
-        initial_k: usize,
-    ) -> usize {
-        let max_possible = std::cmp::min(
-            pref_cur.0.len() - 1 - cursor,
-            pref_prev.0.len() - 1 - ref_start,
-        );
-        let mut low = initial_k;
-        let mut high = max_possible;
-        while low < high {
-            let mid = low + (high - low + 1) / 2;
-            let h1 = range_hash(&pref_cur.0, &pref_cur.1, cursor, cursor + mid);
-            let h2 = range_hash(&pref_prev.0, &pref_prev.1, ref_start, ref_start + mid);
-            if h1 == h2 {
-                low = mid;
-            } else {
-                high = mid - 1;
-            }
+/// State of the exact engine: the thread so far and the k-mer table of its
+/// messages, which maps each k-mer hash to its `(message, start)` positions.
+pub struct BinaryEngine<'a> {
+    history: History<'a>,
+    table: HashMap<u64, Vec<(usize, usize)>>,
+}
+
+impl<'a> BinaryEngine<'a> {
+    fn new(messages: Cow<'a, [Vec<u32>]>, config: Cow<'a, Config>) -> BinaryEngine<'a> {
+        let history = History::new(messages, config);
+        let capacity = table_capacity(&history.kmers, &history.limits, &history.config);
+        BinaryEngine {
+            history,
+            table: HashMap::with_capacity(capacity),
         }
-        low
     }
 
-    for i in 0..messages.len() {
+    /// Segment the first message not segmented yet.
+    fn step(&mut self) {
+        let BinaryEngine { history, table } = self;
+        let History {
+            config,
+            messages,
+            prefixes,
+            kmers,
+            limits,
+            parts,
+            dups,
+            depths,
+            inner,
+            ..
+        } = history;
+        let config: &Config = config;
+        let i = inner.len();
         let msg = &messages[i];
+        let k = config.min_match_len;
 
-        if k > 0 && i > 0 {
+        if k > 0 && i > 0 && dups[i - 1].is_none() {
             let j = i - 1;
-            insert_kmers(&mut table, messages, &prefixes, j, k);
+            let indexable = indexable_windows(&kmers[j], limits, j, &inner[j], config);
+            insert_kmers(table, kmers, &indexable, &parts[j], j);
+        }
+        if let Some(old) = evicted(i, config) {
+            evict_kmers(table, kmers, &parts[old], old);
         }
 
+        let mut own = (config.self_references && k > 0 && depths.is_none())
+            .then(|| Prefix::new(&kmers[i], k));
         let mut cursor = 0usize;
         let mut segs = Vec::new();
+        let mut previous = None;
+        if let Some(j) = dups[i]
+            && within_depth(config, depths, j, 0, msg.len())
+        {
+            segs.push(TokenSegment::Reference {
+                message_idx: j,
+                start: 0,
+                len: msg.len(),
+            });
+            cursor = msg.len();
+            Lookup::new(config).finish(i, 0, Outcome::Duplicate { source: j });
+        }
 
         while cursor < msg.len() {
             let mut best_match: Option<(usize, usize, usize)> = None;
+            let mut lookup = Lookup::new(config);
+            if let Some(own) = &mut own {
+                own.advance(limits, i, cursor);
+            }
 
-            if msg.len() >= cursor + k && k > 0 {
-                let (cur_h, cur_p) = &prefixes[i];
-                let key = range_hash(cur_h, cur_p, cursor, cursor + k);
-                if let Some(cands) = table.get(&key) {
-                    for (examined, &(midx, ref_start)) in cands.iter().enumerate() {
-                        if examined >= 64 {
-                            break;
-                        }
-                        let prev_pref = &prefixes[midx];
-                        let match_len =
-                            extend_candidate(&prefixes[i], prev_pref, cursor, ref_start, k);
-                        if best_match.is_none() || match_len > best_match.unwrap().0 {
-                            best_match = Some((match_len, midx, ref_start));
-                        }
+            if let Some(&h) = kmers[i].get(cursor)
+                && room(limits, i, cursor) >= k
+            {
+                let cands = parts[i]
+                    .iter()
+                    .filter_map(|&p| table.get(&partition_key(h, p)))
+                    .flatten()
+                    .copied()
+                    .chain(own.iter().flat_map(|own| own.get(h)).map(|&p| (i, p)));
+                for (examined, (midx, ref_start)) in cands.enumerate() {
+                    if examined >= 64 {
+                        lookup.cap_hit();
+                        break;
+                    }
+                    let prev_pref = &prefixes[midx];
+                    let mut limit = room(limits, i, cursor).min(room(limits, midx, ref_start));
+                    if midx == i {
+                        limit = limit.min(cursor - ref_start);
+                    }
+                    let match_len =
+                        extend_full(&prefixes[i], prev_pref, cursor, ref_start, k, limit);
+                    lookup.candidate(midx, ref_start, Some(match_len));
+                    let candidate = (match_len, midx, ref_start);
+                    if best_match
+                        .is_none_or(|best| prefer(config, depths, previous, candidate, best))
+                    {
+                        best_match = Some(candidate);
                     }
                 }
             }
 
             if let Some((match_len, midx, ref_start)) = best_match {
-                segs.push(TokenSegment::Reference {
-                    message_idx: midx,
+                let tokens = &msg[cursor..cursor + match_len];
+                let pieces = bounded_reference(inner, depths, tokens, (midx, ref_start), config);
+                let outcome = Outcome::Reference {
+                    source: midx,
                     start: ref_start,
                     len: match_len,
-                });
+                    depth_bounded: !matches!(pieces[..], [TokenSegment::Reference { .. }]),
+                };
+                lookup.finish(i, cursor, outcome);
+                previous = last_reference(&pieces).or(previous);
+                for seg in pieces {
+                    match seg {
+                        TokenSegment::Literal(lit) => push_literal(&mut segs, &lit),
+                        reference => segs.push(reference),
+                    }
+                }
                 cursor += match_len;
             } else {
                 let mut literal_end = cursor + 1;
                 while literal_end < msg.len() {
-                    if msg.len() >= literal_end + k && k > 0 {
-                        let (cur_h, cur_p) = &prefixes[i];
-                        let key = range_hash(cur_h, cur_p, literal_end, literal_end + k);
-                        if table.contains_key(&key) {
-                            break;
-                        }
+                    if let Some(own) = &mut own {
+                        own.advance(limits, i, literal_end);
+                    }
+                    if let Some(&h) = kmers[i].get(literal_end)
+                        && room(limits, i, literal_end) >= k
+                        && (parts[i]
+                            .iter()
+                            .any(|&p| table.contains_key(&partition_key(h, p)))
+                            || own.as_ref().is_some_and(|own| !own.get(h).is_empty()))
+                    {
+                        break;
                     }
                     literal_end += 1;
                 }
+                let outcome = Outcome::Literal {
+                    len: literal_end - cursor,
+                };
+                lookup.finish(i, cursor, outcome);
                 let lit = msg[cursor..literal_end].to_vec();
                 segs.push(TokenSegment::Literal(lit));
                 cursor = literal_end;
             }
         }
 
+        if let Some(depths) = depths {
+            depths.push(&segs);
+        }
         inner.push(segs);
     }
+}
+
+impl Resume for BinaryEngine<'static> {
+    fn run(messages: Vec<Vec<u32>>, config: &Config) -> (Vec<Vec<TokenSegment>>, Option<Self>) {
+        let total = messages.len();
+        let mut engine = BinaryEngine::new(Cow::Owned(messages), Cow::Owned(config.clone()));
+        for i in 0..total {
+            engine.step();
+            if !report_progress(config, i + 1, total) {
+                let History {
+                    messages,
+                    mut inner,
+                    ..
+                } = engine.history;
+                inner.extend(messages[i + 1..].iter().map(|m| unsegmented(m)));
+                return (inner, None);
+            }
+        }
+        engine.history.detach();
+        (engine.history.inner.clone(), Some(engine))
+    }
 
-    inner
+    fn push(&mut self, message: Vec<u32>) -> Vec<TokenSegment> {
+        self.history.extend(message);
+        self.step();
+        self.history.inner.last().cloned().unwrap_or_default()
+    }
+}
+
+/// The k-mers of the message being segmented that end at or before the
+/// cursor, which [`Config::self_references`] matches against.
+struct Prefix<'k> {
+    kmers: &'k [u64],
+    k: usize,
+    table: HashMap<u64, Vec<usize>>,
+    /// Positions indexed so far.
+    indexed: usize,
+}
+
+impl<'k> Prefix<'k> {
+    fn new(kmers: &'k [u64], k: usize) -> Prefix<'k> {
+        Prefix {
+            kmers,
+            k,
+            table: HashMap::new(),
+            indexed: 0,
+        }
+    }
+
+    /// Index the k-mers of message `i` that end by `end`.
+    fn advance(&mut self, limits: &Option<Vec<Vec<usize>>>, i: usize, end: usize) {
+        while self.indexed + self.k <= end && self.indexed < self.kmers.len() {
+            let p = self.indexed;
+            if room(limits, i, p) >= self.k {
+                self.table.entry(self.kmers[p]).or_default().push(p);
+            }
+            self.indexed += 1;
+        }
+    }
+
+    fn get(&self, h: u64) -> &[usize] {
+        self.table.get(&h).map_or(&[], Vec::as_slice)
+    }
+}
+
+fn insert_kmers(
+    table: &mut HashMap<u64, Vec<(usize, usize)>>,
+    kmers: &[Arc<[u64]>],
+    indexable: &[bool],
+    parts: &[u64],
+    j: usize,
+) {
+    for (start, &h) in kmers[j].iter().enumerate() {
+        if !indexable[start] {
+            continue;
+        }
+        for &p in parts {
+            table
+                .entry(partition_key(h, p))
+                .or_default()
+                .push((j, start));
+        }
+    }
+}
+
+fn evict_kmers(
+    table: &mut HashMap<u64, Vec<(usize, usize)>>,
+    kmers: &[Arc<[u64]>],
+    parts: &[u64],
+    j: usize,
+) {
+    for &h in kmers[j].iter() {
+        for &p in parts {
+            let key = partition_key(h, p);
+            if let Some(bucket) = table.get_mut(&key) {
+                bucket.retain(|&(m, _)| m != j);
+                if bucket.is_empty() {
+                    table.remove(&key);
+                }
+            }
+        }
+    }
 }
@@ -1,8 +1,16 @@
 use crate::core::{Config, TokenSegment};
-use crate::hashing::{prefix_hashes_u32, range_hash};
+use crate::engine::{
+    History, Resume, bounded_reference, evicted, extend_full, indexable_windows, last_reference,
+    partition_key, prefer, push_literal, report_progress, room, table_capacity, unsegmented,
+    within_depth,
+};
+use crate::hashing::{PrefixHashes, range_hash};
+use crate::trace::{Lookup, Outcome};
 use ahash::AHashMap as HashMap;
 use ahash::AHashSet as HashSet;
 use smallvec::SmallVec;
+use std::borrow::Cow;
+use std::sync::Arc;
 
 #[derive(Clone, Copy)]
 struct Entry {
@@ -12,135 +20,116 @@ struct Entry {
 }
 type Bucket = SmallVec<[Entry; 4]>;
 
+/// K-mer table plus the `(key, cap hash)` pairs it already holds.
+struct Index {
+    table: HashMap<u64, Bucket>,
+    seen: HashSet<(u64, u64)>,
+}
+
 /// Compute token segments using capped extension with per-candidate early stop
 /// and winner-local full extension using rolling hashes.
 pub fn compute_capped_segments(messages: &[Vec<u32>], config: &Config) -> Vec<Vec<TokenSegment>> {
-    let mut inner: Vec<Vec<TokenSegment>> = Vec::with_capacity(messages.len());
-
-    let base: u64 = 257;
-    let prefixes: Vec<(Vec<u64>, Vec<u64>)> = messages
-        .iter()
-        .map(|m| prefix_hashes_u32(m, base))
-        .collect();
-
-    let k = config.min_match_len;
-    let total_kmers: usize = if k > 0 {
-        messages
-            .iter()
-            .map(|m| if m.len() >= k { m.len() - k + 1 } else { 0 })
-            .sum()
-    } else {
-        0
-    };
-    let mut table: HashMap<u64, Bucket> = HashMap::with_capacity((total_kmers / 2).max(16));
-    let mut seen: HashSet<(u64, u64)> = HashSet::with_capacity((total_kmers / 2).max(16));
-
-    fn insert_kmers(
-        table: &mut HashMap<u64, Bucket>,
-        seen: &mut HashSet<(u64, u64)>,
-        messages: &[Vec<u32>],
-        prefixes: &[(Vec<u64>, Vec<u64>)],
-        j: usize,
-        k: usize,
-        cap_len: usize,
-    ) {
-        if messages[j].len() >= k {
-            let (ref_h, ref_p) = &prefixes[j];
-            for start in 0..=(messages[j].len() - k) {
-                let h = range_hash(ref_h, ref_p, start, start + k);
-                let cap_end = std::cmp::min(messages[j].len(), start + cap_len);
-                let cap_h = range_hash(ref_h, ref_p, start, cap_end);
-                let key = (h, cap_h);
-                if !seen.contains(&key) {
-                    seen.insert(key);
-                    table.entry(h).or_default().push(Entry {
-                        cap_hash: cap_h,
-                        msg_idx: j,
-                        start,
-                    });
-                }
-            }
+    let mut engine = CappedEngine::new(Cow::Borrowed(messages), Cow::Borrowed(config));
+    for i in 0..messages.len() {
+        engine.step();
+        if !report_progress(config, i + 1, messages.len()) {
+            let inner = &mut engine.history.inner;
+            inner.extend(messages[i + 1..].iter().map(|m| unsegmented(m)));
+            break;
         }
     }
+    engine
+        .history
+        .inner
+        .iter()
+        .map(|segs| coalesce(segs))
+        .collect()
+}
 
-    fn extend_capped(
-        cur: &[u32],
-        prev: &[u32],
-        cursor: usize,
-        ref_start: usize,
-        initial_k: usize,
-        cap_len: usize,
-    ) -> usize {
-        let mut match_len = initial_k;
-        while match_len < cap_len
-            && cursor + match_len < cur.len()
-            && ref_start + match_len < prev.len()
-            && cur[cursor + match_len] == prev[ref_start + match_len]
-        {
-            match_len += 1;
-        }
-        match_len
-    }
+/// State of the approximate engine: the thread so far and the k-mer index
+/// of its messages. Segments are kept as matched, before [`coalesce`].
+pub struct CappedEngine<'a> {
+    history: History<'a>,
+    index: Index,
+}
 
-    #[allow(clippy::manual_div_ceil)]
-    fn extend_full(
-        pref_cur: &(Vec<u64>, Vec<u64>),
-        pref_prev: &(Vec<u64>, Vec<u64>),
-        cursor: usize,
-        ref_start: usize,
-        initial_k: usize,
-    ) -> usize {
-        let max_possible = std::cmp::min(
-            pref_cur.0.len() - 1 - cursor,
-            pref_prev.0.len() - 1 - ref_start,
-        );
-        let mut low = initial_k;
-        let mut high = max_possible;
-        while low < high {
-            let mid = low + (high - low + 1) / 2;
-            let h1 = range_hash(&pref_cur.0, &pref_cur.1, cursor, cursor + mid);
-            let h2 = range_hash(&pref_prev.0, &pref_prev.1, ref_start, ref_start + mid);
-            if h1 == h2 {
-                low = mid;
-            } else {
-                high = mid - 1;
-            }
+impl<'a> CappedEngine<'a> {
+    fn new(messages: Cow<'a, [Vec<u32>]>, config: Cow<'a, Config>) -> CappedEngine<'a> {
+        let history = History::new(messages, config);
+        let capacity = table_capacity(&history.kmers, &history.limits, &history.config);
+        CappedEngine {
+            history,
+            index: Index {
+                table: HashMap::with_capacity(capacity),
+                seen: HashSet::with_capacity(capacity),
+            },
         }
-        low
     }
 
-    for i in 0..messages.len() {
+    /// Segment the first message not segmented yet.
+    fn step(&mut self) {
+        let CappedEngine { history, index } = self;
+        let History {
+            config,
+            messages,
+            prefixes,
+            kmers,
+            limits,
+            parts,
+            dups,
+            depths,
+            inner,
+            ..
+        } = history;
+        let config: &Config = config;
+        let i = inner.len();
         let msg = &messages[i];
+        let k = config.min_match_len;
 
-        if k > 0 && i > 0 {
+        if k > 0 && i > 0 && dups[i - 1].is_none() {
             let j = i - 1;
-            insert_kmers(
-                &mut table,
-                &mut seen,
-                messages,
-                &prefixes,
-                j,
-                k,
-                config.cap_len,
-            );
+            let indexable = indexable_windows(&kmers[j], limits, j, &inner[j], config);
+            insert_kmers(index, kmers, prefixes, &indexable, &parts[j], j, config);
+        }
+        if let Some(old) = evicted(i, config) {
+            evict_kmers(index, kmers, &parts[old], old);
         }
+        let table = &index.table;
 
         let mut cursor = 0usize;
         let mut segs = Vec::new();
+        let mut previous = None;
+        if let Some(j) = dups[i]
+            && within_depth(config, depths, j, 0, msg.len())
+        {
+            segs.push(TokenSegment::Reference {
+                message_idx: j,
+                start: 0,
+                len: msg.len(),
+            });
+            cursor = msg.len();
+            Lookup::new(config).finish(i, 0, Outcome::Duplicate { source: j });
+        }
 
         while cursor < msg.len() {
             let mut best_match: Option<(usize, usize, usize)> = None;
-            if msg.len() >= cursor + k && k > 0 {
-                let (cur_h, cur_p) = &prefixes[i];
-                let kmer_hash = range_hash(cur_h, cur_p, cursor, cursor + k);
+            let mut lookup = Lookup::new(config);
+            if let Some(&kmer_hash) = kmers[i].get(cursor)
+                && room(limits, i, cursor) >= k
+            {
+                let (cur_h, cur_p) = &*prefixes[i];
                 let mut examined = 0usize;
                 let cap_len = config.cap_len;
                 let ncap = config.ncap;
                 let cap_end_cur = std::cmp::min(msg.len(), cursor + cap_len);
                 let cap_hash_cur = range_hash(cur_h, cur_p, cursor, cap_end_cur);
-                if let Some(bucket) = table.get(&kmer_hash) {
+                let buckets = parts[i]
+                    .iter()
+                    .filter_map(|&p| table.get(&partition_key(kmer_hash, p)));
+                for bucket in buckets {
                     for e in bucket.iter() {
                         if examined >= ncap {
+                            lookup.cap_hit();
                             break;
                         }
                         let midx = e.msg_idx;
@@ -149,13 +138,20 @@ pub fn compute_capped_segments(messages: &[Vec<u32>], config: &Config) -> Vec<Ve
                             continue;
                         }
                         if e.cap_hash != cap_hash_cur {
+                            lookup.candidate(midx, ref_start, None);
                             examined += 1;
                             continue;
                         }
                         let prev = &messages[midx];
-                        let match_len = extend_capped(msg, prev, cursor, ref_start, k, cap_len);
-                        if best_match.is_none() || match_len > best_match.unwrap().0 {
-                            best_match = Some((match_len, midx, ref_start));
+                        let limit = room(limits, i, cursor).min(room(limits, midx, ref_start));
+                        let match_len =
+                            extend_capped(msg, prev, cursor, ref_start, k, cap_len.min(limit));
+                        lookup.candidate(midx, ref_start, Some(match_len));
+                        let candidate = (match_len, midx, ref_start);
+                        if best_match
+                            .is_none_or(|best| prefer(config, depths, previous, candidate, best))
+                        {
+                            best_match = Some(candidate);
                         }
                         examined += 1;
                     }
@@ -163,84 +159,341 @@ pub fn compute_capped_segments(messages: &[Vec<u32>], config: &Config) -> Vec<Ve
             }
 
             if let Some((match_len, midx, ref_start)) = best_match {
-                let full_len =
-                    extend_full(&prefixes[i], &prefixes[midx], cursor, ref_start, match_len);
-                segs.push(TokenSegment::Reference {
-                    message_idx: midx,
+                let limit = room(limits, i, cursor).min(room(limits, midx, ref_start));
+                let full_len = extend_full(
+                    &prefixes[i],
+                    &prefixes[midx],
+                    cursor,
+                    ref_start,
+                    match_len,
+                    limit,
+                );
+                let tokens = &msg[cursor..cursor + full_len];
+                let pieces = bounded_reference(inner, depths, tokens, (midx, ref_start), config);
+                let outcome = Outcome::Reference {
+                    source: midx,
                     start: ref_start,
                     len: full_len,
-                });
+                    depth_bounded: !matches!(pieces[..], [TokenSegment::Reference { .. }]),
+                };
+                lookup.finish(i, cursor, outcome);
+                previous = last_reference(&pieces).or(previous);
+                for seg in pieces {
+                    match seg {
+                        TokenSegment::Literal(lit) => push_literal(&mut segs, &lit),
+                        reference => segs.push(reference),
+                    }
+                }
                 cursor += full_len;
             } else {
                 let mut literal_end = cursor + 1;
                 while literal_end < msg.len() {
-                    let mut found = false;
-                    if k > 0 {
-                        let (cur_h, cur_p) = &prefixes[i];
-                        if msg.len() >= literal_end + k {
-                            let kmer_hash2 = range_hash(cur_h, cur_p, literal_end, literal_end + k);
-                            if table.contains_key(&kmer_hash2) {
-                                found = true;
-                            }
-                        }
-                    }
-                    if found {
+                    if let Some(&h) = kmers[i].get(literal_end)
+                        && room(limits, i, literal_end) >= k
+                        && parts[i]
+                            .iter()
+                            .any(|&p| table.contains_key(&partition_key(h, p)))
+                    {
                         break;
                     }
                     literal_end += 1;
                 }
+                let outcome = Outcome::Literal {
+                    len: literal_end - cursor,
+                };
+                lookup.finish(i, cursor, outcome);
                 segs.push(TokenSegment::Literal(msg[cursor..literal_end].to_vec()));
                 cursor = literal_end;
             }
         }
 
+        if config.reextend {
+            let depth_ok = |m, s, l| within_depth(config, depths, m, s, l);
+            reextend_backward(&mut segs, i, prefixes, limits, &depth_ok);
+        }
+        if let Some(depths) = depths {
+            depths.push(&segs);
+        }
         inner.push(segs);
     }
+}
+
+impl Resume for CappedEngine<'static> {
+    fn run(messages: Vec<Vec<u32>>, config: &Config) -> (Vec<Vec<TokenSegment>>, Option<Self>) {
+        let total = messages.len();
+        let mut engine = CappedEngine::new(Cow::Owned(messages), Cow::Owned(config.clone()));
+        for i in 0..total {
+            engine.step();
+            if !report_progress(config, i + 1, total) {
+                let History {
+                    messages,
+                    mut inner,
+                    ..
+                } = engine.history;
+                inner.extend(messages[i + 1..].iter().map(|m| unsegmented(m)));
+                let segments = inner.iter().map(|segs| coalesce(segs)).collect();
+                return (segments, None);
+            }
+        }
+        engine.history.detach();
+        let segments = engine
+            .history
+            .inner
+            .iter()
+            .map(|segs| coalesce(segs))
+            .collect();
+        (segments, Some(engine))
+    }
+
+    fn push(&mut self, message: Vec<u32>) -> Vec<TokenSegment> {
+        self.history.extend(message);
+        self.step();
+        self.history
+            .inner
+            .last()
+            .map(|segs| coalesce(segs))
+            .unwrap_or_default()
+    }
+}
 
-    // Coalesce consecutive references to consecutive source spans
-    for segs in inner.iter_mut() {
-        let mut out: Vec<TokenSegment> = Vec::with_capacity(segs.len());
-        let mut i = 0usize;
-        while i < segs.len() {
-            match &segs[i] {
-                TokenSegment::Reference {
-                    message_idx,
+fn insert_kmers(
+    index: &mut Index,
+    kmers: &[Arc<[u64]>],
+    prefixes: &[Arc<PrefixHashes>],
+    indexable: &[bool],
+    parts: &[u64],
+    j: usize,
+    config: &Config,
+) {
+    let Index { table, seen } = index;
+    let cap_len = config.cap_len;
+    let (ref_h, ref_p) = &*prefixes[j];
+    let len = ref_h.len() - 1;
+    for (start, &kmer_hash) in kmers[j].iter().enumerate() {
+        if !indexable[start] {
+            continue;
+        }
+        let cap_end = std::cmp::min(len, start + cap_len);
+        let cap_h = range_hash(ref_h, ref_p, start, cap_end);
+        for &p in parts {
+            let h = partition_key(kmer_hash, p);
+            let key = (h, cap_h);
+            if !seen.contains(&key) {
+                seen.insert(key);
+                table.entry(h).or_default().push(Entry {
+                    cap_hash: cap_h,
+                    msg_idx: j,
                     start,
-                    len,
-                } => {
-                    let cur_msg = *message_idx;
-                    let cur_start = *start;
-                    let mut cur_len = *len;
-                    i += 1;
-                    while i < segs.len() {
-                        if let TokenSegment::Reference {
-                            message_idx: m2,
-                            start: s2,
-                            len: l2,
-                        } = &segs[i]
-                            && *m2 == cur_msg
-                            && *s2 == cur_start + cur_len
-                        {
-                            cur_len += *l2;
-                            i += 1;
-                            continue;
-                        }
-                        break;
+                });
+            } else if config.lookback.is_some() {
+                // Keep the newest occurrence so eviction never drops a key
+                // that is still present inside the window.
+                if let Some(e) = table
+                    .get_mut(&h)
+                    .and_then(|b| b.iter_mut().find(|e| e.cap_hash == cap_h))
+                {
+                    e.msg_idx = j;
+                    e.start = start;
+                }
+            }
+        }
+    }
+}
+
+fn evict_kmers(index: &mut Index, kmers: &[Arc<[u64]>], parts: &[u64], j: usize) {
+    let Index { table, seen } = index;
+    for &kmer_hash in kmers[j].iter() {
+        for &p in parts {
+            let h = partition_key(kmer_hash, p);
+            if let Some(bucket) = table.get_mut(&h) {
+                bucket.retain(|e| {
+                    let keep = e.msg_idx != j;
+                    if !keep {
+                        seen.remove(&(h, e.cap_hash));
                     }
-                    out.push(TokenSegment::Reference {
-                        message_idx: cur_msg,
-                        start: cur_start,
-                        len: cur_len,
-                    });
+                    keep
+                });
+                if bucket.is_empty() {
+                    table.remove(&h);
                 }
-                TokenSegment::Literal(l) => {
-                    out.push(TokenSegment::Literal(l.clone()));
-                    i += 1;
+            }
+        }
+    }
+}
+
+fn extend_capped(
+    cur: &[u32],
+    prev: &[u32],
+    cursor: usize,
+    ref_start: usize,
+    initial_k: usize,
+    cap_len: usize,
+) -> usize {
+    let mut match_len = initial_k;
+    while match_len < cap_len
+        && cursor + match_len < cur.len()
+        && ref_start + match_len < prev.len()
+        && cur[cursor + match_len] == prev[ref_start + match_len]
+    {
+        match_len += 1;
+    }
+    match_len
+}
+
+/// Coalesce consecutive references to consecutive source spans.
+fn coalesce(segs: &[TokenSegment]) -> Vec<TokenSegment> {
+    let mut out: Vec<TokenSegment> = Vec::with_capacity(segs.len());
+    let mut i = 0usize;
+    while i < segs.len() {
+        match &segs[i] {
+            TokenSegment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                let cur_msg = *message_idx;
+                let cur_start = *start;
+                let mut cur_len = *len;
+                i += 1;
+                while i < segs.len() {
+                    if let TokenSegment::Reference {
+                        message_idx: m2,
+                        start: s2,
+                        len: l2,
+                    } = &segs[i]
+                        && *m2 == cur_msg
+                        && *s2 == cur_start + cur_len
+                    {
+                        cur_len += *l2;
+                        i += 1;
+                        continue;
+                    }
+                    break;
                 }
+                out.push(TokenSegment::Reference {
+                    message_idx: cur_msg,
+                    start: cur_start,
+                    len: cur_len,
+                });
+            }
+            TokenSegment::Literal(l) => {
+                out.push(TokenSegment::Literal(l.clone()));
+                i += 1;
             }
         }
-        *segs = out;
     }
+    out
+}
 
-    inner
+/// Grow each reference of message `i` backward across the merge point with
+/// the preceding segment: into a literal as far as the source matches, and
+/// over a reference only when it is swallowed whole. `depth_ok(msg, start,
+/// len)` vets the source units a reference grows over.
+fn reextend_backward(
+    segs: &mut Vec<TokenSegment>,
+    i: usize,
+    prefixes: &[Arc<PrefixHashes>],
+    limits: &Option<Vec<Vec<usize>>>,
+    depth_ok: &dyn Fn(usize, usize, usize) -> bool,
+) {
+    let mut out: Vec<TokenSegment> = Vec::with_capacity(segs.len());
+    let mut pos = 0usize;
+    for seg in segs.drain(..) {
+        let (message_idx, mut start, mut len) = match seg {
+            TokenSegment::Reference {
+                message_idx,
+                start,
+                len,
+            } => (message_idx, start, len),
+            TokenSegment::Literal(lit) => {
+                pos += lit.len();
+                out.push(TokenSegment::Literal(lit));
+                continue;
+            }
+        };
+        let mut cursor = pos;
+        pos += len;
+        loop {
+            let (back, whole) = match out.last() {
+                Some(TokenSegment::Literal(lit)) => {
+                    let back = common_suffix(
+                        prefixes,
+                        limits,
+                        (i, cursor),
+                        (message_idx, start),
+                        lit.len(),
+                        depth_ok,
+                    );
+                    (back, back == lit.len())
+                }
+                Some(&TokenSegment::Reference { len: prev, .. }) => {
+                    let back = common_suffix(
+                        prefixes,
+                        limits,
+                        (i, cursor),
+                        (message_idx, start),
+                        prev,
+                        depth_ok,
+                    );
+                    if back < prev {
+                        break;
+                    }
+                    (back, true)
+                }
+                None => break,
+            };
+            if back == 0 {
+                break;
+            }
+            cursor -= back;
+            start -= back;
+            len += back;
+            if whole {
+                out.pop();
+            } else if let Some(TokenSegment::Literal(lit)) = out.last_mut() {
+                lit.truncate(lit.len() - back);
+                break;
+            }
+        }
+        out.push(TokenSegment::Reference {
+            message_idx,
+            start,
+            len,
+        });
+    }
+    *segs = out;
+}
+
+/// Longest `l <= max` such that the `l` units before `cur` and before `prev`
+/// (each a `(message, position)`) are equal, free of stop phrases and, in
+/// the source, accepted by `depth_ok`.
+#[allow(clippy::manual_div_ceil)]
+fn common_suffix(
+    prefixes: &[Arc<PrefixHashes>],
+    limits: &Option<Vec<Vec<usize>>>,
+    cur: (usize, usize),
+    prev: (usize, usize),
+    max: usize,
+    depth_ok: &dyn Fn(usize, usize, usize) -> bool,
+) -> usize {
+    let ((cm, cp), (pm, pp)) = (cur, prev);
+    let (cur_h, cur_p) = &*prefixes[cm];
+    let (prev_h, prev_p) = &*prefixes[pm];
+    let fits = |l: usize| {
+        range_hash(cur_h, cur_p, cp - l, cp) == range_hash(prev_h, prev_p, pp - l, pp)
+            && room(limits, cm, cp - l) >= l
+            && room(limits, pm, pp - l) >= l
+            && (l == 0 || depth_ok(pm, pp - l, l))
+    };
+    let mut low = 0;
+    let mut high = max.min(cp).min(pp);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
 }
@@ -1,2 +1,665 @@
+// Helpers shared by the engines; some go unused when engines are compiled out.
+#![cfg_attr(
+    not(all(feature = "exact", feature = "approximate", feature = "optimal")),
+    allow(dead_code)
+)]
+
+use crate::core::{Config, TieBreak, TokenSegment};
+use crate::hashing::{PrefixHashes, prefix_hashes_u32, range_hash, window_hashes};
+use crate::normalize;
+use ahash::AHashMap as HashMap;
+use smallvec::{SmallVec, smallvec};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+#[cfg(feature = "exact")]
 pub mod binary;
+#[cfg(feature = "approximate")]
 pub mod capped;
+#[cfg(feature = "optimal")]
+pub mod optimal;
+
+/// Base of the polynomial rolling hash used by the engines.
+const BASE: u64 = 257;
+
+/// An engine whose state can be kept to segment messages appended later.
+pub trait Resume: Sized {
+    /// Segment `messages` exactly as a batch run would, returning their
+    /// segments and, unless [`Config::progress`] cancelled the run, the
+    /// engine, detached from the run's hooks, to push more messages onto.
+    fn run(messages: Vec<Vec<u32>>, config: &Config) -> (Vec<Vec<TokenSegment>>, Option<Self>);
+
+    /// Segment one more message against all earlier ones.
+    fn push(&mut self, message: Vec<u32>) -> Vec<TokenSegment>;
+}
+
+/// What the greedy engines know of a thread: the messages, data derived
+/// from each, and the segments of those matched so far.
+///
+/// Everything but `depths` and `inner` covers every message, matched or
+/// not; [`extend`](History::extend) adds one.
+pub struct History<'a> {
+    pub config: Cow<'a, Config>,
+    pub messages: Cow<'a, [Vec<u32>]>,
+    pub prefixes: Vec<Arc<PrefixHashes>>,
+    pub kmers: Vec<Arc<[u64]>>,
+    pub limits: Option<Vec<Vec<usize>>>,
+    pub parts: Vec<Partitions>,
+    pub dups: Vec<Option<usize>>,
+    seen: Duplicates,
+    pub depths: Option<Depths>,
+    /// Segments of the messages matched so far, in order.
+    pub inner: Vec<Vec<TokenSegment>>,
+}
+
+impl<'a> History<'a> {
+    pub fn new(messages: Cow<'a, [Vec<u32>]>, config: Cow<'a, Config>) -> History<'a> {
+        let (prefixes, kmers) = message_hashes(&messages, &config);
+        let limits = stop_limits(&messages, &config);
+        let parts = partitions(&kmers, &config);
+        let mut seen = Duplicates::default();
+        let dups = (0..messages.len())
+            .map(|i| seen.check(&messages, &prefixes, &limits, &config, i))
+            .collect();
+        History {
+            depths: Depths::for_config(&config),
+            inner: Vec::with_capacity(messages.len()),
+            config,
+            messages,
+            prefixes,
+            kmers,
+            limits,
+            parts,
+            dups,
+            seen,
+        }
+    }
+
+    /// Add `message` after the others, unmatched.
+    pub fn extend(&mut self, message: Vec<u32>) {
+        let config = &*self.config;
+        let one = std::slice::from_ref(&message);
+        let (prefixes, kmers) = message_hashes(one, config);
+        self.parts.extend(partitions(&kmers, config));
+        self.kmers.extend(kmers);
+        self.prefixes.extend(prefixes);
+        if let (Some(limits), Some(new)) = (&mut self.limits, stop_limits(one, config)) {
+            limits.extend(new);
+        }
+        self.messages.to_mut().push(message);
+        let i = self.messages.len() - 1;
+        let dup = self
+            .seen
+            .check(&self.messages, &self.prefixes, &self.limits, config, i);
+        self.dups.push(dup);
+    }
+
+    /// Drop the hooks of the batch run that built this history from its
+    /// config: [`Config::progress`], [`Config::trace`] and
+    /// [`Config::hash_cache`].
+    pub fn detach(&mut self) {
+        self.config = Cow::Owned(Config {
+            hash_cache: None,
+            progress: None,
+            trace: None,
+            ..(*self.config).clone()
+        });
+    }
+}
+
+/// Rolling prefix hashes of every message and the hash of every `k`-unit
+/// window of each, through [`Config::hash_cache`] when one is set:
+/// `kmers[i][p]` covers `messages[i][p..p + k]` with
+/// `k = config.min_match_len`. The k-mers are computed once so indexing,
+/// lookup and literal scanning share them; they are empty for `k == 0` and
+/// for messages shorter than `k` or [`Config::min_indexed_len`], which keeps
+/// those out of the index and unmatched.
+pub fn message_hashes(
+    messages: &[Vec<u32>],
+    config: &Config,
+) -> (Vec<Arc<PrefixHashes>>, Vec<Arc<[u64]>>) {
+    let k = config.min_match_len;
+    messages
+        .iter()
+        .map(|m| {
+            let (prefix, kmers) = match &config.hash_cache {
+                Some(cache) => cache.message_hashes(m, BASE, k),
+                None => {
+                    let prefix = prefix_hashes_u32(m, BASE);
+                    let kmers = window_hashes(&prefix, k).into();
+                    (Arc::new(prefix), kmers)
+                }
+            };
+            if m.len() < config.min_indexed_len {
+                (prefix, Arc::from([]))
+            } else {
+                (prefix, kmers)
+            }
+        })
+        .unzip()
+}
+
+/// Report `done` of `total` messages through [`Config::progress`]; false
+/// once the caller cancelled the run.
+pub fn report_progress(config: &Config, done: usize, total: usize) -> bool {
+    config
+        .progress
+        .as_ref()
+        .is_none_or(|progress| progress.report(done, total))
+}
+
+/// Segments of a message left unprocessed by a cancelled run.
+pub fn unsegmented(message: &[u32]) -> Vec<TokenSegment> {
+    if message.is_empty() {
+        Vec::new()
+    } else {
+        vec![TokenSegment::Literal(message.to_vec())]
+    }
+}
+
+/// Index partitions of one message.
+pub type Partitions = SmallVec<[u64; 4]>;
+
+/// Index partitions each message's k-mers are stored in and looked up from.
+///
+/// Without [`Config::lsh`] every message is in partition 0, a single shared
+/// index. With it, a message is in one partition per band, named by the
+/// MinHash values of its k-mers over that band's rows; messages without
+/// k-mers are in none.
+pub fn partitions(kmers: &[Arc<[u64]>], config: &Config) -> Vec<Partitions> {
+    let Some(lsh) = config.lsh else {
+        return vec![smallvec![0]; kmers.len()];
+    };
+    let rows = lsh.rows.max(1);
+    kmers
+        .iter()
+        .map(|hashes| {
+            if hashes.is_empty() {
+                return Partitions::new();
+            }
+            (0..lsh.bands.max(1))
+                .map(|band| {
+                    let mut key = mix(band as u64 + 1);
+                    for row in 0..rows {
+                        let seed = mix((band * rows + row) as u64 + 1);
+                        let min = hashes.iter().map(|&h| mix(h ^ seed)).min().unwrap_or(0);
+                        key = mix(key ^ min);
+                    }
+                    key.max(1)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Table key of k-mer hash `h` within `partition`.
+pub fn partition_key(h: u64, partition: u64) -> u64 {
+    if partition == 0 {
+        h
+    } else {
+        mix(h ^ partition)
+    }
+}
+
+/// SplitMix64 finalizer.
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Reference-chain depth of the messages segmented so far: 0 for literal
+/// text, one more than its source for referenced text. Stored as runs of
+/// `(start, depth)` per message, so queries cost the number of runs covered.
+pub struct Depths {
+    runs: Vec<Vec<(usize, u16)>>,
+}
+
+impl Depths {
+    /// Tracker for a run, when [`Config::shallow_preference`] or
+    /// [`Config::max_ref_depth`] needs one.
+    pub fn for_config(config: &Config) -> Option<Depths> {
+        (config.shallow_preference.is_some() || config.max_ref_depth.is_some())
+            .then(|| Depths { runs: Vec::new() })
+    }
+
+    /// Record the segments of the next message.
+    pub fn push(&mut self, segments: &[TokenSegment]) {
+        let mut runs: Vec<(usize, u16)> = Vec::new();
+        let mut add = |start: usize, depth: u16| {
+            if runs.last().is_none_or(|&(_, d)| d != depth) {
+                runs.push((start, depth));
+            }
+        };
+        let mut pos = 0;
+        for seg in segments {
+            match seg {
+                TokenSegment::Literal(toks) => {
+                    add(pos, 0);
+                    pos += toks.len();
+                }
+                TokenSegment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => {
+                    let source = &self.runs[*message_idx];
+                    let first = source
+                        .partition_point(|&(s, _)| s <= *start)
+                        .saturating_sub(1);
+                    for &(s, d) in source[first..]
+                        .iter()
+                        .take_while(|&&(s, _)| s < start + len)
+                    {
+                        add(pos + s.max(*start) - start, d.saturating_add(1));
+                    }
+                    pos += len;
+                }
+            }
+        }
+        self.runs.push(runs);
+    }
+
+    /// Deepest chain under `len` units of message `msg` from `start`.
+    pub fn max(&self, msg: usize, start: usize, len: usize) -> u16 {
+        let runs = &self.runs[msg];
+        let first = runs.partition_point(|&(s, _)| s <= start).saturating_sub(1);
+        runs[first..]
+            .iter()
+            .take_while(|&&(s, _)| s < start + len)
+            .map(|&(_, d)| d)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Whether candidate match `candidate` beats `best`, both `(len, message,
+/// start)`: the longer wins, unless [`Config::shallow_preference`] makes
+/// their lengths comparable, in which case the shallower one wins. Equal
+/// matches are settled by [`Config::tie_break`], `previous` being the source
+/// message and end of the message's last reference.
+pub fn prefer(
+    config: &Config,
+    depths: &Option<Depths>,
+    previous: Option<(usize, usize)>,
+    candidate: (usize, usize, usize),
+    best: (usize, usize, usize),
+) -> bool {
+    let longer = || {
+        candidate.0 > best.0
+            || (candidate.0 == best.0 && breaks_tie(config.tie_break, previous, candidate, best))
+    };
+    match (config.shallow_preference, depths) {
+        (Some(slack), Some(depths)) if candidate.0.abs_diff(best.0) <= slack => {
+            let depth = |(len, msg, start)| depths.max(msg, start, len);
+            let (c, b) = (depth(candidate), depth(best));
+            c < b || (c == b && longer())
+        }
+        _ => longer(),
+    }
+}
+
+/// Whether `tie_break` picks `candidate` over the equally long `best`.
+fn breaks_tie(
+    tie_break: TieBreak,
+    previous: Option<(usize, usize)>,
+    (_, msg, start): (usize, usize, usize),
+    (_, best_msg, best_start): (usize, usize, usize),
+) -> bool {
+    let (c, b) = ((msg, start), (best_msg, best_start));
+    match tie_break {
+        TieBreak::PreferEarliest => c < b,
+        TieBreak::PreferMostRecent => c > b,
+        TieBreak::PreferSameSourceAsPrevSegment => {
+            let rank = |(msg, start)| match previous {
+                Some((m, end)) if m == msg && end == start => 0,
+                Some((m, _)) if m == msg => 1,
+                _ => 2,
+            };
+            (rank(c), c) < (rank(b), b)
+        }
+    }
+}
+
+/// Source message and end of the last reference in `segments`.
+pub fn last_reference(segments: &[TokenSegment]) -> Option<(usize, usize)> {
+    segments.iter().rev().find_map(|seg| match *seg {
+        TokenSegment::Reference {
+            message_idx,
+            start,
+            len,
+        } => Some((message_idx, start + len)),
+        TokenSegment::Literal(_) => None,
+    })
+}
+
+/// Whether a reference to `len` units of message `msg` from `start` stays
+/// within [`Config::max_ref_depth`].
+pub fn within_depth(
+    config: &Config,
+    depths: &Option<Depths>,
+    msg: usize,
+    start: usize,
+    len: usize,
+) -> bool {
+    match (config.max_ref_depth, depths) {
+        (Some(max), Some(depths)) => (depths.max(msg, start, len) as usize) < max,
+        _ => true,
+    }
+}
+
+/// Segments for the match of `tokens` against message `msg` from `start`.
+///
+/// One reference when it stays within [`Config::max_ref_depth`]. Otherwise
+/// the match is split along the source's own segments: parts of its
+/// references point at their sources instead, recursively, and parts that
+/// cannot be referenced shallowly enough (or are shorter than
+/// [`Config::min_match_len`], or would point outside [`Config::lookback`])
+/// stay literal. `inner` holds the segments of every message before the one
+/// being matched.
+pub fn bounded_reference(
+    inner: &[Vec<TokenSegment>],
+    depths: &Option<Depths>,
+    tokens: &[u32],
+    (msg, start): (usize, usize),
+    config: &Config,
+) -> Vec<TokenSegment> {
+    let mut out = Vec::new();
+    match (config.max_ref_depth, depths) {
+        (Some(max), Some(depths)) => {
+            let bound = Bound {
+                inner,
+                depths,
+                max,
+                k: config.min_match_len.max(1),
+                oldest: config.lookback.map_or(0, |l| inner.len().saturating_sub(l)),
+            };
+            bound.resolve(tokens, msg, start, &mut out);
+        }
+        _ => out.push(TokenSegment::Reference {
+            message_idx: msg,
+            start,
+            len: tokens.len(),
+        }),
+    }
+    out
+}
+
+struct Bound<'a> {
+    inner: &'a [Vec<TokenSegment>],
+    depths: &'a Depths,
+    max: usize,
+    k: usize,
+    /// First message inside the lookback window.
+    oldest: usize,
+}
+
+impl Bound<'_> {
+    fn resolve(&self, tokens: &[u32], msg: usize, start: usize, out: &mut Vec<TokenSegment>) {
+        if msg < self.oldest {
+            push_literal(out, tokens);
+            return;
+        }
+        let len = tokens.len();
+        if (self.depths.max(msg, start, len) as usize) < self.max {
+            self.emit(tokens, msg, start, out);
+            return;
+        }
+        let mut pos = 0;
+        for seg in &self.inner[msg] {
+            let seg_len = match seg {
+                TokenSegment::Literal(toks) => toks.len(),
+                TokenSegment::Reference { len, .. } => *len,
+            };
+            let (lo, hi) = (pos.max(start), (pos + seg_len).min(start + len));
+            if lo < hi {
+                let part = &tokens[lo - start..hi - start];
+                match seg {
+                    TokenSegment::Literal(_) if self.max >= 1 => self.emit(part, msg, lo, out),
+                    TokenSegment::Literal(_) => push_literal(out, part),
+                    TokenSegment::Reference {
+                        message_idx,
+                        start: source_start,
+                        ..
+                    } => self.resolve(part, *message_idx, source_start + (lo - pos), out),
+                }
+            }
+            pos += seg_len;
+            if pos >= start + len {
+                break;
+            }
+        }
+    }
+
+    fn emit(&self, tokens: &[u32], msg: usize, start: usize, out: &mut Vec<TokenSegment>) {
+        if tokens.len() >= self.k {
+            out.push(TokenSegment::Reference {
+                message_idx: msg,
+                start,
+                len: tokens.len(),
+            });
+        } else {
+            push_literal(out, tokens);
+        }
+    }
+}
+
+/// Append `tokens` as literal text, extending a trailing literal.
+pub fn push_literal(out: &mut Vec<TokenSegment>, tokens: &[u32]) {
+    match out.last_mut() {
+        Some(TokenSegment::Literal(lit)) => lit.extend_from_slice(tokens),
+        _ => out.push(TokenSegment::Literal(tokens.to_vec())),
+    }
+}
+
+/// Earlier message each message repeats verbatim, with
+/// [`Config::dedup_messages`]: the latest repeated message inside the
+/// lookback window that is not itself a repeat. Messages that are too short to
+/// match or index, or contain a stop phrase, repeat nothing.
+pub fn duplicates(
+    messages: &[Vec<u32>],
+    prefixes: &[Arc<PrefixHashes>],
+    limits: &Option<Vec<Vec<usize>>>,
+    config: &Config,
+) -> Vec<Option<usize>> {
+    let mut seen = Duplicates::default();
+    (0..messages.len())
+        .map(|i| seen.check(messages, prefixes, limits, config, i))
+        .collect()
+}
+
+/// [`duplicates`] one message at a time: the messages checked so far that
+/// later ones may repeat, by whole-message hash.
+#[derive(Default)]
+pub struct Duplicates {
+    originals: HashMap<u64, Vec<usize>>,
+}
+
+impl Duplicates {
+    /// Earlier message that message `i` repeats. Every message before `i`
+    /// must have been checked, in order.
+    pub fn check(
+        &mut self,
+        messages: &[Vec<u32>],
+        prefixes: &[Arc<PrefixHashes>],
+        limits: &Option<Vec<Vec<usize>>>,
+        config: &Config,
+        i: usize,
+    ) -> Option<usize> {
+        let msg = &messages[i];
+        let len = msg.len();
+        if !config.dedup_messages
+            || len == 0
+            || len < config.min_match_len
+            || len < config.min_indexed_len
+            || room(limits, i, 0) < len
+        {
+            return None;
+        }
+        let (h, _) = &*prefixes[i];
+        let seen = self.originals.entry(h[len]).or_default();
+        let oldest = config.lookback.map_or(0, |l| i.saturating_sub(l));
+        let dup = seen
+            .iter()
+            .rev()
+            .take_while(|&&j| j >= oldest)
+            .find(|&&j| messages[j] == *msg)
+            .copied();
+        if dup.is_none() {
+            seen.push(i);
+        }
+        dup
+    }
+}
+
+/// Per-message distance limits derived from [`Config::stop_phrases`].
+///
+/// `limits[i][p]` is the first position `>= p` of message `i` covered by a
+/// stop phrase (or the message length), so no match starting at `p` may extend
+/// past it. `None` when there are no stop phrases.
+pub fn stop_limits(messages: &[Vec<u32>], config: &Config) -> Option<Vec<Vec<usize>>> {
+    let stops: Vec<Vec<u32>> = config
+        .stop_phrases
+        .iter()
+        .map(|p| normalize::string_to_u32s(p))
+        .filter(|p| !p.is_empty())
+        .collect();
+    if stops.is_empty() {
+        return None;
+    }
+    Some(
+        messages
+            .iter()
+            .map(|msg| {
+                let mut blocked = vec![false; msg.len()];
+                for stop in &stops {
+                    for (s, window) in msg.windows(stop.len()).enumerate() {
+                        if window == stop.as_slice() {
+                            blocked[s..s + stop.len()].fill(true);
+                        }
+                    }
+                }
+                let mut limits = vec![msg.len(); msg.len() + 1];
+                for p in (0..msg.len()).rev() {
+                    limits[p] = if blocked[p] { p } else { limits[p + 1] };
+                }
+                limits
+            })
+            .collect(),
+    )
+}
+
+/// Number of units from `pos` before the next stop-phrase position.
+pub fn room(limits: &Option<Vec<Vec<usize>>>, msg: usize, pos: usize) -> usize {
+    match limits {
+        Some(l) => l[msg][pos] - pos,
+        None => usize::MAX,
+    }
+}
+
+/// Length of the common extension of `cur[cursor..]` and `prev[ref_start..]`,
+/// found by binary search over rolling hashes between the already matched
+/// `initial` units and `limit`.
+#[allow(clippy::manual_div_ceil)]
+pub fn extend_full(
+    pref_cur: &PrefixHashes,
+    pref_prev: &PrefixHashes,
+    cursor: usize,
+    ref_start: usize,
+    initial: usize,
+    limit: usize,
+) -> usize {
+    let max_possible = std::cmp::min(
+        pref_cur.0.len() - 1 - cursor,
+        pref_prev.0.len() - 1 - ref_start,
+    )
+    .min(limit);
+    let mut low = initial;
+    let mut high = max_possible;
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        let h1 = range_hash(&pref_cur.0, &pref_cur.1, cursor, cursor + mid);
+        let h2 = range_hash(&pref_prev.0, &pref_prev.1, ref_start, ref_start + mid);
+        if h1 == h2 {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+/// Message whose k-mers leave the index before message `i` is matched: with
+/// [`Config::lookback`] `L`, message `i - L - 1`. Evicting it keeps the table
+/// bounded by the window rather than the whole history.
+pub fn evicted(i: usize, config: &Config) -> Option<usize> {
+    match config.lookback {
+        Some(l) if i > l => Some(i - l - 1),
+        _ => None,
+    }
+}
+
+/// Initial capacity of a k-mer table: the windows that will actually be
+/// indexed at once (every message but the last, or the largest lookback
+/// window, minus stop-phrase positions, once per [`Config::lsh`] band),
+/// scaled by [`Config::kmer_density`].
+/// Sizing up front avoids rehashing during build.
+pub fn table_capacity(
+    kmers: &[Arc<[u64]>],
+    limits: &Option<Vec<Vec<usize>>>,
+    config: &Config,
+) -> usize {
+    let k = config.min_match_len;
+    let per_message: Vec<usize> = kmers
+        .iter()
+        .enumerate()
+        .take(kmers.len().saturating_sub(1))
+        .map(|(j, hashes)| match limits {
+            Some(_) => (0..hashes.len())
+                .filter(|&s| room(limits, j, s) >= k)
+                .count(),
+            None => hashes.len(),
+        })
+        .collect();
+    let indexed = match config.lookback {
+        Some(l) if l < per_message.len() => per_message
+            .windows(l.max(1))
+            .map(|w| w.iter().sum::<usize>())
+            .max()
+            .unwrap_or(0),
+        _ => per_message.iter().sum(),
+    };
+    let copies = config.lsh.map_or(1, |lsh| lsh.bands.max(1));
+    ((indexed as f64 * copies as f64 * config.kmer_density).ceil() as usize).max(16)
+}
+
+/// Which window starts of message `j` to index: those with room for a full
+/// k-mer before the next stop phrase and, with
+/// [`Config::skip_referenced_regions`], not lying wholly inside one of the
+/// message's references, whose text is already indexed at its source.
+pub fn indexable_windows(
+    kmers: &[u64],
+    limits: &Option<Vec<Vec<usize>>>,
+    j: usize,
+    segments: &[TokenSegment],
+    config: &Config,
+) -> Vec<bool> {
+    let k = config.min_match_len;
+    let mut indexable: Vec<bool> = (0..kmers.len()).map(|s| room(limits, j, s) >= k).collect();
+    if config.skip_referenced_regions && !kmers.is_empty() {
+        let mut pos = 0;
+        for seg in segments {
+            match seg {
+                TokenSegment::Literal(toks) => pos += toks.len(),
+                TokenSegment::Reference { len, .. } => {
+                    if *len >= k {
+                        indexable[pos..=pos + len - k].fill(false);
+                    }
+                    pos += len;
+                }
+            }
+        }
+    }
+    indexable
+}
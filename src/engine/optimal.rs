@@ -0,0 +1,233 @@
+use crate::codec::varint_len;
+use crate::core::{Config, TokenSegment};
+use crate::engine::{
+    Depths, duplicates, evicted, extend_full, indexable_windows, message_hashes, partition_key,
+    partitions, push_literal, report_progress, room, stop_limits, table_capacity, unsegmented,
+    within_depth,
+};
+use std::collections::HashMap;
+
+/// How the cheapest parse reaches a segment boundary.
+#[derive(Clone, Copy)]
+enum Step {
+    /// A literal run that started at this position.
+    Literal(usize),
+    /// A reference from position `.0` to `(message, start)`.
+    Reference(usize, usize, usize),
+}
+
+/// Compute token segments by a shortest path over every reference any
+/// indexed k-mer allows, costing each segment at its size under
+/// [`crate::codec::encode_segments`]. Units are Unicode scalar values, so
+/// literal and offset costs are counted in UTF-8 bytes.
+pub fn compute_optimal_segments(messages: &[Vec<u32>], config: &Config) -> Vec<Vec<TokenSegment>> {
+    let mut inner: Vec<Vec<TokenSegment>> = Vec::with_capacity(messages.len());
+
+    let (prefixes, kmers) = message_hashes(messages, config);
+
+    let k = config.min_match_len;
+    let limits = stop_limits(messages, config);
+    let parts = partitions(&kmers, config);
+    let dups = duplicates(messages, &prefixes, &limits, config);
+    let mut depths = Depths::for_config(config);
+    let mut table: HashMap<u64, Vec<(usize, usize)>> =
+        HashMap::with_capacity(table_capacity(&kmers, &limits, config));
+    let offsets: Vec<Vec<usize>> = messages.iter().map(|m| byte_offsets(m)).collect();
+
+    for i in 0..messages.len() {
+        let msg = &messages[i];
+
+        if k > 0 && i > 0 && dups[i - 1].is_none() {
+            let j = i - 1;
+            let indexable = indexable_windows(&kmers[j], &limits, j, &inner[j], config);
+            for (start, &h) in kmers[j].iter().enumerate() {
+                if indexable[start] {
+                    for &p in &parts[j] {
+                        table
+                            .entry(partition_key(h, p))
+                            .or_default()
+                            .push((j, start));
+                    }
+                }
+            }
+        }
+        if let Some(old) = evicted(i, config) {
+            for &h in kmers[old].iter() {
+                for &p in &parts[old] {
+                    let key = partition_key(h, p);
+                    if let Some(bucket) = table.get_mut(&key) {
+                        bucket.retain(|&(m, _)| m != old);
+                        if bucket.is_empty() {
+                            table.remove(&key);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut segs = Vec::new();
+        if let Some(j) = dups[i]
+            && within_depth(config, &depths, j, 0, msg.len())
+        {
+            segs.push(TokenSegment::Reference {
+                message_idx: j,
+                start: 0,
+                len: msg.len(),
+            });
+        } else {
+            let offs = &offsets[i];
+            let n = msg.len();
+            // Cheapest encoding of `msg[..q]` ending at a segment boundary, and
+            // of `msg[..q]` ending inside an open literal run.
+            let mut closed: Vec<Option<(usize, Step)>> = vec![None; n + 1];
+            let mut open: Vec<Option<(usize, usize)>> = vec![None; n + 1];
+            closed[0] = Some((0, Step::Literal(0)));
+
+            for q in 0..=n {
+                if let Some((cost, start)) = open[q]
+                    && closed[q].is_none_or(|(best, _)| cost < best)
+                {
+                    closed[q] = Some((cost, Step::Literal(start)));
+                }
+                if q == n {
+                    break;
+                }
+                let width = offs[q + 1] - offs[q];
+                let from_open = open[q].map(|(cost, start)| {
+                    let run = offs[q] - offs[start];
+                    let grown = cost + width + varint_len(run + width) - varint_len(run);
+                    (grown, start)
+                });
+                let from_closed =
+                    closed[q].map(|(cost, _)| (cost + 1 + varint_len(width) + width, q));
+                open[q + 1] = match (from_open, from_closed) {
+                    (Some(a), Some(b)) => Some(if b.0 < a.0 { b } else { a }),
+                    (a, b) => a.or(b),
+                };
+
+                let Some((base, _)) = closed[q] else {
+                    continue;
+                };
+                let Some(&h) = kmers[i].get(q) else {
+                    continue;
+                };
+                if room(&limits, i, q) < k {
+                    continue;
+                }
+                // Every candidate as (longest usable length, fixed cost, message,
+                // start), longest first.
+                let mut cands: Vec<(usize, usize, usize, usize)> = parts[i]
+                    .iter()
+                    .filter_map(|&p| table.get(&partition_key(h, p)))
+                    .flatten()
+                    .filter_map(|&(midx, start)| {
+                        let limit = room(&limits, i, q).min(room(&limits, midx, start));
+                        let len = extend_full(&prefixes[i], &prefixes[midx], q, start, k, limit);
+                        let len = depth_bounded_len(config, &depths, (midx, start), k, len)?;
+                        let fixed = 1 + varint_len(i - midx) + varint_len(offsets[midx][start]);
+                        Some((len, fixed, midx, start))
+                    })
+                    .collect();
+                cands.sort_unstable_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+                let Some(&(longest, ..)) = cands.first() else {
+                    continue;
+                };
+                let mut next = 0;
+                let mut cheapest: Option<(usize, usize, usize)> = None;
+                for len in (k..=longest).rev() {
+                    while let Some(&(l, fixed, midx, start)) = cands.get(next)
+                        && l >= len
+                    {
+                        if cheapest.is_none_or(|(f, ..)| fixed < f) {
+                            cheapest = Some((fixed, midx, start));
+                        }
+                        next += 1;
+                    }
+                    let Some((fixed, midx, start)) = cheapest else {
+                        continue;
+                    };
+                    let cost = base + fixed + varint_len(offs[q + len] - offs[q]);
+                    if closed[q + len].is_none_or(|(best, _)| cost < best) {
+                        closed[q + len] = Some((cost, Step::Reference(q, midx, start)));
+                    }
+                }
+            }
+
+            let mut q = n;
+            while q > 0 {
+                match closed[q].expect("every prefix is reachable by literals").1 {
+                    Step::Literal(start) => {
+                        segs.push(TokenSegment::Literal(msg[start..q].to_vec()));
+                        q = start;
+                    }
+                    Step::Reference(from, message_idx, start) => {
+                        segs.push(TokenSegment::Reference {
+                            message_idx,
+                            start,
+                            len: q - from,
+                        });
+                        q = from;
+                    }
+                }
+            }
+            segs.reverse();
+        }
+        let mut merged: Vec<TokenSegment> = Vec::with_capacity(segs.len());
+        for seg in segs {
+            match seg {
+                TokenSegment::Literal(lit) => push_literal(&mut merged, &lit),
+                reference => merged.push(reference),
+            }
+        }
+
+        if let Some(depths) = &mut depths {
+            depths.push(&merged);
+        }
+        inner.push(merged);
+        if !report_progress(config, i + 1, messages.len()) {
+            inner.extend(messages[i + 1..].iter().map(|m| unsegmented(m)));
+            break;
+        }
+    }
+
+    inner
+}
+
+/// Longest prefix of a `len`-unit match at `source` that stays within
+/// [`Config::max_ref_depth`], if it is at least `k` units.
+fn depth_bounded_len(
+    config: &Config,
+    depths: &Option<Depths>,
+    (msg, start): (usize, usize),
+    k: usize,
+    len: usize,
+) -> Option<usize> {
+    if within_depth(config, depths, msg, start, len) {
+        return Some(len);
+    }
+    if !within_depth(config, depths, msg, start, k) {
+        return None;
+    }
+    let (mut low, mut high) = (k, len);
+    while low + 1 < high {
+        let mid = low + (high - low) / 2;
+        if within_depth(config, depths, msg, start, mid) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Some(low)
+}
+
+/// UTF-8 byte offset of every unit boundary of `message`.
+fn byte_offsets(message: &[u32]) -> Vec<usize> {
+    let mut offs = Vec::with_capacity(message.len() + 1);
+    let mut pos = 0;
+    offs.push(pos);
+    for &c in message {
+        pos += char::from_u32(c).map_or(1, char::len_utf8);
+        offs.push(pos);
+    }
+    offs
+}
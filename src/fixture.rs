@@ -40,3 +40,76 @@ pub fn generate_thread(seed: u64, n: usize, base_sentences: usize) -> Vec<String
 
     messages
 }
+
+/// Shrink a thread on which `fails` returns true to a minimal one on which
+/// it still does, for debugging algorithm failures.
+///
+/// Whole messages are dropped first, then characters are trimmed from each
+/// remaining message, repeating until no single removal keeps the failure.
+/// `fails` typically checks an invariant or compares an algorithm against
+/// [`exact()`](crate::exact()):
+///
+/// ```
+/// use copyforward::fixture::{generate_thread, minimize};
+/// use copyforward::{approximate, exact, Config, CopyForward};
+///
+/// // Threads on which the two algorithms split the text differently.
+/// let count = |cf: &dyn CopyForward| cf.segments().iter().flatten().count();
+/// let differs = |messages: &[String]| {
+///     count(&exact(messages, Config::default()))
+///         != count(&approximate(messages, Config::default()))
+/// };
+/// let thread = generate_thread(1, 20, 3);
+/// assert!(differs(&thread));
+/// let small = minimize(&thread, differs);
+/// assert!(differs(&small));
+/// assert!(small.len() < thread.len());
+/// ```
+///
+/// # Panics
+/// If `fails` does not hold for `messages` itself.
+pub fn minimize<F>(messages: &[String], mut fails: F) -> Vec<String>
+where
+    F: FnMut(&[String]) -> bool,
+{
+    assert!(fails(messages), "the thread does not reproduce the failure");
+    let mut current = messages.to_vec();
+    loop {
+        let size = |msgs: &[String]| msgs.len() + msgs.iter().map(String::len).sum::<usize>();
+        let before = size(&current);
+        current = shrink(current, &mut fails);
+        for i in 0..current.len() {
+            let chars: Vec<char> = current[i].chars().collect();
+            let kept = shrink(chars, |cs| {
+                current[i] = cs.iter().collect();
+                fails(&current)
+            });
+            current[i] = kept.into_iter().collect();
+        }
+        if size(&current) == before {
+            return current;
+        }
+    }
+}
+
+/// Remove chunks of `items`, halving the chunk size down to one, while
+/// `fails` holds for what remains.
+fn shrink<T: Clone>(mut items: Vec<T>, mut fails: impl FnMut(&[T]) -> bool) -> Vec<T> {
+    let mut chunk = (items.len() / 2).max(1);
+    loop {
+        let mut start = 0;
+        while start < items.len() {
+            let end = (start + chunk).min(items.len());
+            let candidate: Vec<T> = [&items[..start], &items[end..]].concat();
+            if fails(&candidate) {
+                items = candidate;
+            } else {
+                start = end;
+            }
+        }
+        if chunk == 1 {
+            return items;
+        }
+        chunk /= 2;
+    }
+}
@@ -0,0 +1,220 @@
+//! Precomputed origins of every referenced span.
+//!
+//! A reference may copy text that its source itself copied, so finding where
+//! a byte was first written ([`render::locate`]) walks a chain of references
+//! on every call. [`Flattened`] walks each chain once: it splits every
+//! message into [`Piece`]s that each come from one literal segment of one
+//! message, after which attribution is a binary search and rendering with
+//! origins costs one replacer call per piece.
+//!
+//! # Example
+//! ```
+//! use copyforward::flatten::Flattened;
+//! use copyforward::{exact, Config, CopyForward};
+//!
+//! let messages = ["Hello world", "Hello world today", "> Hello world today"];
+//! let flat = Flattened::new(&exact(&messages, Config::default()).segments()).unwrap();
+//! let origin = flat.locate(2, 8).unwrap();
+//! assert_eq!((origin.source_message, origin.source_offset), (0, 6));
+//! let rendered = flat.render_with(|m, start, len, _| format!("[{m}:{start}+{len}]"));
+//! assert_eq!(rendered[2], "> [0:0+11][1:11+6]");
+//! ```
+
+use crate::core::Segment;
+use crate::render::{Origin, ReconstructError, reconstruct};
+use std::ops::Range;
+
+/// Part of a message copied from one literal segment of its origin message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    /// Byte span in the message.
+    pub output: Range<usize>,
+    /// Index of the message's segment the piece belongs to.
+    pub segment: usize,
+    /// Message where the text was written as a literal; the message itself
+    /// for its own literal text.
+    pub source_message: usize,
+    /// Byte span of the text in `source_message`.
+    pub source: Range<usize>,
+}
+
+/// Rewrite `segments` so every reference points at the literal text of the
+/// message where it was first written, instead of at an intermediate copy.
+///
+/// Shorthand for [`Flattened::new`] followed by [`Flattened::segments`]; a
+/// reference whose text came from several origins is split into one
+/// reference per origin.
+///
+/// # Example
+/// ```
+/// use copyforward::flatten::resolve_to_origin;
+/// use copyforward::{exact, Config, CopyForward, Segment};
+///
+/// let messages = ["Hello world", "Hello world today", "> Hello world today"];
+/// let resolved = resolve_to_origin(&exact(&messages, Config::default()).segments()).unwrap();
+/// assert_eq!(
+///     resolved[2][1],
+///     Segment::Reference { message_idx: 0, start: 0, len: 11 }
+/// );
+/// ```
+pub fn resolve_to_origin(segments: &[Vec<Segment>]) -> Result<Vec<Vec<Segment>>, ReconstructError> {
+    Ok(Flattened::new(segments)?.segments())
+}
+
+/// Every message split into [`Piece`]s with fully resolved origins; see the
+/// [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Flattened {
+    texts: Vec<String>,
+    pieces: Vec<Vec<Piece>>,
+}
+
+impl Flattened {
+    /// Resolve the origins of every segment, checking the segments as
+    /// [`reconstruct`] does.
+    pub fn new(segments: &[Vec<Segment>]) -> Result<Flattened, ReconstructError> {
+        let texts = reconstruct(segments)?;
+        let mut pieces: Vec<Vec<Piece>> = Vec::with_capacity(segments.len());
+        for (message, segs) in segments.iter().enumerate() {
+            let mut out = Vec::new();
+            let mut pos = 0;
+            for (segment, seg) in segs.iter().enumerate() {
+                match seg {
+                    Segment::Literal(s) => {
+                        out.push(Piece {
+                            output: pos..pos + s.len(),
+                            segment,
+                            source_message: message,
+                            source: pos..pos + s.len(),
+                        });
+                        pos += s.len();
+                    }
+                    Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => {
+                        // A self-reference resolves through the pieces
+                        // built so far for this message.
+                        let source = pieces.get(*message_idx).unwrap_or(&out);
+                        let end = start + len;
+                        let first = source.partition_point(|p| p.output.end <= *start);
+                        let resolved: Vec<Piece> = source[first..]
+                            .iter()
+                            .take_while(|p| p.output.start < end)
+                            .map(|p| {
+                                let lo = p.output.start.max(*start);
+                                let hi = p.output.end.min(end);
+                                let shift = p.source.start + lo - p.output.start;
+                                Piece {
+                                    output: pos + lo - start..pos + hi - start,
+                                    segment,
+                                    source_message: p.source_message,
+                                    source: shift..shift + (hi - lo),
+                                }
+                            })
+                            .collect();
+                        out.extend(resolved);
+                        pos += len;
+                    }
+                }
+            }
+            pieces.push(out);
+        }
+        Ok(Flattened { texts, pieces })
+    }
+
+    /// Number of messages.
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    /// Whether there are no messages.
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    /// Text of message `idx`.
+    pub fn message(&self, idx: usize) -> &str {
+        &self.texts[idx]
+    }
+
+    /// Pieces of message `idx`, in order; they cover the message exactly.
+    pub fn pieces(&self, idx: usize) -> &[Piece] {
+        &self.pieces[idx]
+    }
+
+    /// [`render::locate`](crate::render::locate) by binary search instead of
+    /// following references.
+    pub fn locate(&self, message: usize, offset: usize) -> Option<Origin> {
+        let pieces = self.pieces.get(message)?;
+        let p = &pieces[pieces.partition_point(|p| p.output.end <= offset)..]
+            .first()
+            .filter(|p| p.output.start <= offset)?;
+        Some(Origin {
+            segment: p.segment,
+            source_message: p.source_message,
+            source_offset: p.source.start + offset - p.output.start,
+            source_span: p.source.clone(),
+        })
+    }
+
+    /// Segments equivalent to the input whose references all point at
+    /// literal text of their origin, so no reference chain is longer than
+    /// one.
+    pub fn segments(&self) -> Vec<Vec<Segment>> {
+        self.pieces
+            .iter()
+            .enumerate()
+            .map(|(message, pieces)| {
+                let mut segs: Vec<Segment> = Vec::new();
+                for p in pieces {
+                    if p.source_message == message {
+                        let text = &self.texts[message][p.output.clone()];
+                        match segs.last_mut() {
+                            Some(Segment::Literal(last)) => last.push_str(text),
+                            _ => segs.push(Segment::Literal(text.into())),
+                        }
+                    } else {
+                        segs.push(Segment::Reference {
+                            message_idx: p.source_message,
+                            start: p.source.start,
+                            len: p.source.len(),
+                        });
+                    }
+                }
+                segs
+            })
+            .collect()
+    }
+
+    /// Render every message, calling `replacer(origin_message, start, len,
+    /// text)` for each piece copied from another message and keeping the
+    /// message's own literal text.
+    pub fn render_with<F>(&self, mut replacer: F) -> Vec<String>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        self.pieces
+            .iter()
+            .enumerate()
+            .map(|(message, pieces)| {
+                let mut out = String::new();
+                for p in pieces {
+                    let text = &self.texts[message][p.output.clone()];
+                    if p.source_message == message {
+                        out.push_str(text);
+                    } else {
+                        out.push_str(&replacer(
+                            p.source_message,
+                            p.source.start,
+                            p.source.len(),
+                            text,
+                        ));
+                    }
+                }
+                out
+            })
+            .collect()
+    }
+}
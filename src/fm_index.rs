@@ -0,0 +1,373 @@
+//! FM-index over an archive of messages, for exact longest-match queries.
+//!
+//! The k-mer tables behind [`exact()`](crate::exact) cost several machine
+//! words per indexed position. An [`FmIndex`] stores the Burrows-Wheeler
+//! transform of the archive in a wavelet matrix plus a sparse suffix-array
+//! sample, a few bits per character, and still finds the longest match at
+//! any position of a query. It suits read-mostly archives: build the index
+//! once, then [`segment`](FmIndex::segment) many new messages against it.
+//!
+//! The index is built over the reversed archive, so backward search extends a
+//! match forward one character at a time, which is exactly the greedy
+//! longest-match step.
+//!
+//! # Example
+//! ```
+//! use copyforward::fm_index::FmIndex;
+//! use copyforward::Segment;
+//!
+//! let archive = FmIndex::new(&["The meeting is at noon.", "Bring the slides."]);
+//! let segments = archive.segment("Reminder: the meeting is at noon.", 8);
+//! assert_eq!(
+//!     segments,
+//!     [
+//!         Segment::Literal("Reminder: t".into()),
+//!         Segment::Reference { message_idx: 0, start: 1, len: 22 },
+//!     ]
+//! );
+//! ```
+
+use crate::core::Segment;
+use crate::{MessageLike, compute_offsets};
+
+/// Suffix-array rows sampled for locating matches: one per this many text
+/// positions. Locating walks at most this many LF steps.
+const SAMPLE_RATE: u32 = 32;
+
+/// Symbol ending the (reversed) text; sorts before everything else.
+const TERMINATOR: u32 = 0;
+/// Symbol separating messages, so matches never span two of them.
+const SEPARATOR: u32 = 1;
+/// First symbol id used for archive characters.
+const FIRST_CHAR: u32 = 2;
+
+/// Compact FM-index over archived messages; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct FmIndex {
+    /// Distinct archive characters, sorted; character `alphabet[i]` has
+    /// symbol id `FIRST_CHAR + i`.
+    alphabet: Vec<char>,
+    /// `counts[c]`: number of text symbols smaller than `c`.
+    counts: Vec<u32>,
+    bwt: WaveletMatrix,
+    /// Rows whose suffix-array value is sampled.
+    sampled: BitVec,
+    /// Sampled suffix-array values, in row order.
+    samples: Vec<u32>,
+    /// Length of the archive text including separators, without terminator.
+    text_len: u32,
+    /// Start of each message in the archive text.
+    starts: Vec<u32>,
+    /// Per message, `(char position, extra UTF-8 bytes up to and including
+    /// it)` for every multi-byte character.
+    wide: Vec<Vec<(u32, u32)>>,
+}
+
+impl FmIndex {
+    /// Index `messages`; `None` entries keep their index but match nothing.
+    pub fn new<M: MessageLike>(messages: &[M]) -> FmIndex {
+        let mut alphabet: Vec<char> = messages
+            .iter()
+            .filter_map(|m| m.as_message())
+            .flat_map(str::chars)
+            .collect();
+        alphabet.sort_unstable();
+        alphabet.dedup();
+
+        let mut text: Vec<u32> = Vec::new();
+        let mut starts = Vec::with_capacity(messages.len());
+        let mut wide = Vec::with_capacity(messages.len());
+        for m in messages {
+            starts.push(text.len() as u32);
+            let mut extra = 0;
+            let mut wide_chars = Vec::new();
+            for (pos, ch) in m.as_message().unwrap_or("").chars().enumerate() {
+                if ch.len_utf8() > 1 {
+                    extra += ch.len_utf8() as u32 - 1;
+                    wide_chars.push((pos as u32, extra));
+                }
+                let id = alphabet.binary_search(&ch).expect("char is in alphabet");
+                text.push(FIRST_CHAR + id as u32);
+            }
+            text.push(SEPARATOR);
+            wide.push(wide_chars);
+        }
+        let text_len = text.len() as u32;
+
+        text.reverse();
+        text.push(TERMINATOR);
+        let sa = suffix_array(&text);
+        let n = text.len();
+        let bwt: Vec<u32> = sa.iter().map(|&p| text[(p as usize + n - 1) % n]).collect();
+
+        let symbols = FIRST_CHAR as usize + alphabet.len();
+        let mut counts = vec![0u32; symbols + 1];
+        for &c in &text {
+            counts[c as usize + 1] += 1;
+        }
+        for c in 1..counts.len() {
+            counts[c] += counts[c - 1];
+        }
+
+        let sampled = BitVec::from_bits(sa.iter().map(|&p| p % SAMPLE_RATE == 0));
+        let samples = sa.into_iter().filter(|&p| p % SAMPLE_RATE == 0).collect();
+        FmIndex {
+            alphabet,
+            counts,
+            bwt: WaveletMatrix::new(&bwt, symbols as u32),
+            sampled,
+            samples,
+            text_len,
+            starts,
+            wide,
+        }
+    }
+
+    /// Number of archived messages.
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Whether the archive holds no messages.
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// Longest prefix of `pattern` that occurs in the archive, as a
+    /// reference to one of its occurrences; `None` if not even the first
+    /// character occurs.
+    pub fn longest_match(&self, pattern: &str) -> Option<Segment> {
+        let codes: Vec<char> = pattern.chars().collect();
+        let (len, row) = self.longest_at(&codes)?;
+        let bytes = codes[..len].iter().map(|c| c.len_utf8()).sum();
+        Some(self.reference(row, len, bytes))
+    }
+
+    /// Greedily segment `message` against the archive: each position takes
+    /// the longest archive match of at least `min_match_len` characters
+    /// (at least 1), everything else stays literal.
+    pub fn segment(&self, message: &str, min_match_len: usize) -> Vec<Segment> {
+        let k = min_match_len.max(1);
+        let codes: Vec<char> = message.chars().collect();
+        let offsets = compute_offsets(message);
+        let mut segments = Vec::new();
+        let mut cursor = 0;
+        let mut literal_start = 0;
+        while cursor < codes.len() {
+            match self.longest_at(&codes[cursor..]) {
+                Some((len, row)) if len >= k => {
+                    if literal_start < cursor {
+                        let text = &message[offsets[literal_start]..offsets[cursor]];
+                        segments.push(Segment::Literal(text.into()));
+                    }
+                    let bytes = offsets[cursor + len] - offsets[cursor];
+                    segments.push(self.reference(row, len, bytes));
+                    cursor += len;
+                    literal_start = cursor;
+                }
+                _ => cursor += 1,
+            }
+        }
+        if literal_start < codes.len() {
+            segments.push(Segment::Literal(message[offsets[literal_start]..].into()));
+        }
+        segments
+    }
+
+    /// Length of the longest archive match of a prefix of `pattern`, and a
+    /// suffix-array row of one occurrence.
+    fn longest_at(&self, pattern: &[char]) -> Option<(usize, usize)> {
+        let (mut lo, mut hi) = (0, self.bwt.len());
+        let mut best = None;
+        for (i, ch) in pattern.iter().enumerate() {
+            let Ok(id) = self.alphabet.binary_search(ch) else {
+                break;
+            };
+            let c = FIRST_CHAR + id as u32;
+            let base = self.counts[c as usize] as usize;
+            lo = base + self.bwt.rank(c, lo);
+            hi = base + self.bwt.rank(c, hi);
+            if lo >= hi {
+                break;
+            }
+            best = Some((i + 1, lo));
+        }
+        best
+    }
+
+    /// Reference for a `len`-character, `bytes`-byte match found at `row`.
+    fn reference(&self, row: usize, len: usize, bytes: usize) -> Segment {
+        let reversed_start = self.locate(row);
+        let start = self.text_len - reversed_start - len as u32;
+        let message_idx = self.starts.partition_point(|&s| s <= start) - 1;
+        let pos = start - self.starts[message_idx];
+        let wide = &self.wide[message_idx];
+        let extra = match wide.partition_point(|&(p, _)| p < pos) {
+            0 => 0,
+            i => wide[i - 1].1,
+        };
+        Segment::Reference {
+            message_idx,
+            start: (pos + extra) as usize,
+            len: bytes,
+        }
+    }
+
+    /// Suffix-array value of `row`, walking LF to the nearest sample.
+    fn locate(&self, mut row: usize) -> u32 {
+        let mut steps = 0;
+        while !self.sampled.get(row) {
+            let c = self.bwt.access(row);
+            row = self.counts[c as usize] as usize + self.bwt.rank(c, row);
+            steps += 1;
+        }
+        self.samples[self.sampled.rank1(row)] + steps
+    }
+}
+
+/// Suffix array of `text` by prefix doubling; `text` must end with a unique
+/// smallest symbol.
+fn suffix_array(text: &[u32]) -> Vec<u32> {
+    let n = text.len();
+    let mut sa: Vec<u32> = (0..n as u32).collect();
+    let mut rank: Vec<u32> = text.to_vec();
+    let mut next = vec![0u32; n];
+    let mut step = 1;
+    loop {
+        let key = |i: u32| {
+            let i = i as usize;
+            let second = rank.get(i + step).map_or(0, |&r| r + 1);
+            (rank[i], second)
+        };
+        sa.sort_unstable_by_key(|&i| key(i));
+        next[sa[0] as usize] = 0;
+        for w in 1..n {
+            let bump = u32::from(key(sa[w - 1]) < key(sa[w]));
+            next[sa[w] as usize] = next[sa[w - 1] as usize] + bump;
+        }
+        std::mem::swap(&mut rank, &mut next);
+        if rank[sa[n - 1] as usize] as usize == n - 1 {
+            return sa;
+        }
+        step *= 2;
+    }
+}
+
+/// Bit vector with constant-time rank.
+#[derive(Debug, Clone, Default)]
+struct BitVec {
+    words: Vec<u64>,
+    /// Ones before each word.
+    ranks: Vec<u32>,
+}
+
+impl BitVec {
+    fn from_bits(bits: impl Iterator<Item = bool>) -> BitVec {
+        let mut words = Vec::new();
+        for (i, bit) in bits.enumerate() {
+            if i % 64 == 0 {
+                words.push(0);
+            }
+            if bit {
+                *words.last_mut().expect("word pushed") |= 1 << (i % 64);
+            }
+        }
+        let ranks = words
+            .iter()
+            .scan(0, |ones, w: &u64| {
+                let before = *ones;
+                *ones += w.count_ones();
+                Some(before)
+            })
+            .collect();
+        BitVec { words, ranks }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    /// Ones in `[0, i)`.
+    fn rank1(&self, i: usize) -> usize {
+        let (word, bit) = (i / 64, i % 64);
+        if word == self.words.len() {
+            return self.ranks.last().map_or(0, |&r| {
+                r as usize + self.words[word - 1].count_ones() as usize
+            });
+        }
+        let mask = (1u64 << bit) - 1;
+        self.ranks[word] as usize + (self.words[word] & mask).count_ones() as usize
+    }
+
+    /// Zeros in `[0, i)`.
+    fn rank0(&self, i: usize) -> usize {
+        i - self.rank1(i)
+    }
+}
+
+/// Wavelet matrix: a sequence over `[0, sigma)` with access and rank in
+/// `O(log sigma)`.
+#[derive(Debug, Clone)]
+struct WaveletMatrix {
+    len: usize,
+    /// One bit vector per bit of the symbols, most significant first.
+    levels: Vec<BitVec>,
+    /// Zeros in each level.
+    zeros: Vec<usize>,
+}
+
+impl WaveletMatrix {
+    fn new(symbols: &[u32], sigma: u32) -> WaveletMatrix {
+        let bits = (u32::BITS - sigma.saturating_sub(1).leading_zeros()).max(1);
+        let mut current = symbols.to_vec();
+        let mut levels = Vec::with_capacity(bits as usize);
+        let mut zeros = Vec::with_capacity(bits as usize);
+        for level in (0..bits).rev() {
+            let bit = |c: u32| c >> level & 1 == 1;
+            levels.push(BitVec::from_bits(current.iter().map(|&c| bit(c))));
+            let (mut next, ones): (Vec<u32>, Vec<u32>) = current.iter().partition(|&&c| !bit(c));
+            zeros.push(next.len());
+            next.extend(ones);
+            current = next;
+        }
+        WaveletMatrix {
+            len: symbols.len(),
+            levels,
+            zeros,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn access(&self, mut i: usize) -> u32 {
+        let mut c = 0;
+        for (bv, &zeros) in self.levels.iter().zip(&self.zeros) {
+            c <<= 1;
+            if bv.get(i) {
+                c |= 1;
+                i = zeros + bv.rank1(i);
+            } else {
+                i = bv.rank0(i);
+            }
+        }
+        c
+    }
+
+    /// Occurrences of `c` in `[0, i)`.
+    fn rank(&self, c: u32, i: usize) -> usize {
+        let depth = self.levels.len() as u32;
+        let (mut start, mut end) = (0, i);
+        for (level, (bv, &zeros)) in self.levels.iter().zip(&self.zeros).enumerate() {
+            if c >> (depth - 1 - level as u32) & 1 == 1 {
+                start = zeros + bv.rank1(start);
+                end = zeros + bv.rank1(end);
+            } else {
+                start = bv.rank0(start);
+                end = bv.rank0(end);
+            }
+        }
+        end - start
+    }
+}
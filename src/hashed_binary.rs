@@ -1,8 +1,10 @@
 // HashedGreedyBinary: token-only variant using binary-search extension
 // per candidate with rolling hashes. All logic operates on u32 sequences.
 
-use crate::core::{Config, CopyForwardTokens, TokenSegment};
+use crate::core::{Config, CopyForwardTokens, DynTokenReplacer, TokenSegment};
+#[cfg(feature = "exact")]
 use crate::engine::binary::compute_binary_segments;
+use crate::metadata::Metadata;
 
 #[derive(Debug, Clone)]
 pub struct HashedGreedyBinary {
@@ -12,6 +14,7 @@ pub struct HashedGreedyBinary {
 }
 
 impl HashedGreedyBinary {
+    #[cfg(feature = "exact")]
     pub fn new_tokens(messages: &[&[u32]], config: Config) -> HashedGreedyBinary {
         let messages_vec: Vec<Vec<u32>> = messages.iter().map(|s| s.to_vec()).collect();
         let token_segs = compute_binary_segments(&messages_vec, &config);
@@ -21,6 +24,19 @@ impl HashedGreedyBinary {
             config,
         }
     }
+
+    /// Wrap existing segments of `messages`, which they must reproduce.
+    pub(crate) fn from_parts(
+        token_segs: Vec<Vec<TokenSegment>>,
+        messages: Vec<Vec<u32>>,
+        config: Config,
+    ) -> HashedGreedyBinary {
+        HashedGreedyBinary {
+            token_segs,
+            messages,
+            config,
+        }
+    }
 }
 
 impl CopyForwardTokens for HashedGreedyBinary {
@@ -28,6 +44,22 @@ impl CopyForwardTokens for HashedGreedyBinary {
         self.token_segs.clone()
     }
 
+    fn segments_for(&self, idx: usize) -> &[TokenSegment] {
+        &self.token_segs[idx]
+    }
+
+    fn message(&self, idx: usize) -> &[u32] {
+        &self.messages[idx]
+    }
+
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        Some(Metadata::new("exact", &self.config))
+    }
+
     fn render_with<F>(&self, mut replacer: F) -> Vec<Vec<u32>>
     where
         F: FnMut(usize, usize, usize, &[u32]) -> Vec<u32>,
@@ -53,4 +85,8 @@ impl CopyForwardTokens for HashedGreedyBinary {
         }
         out
     }
+
+    fn render_with_dyn(&self, replacer: &mut DynTokenReplacer<'_>) -> Vec<Vec<u32>> {
+        self.render_with(replacer)
+    }
 }
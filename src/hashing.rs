@@ -3,6 +3,10 @@
 //! Uses wrapping u64 arithmetic (mod 2^64) for speed. This is not cryptographically
 //! secure but collision rates are extremely low in practice for text compression.
 
+use ahash::{AHashMap, RandomState};
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
 /// Compute rolling prefix hashes and powers for a byte string.
 /// Returns (h, p) where h[r] - h[l]*p[r-l] yields the rolling hash for s[l..r).
 pub fn prefix_hashes(s: &[u8], base: u64) -> (Vec<u64>, Vec<u64>) {
@@ -39,3 +43,151 @@ pub fn prefix_hashes_u32(s: &[u32], base: u64) -> (Vec<u64>, Vec<u64>) {
     }
     (h, p)
 }
+
+/// Rolling prefix hashes and powers of one message, as returned by
+/// [`prefix_hashes_u32`].
+pub type PrefixHashes = (Vec<u64>, Vec<u64>);
+
+/// Rolling prefix hashes of one message with the hash of each of its
+/// k-mers, as a [`HashCache`] stores them.
+pub(crate) type MessageHashes = (Arc<PrefixHashes>, Arc<[u64]>);
+
+/// Hash of every `k`-unit window of the message with prefix hashes `prefix`;
+/// empty for `k == 0` and messages shorter than `k`.
+pub(crate) fn window_hashes(prefix: &PrefixHashes, k: usize) -> Vec<u64> {
+    let (h, p) = prefix;
+    let len = h.len() - 1;
+    if k == 0 || len < k {
+        return Vec::new();
+    }
+    (0..=len - k).map(|s| range_hash(h, p, s, s + k)).collect()
+}
+
+/// Content-addressed cache of per-message rolling and k-mer hashes.
+///
+/// Identical messages recurring across threads (forwarded boilerplate,
+/// crossposts) only need hashing once: set
+/// [`Config::hash_cache`](crate::Config::hash_cache) to a shared cache and
+/// every run consults it. Clones share storage, so one cache can serve many
+/// threads, including from several OS threads.
+///
+/// Entries are found by one 64-bit hash of the message and confirmed by
+/// comparing it with the stored copy, so a collision costs a recomputation,
+/// never wrong hashes. They are kept until [`clear`](HashCache::clear); once
+/// `capacity` entries are stored, further messages are hashed without being
+/// cached. A message hashed for two k-mer lengths takes two entries.
+///
+/// # Example
+/// ```
+/// use copyforward::hashing::HashCache;
+/// use copyforward::{exact, Config, CopyForward};
+///
+/// let config = Config { hash_cache: Some(HashCache::new(10_000)), ..Config::default() };
+/// let footer = "Sent from the shared support inbox. Do not reply.";
+/// for thread in [["Printer jammed.", footer], ["VPN is down again.", footer]] {
+///     let rendered = exact(&thread, config.clone()).render_with(|_, _, _, t| t.to_string());
+///     assert_eq!(rendered, thread);
+/// }
+/// assert_eq!(config.hash_cache.unwrap().len(), 3);
+/// ```
+#[derive(Clone)]
+pub struct HashCache {
+    capacity: usize,
+    entries: Arc<RwLock<Entries>>,
+}
+
+/// Cached messages by content hash, with their total count.
+#[derive(Default)]
+struct Entries {
+    by_hash: AHashMap<u64, Vec<Entry>>,
+    len: usize,
+}
+
+struct Entry {
+    tokens: Box<[u32]>,
+    base: u64,
+    k: usize,
+    hashes: MessageHashes,
+}
+
+impl HashCache {
+    /// Create an empty cache holding at most `capacity` messages.
+    pub fn new(capacity: usize) -> Self {
+        HashCache {
+            capacity,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Number of cached messages.
+    pub fn len(&self) -> usize {
+        self.read().len
+    }
+
+    /// Whether the cache holds no messages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every cached message.
+    pub fn clear(&self) {
+        *self.write() = Entries::default();
+    }
+
+    /// [`prefix_hashes_u32`] of `tokens` over `base` with the
+    /// [`window_hashes`] of length `k`, reused from the cache when the same
+    /// content was hashed before with the same `base` and `k`.
+    pub(crate) fn message_hashes(&self, tokens: &[u32], base: u64, k: usize) -> MessageHashes {
+        let key = CONTENT_HASH.hash_one(tokens);
+        let find = |entries: &Entries| {
+            entries.by_hash.get(&key).and_then(|bucket| {
+                bucket
+                    .iter()
+                    .find(|e| e.base == base && e.k == k && *e.tokens == *tokens)
+                    .map(|e| e.hashes.clone())
+            })
+        };
+        if let Some(hit) = find(&self.read()) {
+            return hit;
+        }
+        let prefix = prefix_hashes_u32(tokens, base);
+        let kmers = window_hashes(&prefix, k).into();
+        let hashes: MessageHashes = (Arc::new(prefix), kmers);
+        let mut entries = self.write();
+        if entries.len < self.capacity && find(&entries).is_none() {
+            entries.by_hash.entry(key).or_default().push(Entry {
+                tokens: tokens.into(),
+                base,
+                k,
+                hashes: hashes.clone(),
+            });
+            entries.len += 1;
+        }
+        hashes
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Entries> {
+        self.entries.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Entries> {
+        self.entries.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for HashCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Fixed-seed hasher for the content hash of cached messages.
+const CONTENT_HASH: RandomState = RandomState::with_seeds(
+    0x243f_6a88_85a3_08d3,
+    0x1319_8a2e_0370_7344,
+    0xa409_3822_299f_31d0,
+    0x082e_fa98_ec4e_6c89,
+);
@@ -0,0 +1,107 @@
+//! Self-contained HTML views of a compressed thread.
+//!
+//! [`render_thread`] writes one page per thread: every message in order, its
+//! literal text as-is and every quoted region highlighted and linked to the
+//! message it was copied from. It is meant for people evaluating what the
+//! algorithms found, so the page needs no scripts or external assets.
+//!
+//! # Example
+//! ```
+//! use copyforward::html::render_thread;
+//! use copyforward::{exact, Config};
+//!
+//! let model = exact(&["Lunch at noon?", "> Lunch at noon?\nSure!"], Config::default());
+//! let page = render_thread(&model, "Lunch");
+//! assert!(page.contains(r##"<a class="quote" href="#m0""##));
+//! ```
+
+use crate::core::{CopyForward, Segment};
+use crate::corpus::Thread;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 60em; margin: 2em auto; color: #222; }
+section { border-top: 1px solid #ccc; padding: 0.5em 0; }
+section:target { background: #f3f7ff; }
+h2 { font-size: 1em; margin: 0.2em 0; }
+pre { white-space: pre-wrap; font-family: inherit; margin: 0; }
+a.quote { background: #fff1a8; color: inherit; text-decoration: none; }
+a.quote:hover { background: #ffd84d; }
+";
+
+/// A complete HTML document showing the messages of `model` under `title`.
+///
+/// Message `i` is the element with id `m{i}`; each reference is an
+/// `<a class="quote">` linking to its source message, with the source
+/// message and byte range in its tooltip.
+pub fn render_thread(model: &dyn CopyForward, title: &str) -> String {
+    let mut out = head(title);
+    for idx in 0..model.len() {
+        out.push_str(&format!(
+            "<section id=\"m{idx}\">\n<h2><a href=\"#m{idx}\">Message {idx}</a></h2>\n<pre>"
+        ));
+        for seg in model.segments_for(idx) {
+            match seg {
+                Segment::Literal(text) => out.push_str(&escape(text)),
+                &Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => {
+                    let text = &model.message(message_idx)[start..start + len];
+                    out.push_str(&format!(
+                        "<a class=\"quote\" href=\"#m{message_idx}\" \
+                         title=\"message {message_idx}, bytes {start}..{}\">{}</a>",
+                        start + len,
+                        escape(text)
+                    ));
+                }
+            }
+        }
+        out.push_str("</pre>\n</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// A page titled `title` linking to each thread's page, given as
+/// `(href, thread)` pairs.
+pub fn render_index(title: &str, pages: &[(String, &Thread)]) -> String {
+    let mut out = head(title);
+    out.push_str("<ul>\n");
+    for (href, thread) in pages {
+        let count = thread.messages.len();
+        out.push_str(&format!(
+            "<li><a href=\"{}\">{}</a> ({count} message{})</li>\n",
+            escape(href),
+            escape(&thread.id),
+            if count == 1 { "" } else { "s" }
+        ));
+    }
+    out.push_str("</ul>\n</body>\n</html>\n");
+    out
+}
+
+/// Document start up to and including the `<h1>` heading.
+fn head(title: &str) -> String {
+    let title = escape(title);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\n{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    )
+}
+
+/// Escape text for HTML element content and quoted attribute values.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,289 @@
+//! JSON Lines ingestion.
+//!
+//! Chat exports and dataset dumps often arrive as one JSON object per line.
+//! [`read_threads`] takes the message text from one field and, optionally,
+//! the thread id from another, and groups lines into [`Thread`]s in
+//! first-seen order, like [`crate::csv::read_threads`] does for rows. Other
+//! fields may hold any JSON value and are ignored. [`write_threads`] writes
+//! threads back out in the same shape.
+//!
+//! # Example
+//! ```
+//! use copyforward::jsonl::{read_threads, JsonlOptions};
+//!
+//! let input = r#"{"thread": "a", "text": "Hello"}
+//! {"thread": "b", "text": "Hi there", "likes": [1, 2]}
+//! {"thread": "a", "text": "> Hello\nHello back"}
+//! "#;
+//! let options = JsonlOptions { thread_field: Some("thread".into()), ..JsonlOptions::default() };
+//! let threads = read_threads(input.as_bytes(), &options).unwrap();
+//! assert_eq!(threads[0].messages, ["Hello", "> Hello\nHello back"]);
+//! ```
+
+use crate::corpus::Thread;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Options for [`read_threads`].
+#[derive(Debug, Clone)]
+pub struct JsonlOptions {
+    /// Field holding the message text, which must be a string.
+    pub text_field: String,
+    /// Field holding the thread id, a string or number; `None` puts every
+    /// line in one thread.
+    pub thread_field: Option<String>,
+}
+
+impl Default for JsonlOptions {
+    /// Text in `"text"`, no thread field.
+    fn default() -> Self {
+        JsonlOptions {
+            text_field: "text".to_string(),
+            thread_field: None,
+        }
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read JSON Lines and group them into threads.
+///
+/// Without a thread field every line belongs to a single thread named `all`.
+/// Blank lines are skipped. A line that is not a JSON object, or lacks a
+/// selected field, is an [`io::ErrorKind::InvalidData`] error.
+pub fn read_threads<R: Read>(mut reader: R, options: &JsonlOptions) -> io::Result<Vec<Thread>> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let mut threads: Vec<Thread> = Vec::new();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+    for (n, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let at = |msg: String| invalid(format!("line {}: {msg}", n + 1));
+        let mut fields = parse_object(line).map_err(at)?;
+        let text = match fields.remove(&options.text_field) {
+            Some(Value::Text(s)) => s,
+            _ => return Err(at(format!("no string field '{}'", options.text_field))),
+        };
+        let id = match &options.thread_field {
+            Some(name) => match fields.remove(name) {
+                Some(Value::Text(s) | Value::Number(s)) => s,
+                _ => return Err(at(format!("no string or number field '{name}'"))),
+            },
+            None => "all".to_string(),
+        };
+        let t = *by_id.entry(id).or_insert_with_key(|id| {
+            threads.push(Thread::new(id.clone(), Vec::new()));
+            threads.len() - 1
+        });
+        threads[t].messages.push(text);
+    }
+    Ok(threads)
+}
+
+/// Write one line per message, in thread order, that [`read_threads`] with
+/// the same options reads back.
+///
+/// Without a thread field only the text is written, so threads are merged on
+/// reading.
+pub fn write_threads<W: Write>(
+    mut writer: W,
+    threads: &[Thread],
+    options: &JsonlOptions,
+) -> io::Result<()> {
+    for thread in threads {
+        let prefix = match &options.thread_field {
+            Some(name) => format!("{}: {}, ", quote(name), quote(&thread.id)),
+            None => String::new(),
+        };
+        for text in &thread.messages {
+            writeln!(
+                writer,
+                "{{{prefix}{}: {}}}",
+                quote(&options.text_field),
+                quote(text)
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+/// `text` as a JSON string literal.
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A top-level field value; only strings and numbers are kept.
+enum Value {
+    Text(String),
+    Number(String),
+    Other,
+}
+
+/// Parse one JSON object, keeping the last value of repeated keys.
+fn parse_object(line: &str) -> Result<HashMap<String, Value>, String> {
+    let mut chars = line.chars().peekable();
+    let mut fields = HashMap::new();
+    skip_ws(&mut chars);
+    expect(&mut chars, '{')?;
+    skip_ws(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_ws(&mut chars);
+            expect(&mut chars, '"')?;
+            let key = parse_string(&mut chars)?;
+            skip_ws(&mut chars);
+            expect(&mut chars, ':')?;
+            let value = parse_value(&mut chars)?;
+            fields.insert(key, value);
+            skip_ws(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err("expected ',' or '}'".to_string()),
+            }
+        }
+    }
+    skip_ws(&mut chars);
+    match chars.next() {
+        None => Ok(fields),
+        Some(c) => Err(format!("unexpected '{c}' after object")),
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    skip_ws(chars);
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            Ok(Value::Text(parse_string(chars)?))
+        }
+        Some('{' | '[') => {
+            skip_nested(chars)?;
+            Ok(Value::Other)
+        }
+        Some(c) if c.is_ascii_digit() || *c == '-' => {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek()
+                && (c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+            {
+                number.push(c);
+                chars.next();
+            }
+            Ok(Value::Number(number))
+        }
+        _ => {
+            for word in ["true", "false", "null"] {
+                let mut ahead = chars.clone();
+                if word.chars().all(|w| ahead.next() == Some(w)) {
+                    *chars = ahead;
+                    return Ok(Value::Other);
+                }
+            }
+            Err("expected a value".to_string())
+        }
+    }
+}
+
+/// Parse the rest of a string whose opening quote was consumed.
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("unterminated string".to_string()),
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('/') => out.push('/'),
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let high = parse_hex4(chars)?;
+                    let code = if (0xD800..0xDC00).contains(&high) {
+                        if chars.next() != Some('\\') || chars.next() != Some('u') {
+                            return Err("unpaired surrogate".to_string());
+                        }
+                        let low = parse_hex4(chars)?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err("unpaired surrogate".to_string());
+                        }
+                        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                    } else {
+                        high
+                    };
+                    out.push(char::from_u32(code).ok_or("invalid \\u escape")?);
+                }
+                _ => return Err("invalid escape".to_string()),
+            },
+            Some(c) => out.push(c),
+        }
+    }
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let digits: String = chars.by_ref().take(4).collect();
+    if digits.len() != 4 {
+        return Err("truncated \\u escape".to_string());
+    }
+    u32::from_str_radix(&digits, 16).map_err(|_| "invalid \\u escape".to_string())
+}
+
+/// Skip an array or object, including strings that contain brackets.
+fn skip_nested(chars: &mut Peekable<Chars>) -> Result<(), String> {
+    let mut depth = 0usize;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            }
+            '"' => {
+                parse_string(chars)?;
+            }
+            _ => {}
+        }
+    }
+    Err("unterminated array or object".to_string())
+}
+
+fn skip_ws(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, want: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == want => Ok(()),
+        Some(c) => Err(format!("expected '{want}', found '{c}'")),
+        None => Err(format!("expected '{want}'")),
+    }
+}
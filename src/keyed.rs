@@ -0,0 +1,162 @@
+//! Segments whose references name messages by a stable key.
+//!
+//! [`Segment::Reference`] points at a message by its position, which stops
+//! meaning anything once a store deletes or reorders messages. [`to_keyed`]
+//! rewrites each reference to carry the key of its source instead (a
+//! database id, a `Message-ID`, any `Eq + Hash` value), and [`from_keyed`]
+//! turns keyed segments back into positional ones against the messages as
+//! they are ordered now.
+//!
+//! # Example
+//! ```
+//! use copyforward::keyed::{from_keyed, to_keyed, KeyedSegment};
+//! use copyforward::render::reconstruct;
+//! use copyforward::{exact, Config, CopyForward};
+//!
+//! let messages = ["Hello world", "Hello world today", "Unrelated"];
+//! let keys = [101u64, 102, 103];
+//! let keyed = to_keyed(&exact(&messages, Config::default()).segments(), &keys).unwrap();
+//! assert_eq!(keyed[1][0], KeyedSegment::Reference { message: 101, start: 0, len: 11 });
+//!
+//! // The store drops message 103 and keeps the others in order.
+//! let segments = from_keyed(&keyed[..2], &keys[..2]).unwrap();
+//! assert_eq!(reconstruct(&segments).unwrap(), messages[..2]);
+//! ```
+
+use crate::core::Segment;
+use crate::literal::LiteralText;
+use ahash::AHashMap as HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// A [`Segment`] whose reference names its source message by key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum KeyedSegment<K> {
+    /// Literal text, as in [`Segment::Literal`].
+    Literal(LiteralText),
+    /// `len` bytes of the message keyed `message`, from byte `start`.
+    Reference {
+        /// Key of the referenced message.
+        message: K,
+        /// Byte offset where the referenced substring starts.
+        start: usize,
+        /// Length in bytes of the referenced substring.
+        len: usize,
+    },
+}
+
+/// Error converting between positional and keyed segments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyError {
+    /// Message `message` has the same key as an earlier one.
+    DuplicateKey { message: usize },
+    /// A reference names a key no message has, e.g. that of a deleted
+    /// message.
+    UnknownKey { message: usize, segment: usize },
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::DuplicateKey { message } => {
+                write!(f, "message {message} repeats the key of an earlier message")
+            }
+            KeyError::UnknownKey { message, segment } => write!(
+                f,
+                "segment {segment} of message {message} references an unknown key"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+/// Rewrite the references of `segments` to carry the key of their source,
+/// `keys[i]` being the key of message `i`.
+///
+/// # Panics
+/// If `keys` and `segments` differ in length, or a reference points past the
+/// last message.
+pub fn to_keyed<K: Clone + Eq + Hash>(
+    segments: &[Vec<Segment>],
+    keys: &[K],
+) -> Result<Vec<Vec<KeyedSegment<K>>>, KeyError> {
+    assert_eq!(segments.len(), keys.len(), "one key per message");
+    positions(keys)?;
+    Ok(segments
+        .iter()
+        .map(|segs| {
+            segs.iter()
+                .map(|seg| match seg {
+                    Segment::Literal(s) => KeyedSegment::Literal(s.clone()),
+                    Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => KeyedSegment::Reference {
+                        message: keys[*message_idx].clone(),
+                        start: *start,
+                        len: *len,
+                    },
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Resolve keyed segments to positional ones, `keys[i]` being the key of
+/// message `i` in the current order.
+///
+/// Only keys are checked; pass the result to
+/// [`reconstruct`](crate::render::reconstruct) or
+/// [`Reconstructed::new`](crate::render::Reconstructed::new) to also reject
+/// references that now point forward or past their source.
+///
+/// # Panics
+/// If `keys` and `segments` differ in length.
+pub fn from_keyed<K: Eq + Hash>(
+    segments: &[Vec<KeyedSegment<K>>],
+    keys: &[K],
+) -> Result<Vec<Vec<Segment>>, KeyError> {
+    assert_eq!(segments.len(), keys.len(), "one key per message");
+    let positions = positions(keys)?;
+    segments
+        .iter()
+        .enumerate()
+        .map(|(message, segs)| {
+            segs.iter()
+                .enumerate()
+                .map(|(segment, seg)| match seg {
+                    KeyedSegment::Literal(s) => Ok(Segment::Literal(s.clone())),
+                    KeyedSegment::Reference {
+                        message: key,
+                        start,
+                        len,
+                    } => {
+                        let message_idx = *positions
+                            .get(key)
+                            .ok_or(KeyError::UnknownKey { message, segment })?;
+                        Ok(Segment::Reference {
+                            message_idx,
+                            start: *start,
+                            len: *len,
+                        })
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Position of each key, rejecting repeated keys.
+fn positions<K: Eq + Hash>(keys: &[K]) -> Result<HashMap<&K, usize>, KeyError> {
+    let mut positions = HashMap::with_capacity(keys.len());
+    for (message, key) in keys.iter().enumerate() {
+        if positions.insert(key, message).is_some() {
+            return Err(KeyError::DuplicateKey { message });
+        }
+    }
+    Ok(positions)
+}
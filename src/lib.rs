@@ -46,24 +46,107 @@
 //!   - Best for: >1MB text, when speed matters more than perfect compression
 //!   - May split long references into multiple shorter ones
 //!   - Still achieves excellent compression ratios (typically 50-90% size reduction)
+//!
+//! [`optimal()`] is a much slower reference point: it chooses each message's
+//! segmentation globally rather than greedily, bounding what the two can reach.
 
 #![allow(unsafe_op_in_unsafe_fn)]
 
+pub mod archive;
+#[cfg(feature = "baseline")]
+pub mod baseline;
+pub mod boilerplate;
+pub mod bridge;
+#[cfg(any(feature = "exact", feature = "approximate", feature = "optimal"))]
+pub mod builder;
+pub mod bytes;
+#[cfg(feature = "approximate")]
 mod capped;
+pub mod codec;
+pub mod contamination;
 pub mod core;
+pub mod corpus;
+pub mod csv;
+#[cfg(feature = "server")]
+pub mod daemon;
+#[cfg(all(feature = "exact", feature = "approximate"))]
+pub mod dedup;
+pub mod edit;
 mod engine;
 pub mod fixture;
+pub mod flatten;
+pub mod fm_index;
 mod hashed_binary;
 pub mod hashing;
+pub mod html;
+pub mod jsonl;
+pub mod keyed;
+mod literal;
+pub mod maildir;
+pub mod metadata;
+pub mod minhash;
 mod normalize;
+pub mod peephole;
+pub mod prelude;
+pub mod pretty;
+#[cfg(feature = "exact")]
+pub mod process;
 #[cfg(feature = "python")]
 pub mod python_bindings;
+pub mod qgram;
+pub mod quality;
+#[cfg(feature = "exact")]
+pub mod quotes;
+pub mod render;
+pub mod rolling;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod shard;
+pub mod sourcemap;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
+pub mod store;
+mod text;
 pub mod tokenization;
+pub mod trace;
 
 // Public API - only expose what users need
-pub use crate::core::{Config, CopyForward, CopyForwardTokens, Segment, TokenSegment};
+#[cfg(any(feature = "exact", feature = "approximate", feature = "optimal"))]
+pub use crate::builder::CopyForwardBuilder;
+pub use crate::bytes::{ByteSegment, BytesLike, BytesModel, CopyForwardBytes};
+#[cfg(feature = "approximate")]
+pub use crate::bytes::approximate_bytes;
+#[cfg(feature = "exact")]
+pub use crate::bytes::exact_bytes;
+#[cfg(feature = "optimal")]
+pub use crate::bytes::optimal_bytes;
+pub use crate::core::{
+    Config, CopyForward, CopyForwardError, CopyForwardTokens, DynReplacer, DynTokenReplacer, Lsh,
+    Progress, ReplaceContext, Segment, SegmentRef, TieBreak, TokenSegment,
+};
+pub use crate::corpus::Thread;
+pub use crate::literal::LiteralText;
+#[cfg(feature = "exact")]
+pub use crate::process::{ProcessIter, process_iter};
+pub use crate::stats::Stats;
+pub use crate::trace::Trace;
+pub use compact_str::CompactString;
+
+use crate::metadata::Metadata;
+#[cfg(any(feature = "exact", feature = "approximate"))]
+use crate::text::Resumed;
+#[cfg(any(feature = "exact", feature = "approximate", feature = "optimal"))]
+use crate::text::TextInput;
+use crate::text::TextModel;
+use std::borrow::Cow;
+use std::ops::Range;
 
 /// Trait for types that can be used as message inputs, supporting both regular strings and None values.
+///
+/// Implemented for `&str`, `String`, `Cow<str>`, `Box<str>`, `Arc<str>`, [`CompactString`],
+/// references to any of these, and `Option` of `&str` or `String`, so a `Vec<String>` can be
+/// passed as `&messages` without collecting a parallel `Vec<&str>`.
 pub trait MessageLike {
     fn as_message(&self) -> Option<&str>;
 }
@@ -84,6 +167,27 @@ impl MessageLike for Option<String> {
     fn as_message(&self) -> Option<&str> { self.as_deref() }
 }
 
+impl MessageLike for Cow<'_, str> {
+    fn as_message(&self) -> Option<&str> { Some(self) }
+}
+
+impl MessageLike for Box<str> {
+    fn as_message(&self) -> Option<&str> { Some(self) }
+}
+
+impl MessageLike for std::sync::Arc<str> {
+    fn as_message(&self) -> Option<&str> { Some(self) }
+}
+
+impl MessageLike for CompactString {
+    fn as_message(&self) -> Option<&str> { Some(self.as_str()) }
+}
+
+/// Borrowed messages, such as the `&String`s of a filtered `Vec<String>`.
+impl<M: MessageLike + ?Sized> MessageLike for &M {
+    fn as_message(&self) -> Option<&str> { (**self).as_message() }
+}
+
 /// Trait for types that can be used as token inputs, supporting both regular tokens and None values.
 pub trait TokenLike {
     fn as_tokens(&self) -> Option<&[u32]>;
@@ -106,6 +210,7 @@ impl TokenLike for Option<Vec<u32>> {
 }
 
 /// Exact copy-forward compression for token sequences (u32 IDs).
+#[cfg(feature = "exact")]
 pub type ExactTokens = hashed_binary::HashedGreedyBinary;
 
 /// Approximate copy-forward compression with capped extension.
@@ -119,26 +224,33 @@ pub type ExactTokens = hashed_binary::HashedGreedyBinary;
 /// Best for large message sets (>1MB) where speed matters more than perfect compression.
 /// Still achieves excellent ratios, just may split some long matches into multiple references.
 /// Approximate copy-forward compression for token sequences (u32 IDs).
+#[cfg(feature = "approximate")]
 pub type ApproximateTokens = capped::CappedHashedGreedy;
 
 /// Text-mode wrapper for exact algorithm routing through the token core.
+#[cfg(feature = "exact")]
 #[derive(Debug, Clone)]
 pub struct Exact {
-    inner: ExactTokens,
-    originals: Vec<String>,
-    offsets: Vec<Vec<usize>>, // byte offsets per Unicode-scalar boundary
-    valid_indices: Vec<usize>, // indices of non-None messages
-    none_mask: Vec<bool>, // true for None entries
+    model: TextModel,
+    metadata: Metadata,
+    resumed: Resumed<engine::binary::BinaryEngine<'static>>,
 }
 
 /// Text-mode wrapper for approximate algorithm routing through the token core.
+#[cfg(feature = "approximate")]
 #[derive(Debug, Clone)]
 pub struct Approximate {
-    inner: ApproximateTokens,
-    originals: Vec<String>,
-    offsets: Vec<Vec<usize>>, // byte offsets per Unicode-scalar boundary
-    valid_indices: Vec<usize>, // indices of non-None messages
-    none_mask: Vec<bool>, // true for None entries
+    model: TextModel,
+    metadata: Metadata,
+    resumed: Resumed<engine::capped::CappedEngine<'static>>,
+}
+
+/// Text-mode result of the globally optimal algorithm.
+#[cfg(feature = "optimal")]
+#[derive(Debug, Clone)]
+pub struct Optimal {
+    model: TextModel,
+    metadata: Metadata,
 }
 
 fn compute_offsets(s: &str) -> Vec<usize> {
@@ -172,32 +284,45 @@ fn compute_offsets(s: &str) -> Vec<usize> {
 /// let messages_with_none = &[Some("Hello"), None, Some("World")];
 /// let compressed = exact(messages_with_none, Config::default());
 /// ```
+#[cfg(feature = "exact")]
 pub fn exact<M: MessageLike>(messages: &[M], config: Config) -> Exact {
-    let opts: Vec<Option<&str>> = messages.iter().map(|m| m.as_message()).collect();
-    let originals: Vec<String> = opts.iter().map(|opt| opt.unwrap_or("").to_string()).collect();
-    let offsets: Vec<Vec<usize>> = originals.iter().map(|s| compute_offsets(s)).collect();
-    let toks: Vec<Vec<u32>> = originals
-        .iter()
-        .map(|s| normalize::string_to_u32s(s))
-        .collect();
-    let valid_indices: Vec<usize> = opts.iter().enumerate().filter_map(|(i, opt)| if opt.is_some() { Some(i) } else { None }).collect();
-    let filtered_toks: Vec<Vec<u32>> = valid_indices.iter().map(|&i| toks[i].clone()).collect();
-    let refs: Vec<&[u32]> = filtered_toks.iter().map(|v| v.as_slice()).collect();
-    let inner = hashed_binary::HashedGreedyBinary::new_tokens(&refs, config);
+    exact_input(TextInput::new(messages), config)
+}
+
+/// [`exact()`] taking ownership of `messages`, which become the model's
+/// originals without being copied; `None` entries keep their index as there.
+///
+/// ```
+/// use copyforward::{exact_owned, Config, CopyForward};
+///
+/// let messages = vec!["Hello world".to_string(), "Hello world today".to_string()];
+/// let compressed = exact_owned(messages, Config::default());
+/// assert_eq!(compressed.render_with_static("[REF]")[1], "[REF] today");
+/// ```
+#[cfg(feature = "exact")]
+pub fn exact_owned<M: Into<Option<String>>>(messages: Vec<M>, config: Config) -> Exact {
+    exact_input(TextInput::owned(messages.into_iter().map(Into::into)), config)
+}
+
+#[cfg(feature = "exact")]
+fn exact_input(input: TextInput, config: Config) -> Exact {
+    let metadata = Metadata::new("exact", &config);
+    let (model, resumed) = TextModel::build_resumable(input, &config);
     Exact {
-        inner,
-        originals,
-        offsets,
-        valid_indices,
-        none_mask: opts.iter().map(|opt| opt.is_none()).collect(),
+        model,
+        metadata,
+        resumed,
     }
 }
 
 /// Create an exact token-mode compressor over u32 token sequences.
 /// 
 /// Supports both regular token slices and optional token slices for handling missing values.
+#[cfg(feature = "exact")]
 pub fn exact_tokens<T: TokenLike>(messages: &[T], config: Config) -> ExactTokens {
     let filtered_tokens: Vec<&[u32]> = messages.iter().filter_map(|t| t.as_tokens()).collect();
+    // Stop phrases are text; they have no meaning for token ids.
+    let config = Config { stop_phrases: Vec::new(), ..config };
     hashed_binary::HashedGreedyBinary::new_tokens(&filtered_tokens, config)
 }
 
@@ -218,201 +343,471 @@ pub fn exact_tokens<T: TokenLike>(messages: &[T], config: Config) -> ExactTokens
 /// let messages_with_none = &[Some("Hello"), None, Some("World")];
 /// let compressed = approximate(messages_with_none, Config::default());
 /// ```
+#[cfg(feature = "approximate")]
 pub fn approximate<M: MessageLike>(messages: &[M], config: Config) -> Approximate {
-    let opts: Vec<Option<&str>> = messages.iter().map(|m| m.as_message()).collect();
-    let originals: Vec<String> = opts.iter().map(|opt| opt.unwrap_or("").to_string()).collect();
-    let offsets: Vec<Vec<usize>> = originals.iter().map(|s| compute_offsets(s)).collect();
-    let toks: Vec<Vec<u32>> = originals
-        .iter()
-        .map(|s| normalize::string_to_u32s(s))
-        .collect();
-    let valid_indices: Vec<usize> = opts.iter().enumerate().filter_map(|(i, opt)| if opt.is_some() { Some(i) } else { None }).collect();
-    let filtered_toks: Vec<Vec<u32>> = valid_indices.iter().map(|&i| toks[i].clone()).collect();
-    let refs: Vec<&[u32]> = filtered_toks.iter().map(|v| v.as_slice()).collect();
-    let inner = capped::CappedHashedGreedy::new_tokens(&refs, config);
+    approximate_input(TextInput::new(messages), config)
+}
+
+/// [`approximate()`] taking ownership of `messages`; see [`exact_owned`].
+#[cfg(feature = "approximate")]
+pub fn approximate_owned<M: Into<Option<String>>>(messages: Vec<M>, config: Config) -> Approximate {
+    approximate_input(TextInput::owned(messages.into_iter().map(Into::into)), config)
+}
+
+#[cfg(feature = "approximate")]
+fn approximate_input(input: TextInput, config: Config) -> Approximate {
+    let metadata = Metadata::new("approximate", &config);
+    let (model, resumed) = TextModel::build_resumable(input, &config);
     Approximate {
-        inner,
-        originals,
-        offsets,
-        valid_indices,
-        none_mask: opts.iter().map(|opt| opt.is_none()).collect(),
+        model,
+        metadata,
+        resumed,
     }
 }
 
-impl CopyForward for Exact {
-    fn segments(&self) -> Vec<Vec<Segment>> {
-        let token_segs = <ExactTokens as CopyForwardTokens>::segments(&self.inner);
-        let mut out: Vec<Vec<Segment>> = Vec::with_capacity(self.none_mask.len());
-        let mut token_seg_idx = 0;
-        
-        for &is_none in &self.none_mask {
-            if is_none {
-                // Empty segments for None entries
-                out.push(vec![]);
-            } else {
-                let segs = &token_segs[token_seg_idx];
-                let mut v: Vec<Segment> = Vec::with_capacity(segs.len());
-                for seg in segs {
-                    match seg {
-                        TokenSegment::Literal(toks) => {
-                            v.push(Segment::Literal(normalize::u32s_to_string(toks)))
-                        }
-                        TokenSegment::Reference {
-                            message_idx: ref_idx,
-                            start,
-                            len,
-                        } => {
-                            // Map back to original indices
-                            let orig_msg_idx = self.valid_indices[*ref_idx];
-                            let offs = &self.offsets[orig_msg_idx];
-                            let bstart = offs[*start];
-                            let bend = offs[start + len];
-                            v.push(Segment::Reference {
-                                message_idx: orig_msg_idx,
-                                start: bstart,
-                                len: bend - bstart,
-                            });
-                        }
-                    }
-                }
-                out.push(v);
-                token_seg_idx += 1;
+/// Implement [`CopyForward`] for a result type with `model: TextModel` and
+/// `metadata: Metadata` fields by delegating to the model.
+macro_rules! text_model_copy_forward {
+    ($ty:ty) => {
+        impl CopyForward for $ty {
+            fn segments(&self) -> Vec<Vec<Segment>> {
+                self.model.segments()
+            }
+
+            fn segments_for(&self, idx: usize) -> &[Segment] {
+                self.model.segments_for(idx)
             }
-        }
-        out
-    }
 
-    fn render_with<F>(&self, mut replacer: F) -> Vec<String>
-    where
-        F: FnMut(usize, usize, usize, &str) -> String,
-    {
-        let token_segs = <ExactTokens as CopyForwardTokens>::segments(&self.inner);
-        let mut out: Vec<String> = Vec::with_capacity(self.none_mask.len());
-        let mut token_seg_idx = 0;
-        
-        for &is_none in &self.none_mask {
-            if is_none {
-                // Return original empty/none value
-                out.push(String::new());
-            } else {
-                let segs = &token_segs[token_seg_idx];
-                let mut s = String::new();
-                for seg in segs {
-                    match seg {
-                        TokenSegment::Literal(toks) => s.push_str(&normalize::u32s_to_string(toks)),
-                        TokenSegment::Reference {
-                            message_idx: ref_idx,
-                            start,
-                            len,
-                        } => {
-                            let orig_msg_idx = self.valid_indices[*ref_idx];
-                            let offs = &self.offsets[orig_msg_idx];
-                            let bstart = offs[*start];
-                            let bend = offs[start + len];
-                            let ref_text = &self.originals[orig_msg_idx][bstart..bend];
-                            let replaced = replacer(orig_msg_idx, bstart, bend - bstart, ref_text);
-                            s.push_str(&replaced);
-                        }
-                    }
-                }
-                out.push(s);
-                token_seg_idx += 1;
+            fn message(&self, idx: usize) -> &str {
+                self.model.message(idx)
+            }
+
+            fn len(&self) -> usize {
+                self.model.len()
+            }
+
+            fn metadata(&self) -> Option<Metadata> {
+                Some(self.metadata.clone())
+            }
+
+            fn render_with<F>(&self, replacer: F) -> Vec<String>
+            where
+                F: FnMut(usize, usize, usize, &str) -> String,
+            {
+                self.model.render_with(replacer)
+            }
+
+            fn render_with_dyn(&self, replacer: &mut DynReplacer<'_>) -> Vec<String> {
+                self.model.render_with(replacer)
+            }
+
+            fn render_into_dyn(
+                &self,
+                idx: usize,
+                buf: &mut String,
+                replacer: &mut DynReplacer<'_>,
+            ) {
+                self.model.render_into(idx, buf, replacer)
+            }
+
+            fn render_into<F>(&self, idx: usize, buf: &mut String, replacer: F)
+            where
+                F: FnMut(usize, usize, usize, &str) -> String,
+            {
+                self.model.render_into(idx, buf, replacer)
+            }
+
+            fn render_all_into<F>(&self, buf: &mut String, replacer: F) -> Vec<Range<usize>>
+            where
+                F: FnMut(usize, usize, usize, &str) -> String,
+            {
+                self.model.render_all_into(buf, replacer)
+            }
+
+            fn render_cow<'a, F>(&'a self, replacer: F) -> Vec<Cow<'a, str>>
+            where
+                F: FnMut(usize, usize, usize, &'a str) -> Cow<'a, str>,
+            {
+                self.model.render_cow(replacer)
             }
         }
-        out
+    };
+}
+
+#[cfg(feature = "exact")]
+text_model_copy_forward!(Exact);
+#[cfg(feature = "approximate")]
+text_model_copy_forward!(Approximate);
+#[cfg(feature = "optimal")]
+text_model_copy_forward!(Optimal);
+text_model_copy_forward!(Compressed);
+
+#[cfg(feature = "exact")]
+impl Exact {
+    /// [`exact()`] after checking `config` with [`Config::validate`] and that
+    /// there is at least one message.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{Config, CopyForwardError, Exact};
+    ///
+    /// let config = Config { min_match_len: 0, ..Config::default() };
+    /// assert!(Exact::try_with_config(&["Hello world"], config).is_err());
+    /// let none: [&str; 0] = [];
+    /// assert_eq!(
+    ///     Exact::try_with_config(&none, Config::default()).unwrap_err(),
+    ///     CopyForwardError::EmptyInput
+    /// );
+    /// ```
+    pub fn try_with_config<M: MessageLike>(
+        messages: &[M],
+        config: Config,
+    ) -> Result<Exact, CopyForwardError> {
+        check_input(messages, &config)?;
+        Ok(exact(messages, config))
+    }
+
+    /// Segment `message` against every earlier message and append it, with
+    /// the same result as [`exact()`] over the whole thread.
+    ///
+    /// The model keeps the engine's k-mer index and hashes from its build,
+    /// so a push only hashes and segments the new message; a clone rebuilds
+    /// them on its first push. The run uses the model's
+    /// [`metadata`](CopyForward::metadata) configuration, which has no
+    /// progress callback, trace or hash cache.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::{exact, Config, CopyForward, Segment};
+    ///
+    /// let mut thread = exact(&["Ship it on Friday"], Config::default());
+    /// let segments = thread.push_message("> Ship it on Friday\nAgreed");
+    /// assert!(matches!(segments[1], Segment::Reference { message_idx: 0, .. }));
+    /// assert_eq!(thread.len(), 2);
+    /// ```
+    pub fn push_message(&mut self, message: &str) -> &[Segment] {
+        let config = &self.metadata.config;
+        self.model.push(&mut self.resumed, config, message)
     }
 }
 
-impl CopyForward for Approximate {
-    fn segments(&self) -> Vec<Vec<Segment>> {
-        let token_segs = <ApproximateTokens as CopyForwardTokens>::segments(&self.inner);
-        let mut out: Vec<Vec<Segment>> = Vec::with_capacity(self.none_mask.len());
-        let mut token_seg_idx = 0;
-        
-        for &is_none in &self.none_mask {
-            if is_none {
-                // Empty segments for None entries
-                out.push(vec![]);
-            } else {
-                let segs = &token_segs[token_seg_idx];
-                let mut v: Vec<Segment> = Vec::with_capacity(segs.len());
-                for seg in segs {
-                    match seg {
-                        TokenSegment::Literal(toks) => {
-                            v.push(Segment::Literal(normalize::u32s_to_string(toks)))
-                        }
-                        TokenSegment::Reference {
-                            message_idx: ref_idx,
-                            start,
-                            len,
-                        } => {
-                            // Map back to original indices
-                            let orig_msg_idx = self.valid_indices[*ref_idx];
-                            let offs = &self.offsets[orig_msg_idx];
-                            let bstart = offs[*start];
-                            let bend = offs[start + len];
-                            v.push(Segment::Reference {
-                                message_idx: orig_msg_idx,
-                                start: bstart,
-                                len: bend - bstart,
-                            });
-                        }
-                    }
-                }
-                out.push(v);
-                token_seg_idx += 1;
-            }
-        }
-        out
+#[cfg(feature = "approximate")]
+impl Approximate {
+    /// [`approximate()`] after checking `config` with [`Config::validate`]
+    /// and that there is at least one message.
+    pub fn try_with_config<M: MessageLike>(
+        messages: &[M],
+        config: Config,
+    ) -> Result<Approximate, CopyForwardError> {
+        check_input(messages, &config)?;
+        Ok(approximate(messages, config))
     }
 
-    fn render_with<F>(&self, mut replacer: F) -> Vec<String>
-    where
-        F: FnMut(usize, usize, usize, &str) -> String,
-    {
-        let token_segs = <ApproximateTokens as CopyForwardTokens>::segments(&self.inner);
-        let mut out: Vec<String> = Vec::with_capacity(self.none_mask.len());
-        let mut token_seg_idx = 0;
-        
-        for &is_none in &self.none_mask {
-            if is_none {
-                // Return original empty/none value
-                out.push(String::new());
-            } else {
-                let segs = &token_segs[token_seg_idx];
-                let mut s = String::new();
-                for seg in segs {
-                    match seg {
-                        TokenSegment::Literal(toks) => s.push_str(&normalize::u32s_to_string(toks)),
-                        TokenSegment::Reference {
-                            message_idx: ref_idx,
-                            start,
-                            len,
-                        } => {
-                            let orig_msg_idx = self.valid_indices[*ref_idx];
-                            let offs = &self.offsets[orig_msg_idx];
-                            let bstart = offs[*start];
-                            let bend = offs[start + len];
-                            let ref_text = &self.originals[orig_msg_idx][bstart..bend];
-                            let replaced = replacer(orig_msg_idx, bstart, bend - bstart, ref_text);
-                            s.push_str(&replaced);
-                        }
-                    }
-                }
-                out.push(s);
-                token_seg_idx += 1;
-            }
-        }
-        out
+    /// Segment `message` against every earlier message and append it, with
+    /// the same result as [`approximate()`] over the whole thread; see
+    /// [`Exact::push_message`].
+    pub fn push_message(&mut self, message: &str) -> &[Segment] {
+        let config = &self.metadata.config;
+        self.model.push(&mut self.resumed, config, message)
+    }
+}
+
+/// Create a globally optimal copy-forward compressor.
+///
+/// Collects every match the k-mer index allows and picks the segmentation of
+/// each message with the smallest size under [`codec::encode_segments`] by a
+/// shortest path over its segment graph, instead of taking the longest match
+/// at each position. Much slower than [`exact()`]; use it as the quality
+/// ceiling the fast algorithms are measured against.
+/// [`Config::shallow_preference`] and the approximate tuning knobs are ignored.
+///
+/// ```
+/// use copyforward::{exact, optimal, Config, CopyForward};
+/// use copyforward::codec::encode_segments;
+///
+/// let messages = &["Hello world", "Hello world today", "world today, Hello world"];
+/// let best = optimal(messages, Config::default());
+/// let greedy = exact(messages, Config::default());
+/// assert!(encode_segments(&best.segments()).len() <= encode_segments(&greedy.segments()).len());
+/// ```
+#[cfg(feature = "optimal")]
+pub fn optimal<M: MessageLike>(messages: &[M], config: Config) -> Optimal {
+    optimal_input(TextInput::new(messages), config)
+}
+
+/// [`optimal()`] taking ownership of `messages`; see [`exact_owned`].
+#[cfg(feature = "optimal")]
+pub fn optimal_owned<M: Into<Option<String>>>(messages: Vec<M>, config: Config) -> Optimal {
+    optimal_input(TextInput::owned(messages.into_iter().map(Into::into)), config)
+}
+
+#[cfg(feature = "optimal")]
+fn optimal_input(input: TextInput, config: Config) -> Optimal {
+    let metadata = Metadata::new("optimal", &config);
+    let model = TextModel::build(input, config.min_encoded_gain, |toks| {
+        let messages: Vec<Vec<u32>> = toks.iter().map(|t| t.to_vec()).collect();
+        engine::optimal::compute_optimal_segments(&messages, &config)
+    });
+    Optimal { model, metadata }
+}
+
+#[cfg(feature = "optimal")]
+impl Optimal {
+    /// [`optimal()`] after checking `config` with [`Config::validate`] and
+    /// that there is at least one message.
+    pub fn try_with_config<M: MessageLike>(
+        messages: &[M],
+        config: Config,
+    ) -> Result<Optimal, CopyForwardError> {
+        check_input(messages, &config)?;
+        Ok(optimal(messages, config))
     }
 }
 
 /// Create an approximate token-mode compressor over u32 token sequences.
 /// 
 /// Supports both regular token slices and optional token slices for handling missing values.
+#[cfg(feature = "approximate")]
 pub fn approximate_tokens<T: TokenLike>(messages: &[T], config: Config) -> ApproximateTokens {
     let filtered_tokens: Vec<&[u32]> = messages.iter().filter_map(|t| t.as_tokens()).collect();
+    // Stop phrases are text; they have no meaning for token ids.
+    let config = Config { stop_phrases: Vec::new(), ..config };
     capped::CappedHashedGreedy::new_tokens(&filtered_tokens, config)
 }
 
+/// Result of [`copy_forward_exact`] and [`copy_forward_approx`], whichever
+/// algorithm produced it.
+///
+/// Unlike [`Exact`], [`Approximate`] and [`Optimal`], whose set grows with
+/// the algorithms, this one type is part of the stable [`prelude`]. Which
+/// algorithm ran is recorded in its [`metadata`](CopyForward::metadata).
+#[derive(Debug, Clone)]
+pub struct Compressed {
+    model: TextModel,
+    metadata: Metadata,
+}
+
+#[cfg(feature = "exact")]
+impl From<Exact> for Compressed {
+    fn from(result: Exact) -> Compressed {
+        Compressed {
+            model: result.model,
+            metadata: result.metadata,
+        }
+    }
+}
+
+#[cfg(feature = "approximate")]
+impl From<Approximate> for Compressed {
+    fn from(result: Approximate) -> Compressed {
+        Compressed {
+            model: result.model,
+            metadata: result.metadata,
+        }
+    }
+}
+
+#[cfg(feature = "optimal")]
+impl From<Optimal> for Compressed {
+    fn from(result: Optimal) -> Compressed {
+        Compressed {
+            model: result.model,
+            metadata: result.metadata,
+        }
+    }
+}
+
+/// Compress `messages` with the [`exact()`] algorithm.
+///
+/// ```
+/// use copyforward::prelude::*;
+///
+/// let compressed = copy_forward_exact(&["Hello world", "Hello world today"], Config::default());
+/// assert_eq!(compressed.render_with_static("[REF]")[1], "[REF] today");
+/// ```
+#[cfg(feature = "exact")]
+pub fn copy_forward_exact<M: MessageLike>(messages: &[M], config: Config) -> Compressed {
+    exact(messages, config).into()
+}
+
+/// Compress `messages` with the faster [`approximate()`] algorithm.
+///
+/// ```
+/// use copyforward::prelude::*;
+///
+/// let compressed = copy_forward_approx(&["Hello world", "Hello world today"], Config::default());
+/// assert_eq!(compressed.message(1), "Hello world today");
+/// assert_eq!(compressed.metadata().unwrap().algorithm, "approximate");
+/// ```
+#[cfg(feature = "approximate")]
+pub fn copy_forward_approx<M: MessageLike>(messages: &[M], config: Config) -> Compressed {
+    approximate(messages, config).into()
+}
+
+/// Algorithm run by [`compress`], for choosing one at runtime.
+///
+/// Parses from and displays as the name recorded in
+/// [`Metadata::algorithm`](metadata::Metadata::algorithm), so it can come
+/// straight from a command-line flag or a config file.
+///
+/// ```
+/// use copyforward::{compress, Algorithm, Config, CopyForward};
+///
+/// let algorithm: Algorithm = "approximate".parse().unwrap();
+/// let compressed = compress(&["Hello world", "Hello world today"], algorithm, Config::default());
+/// assert_eq!(compressed.metadata().unwrap().algorithm, algorithm.to_string());
+/// assert!("greedy".parse::<Algorithm>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Algorithm {
+    /// [`exact()`]
+    #[cfg(feature = "exact")]
+    Exact,
+    /// [`approximate()`]
+    #[cfg(feature = "approximate")]
+    Approximate,
+    /// [`optimal()`]
+    #[cfg(feature = "optimal")]
+    Optimal,
+}
+
+impl Algorithm {
+    /// Every algorithm compiled into this build.
+    pub const ALL: &[Algorithm] = &[
+        #[cfg(feature = "exact")]
+        Algorithm::Exact,
+        #[cfg(feature = "approximate")]
+        Algorithm::Approximate,
+        #[cfg(feature = "optimal")]
+        Algorithm::Optimal,
+    ];
+
+    /// Name of the algorithm, as recorded in [`metadata::Metadata`].
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "exact")]
+            Algorithm::Exact => "exact",
+            #[cfg(feature = "approximate")]
+            Algorithm::Approximate => "approximate",
+            #[cfg(feature = "optimal")]
+            Algorithm::Optimal => "optimal",
+        }
+    }
+}
+
+impl std::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error parsing an [`Algorithm`] from a name it does not have, or one not
+/// compiled into this build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownAlgorithm(pub String);
+
+impl std::fmt::Display for UnknownAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<&str> = Algorithm::ALL.iter().map(|a| a.name()).collect();
+        write!(
+            f,
+            "unknown algorithm {:?}; expected one of: {}",
+            self.0,
+            names.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownAlgorithm {}
+
+impl std::str::FromStr for Algorithm {
+    type Err = UnknownAlgorithm;
+
+    fn from_str(name: &str) -> Result<Algorithm, UnknownAlgorithm> {
+        Algorithm::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name() == name)
+            .ok_or_else(|| UnknownAlgorithm(name.to_string()))
+    }
+}
+
+/// Compress `messages` with `algorithm`, chosen at runtime.
+///
+/// Same result as calling [`exact()`], [`approximate()`] or [`optimal()`]
+/// and converting into [`Compressed`].
+#[cfg(any(feature = "exact", feature = "approximate", feature = "optimal"))]
+pub fn compress<M: MessageLike>(
+    messages: &[M],
+    algorithm: Algorithm,
+    config: Config,
+) -> Compressed {
+    match algorithm {
+        #[cfg(feature = "exact")]
+        Algorithm::Exact => exact(messages, config).into(),
+        #[cfg(feature = "approximate")]
+        Algorithm::Approximate => approximate(messages, config).into(),
+        #[cfg(feature = "optimal")]
+        Algorithm::Optimal => optimal(messages, config).into(),
+    }
+}
+
+/// [`compress`] after checking `config` with [`Config::validate`] and that
+/// there is at least one message.
+///
+/// ```
+/// use copyforward::{try_compress, Algorithm, Config, CopyForward, CopyForwardError};
+///
+/// let config = Config { min_match_len: 0, ..Config::default() };
+/// let err = try_compress(&["Hello world"], Algorithm::Exact, config).unwrap_err();
+/// assert_eq!(err.to_string(), "min_match_len must be at least 1");
+/// let none: [&str; 0] = [];
+/// let err = try_compress(&none, Algorithm::Exact, Config::default()).unwrap_err();
+/// assert_eq!(err, CopyForwardError::EmptyInput);
+/// ```
+#[cfg(any(feature = "exact", feature = "approximate", feature = "optimal"))]
+pub fn try_compress<M: MessageLike>(
+    messages: &[M],
+    algorithm: Algorithm,
+    config: Config,
+) -> Result<Compressed, CopyForwardError> {
+    check_input(messages, &config)?;
+    Ok(compress(messages, algorithm, config))
+}
+
+/// Reject an empty `messages` or a `config` failing [`Config::validate`].
+#[cfg(any(feature = "exact", feature = "approximate", feature = "optimal"))]
+fn check_input<M>(messages: &[M], config: &Config) -> Result<(), CopyForwardError> {
+    if messages.is_empty() {
+        return Err(CopyForwardError::EmptyInput);
+    }
+    config.validate()
+}
+
+/// [`try_compress`], then [`CopyForward::verify`] on the result, so hash
+/// collisions surface as an error instead of corrupted output.
+///
+/// ```
+/// use copyforward::{compress_verified, Algorithm, Config, CopyForward};
+///
+/// let messages = ["Hello world", "Hello world today"];
+/// let compressed = compress_verified(&messages, Algorithm::Approximate, Config::default()).unwrap();
+/// assert_eq!(compressed.render_with(|_, _, _, text| text.to_string()), messages);
+/// ```
+#[cfg(any(feature = "exact", feature = "approximate", feature = "optimal"))]
+pub fn compress_verified<M: MessageLike>(
+    messages: &[M],
+    algorithm: Algorithm,
+    config: Config,
+) -> Result<Compressed, CopyForwardError> {
+    let compressed = try_compress(messages, algorithm, config)?;
+    compressed.verify()?;
+    Ok(compressed)
+}
+
 // Tests live in the `tests/` directory as integration tests.
@@ -0,0 +1,236 @@
+//! Storage for the text of [`Segment::Literal`](crate::Segment::Literal).
+//!
+//! Short literals, typically the connective text between references, are
+//! stored inline. Longer literals that a model emits more than once, such as
+//! a signature or a reply header repeated across a thread, are interned in a
+//! per-model [`LiteralPool`] so every copy shares one allocation.
+
+use compact_str::CompactString;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Text of a literal segment: inline when short, otherwise on the heap and
+/// possibly shared with equal literals of the same model.
+///
+/// Derefs to `&str`; build one with `.into()` from a `&str`, `String` or
+/// [`CompactString`]. Equality, ordering and hashing are those of the text.
+///
+/// # Example
+/// ```
+/// use copyforward::{LiteralText, Segment};
+///
+/// let literal = Segment::Literal("Hello ".into());
+/// let text = LiteralText::from("Hello ");
+/// assert_eq!(literal, Segment::Literal(text.clone()));
+/// assert_eq!(text, "Hello ");
+/// assert!(!text.is_heap_allocated());
+/// ```
+#[derive(Clone)]
+pub struct LiteralText(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    /// Owned text, inline up to [`INLINE_CAPACITY`] bytes.
+    Owned(CompactString),
+    /// Text interned by a [`LiteralPool`].
+    Shared(Arc<str>),
+}
+
+/// Longest text a [`CompactString`] stores without a heap allocation.
+const INLINE_CAPACITY: usize = std::mem::size_of::<CompactString>();
+
+impl LiteralText {
+    /// The literal's text.
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Owned(s) => s.as_str(),
+            Repr::Shared(s) => s,
+        }
+    }
+
+    /// Whether the text lives on the heap rather than inline.
+    pub fn is_heap_allocated(&self) -> bool {
+        match &self.0 {
+            Repr::Owned(s) => s.is_heap_allocated(),
+            Repr::Shared(_) => true,
+        }
+    }
+
+    /// Whether the text is interned, so equal literals of the same model
+    /// share its allocation.
+    pub fn is_shared(&self) -> bool {
+        matches!(self.0, Repr::Shared(_))
+    }
+
+    /// Append `s`, copying shared text into a literal of its own first.
+    pub fn push_str(&mut self, s: &str) {
+        match &mut self.0 {
+            Repr::Owned(owned) => owned.push_str(s),
+            Repr::Shared(shared) => {
+                let mut owned = CompactString::from(&**shared);
+                owned.push_str(s);
+                self.0 = Repr::Owned(owned);
+            }
+        }
+    }
+
+    /// Convert into a `String`.
+    pub fn into_string(self) -> String {
+        match self.0 {
+            Repr::Owned(s) => s.into_string(),
+            Repr::Shared(s) => s.to_string(),
+        }
+    }
+}
+
+impl Default for LiteralText {
+    fn default() -> Self {
+        LiteralText(Repr::Owned(CompactString::default()))
+    }
+}
+
+impl Deref for LiteralText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for LiteralText {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for LiteralText {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for LiteralText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for LiteralText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for LiteralText {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for LiteralText {}
+
+impl PartialEq<str> for LiteralText {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for LiteralText {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for LiteralText {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialOrd for LiteralText {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LiteralText {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for LiteralText {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl From<&str> for LiteralText {
+    fn from(s: &str) -> Self {
+        LiteralText(Repr::Owned(s.into()))
+    }
+}
+
+impl From<String> for LiteralText {
+    fn from(s: String) -> Self {
+        LiteralText(Repr::Owned(s.into()))
+    }
+}
+
+impl From<CompactString> for LiteralText {
+    fn from(s: CompactString) -> Self {
+        LiteralText(Repr::Owned(s))
+    }
+}
+
+impl From<LiteralText> for String {
+    fn from(s: LiteralText) -> Self {
+        s.into_string()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for LiteralText {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LiteralText {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        CompactString::deserialize(deserializer).map(LiteralText::from)
+    }
+}
+
+/// Interned literal text of one model.
+///
+/// Literals too long to store inline are kept once; [`intern`](Self::intern)
+/// hands out clones sharing that allocation.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LiteralPool {
+    interned: HashSet<Arc<str>>,
+}
+
+impl LiteralPool {
+    /// A literal with text `s`, sharing storage with earlier equal literals
+    /// when it is too long to store inline.
+    pub(crate) fn intern(&mut self, s: &str) -> LiteralText {
+        if s.len() <= INLINE_CAPACITY {
+            return s.into();
+        }
+        let shared = match self.interned.get(s) {
+            Some(shared) => Arc::clone(shared),
+            None => {
+                let shared: Arc<str> = s.into();
+                self.interned.insert(Arc::clone(&shared));
+                shared
+            }
+        };
+        LiteralText(Repr::Shared(shared))
+    }
+}
@@ -0,0 +1,186 @@
+//! Maildir ingestion.
+//!
+//! Reads every message in a Maildir folder (`cur/` and `new/`), parses the
+//! threading headers and groups messages into [`Thread`]s so each
+//! conversation can be compressed on its own.
+//!
+//! Messages are ordered by file name, which for Maildir starts with the
+//! delivery timestamp. Threads are formed from the transitive closure of
+//! `Message-ID`, `In-Reply-To` and `References`, and are named after the
+//! `Message-ID` of their earliest message.
+
+use crate::corpus::Thread;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A parsed mail message: threading headers plus the body text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailMessage {
+    /// `Message-ID` without angle brackets.
+    pub message_id: Option<String>,
+    /// `In-Reply-To` without angle brackets.
+    pub in_reply_to: Option<String>,
+    /// Ids listed in `References`, oldest first.
+    pub references: Vec<String>,
+    /// `Subject` header, if present.
+    pub subject: Option<String>,
+    /// Message body (everything after the header block), with CRLF normalized to LF.
+    pub body: String,
+}
+
+/// Extract all `<id>` tokens from a header value.
+fn angle_ids(value: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut rest = value;
+    while let Some(open) = rest.find('<') {
+        let Some(close) = rest[open..].find('>') else {
+            break;
+        };
+        let id = rest[open + 1..open + close].trim();
+        if !id.is_empty() {
+            ids.push(id.to_string());
+        }
+        rest = &rest[open + close + 1..];
+    }
+    ids
+}
+
+/// Parse a raw RFC 5322 message into threading headers and body.
+///
+/// Header names are matched case-insensitively and folded continuation lines
+/// are unfolded. Only the headers needed for threading are retained.
+pub fn parse_message(raw: &str) -> MailMessage {
+    let raw = raw.replace("\r\n", "\n");
+    let (head, body) = match raw.find("\n\n") {
+        Some(pos) => (&raw[..pos], &raw[pos + 2..]),
+        None => (raw.as_str(), ""),
+    };
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+
+    MailMessage {
+        message_id: header("message-id").and_then(|v| angle_ids(v).into_iter().next()),
+        in_reply_to: header("in-reply-to").and_then(|v| angle_ids(v).into_iter().next()),
+        references: header("references").map(angle_ids).unwrap_or_default(),
+        subject: header("subject").map(str::to_string),
+        body: body.to_string(),
+    }
+}
+
+/// Read all messages from a Maildir folder, ordered by file name.
+///
+/// Both `cur/` and `new/` are scanned; `tmp/` is ignored because it holds
+/// messages still being delivered. Invalid UTF-8 is replaced lossily.
+pub fn read_maildir(path: &Path) -> io::Result<Vec<MailMessage>> {
+    let mut files = Vec::new();
+    let mut found = false;
+    for sub in ["cur", "new"] {
+        let dir = path.join(sub);
+        if !dir.is_dir() {
+            continue;
+        }
+        found = true;
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push((entry.file_name(), entry.path()));
+            }
+        }
+    }
+    if !found {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a maildir (no cur/ or new/)", path.display()),
+        ));
+    }
+    files.sort();
+
+    let mut messages = Vec::with_capacity(files.len());
+    for (_, file) in files {
+        let bytes = fs::read(&file)?;
+        messages.push(parse_message(&String::from_utf8_lossy(&bytes)));
+    }
+    Ok(messages)
+}
+
+/// Group messages into threads via `Message-ID`, `In-Reply-To` and `References`.
+///
+/// Message order is preserved within each thread, and threads are returned in
+/// order of their first message.
+pub fn thread_messages(messages: Vec<MailMessage>) -> Vec<Thread> {
+    fn find(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+
+    // Union-find over ids; each message also gets a node so that messages
+    // without a Message-ID still form (singleton) threads.
+    let mut parent: Vec<usize> = Vec::new();
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let mut node_of_message = Vec::with_capacity(messages.len());
+    for msg in &messages {
+        let node = match &msg.message_id {
+            Some(id) => *ids.entry(id.as_str()).or_insert_with(|| {
+                parent.push(parent.len());
+                parent.len() - 1
+            }),
+            None => {
+                parent.push(parent.len());
+                parent.len() - 1
+            }
+        };
+        node_of_message.push(node);
+        for related in msg.references.iter().chain(msg.in_reply_to.iter()) {
+            let other = *ids.entry(related.as_str()).or_insert_with(|| {
+                parent.push(parent.len());
+                parent.len() - 1
+            });
+            let (a, b) = (find(&mut parent, node), find(&mut parent, other));
+            if a != b {
+                parent[b] = a;
+            }
+        }
+    }
+
+    let mut thread_of_root: HashMap<usize, usize> = HashMap::new();
+    let mut threads: Vec<Thread> = Vec::new();
+    for (msg, node) in messages.iter().zip(node_of_message) {
+        let root = find(&mut parent, node);
+        let t = *thread_of_root.entry(root).or_insert_with(|| {
+            let id = msg
+                .message_id
+                .clone()
+                .unwrap_or_else(|| format!("thread-{}", threads.len()));
+            threads.push(Thread::new(id, Vec::new()));
+            threads.len() - 1
+        });
+        threads[t].messages.push(msg.body.clone());
+    }
+    threads
+}
+
+/// Read a Maildir folder and group its messages into threads.
+pub fn read_threads(path: &Path) -> io::Result<Vec<Thread>> {
+    Ok(thread_messages(read_maildir(path)?))
+}
@@ -1,3 +1,522 @@
-fn main() {
-    println!("Hello, world!");
+//! Command-line interface for copyforward.
+
+use copyforward::csv::{Column, CsvOptions};
+use copyforward::dedup::{DedupMode, DedupOptions, dedup};
+use copyforward::hashing::HashCache;
+use copyforward::html::{render_index, render_thread};
+use copyforward::jsonl::JsonlOptions;
+use copyforward::pretty::fmt_segments;
+use copyforward::{
+    Config, CopyForward, Segment, Stats, Thread, approximate, csv, exact, jsonl, maildir, render,
+};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: copyforward <COMMAND> [OPTIONS]
+
+Commands:
+  maildir <DIR>           Compress each thread of a Maildir folder and print statistics
+  csv <FILE>              Compress rows of a CSV/TSV file (`-` for stdin) grouped by thread
+  segments <FILE>         Print the segments of each thread of a CSV/TSV file (`-` for stdin)
+  render <PATH> -o <DIR>  Write one HTML page per thread with quotes highlighted and linked
+  explain <PATH> --message <N>
+                          Print each segment of one message with its source and chain depth
+  dedupe <PATH>           Remove or mask text repeated from earlier messages and write the rest as
+                          JSON Lines
+  bench <PATH>            Compare sizes and times against zstd and gzip (requires the `baseline`
+                          feature)
+
+<PATH> is a Maildir folder, a JSON Lines file (`.jsonl` or `.ndjson`) or a CSV/TSV file.
+
+Options:
+  --approximate           Use the approximate (capped) algorithm instead of exact
+  --min-match-len <N>     Minimum match length in characters (default 4)
+  --lookback <N>          Only reference the previous N messages
+  --pretty                Print segments as an aligned, human-readable dump
+  --format <FMT>          Output format of render: html (default)
+  -o, --output <PATH>     Directory render writes into, or file dedupe writes (default stdout)
+  --message <N>           Message explain prints, by 0-based position in its thread
+  --thread <ID>           Thread explain reads the message from; required with several threads
+  -h, --help              Print this help
+
+Dedupe options:
+  --min-span-len <N>      Shortest repeated span removed, in characters (default 50)
+  --max-duplicate-fraction <F>
+                          Drop messages more than this fraction duplicated (default 1.0, never)
+  --mask <TEXT>           Replace each repeated span with TEXT instead of removing it
+  --per-thread            Only remove text repeated within the same thread
+
+CSV and JSON Lines options:
+  --text-column <COL>     Column holding message text, by header name or 0-based index (default 0);
+                          for JSON Lines, the field name (default \"text\")
+  --thread-column <COL>   Column or field holding the thread id; all rows form one thread if omitted
+  --tsv                   Tab-delimited input
+  --no-header             The first row is data, not column names
+";
+
+/// Parsed command-line options shared by all commands.
+struct Options {
+    approximate: bool,
+    pretty: bool,
+    format: String,
+    output: Option<String>,
+    message: Option<usize>,
+    thread: Option<String>,
+    dedup: DedupOptions,
+    per_thread: bool,
+    config: Config,
+    csv: CsvOptions,
+    jsonl: JsonlOptions,
+    positional: Vec<String>,
+}
+
+fn parse_options(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options {
+        approximate: false,
+        pretty: false,
+        format: "html".to_string(),
+        output: None,
+        message: None,
+        thread: None,
+        dedup: DedupOptions::default(),
+        per_thread: false,
+        // Threads of one corpus often repeat whole messages (footers, crossposts).
+        config: Config {
+            hash_cache: Some(HashCache::new(100_000)),
+            ..Config::default()
+        },
+        csv: CsvOptions::default(),
+        jsonl: JsonlOptions::default(),
+        positional: Vec::new(),
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{name} requires a value"))
+        };
+        match arg.as_str() {
+            "--approximate" => opts.approximate = true,
+            "--pretty" => opts.pretty = true,
+            "--min-match-len" => {
+                opts.config.min_match_len = parse_number(&value(&arg)?, &arg)?;
+            }
+            "--lookback" => opts.config.lookback = Some(parse_number(&value(&arg)?, &arg)?),
+            "--format" => opts.format = value(&arg)?,
+            "-o" | "--output" => opts.output = Some(value(&arg)?),
+            "--message" => opts.message = Some(parse_number(&value(&arg)?, &arg)?),
+            "--thread" => opts.thread = Some(value(&arg)?),
+            "--min-span-len" => opts.dedup.min_span_len = parse_number(&value(&arg)?, &arg)?,
+            "--max-duplicate-fraction" => {
+                let fraction = value(&arg)?;
+                opts.dedup.max_duplicate_fraction = fraction
+                    .parse()
+                    .ok()
+                    .filter(|f: &f64| (0.0..=1.0).contains(f))
+                    .ok_or_else(|| {
+                        format!("{arg} expects a number from 0 to 1, got '{fraction}'")
+                    })?;
+            }
+            "--mask" => opts.dedup.mode = DedupMode::Mask(value(&arg)?),
+            "--per-thread" => opts.per_thread = true,
+            "--text-column" => {
+                let column = value(&arg)?;
+                opts.jsonl.text_field = column.clone();
+                opts.csv.text_column = parse_column(column);
+            }
+            "--thread-column" => {
+                let column = value(&arg)?;
+                opts.jsonl.thread_field = Some(column.clone());
+                opts.csv.thread_column = Some(parse_column(column));
+            }
+            "--tsv" => opts.csv.delimiter = b'\t',
+            "--no-header" => opts.csv.has_headers = false,
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option {arg}")),
+            _ => opts.positional.push(arg),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_number(value: &str, name: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{name} expects a non-negative integer, got '{value}'"))
+}
+
+/// Columns given as plain integers select by position, anything else by header name.
+fn parse_column(value: String) -> Column {
+    match value.parse() {
+        Ok(idx) => Column::Index(idx),
+        Err(_) => Column::Name(value),
+    }
+}
+
+fn thread_model(thread: &Thread, opts: &Options) -> Box<dyn CopyForward> {
+    if opts.approximate {
+        Box::new(approximate(&thread.messages, opts.config.clone()))
+    } else {
+        Box::new(exact(&thread.messages, opts.config.clone()))
+    }
+}
+
+fn thread_segments(thread: &Thread, opts: &Options) -> Vec<Vec<Segment>> {
+    thread_model(thread, opts).segments()
+}
+
+fn thread_stats(thread: &Thread, opts: &Options) -> Stats {
+    Stats::from_segments(&thread_segments(thread, opts))
+}
+
+/// Print one tab-separated statistics row per thread followed by a total row.
+fn print_thread_stats(threads: &[Thread], opts: &Options) {
+    println!("thread\tmessages\toriginal_bytes\tliteral_bytes\treferences\tratio");
+    let mut total = Stats::default();
+    for thread in threads {
+        let stats = thread_stats(thread, opts);
+        print_stats_row(&thread.id, &stats);
+        total.messages += stats.messages;
+        total.original_bytes += stats.original_bytes;
+        total.literal_bytes += stats.literal_bytes;
+        total.references += stats.references;
+    }
+    print_stats_row("total", &total);
+}
+
+fn print_stats_row(name: &str, stats: &Stats) {
+    println!(
+        "{}\t{}\t{}\t{}\t{}\t{:.3}",
+        name,
+        stats.messages,
+        stats.original_bytes,
+        stats.literal_bytes,
+        stats.references,
+        stats.compression_ratio()
+    );
+}
+
+/// Print the segments of each thread, as tab-separated rows or a pretty dump.
+fn print_thread_segments(threads: &[Thread], opts: &Options) {
+    if !opts.pretty {
+        println!("thread\tmessage\tstart\tend\tkind\tsource\ttext");
+    }
+    for thread in threads {
+        let segments = thread_segments(thread, opts);
+        if opts.pretty {
+            println!("== thread {} ==", thread.id);
+            print!("{}", fmt_segments(&segments));
+            continue;
+        }
+        for (i, segs) in segments.iter().enumerate() {
+            let mut pos = 0;
+            for seg in segs {
+                match seg {
+                    Segment::Literal(text) => {
+                        let end = pos + text.len();
+                        println!(
+                            "{}\t{i}\t{pos}\t{end}\tliteral\t\t{}",
+                            thread.id,
+                            text.escape_debug()
+                        );
+                        pos = end;
+                    }
+                    Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => {
+                        let end = pos + len;
+                        println!(
+                            "{}\t{i}\t{pos}\t{end}\treference\t{message_idx}:{start}..{}\t",
+                            thread.id,
+                            start + len
+                        );
+                        pos = end;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Print every segment of message `idx` of `thread`: its byte range, where
+/// a reference copies from, the deepest reference chain under it and its text.
+fn print_explanation(thread: &Thread, idx: usize, opts: &Options) -> Result<(), String> {
+    let count = thread.messages.len();
+    if idx >= count {
+        return Err(format!("thread {} has only {count} messages", thread.id));
+    }
+    let segments = thread_segments(thread, opts);
+    let segs = &segments[idx];
+    println!(
+        "thread {}, message {idx}: {} bytes in {} segments",
+        thread.id,
+        thread.messages[idx].len(),
+        segs.len()
+    );
+    println!("segment\tstart\tend\tkind\tsource\tdepth\ttext");
+    let mut pos = 0;
+    for (i, seg) in segs.iter().enumerate() {
+        let (kind, source, text) = match seg {
+            Segment::Literal(text) => ("literal", String::new(), text.as_str()),
+            &Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => (
+                "reference",
+                format!("{message_idx}:{start}..{}", start + len),
+                &thread.messages[message_idx][start..start + len],
+            ),
+        };
+        let end = pos + text.len();
+        let depth = render::chain_depth(&segments, idx, pos..end).ok_or("inconsistent segments")?;
+        println!(
+            "{i}\t{pos}\t{end}\t{kind}\t{source}\t{depth}\t{}",
+            text.escape_debug()
+        );
+        pos = end;
+    }
+    Ok(())
+}
+
+/// Deduplicate the messages of `threads`, across the whole corpus or within
+/// each thread, keeping the messages that were not dropped.
+fn dedupe_threads(threads: &[Thread], opts: &Options) -> Vec<Thread> {
+    let options = DedupOptions {
+        approximate: opts.approximate,
+        ..opts.dedup.clone()
+    };
+    let deduped = if opts.per_thread {
+        threads
+            .iter()
+            .flat_map(|t| dedup(&t.messages, &options))
+            .collect()
+    } else {
+        let all: Vec<&str> = threads
+            .iter()
+            .flat_map(|t| t.messages.iter().map(String::as_str))
+            .collect();
+        dedup(&all, &options)
+    };
+    let (mut removed, mut dropped, mut total) = (0, 0, 0);
+    let mut docs = deduped.into_iter();
+    let cleaned = threads
+        .iter()
+        .map(|thread| {
+            let mut messages = Vec::new();
+            for (message, doc) in thread.messages.iter().zip(docs.by_ref()) {
+                total += message.len();
+                if doc.dropped {
+                    removed += message.len();
+                    dropped += 1;
+                } else {
+                    removed += doc.duplicate_bytes;
+                    messages.push(doc.text);
+                }
+            }
+            Thread::new(thread.id.clone(), messages)
+        })
+        .collect();
+    eprintln!("removed {removed} of {total} bytes; dropped {dropped} messages");
+    cleaned
+}
+
+/// Read threads from a Maildir folder, a JSON Lines file or a CSV/TSV file.
+fn read_input(path: &str, opts: &Options) -> Result<Vec<Thread>, String> {
+    if Path::new(path).is_dir() {
+        maildir::read_threads(Path::new(path)).map_err(|e| e.to_string())
+    } else if path.ends_with(".jsonl") || path.ends_with(".ndjson") {
+        File::open(path)
+            .and_then(|f| jsonl::read_threads(f, &opts.jsonl))
+            .map_err(|e| format!("{path}: {e}"))
+    } else {
+        read_csv_threads(path, opts)
+    }
+}
+
+/// File name for a thread's page: its id with anything but ASCII letters,
+/// digits, `-` and `_` replaced, made unique among the names in `used`.
+fn page_name(id: &str, used: &mut HashSet<String>) -> String {
+    let mut stem: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() {
+        stem.push_str("thread");
+    }
+    let mut name = format!("{stem}.html");
+    let mut n = 1;
+    while !used.insert(name.clone()) {
+        n += 1;
+        name = format!("{stem}-{n}.html");
+    }
+    name
+}
+
+/// Write one HTML page per thread into `dir`, plus an `index.html` linking them.
+fn write_html(threads: &[Thread], dir: &str, opts: &Options) -> Result<(), String> {
+    let err = |e: io::Error| format!("{dir}: {e}");
+    fs::create_dir_all(dir).map_err(err)?;
+    let mut used = HashSet::from(["index.html".to_string()]);
+    let mut pages = Vec::with_capacity(threads.len());
+    for thread in threads {
+        let name = page_name(&thread.id, &mut used);
+        let page = render_thread(thread_model(thread, opts).as_ref(), &thread.id);
+        fs::write(Path::new(dir).join(&name), page).map_err(err)?;
+        pages.push((name, thread));
+    }
+    let index = render_index("Threads", &pages);
+    fs::write(Path::new(dir).join("index.html"), index).map_err(err)?;
+    Ok(())
+}
+
+fn read_csv_threads(file: &str, opts: &Options) -> Result<Vec<Thread>, String> {
+    if file == "-" {
+        csv::read_threads(io::stdin().lock(), &opts.csv)
+    } else {
+        File::open(file).and_then(|f| csv::read_threads(f, &opts.csv))
+    }
+    .map_err(|e| format!("{file}: {e}"))
+}
+
+/// Print the comparison of copyforward against zstd and gzip on `threads`.
+#[cfg(feature = "baseline")]
+fn print_baseline(threads: &[Thread], opts: &Options) -> Result<(), String> {
+    use copyforward::baseline::{BaselineOptions, compare};
+    let options = BaselineOptions {
+        config: opts.config.clone(),
+        approximate: opts.approximate,
+        ..BaselineOptions::default()
+    };
+    let report = compare(threads, &options).map_err(|e| e.to_string())?;
+    print!("{report}");
+    Ok(())
+}
+
+#[cfg(not(feature = "baseline"))]
+fn print_baseline(_: &[Thread], _: &Options) -> Result<(), String> {
+    Err("bench requires building with the `baseline` feature".to_string())
+}
+
+fn run(command: &str, opts: Options) -> Result<(), String> {
+    match command {
+        "maildir" => {
+            let [dir] = opts.positional.as_slice() else {
+                return Err("maildir expects exactly one directory".to_string());
+            };
+            let threads = maildir::read_threads(Path::new(dir)).map_err(|e| e.to_string())?;
+            print_thread_stats(&threads, &opts);
+            Ok(())
+        }
+        "csv" => {
+            let [file] = opts.positional.as_slice() else {
+                return Err("csv expects exactly one file".to_string());
+            };
+            let threads = read_csv_threads(file, &opts)?;
+            print_thread_stats(&threads, &opts);
+            Ok(())
+        }
+        "segments" => {
+            let [file] = opts.positional.as_slice() else {
+                return Err("segments expects exactly one file".to_string());
+            };
+            let threads = read_csv_threads(file, &opts)?;
+            print_thread_segments(&threads, &opts);
+            Ok(())
+        }
+        "render" => {
+            let [path] = opts.positional.as_slice() else {
+                return Err("render expects exactly one directory or file".to_string());
+            };
+            if opts.format != "html" {
+                return Err(format!("unknown render format '{}'", opts.format));
+            }
+            let Some(dir) = &opts.output else {
+                return Err("render requires -o <DIR>".to_string());
+            };
+            let threads = read_input(path, &opts)?;
+            write_html(&threads, dir, &opts)
+        }
+        "explain" => {
+            let [path] = opts.positional.as_slice() else {
+                return Err("explain expects exactly one directory or file".to_string());
+            };
+            let Some(idx) = opts.message else {
+                return Err("explain requires --message <N>".to_string());
+            };
+            let threads = read_input(path, &opts)?;
+            let thread = match (&opts.thread, threads.as_slice()) {
+                (Some(id), _) => threads
+                    .iter()
+                    .find(|t| &t.id == id)
+                    .ok_or_else(|| format!("no thread '{id}' in {path}"))?,
+                (None, [thread]) => thread,
+                (None, _) => {
+                    return Err(format!(
+                        "{path} has {} threads; choose one with --thread <ID>",
+                        threads.len()
+                    ));
+                }
+            };
+            print_explanation(thread, idx, &opts)
+        }
+        "dedupe" => {
+            let [path] = opts.positional.as_slice() else {
+                return Err("dedupe expects exactly one directory or file".to_string());
+            };
+            let threads = dedupe_threads(&read_input(path, &opts)?, &opts);
+            // Keep thread ids of Maildir folders and multi-thread inputs.
+            let mut output = opts.jsonl.clone();
+            if threads.len() > 1 {
+                output
+                    .thread_field
+                    .get_or_insert_with(|| "thread".to_string());
+            }
+            match &opts.output {
+                Some(file) => File::create(file)
+                    .and_then(|f| jsonl::write_threads(BufWriter::new(f), &threads, &output))
+                    .map_err(|e| format!("{file}: {e}")),
+                None => jsonl::write_threads(io::stdout().lock(), &threads, &output)
+                    .map_err(|e| e.to_string()),
+            }
+        }
+        "bench" => {
+            let [path] = opts.positional.as_slice() else {
+                return Err("bench expects exactly one directory or file".to_string());
+            };
+            let threads = read_input(path, &opts)?;
+            print_baseline(&threads, &opts)
+        }
+        _ => Err(format!("unknown command '{command}'")),
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let command = match args.next() {
+        Some(c) if c != "-h" && c != "--help" => c,
+        _ => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+    };
+    let result = parse_options(args).and_then(|opts| run(&command, opts));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
 }
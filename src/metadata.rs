@@ -0,0 +1,195 @@
+//! Provenance recorded alongside serialized segmentations.
+//!
+//! A stored segmentation is only reproducible if one knows what produced it.
+//! [`Metadata`] names the crate version, the algorithm and its full
+//! [`Config`]; [`crate::codec::encode_model`], archives built with
+//! [`crate::archive::Archive::from_model`] and the JSON outputs embed it, and
+//! [`crate::codec::decode_metadata`] or
+//! [`crate::archive::Archive::metadata`] read it back.
+//!
+//! # Example
+//! ```
+//! use copyforward::codec::{decode_metadata, encode_model};
+//! use copyforward::{approximate, Config};
+//!
+//! let config = Config { min_match_len: 8, ..Config::default() };
+//! let bytes = encode_model(&approximate(&["Hello world"], config));
+//! let metadata = decode_metadata(&bytes).unwrap().unwrap();
+//! assert_eq!(metadata.algorithm, "approximate");
+//! assert_eq!(metadata.config.min_match_len, 8);
+//! assert_eq!(metadata.crate_version, copyforward::metadata::CRATE_VERSION);
+//! ```
+
+use crate::codec::{DecodeError, Reader, put_varint};
+use crate::core::{Config, Lsh, TieBreak};
+
+/// Version of this crate, as recorded in new [`Metadata`].
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// What produced a segmentation; see the [module docs](self).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    /// Crate version that built the segmentation.
+    pub crate_version: String,
+    /// Algorithm name: `exact`, `approximate` or `optimal`.
+    pub algorithm: String,
+    /// Configuration of the run. [`Config::hash_cache`], [`Config::progress`]
+    /// and [`Config::trace`] are not recorded; the cache and trace never
+    /// change the output, but a progress callback that cancels the run does.
+    pub config: Config,
+}
+
+impl Metadata {
+    /// Metadata of a run of `algorithm` with `config` by this crate version.
+    pub fn new(algorithm: &str, config: &Config) -> Metadata {
+        Metadata {
+            crate_version: CRATE_VERSION.to_string(),
+            algorithm: algorithm.to_string(),
+            config: Config {
+                hash_cache: None,
+                progress: None,
+                trace: None,
+                ..config.clone()
+            },
+        }
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_varint(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn put_option(out: &mut Vec<u8>, value: Option<usize>) {
+    match value {
+        Some(v) => {
+            out.push(1);
+            put_varint(out, v);
+        }
+        None => out.push(0),
+    }
+}
+
+/// Append the metadata block of the codec and archive headers: a presence
+/// byte, then the crate version, algorithm and every recorded config field.
+pub(crate) fn put_metadata(out: &mut Vec<u8>, metadata: Option<&Metadata>) {
+    let Some(m) = metadata else {
+        out.push(0);
+        return;
+    };
+    out.push(1);
+    put_str(out, &m.crate_version);
+    put_str(out, &m.algorithm);
+    let c = &m.config;
+    put_varint(out, c.min_match_len);
+    put_option(out, c.lookback);
+    put_varint(out, c.cap_len);
+    put_varint(out, c.ncap);
+    out.push(c.reextend as u8);
+    put_varint(out, c.stop_phrases.len());
+    for phrase in &c.stop_phrases {
+        put_str(out, phrase);
+    }
+    out.extend_from_slice(&c.kmer_density.to_le_bytes());
+    out.push(c.skip_referenced_regions as u8);
+    put_varint(out, c.min_indexed_len);
+    out.push(c.dedup_messages as u8);
+    match c.lsh {
+        Some(lsh) => {
+            out.push(1);
+            put_varint(out, lsh.bands);
+            put_varint(out, lsh.rows);
+        }
+        None => out.push(0),
+    }
+    put_option(out, c.shallow_preference);
+    out.push(c.tie_break as u8);
+    put_option(out, c.max_ref_depth);
+    out.push(c.self_references as u8);
+    put_option(out, c.min_encoded_gain);
+}
+
+fn read_str(r: &mut Reader) -> Result<String, DecodeError> {
+    let len = r.varint()?;
+    let bytes = r.bytes(len)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+fn read_flag(r: &mut Reader) -> Result<bool, DecodeError> {
+    match r.byte()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(DecodeError::InvalidMetadata),
+    }
+}
+
+fn read_option(r: &mut Reader) -> Result<Option<usize>, DecodeError> {
+    Ok(if read_flag(r)? {
+        Some(r.varint()?)
+    } else {
+        None
+    })
+}
+
+/// Read a block written by [`put_metadata`].
+pub(crate) fn read_metadata(r: &mut Reader) -> Result<Option<Metadata>, DecodeError> {
+    if !read_flag(r)? {
+        return Ok(None);
+    }
+    let crate_version = read_str(r)?;
+    let algorithm = read_str(r)?;
+    let min_match_len = r.varint()?;
+    let lookback = read_option(r)?;
+    let cap_len = r.varint()?;
+    let ncap = r.varint()?;
+    let reextend = read_flag(r)?;
+    let count = r.varint()?;
+    let mut stop_phrases = Vec::new();
+    for _ in 0..count {
+        stop_phrases.push(read_str(r)?);
+    }
+    let kmer_density = f64::from_le_bytes(r.bytes(8)?.try_into().unwrap());
+    let skip_referenced_regions = read_flag(r)?;
+    let min_indexed_len = r.varint()?;
+    let dedup_messages = read_flag(r)?;
+    let lsh = if read_flag(r)? {
+        Some(Lsh {
+            bands: r.varint()?,
+            rows: r.varint()?,
+        })
+    } else {
+        None
+    };
+    let config = Config {
+        min_match_len,
+        lookback,
+        cap_len,
+        ncap,
+        reextend,
+        stop_phrases,
+        kmer_density,
+        skip_referenced_regions,
+        min_indexed_len,
+        dedup_messages,
+        lsh,
+        shallow_preference: read_option(r)?,
+        tie_break: match r.byte()? {
+            0 => TieBreak::PreferEarliest,
+            1 => TieBreak::PreferMostRecent,
+            2 => TieBreak::PreferSameSourceAsPrevSegment,
+            _ => return Err(DecodeError::InvalidMetadata),
+        },
+        max_ref_depth: read_option(r)?,
+        self_references: read_flag(r)?,
+        min_encoded_gain: read_option(r)?,
+        hash_cache: None,
+        progress: None,
+        trace: None,
+    };
+    Ok(Some(Metadata {
+        crate_version,
+        algorithm,
+        config,
+    }))
+}
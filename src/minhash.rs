@@ -0,0 +1,200 @@
+//! MinHash signatures compatible with Python's `datasketch`.
+//!
+//! Signatures are bit-for-bit identical to `datasketch.MinHash(num_perm, seed)`
+//! updated with the same shingles: SHA-1 based 32-bit shingle hashes, and
+//! permutations `(a * h + b) mod (2^61 - 1)` truncated to 32 bits, with `a`
+//! and `b` drawn from `numpy.random.RandomState(seed)`. Rebuild them in Python
+//! with `MinHash(num_perm=len(sig), seed=seed, hashvalues=sig)` or
+//! `LeanMinHash(seed=seed, hashvalues=sig)` and insert into `MinHashLSH`.
+//!
+//! # Example
+//! ```
+//! use copyforward::minhash::{MinHasher, Shingling};
+//!
+//! let hasher = MinHasher::new(128, 1);
+//! let a = hasher.signature("the quick brown fox jumps", Shingling::Words);
+//! let b = hasher.signature("the quick brown fox leaps", Shingling::Words);
+//! assert_eq!(a.len(), 128);
+//! let j = MinHasher::jaccard(&a, &b);
+//! assert!(j > 0.3 && j < 1.0);
+//! ```
+
+use crate::MessageLike;
+use sha1::{Digest, Sha1};
+
+const MERSENNE_PRIME: u64 = (1 << 61) - 1;
+const MAX_HASH: u64 = (1 << 32) - 1;
+
+/// How a message is split into the byte strings fed to the MinHash.
+///
+/// Python equivalents, for `m = MinHash(...)`:
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shingling {
+    /// Whitespace-separated words: `for w in text.split(): m.update(w.encode("utf8"))`.
+    Words,
+    /// Overlapping character n-grams:
+    /// `for i in range(len(text) - n + 1): m.update(text[i:i+n].encode("utf8"))`.
+    /// Texts shorter than `n` characters contribute nothing.
+    Chars(usize),
+}
+
+/// Generator of datasketch-compatible MinHash signatures.
+#[derive(Debug, Clone)]
+pub struct MinHasher {
+    seed: u32,
+    permutations: Vec<(u64, u64)>,
+}
+
+impl MinHasher {
+    /// Equivalent to `datasketch.MinHash(num_perm=num_perm, seed=seed)`.
+    pub fn new(num_perm: usize, seed: u32) -> MinHasher {
+        let mut rng = Mt19937::new(seed);
+        let permutations = (0..num_perm)
+            .map(|_| {
+                let a = rng.randint_u64(1, MERSENNE_PRIME);
+                let b = rng.randint_u64(0, MERSENNE_PRIME);
+                (a, b)
+            })
+            .collect();
+        MinHasher { seed, permutations }
+    }
+
+    pub fn num_perm(&self) -> usize {
+        self.permutations.len()
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Signature of an empty set (every value is `2^32 - 1`).
+    pub fn empty_signature(&self) -> Vec<u64> {
+        vec![MAX_HASH; self.permutations.len()]
+    }
+
+    /// Add one shingle to `signature`, like `MinHash.update(shingle)`.
+    pub fn update(&self, signature: &mut [u64], shingle: &[u8]) {
+        let hv = sha1_hash32(shingle);
+        for (value, &(a, b)) in signature.iter_mut().zip(&self.permutations) {
+            let phv = (a.wrapping_mul(hv).wrapping_add(b) % MERSENNE_PRIME) & MAX_HASH;
+            *value = (*value).min(phv);
+        }
+    }
+
+    /// Signature of `text` under the given shingling.
+    pub fn signature(&self, text: &str, shingling: Shingling) -> Vec<u64> {
+        let mut sig = self.empty_signature();
+        match shingling {
+            Shingling::Words => {
+                for word in text.split_whitespace() {
+                    self.update(&mut sig, word.as_bytes());
+                }
+            }
+            Shingling::Chars(n) => {
+                let bounds: Vec<usize> = text
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .chain(std::iter::once(text.len()))
+                    .collect();
+                let n = n.max(1);
+                for w in bounds.windows(n + 1) {
+                    self.update(&mut sig, &text.as_bytes()[w[0]..w[n]]);
+                }
+            }
+        }
+        sig
+    }
+
+    /// One signature per message; `None` entries get the empty signature.
+    pub fn signatures<M: MessageLike>(
+        &self,
+        messages: &[M],
+        shingling: Shingling,
+    ) -> Vec<Vec<u64>> {
+        messages
+            .iter()
+            .map(|m| match m.as_message() {
+                Some(text) => self.signature(text, shingling),
+                None => self.empty_signature(),
+            })
+            .collect()
+    }
+
+    /// Estimated Jaccard similarity, like `MinHash.jaccard`.
+    ///
+    /// # Panics
+    /// If the signatures have different lengths.
+    pub fn jaccard(a: &[u64], b: &[u64]) -> f64 {
+        assert_eq!(a.len(), b.len(), "signatures must have the same num_perm");
+        if a.is_empty() {
+            return 0.0;
+        }
+        let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+        equal as f64 / a.len() as f64
+    }
+}
+
+/// `datasketch.hashfunc.sha1_hash32`: first 4 bytes of SHA-1, little-endian.
+fn sha1_hash32(data: &[u8]) -> u64 {
+    let digest = Sha1::digest(data);
+    u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]]) as u64
+}
+
+/// MT19937 as used by `numpy.random.RandomState`.
+struct Mt19937 {
+    state: [u32; 624],
+    index: usize,
+}
+
+impl Mt19937 {
+    fn new(seed: u32) -> Mt19937 {
+        let mut state = [0u32; 624];
+        state[0] = seed;
+        for i in 1..624 {
+            state[i] = 1812433253u32
+                .wrapping_mul(state[i - 1] ^ (state[i - 1] >> 30))
+                .wrapping_add(i as u32);
+        }
+        Mt19937 { state, index: 624 }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        if self.index >= 624 {
+            for i in 0..624 {
+                let y = (self.state[i] & 0x8000_0000) | (self.state[(i + 1) % 624] & 0x7fff_ffff);
+                let mut next = self.state[(i + 397) % 624] ^ (y >> 1);
+                if y & 1 != 0 {
+                    next ^= 0x9908_b0df;
+                }
+                self.state[i] = next;
+            }
+            self.index = 0;
+        }
+        let mut y = self.state[self.index];
+        self.index += 1;
+        y ^= y >> 11;
+        y ^= (y << 7) & 0x9d2c_5680;
+        y ^= (y << 15) & 0xefc6_0000;
+        y ^ (y >> 18)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let hi = self.next_u32() as u64;
+        let lo = self.next_u32() as u64;
+        (hi << 32) | lo
+    }
+
+    /// `RandomState.randint(low, high, dtype=np.uint64)` for ranges wider than
+    /// 32 bits: masked rejection sampling over 64-bit draws.
+    fn randint_u64(&mut self, low: u64, high: u64) -> u64 {
+        let rng = high - 1 - low;
+        debug_assert!(rng > u32::MAX as u64);
+        let mask = u64::MAX >> rng.leading_zeros();
+        loop {
+            let val = self.next_u64() & mask;
+            if val <= rng {
+                return low + val;
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Peephole optimization of finished segmentations.
+//!
+//! The engines decide segment by segment and leave small inefficiencies
+//! behind: literals split in two, references to spans that are themselves a
+//! single reference, and references so short that spelling the text out would
+//! be cheaper. [`optimize`] rewrites each message locally to remove them:
+//!
+//! * references that fall entirely inside one reference of their source are
+//!   pointed at that reference's source instead, repeatedly, so equivalent
+//!   spans name the same origin (unless the longer distance or offset would
+//!   take more bytes to store);
+//! * references to consecutive spans of one source are merged;
+//! * a reference is replaced by its text when that, together with the
+//!   neighbouring literals it joins, encodes to no more bytes under
+//!   [`encode_segments`](crate::codec::encode_segments);
+//! * adjacent literals are merged and empty segments dropped.
+//!
+//! The output renders exactly the same messages as the input, so it can run
+//! after any algorithm. [`normalize_segments`] does only the merging, in
+//! place and without a model, for segments from any source.
+//!
+//! # Example
+//! ```
+//! use copyforward::peephole::optimize;
+//! use copyforward::render::Reconstructed;
+//! use copyforward::Segment;
+//!
+//! let model = Reconstructed::new(vec![
+//!     vec![Segment::Literal("Hello world".into())],
+//!     vec![
+//!         Segment::Literal("Say ".into()),
+//!         Segment::Reference { message_idx: 0, start: 0, len: 2 },
+//!         Segment::Literal("!".into()),
+//!     ],
+//! ])
+//! .unwrap();
+//! assert_eq!(optimize(&model)[1], [Segment::Literal("Say He!".into())]);
+//! ```
+
+use crate::codec::segment_size;
+use crate::core::{CopyForward, Segment};
+
+/// Segments of every message in `model`, rewritten to encode to at most as
+/// many bytes while rendering the same text.
+pub fn optimize(model: &dyn CopyForward) -> Vec<Vec<Segment>> {
+    (0..model.len())
+        .map(|idx| {
+            let canonical = canonicalize(model, idx, model.segments_for(idx));
+            inline_references(idx, &canonical, 1, |m| model.message(m))
+        })
+        .collect()
+}
+
+/// Merge adjacent literals and references to consecutive spans of one source,
+/// and drop empty segments, in every message of `segments`.
+///
+/// Unlike [`optimize`] this needs no model and never changes which source a
+/// reference names, so the result spells the same references in the fewest
+/// segments. References of a message to itself are only merged while the
+/// merged span ends at or before the reference's position, so valid segments
+/// stay valid.
+///
+/// # Example
+/// ```
+/// use copyforward::peephole::normalize_segments;
+/// use copyforward::Segment;
+///
+/// let mut segments = vec![
+///     vec![Segment::Literal("Hello world".into())],
+///     vec![
+///         Segment::Reference { message_idx: 0, start: 0, len: 5 },
+///         Segment::Reference { message_idx: 0, start: 5, len: 6 },
+///         Segment::Literal("".into()),
+///         Segment::Literal("!".into()),
+///     ],
+/// ];
+/// normalize_segments(&mut segments);
+/// assert_eq!(
+///     segments[1],
+///     [
+///         Segment::Reference { message_idx: 0, start: 0, len: 11 },
+///         Segment::Literal("!".into()),
+///     ]
+/// );
+/// ```
+pub fn normalize_segments(segments: &mut [Vec<Segment>]) {
+    for (idx, message) in segments.iter_mut().enumerate() {
+        let mut out = Vec::with_capacity(message.len());
+        for seg in message.drain(..) {
+            push(&mut out, idx, seg);
+        }
+        *message = out;
+    }
+}
+
+/// Resolve references through their sources and merge what becomes adjacent.
+fn canonicalize(model: &dyn CopyForward, idx: usize, segments: &[Segment]) -> Vec<Segment> {
+    let mut out = Vec::with_capacity(segments.len());
+    for seg in segments {
+        match seg {
+            Segment::Literal(text) => push(&mut out, idx, Segment::Literal(text.clone())),
+            &Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                let (message_idx, start) = origin(model, message_idx, start, len);
+                let resolved = Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                };
+                if segment_size(idx, &resolved) <= segment_size(idx, seg) {
+                    push(&mut out, idx, resolved);
+                } else {
+                    push(&mut out, idx, seg.clone());
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Follow `len` bytes at `start` of message `message_idx` through every
+/// reference that covers them whole.
+fn origin(
+    model: &dyn CopyForward,
+    mut message_idx: usize,
+    mut start: usize,
+    len: usize,
+) -> (usize, usize) {
+    'outer: loop {
+        let mut pos = 0;
+        for seg in model.segments_for(message_idx) {
+            let seg_len = seg_len(seg);
+            if start < pos + seg_len {
+                if let &Segment::Reference {
+                    message_idx: inner,
+                    start: inner_start,
+                    ..
+                } = seg
+                    && start + len <= pos + seg_len
+                {
+                    message_idx = inner;
+                    start = inner_start + (start - pos);
+                    continue 'outer;
+                }
+                break;
+            }
+            pos += seg_len;
+        }
+        return (message_idx, start);
+    }
+}
+
+/// Replace references of message `idx` that save fewer than `min_gain` bytes
+/// of storage over their text merged with the literals around it, reading
+/// the text of message `m` from `source(m)`.
+///
+/// With a `min_gain` of 1 every reference costing no less than its text is
+/// spelled out; [`Config::min_encoded_gain`](crate::Config::min_encoded_gain)
+/// sets it for the text constructors.
+pub(crate) fn inline_references<'a>(
+    idx: usize,
+    segments: &[Segment],
+    min_gain: usize,
+    source: impl Fn(usize) -> &'a str,
+) -> Vec<Segment> {
+    let mut out: Vec<Segment> = Vec::with_capacity(segments.len());
+    for (i, seg) in segments.iter().enumerate() {
+        if let &Segment::Reference {
+            message_idx,
+            start,
+            len,
+        } = seg
+        {
+            let prev = match out.last() {
+                Some(Segment::Literal(text)) => text.as_str(),
+                _ => "",
+            };
+            let next = match segments.get(i + 1) {
+                Some(Segment::Literal(text)) => text.as_str(),
+                _ => "",
+            };
+            let text = &source(message_idx)[start..start + len];
+            let merged = Segment::Literal(format!("{prev}{text}{next}").into());
+            let separate =
+                literal_size(idx, prev) + segment_size(idx, seg) + literal_size(idx, next);
+            if segment_size(idx, &merged) < separate + min_gain {
+                push(&mut out, idx, Segment::Literal(text.into()));
+                continue;
+            }
+        }
+        push(&mut out, idx, seg.clone());
+    }
+    out
+}
+
+fn literal_size(idx: usize, text: &str) -> usize {
+    if text.is_empty() {
+        0
+    } else {
+        segment_size(idx, &Segment::Literal(text.into()))
+    }
+}
+
+/// Append `seg` to the segments `out` of message `idx`, dropping it when
+/// empty and merging it into the previous segment when both are literals or
+/// references to consecutive source spans.
+///
+/// A self-reference is only extended while its span still ends at or before
+/// its own position in the message.
+fn push(out: &mut Vec<Segment>, idx: usize, seg: Segment) {
+    let overlaps_itself = match (out.split_last(), &seg) {
+        (
+            Some((
+                &Segment::Reference {
+                    message_idx,
+                    start,
+                    len: prev_len,
+                },
+                before,
+            )),
+            Segment::Reference { len, .. },
+        ) if message_idx == idx => start + prev_len + len > before.iter().map(seg_len).sum(),
+        _ => false,
+    };
+    match (out.last_mut(), seg) {
+        (_, Segment::Literal(text)) if text.is_empty() => {}
+        (_, Segment::Reference { len: 0, .. }) => {}
+        (Some(Segment::Literal(prev)), Segment::Literal(text)) => prev.push_str(&text),
+        (
+            Some(Segment::Reference {
+                message_idx: prev_idx,
+                start: prev_start,
+                len: prev_len,
+            }),
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            },
+        ) if *prev_idx == message_idx && *prev_start + *prev_len == start && !overlaps_itself => {
+            *prev_len += len
+        }
+        (_, seg) => out.push(seg),
+    }
+}
+
+/// Bytes `seg` renders to.
+fn seg_len(seg: &Segment) -> usize {
+    match seg {
+        Segment::Literal(text) => text.len(),
+        Segment::Reference { len, .. } => *len,
+    }
+}
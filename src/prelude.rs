@@ -0,0 +1,29 @@
+//! The stable core of the API in one import.
+//!
+//! ```
+//! use copyforward::prelude::*;
+//!
+//! let messages = ["Hello world", "> Hello world\nHi!"];
+//! let compressed = copy_forward_exact(&messages, Config::default());
+//! for (idx, segments) in compressed.segments().iter().enumerate() {
+//!     let refs = segments
+//!         .iter()
+//!         .filter(|seg| matches!(seg, Segment::Reference { .. }))
+//!         .count();
+//!     println!("message {idx}: {refs} references");
+//! }
+//! ```
+//!
+//! Items here keep their names and signatures across minor releases; the
+//! per-algorithm structs ([`crate::Exact`], [`crate::Approximate`], ...) and
+//! the other modules may change as algorithms are added.
+
+#[cfg(feature = "approximate")]
+pub use crate::copy_forward_approx;
+#[cfg(feature = "exact")]
+pub use crate::copy_forward_exact;
+pub use crate::core::{Config, CopyForward, CopyForwardError, Segment, SegmentRef};
+pub use crate::metadata::Metadata;
+pub use crate::{Algorithm, Compressed, MessageLike};
+#[cfg(any(feature = "exact", feature = "approximate", feature = "optimal"))]
+pub use crate::{CopyForwardBuilder, compress, compress_verified, try_compress};
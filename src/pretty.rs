@@ -0,0 +1,137 @@
+//! Human-readable dumps of segmentations for debugging.
+//!
+//! # Example
+//! ```
+//! use copyforward::pretty::fmt_segments;
+//! use copyforward::{exact, Config, CopyForward};
+//!
+//! let segments = exact(&["Hello world", "Hello world today"], Config::default()).segments();
+//! let dump = fmt_segments(&segments).to_string();
+//! assert_eq!(
+//!     dump,
+//!     "\
+//! #0 (11 bytes)
+//!    0..11  lit             \"Hello world\"
+//! #1 (17 bytes)
+//!    0..11  ref  #0[0..11]  \"Hello world\"
+//!   11..17  lit             \" today\"
+//! "
+//! );
+//! ```
+
+use crate::core::Segment;
+use crate::render::reconstruct;
+use std::fmt;
+
+/// Aligned dump of a segmentation, created by [`fmt_segments`].
+///
+/// Each message gets a header line with its index and length, then one line
+/// per segment: its byte range in the message, `lit` or `ref`, the source
+/// (`#message[start..end]`) of references, and a preview of the text.
+/// Reference previews are omitted when the segments cannot be reconstructed.
+#[derive(Debug, Clone)]
+pub struct PrettySegments<'a> {
+    segments: &'a [Vec<Segment>],
+    preview_len: usize,
+}
+
+/// Format `segments` for display.
+pub fn fmt_segments(segments: &[Vec<Segment>]) -> PrettySegments<'_> {
+    PrettySegments {
+        segments,
+        preview_len: 40,
+    }
+}
+
+impl PrettySegments<'_> {
+    /// Truncate previews to this many characters (default 40).
+    pub fn preview_len(mut self, chars: usize) -> Self {
+        self.preview_len = chars;
+        self
+    }
+
+    fn preview(&self, text: &str) -> String {
+        let mut out: String = text
+            .chars()
+            .take(self.preview_len)
+            .flat_map(char::escape_debug)
+            .collect();
+        if text.chars().nth(self.preview_len).is_some() {
+            out.push('…');
+        }
+        format!("\"{out}\"")
+    }
+}
+
+fn segment_len(seg: &Segment) -> usize {
+    match seg {
+        Segment::Literal(s) => s.len(),
+        Segment::Reference { len, .. } => *len,
+    }
+}
+
+fn source(seg: &Segment) -> String {
+    match seg {
+        Segment::Literal(_) => String::new(),
+        Segment::Reference {
+            message_idx,
+            start,
+            len,
+        } => format!("#{message_idx}[{start}..{}]", start + len),
+    }
+}
+
+impl fmt::Display for PrettySegments<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let texts = reconstruct(self.segments).ok();
+        let longest = self
+            .segments
+            .iter()
+            .map(|segs| segs.iter().map(segment_len).sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        let offset_width = longest.to_string().len();
+        let source_width = self
+            .segments
+            .iter()
+            .flatten()
+            .map(|seg| source(seg).len())
+            .max()
+            .unwrap_or(0);
+
+        for (i, segs) in self.segments.iter().enumerate() {
+            let total: usize = segs.iter().map(segment_len).sum();
+            writeln!(f, "#{i} ({total} bytes)")?;
+            let mut pos = 0;
+            for seg in segs {
+                let end = pos + segment_len(seg);
+                let (kind, text) = match seg {
+                    Segment::Literal(s) => ("lit", Some(s.as_str())),
+                    Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => (
+                        "ref",
+                        texts
+                            .as_ref()
+                            .map(|t| &t[*message_idx][*start..start + len]),
+                    ),
+                };
+                let preview = text.map(|t| self.preview(t)).unwrap_or_default();
+                let src = if source_width > 0 {
+                    format!("  {:<source_width$}", source(seg))
+                } else {
+                    String::new()
+                };
+                let line = format!(
+                    "  {pos:>ow$}..{end:<ow$}  {kind}{src}  {preview}",
+                    ow = offset_width,
+                );
+                writeln!(f, "{}", line.trim_end())?;
+                pos = end;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! Pull-based segmentation yielding each message as soon as it is done.
+//!
+//! [`exact()`](crate::exact) returns once the whole thread is segmented.
+//! [`process_iter`] runs the same algorithm on a worker thread and hands out
+//! each message's segments as they are computed, in message order, so a UI
+//! can show or a pipeline forward early messages while later ones are still
+//! being processed. The segments are exactly those of `exact()`.
+//!
+//! Dropping the iterator early stops the worker at its next message.
+//!
+//! # Example
+//! ```
+//! use copyforward::{process_iter, Config, Segment};
+//!
+//! let messages = ["Hello world", "> Hello world\nHi!"];
+//! for (idx, segments) in process_iter(&messages, Config::default()).enumerate() {
+//!     let quoted = segments
+//!         .iter()
+//!         .any(|seg| matches!(seg, Segment::Reference { .. }));
+//!     println!("message {idx} ready, quotes earlier text: {quoted}");
+//! }
+//! ```
+
+use crate::MessageLike;
+use crate::core::{Config, Segment};
+use crate::engine::binary::compute_binary_segments_with;
+use crate::literal::LiteralPool;
+use crate::text::TextInput;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread::JoinHandle;
+
+/// Iterator over the segments of each message, in order; see the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct ProcessIter {
+    receiver: Receiver<Vec<Segment>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Segment `messages` with the exact algorithm on a worker thread, yielding
+/// each message's segments as soon as they are computed.
+///
+/// `None` messages yield empty segments, as with [`exact()`](crate::exact).
+/// Messages are copied so the worker can own them.
+pub fn process_iter<M: MessageLike>(messages: &[M], config: Config) -> ProcessIter {
+    let input = TextInput::new(messages);
+    let (sender, receiver) = channel();
+    let worker = std::thread::spawn(move || run(&input, &config, &sender));
+    ProcessIter {
+        receiver,
+        worker: Some(worker),
+    }
+}
+
+/// Segment `input`, sending every message's segments in order until the
+/// receiver hangs up.
+fn run(input: &TextInput, config: &Config, sender: &Sender<Vec<Segment>>) {
+    let mut out = Emitter { sender, sent: 0 };
+    let min_gain = config.min_encoded_gain;
+    let mut literals = LiteralPool::default();
+    let token_segs = compute_binary_segments_with(input.codes(), config, &mut |valid, segs| {
+        out.send(
+            input.valid_indices[valid],
+            input.byte_segments(valid, segs, min_gain, &mut literals),
+        )
+    });
+    // A cancelled run leaves the rest of its messages as unsent literals.
+    for (valid, segs) in token_segs.iter().enumerate() {
+        let idx = input.valid_indices[valid];
+        if idx >= out.sent
+            && !out.send(
+                idx,
+                input.byte_segments(valid, segs, min_gain, &mut literals),
+            )
+        {
+            return;
+        }
+    }
+    out.pad(input.originals.len());
+}
+
+struct Emitter<'a> {
+    sender: &'a Sender<Vec<Segment>>,
+    /// Messages sent so far, `None` entries included.
+    sent: usize,
+}
+
+impl Emitter<'_> {
+    /// Send message `idx`, after empty segments for the `None` messages
+    /// before it; false once the receiver hung up.
+    fn send(&mut self, idx: usize, segments: Vec<Segment>) -> bool {
+        if !self.pad(idx) || self.sender.send(segments).is_err() {
+            return false;
+        }
+        self.sent += 1;
+        true
+    }
+
+    /// Send empty segments up to message `end`.
+    fn pad(&mut self, end: usize) -> bool {
+        while self.sent < end {
+            if self.sender.send(Vec::new()).is_err() {
+                return false;
+            }
+            self.sent += 1;
+        }
+        true
+    }
+}
+
+impl Iterator for ProcessIter {
+    type Item = Vec<Segment>;
+
+    fn next(&mut self) -> Option<Vec<Segment>> {
+        match self.receiver.recv() {
+            Ok(segments) => Some(segments),
+            Err(_) => {
+                // The worker is done; surface a panic instead of ending early.
+                if let Some(worker) = self.worker.take()
+                    && let Err(panic) = worker.join()
+                {
+                    std::panic::resume_unwind(panic);
+                }
+                None
+            }
+        }
+    }
+}
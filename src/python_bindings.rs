@@ -1,12 +1,210 @@
+use crate::codec::{decode_metadata, decode_segments, encode_model};
+use crate::metadata::Metadata;
+use crate::corpus::compress_threads;
+use crate::minhash::{MinHasher, Shingling};
+use crate::render::{Reconstructed, ReconstructedTokens};
+use crate::rolling::{RollingCopyForward, RollingSnapshot};
 use crate::tokenization::get_tokenizer;
 use crate::{
     Approximate, ApproximateTokens, Config, CopyForward, CopyForwardTokens, Exact, ExactTokens,
-    Segment, TokenSegment, approximate, approximate_tokens, exact, exact_tokens,
+    Progress, Segment, Stats, TokenSegment, approximate, approximate_tokens, exact, exact_tokens,
 };
 use numpy::{PyArray1, PyReadonlyArray1};
-use pyo3::exceptions::PyTypeError;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyIndexError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyAny, PyList, PySequence};
+use pyo3::types::{PyAny, PyBytes, PyDict, PyList, PySequence};
+use std::sync::{Arc, Mutex};
+
+create_exception!(
+    copyforward,
+    CopyForwardError,
+    PyValueError,
+    "Base class for errors raised by copyforward."
+);
+create_exception!(
+    copyforward,
+    ConfigError,
+    CopyForwardError,
+    "Invalid algorithm configuration or tokenizer."
+);
+
+/// Whether `item` is a missing value: `None`, a float NaN or `pandas.NA`.
+fn is_missing(item: &PyAny) -> PyResult<bool> {
+    if item.is_none() {
+        return Ok(true);
+    }
+    if let Ok(f) = item.extract::<f64>()
+        && f.is_nan()
+    {
+        return Ok(true);
+    }
+    Ok(item.get_type().name()? == "NAType")
+}
+
+/// Collect messages from any iterable, such as a list or a pandas `Series`,
+/// without an intermediate Python list.
+fn extract_messages<'py, T: FromPyObject<'py>>(messages: &'py PyAny) -> PyResult<Vec<Option<T>>> {
+    let mut out = Vec::with_capacity(messages.len().unwrap_or(0));
+    for item in messages.iter()? {
+        let item = item?;
+        out.push(if is_missing(item)? { None } else { Some(item.extract()?) });
+    }
+    Ok(out)
+}
+
+/// Run `build` with the GIL released, calling the Python `progress(done,
+/// total)` every `every` messages. The callback returning `False`, raising,
+/// or a pending signal such as Ctrl-C cancels the build and raises.
+fn with_progress<R, F>(
+    py: Python,
+    mut config: Config,
+    progress: Option<PyObject>,
+    every: usize,
+    build: F,
+) -> PyResult<R>
+where
+    R: Send,
+    F: FnOnce(Config) -> R + Send,
+{
+    let failure: Arc<Mutex<Option<PyErr>>> = Arc::default();
+    if let Some(callback) = progress {
+        let failure = Arc::clone(&failure);
+        config.progress = Some(Progress::new(every, move |done, total| {
+            Python::with_gil(|py| {
+                let outcome = py
+                    .check_signals()
+                    .and_then(|()| callback.call1(py, (done, total)));
+                let error = match outcome {
+                    Ok(ret) if matches!(ret.extract::<bool>(py), Ok(false)) => {
+                        CopyForwardError::new_err("cancelled by progress callback")
+                    }
+                    Ok(_) => return true,
+                    Err(e) => e,
+                };
+                *failure.lock().expect("progress error lock") = Some(error);
+                false
+            })
+        }));
+    }
+    let result = py.allow_threads(move || build(config));
+    match failure.lock().expect("progress error lock").take() {
+        Some(error) => Err(error),
+        None => Ok(result),
+    }
+}
+
+/// Reject settings the engines accept but that cannot produce references.
+fn check_config(config: &Config) -> PyResult<()> {
+    config
+        .validate()
+        .map_err(|e| ConfigError::new_err(e.to_string()))
+}
+
+/// Validated algorithm settings, mirroring the Rust `Config`.
+///
+/// Pass as `config=` to the constructors, or pass the same settings to them
+/// as keyword arguments; instances are immutable and checked on creation.
+#[pyclass(name = "Config", frozen)]
+#[derive(Debug, Clone, PartialEq)]
+struct PyConfig {
+    #[pyo3(get)]
+    min_match_len: usize,
+    #[pyo3(get)]
+    lookback: Option<usize>,
+    #[pyo3(get)]
+    cap_len: usize,
+    #[pyo3(get)]
+    ncap: usize,
+    #[pyo3(get)]
+    stop_phrases: Vec<String>,
+    #[pyo3(get)]
+    min_indexed_len: usize,
+    #[pyo3(get)]
+    kmer_density: f64,
+    #[pyo3(get)]
+    skip_referenced_regions: bool,
+}
+
+impl PyConfig {
+    fn to_config(&self) -> Config {
+        Config {
+            min_match_len: self.min_match_len,
+            lookback: self.lookback,
+            cap_len: self.cap_len,
+            ncap: self.ncap,
+            stop_phrases: self.stop_phrases.clone(),
+            min_indexed_len: self.min_indexed_len,
+            kmer_density: self.kmer_density,
+            skip_referenced_regions: self.skip_referenced_regions,
+            ..Config::default()
+        }
+    }
+}
+
+#[pymethods]
+impl PyConfig {
+    #[new]
+    #[allow(clippy::too_many_arguments)] // one keyword argument per setting
+    #[pyo3(signature = (*, min_match_len=4, lookback=None, cap_len=64, ncap=64, stop_phrases=None, min_indexed_len=0, kmer_density=0.5, skip_referenced_regions=false))]
+    fn new(
+        min_match_len: usize,
+        lookback: Option<usize>,
+        cap_len: usize,
+        ncap: usize,
+        stop_phrases: Option<Vec<String>>,
+        min_indexed_len: usize,
+        kmer_density: f64,
+        skip_referenced_regions: bool,
+    ) -> PyResult<Self> {
+        let config = PyConfig {
+            min_match_len,
+            lookback,
+            cap_len,
+            ncap,
+            stop_phrases: stop_phrases.unwrap_or_default(),
+            min_indexed_len,
+            kmer_density,
+            skip_referenced_regions,
+        };
+        check_config(&config.to_config())?;
+        Ok(config)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Config(min_match_len={}, lookback={}, cap_len={}, ncap={}, stop_phrases={:?}, min_indexed_len={}, kmer_density={}, skip_referenced_regions={})",
+            self.min_match_len,
+            self.lookback.map_or("None".to_string(), |n| n.to_string()),
+            self.cap_len,
+            self.ncap,
+            self.stop_phrases,
+            self.min_indexed_len,
+            self.kmer_density,
+            if self.skip_referenced_regions { "True" } else { "False" },
+        )
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// Settings from `config`, or else a `Config` built from the keyword
+/// arguments in `keywords`; mixing both is an error.
+fn settings(py: Python, config: Option<PyConfig>, keywords: Option<&PyDict>) -> PyResult<Config> {
+    match (config, keywords.filter(|k| !k.is_empty())) {
+        (Some(_), Some(_)) => Err(ConfigError::new_err(
+            "pass settings either as config= or as keyword arguments, not both",
+        )),
+        (Some(config), None) => Ok(config.to_config()),
+        (None, Some(keywords)) => {
+            let config: PyConfig = py.get_type::<PyConfig>().call((), Some(keywords))?.extract()?;
+            Ok(config.to_config())
+        }
+        (None, None) => Ok(Config::default()),
+    }
+}
 
 #[pyclass]
 #[derive(Debug, Clone)]
@@ -36,16 +234,25 @@ struct PyReferenceSegment {
     start: usize,
     #[pyo3(get)]
     len: usize,
+    /// `start` counted in characters, which index Python strings.
+    #[pyo3(get)]
+    char_start: Option<usize>,
+    /// `len` counted in characters.
+    #[pyo3(get)]
+    char_len: Option<usize>,
 }
 
 #[pymethods]
 impl PyReferenceSegment {
     #[new]
-    fn new(message: usize, start: usize, len: usize) -> Self {
+    #[pyo3(signature = (message, start, len, char_start=None, char_len=None))]
+    fn new(message: usize, start: usize, len: usize, char_start: Option<usize>, char_len: Option<usize>) -> Self {
         Self {
             message,
             start,
             len,
+            char_start,
+            char_len,
         }
     }
 
@@ -117,12 +324,115 @@ impl PyReferenceTokens {
 enum TextAlg {
     Exact(Exact),
     Approx(Approximate),
+    Loaded(Reconstructed),
 }
 
 #[derive(Debug, Clone)]
 enum TokensAlg {
     Exact(ExactTokens),
     Approx(ApproximateTokens),
+    Loaded(Box<ReconstructedTokens>),
+}
+
+impl TextAlg {
+    fn model(&self) -> &dyn CopyForward {
+        match self {
+            TextAlg::Exact(inner) => inner,
+            TextAlg::Approx(inner) => inner,
+            TextAlg::Loaded(inner) => inner,
+        }
+    }
+}
+
+impl TokensAlg {
+    fn model(&self) -> &dyn CopyForwardTokens {
+        match self {
+            TokensAlg::Exact(inner) => inner,
+            TokensAlg::Approx(inner) => inner,
+            TokensAlg::Loaded(inner) => inner.as_ref(),
+        }
+    }
+}
+
+/// Resolve a Python index (negative counts from the end) against `len`.
+fn message_index(idx: isize, len: usize) -> PyResult<usize> {
+    let resolved = if idx < 0 { idx + len as isize } else { idx };
+    if (0..len as isize).contains(&resolved) {
+        Ok(resolved as usize)
+    } else {
+        Err(PyIndexError::new_err("message index out of range"))
+    }
+}
+
+/// JSON written by `to_json`: `{"metadata": ..., "segments": [...]}`.
+fn to_saved_json<S: serde::Serialize>(metadata: Option<Metadata>, segments: &[Vec<S>]) -> PyResult<String> {
+    serde_json::to_string(&serde_json::json!({ "metadata": metadata, "segments": segments }))
+        .map_err(|e| CopyForwardError::new_err(e.to_string()))
+}
+
+/// Parse `to_json` output, or a bare segments array as written before
+/// metadata was recorded.
+fn from_saved_json<S: serde::de::DeserializeOwned>(data: &str) -> PyResult<(Vec<Vec<S>>, Option<Metadata>)> {
+    let err = |e: serde_json::Error| CopyForwardError::new_err(e.to_string());
+    let (segments, metadata) = match serde_json::from_str(data).map_err(err)? {
+        serde_json::Value::Object(mut saved) => (
+            saved.remove("segments").unwrap_or_default(),
+            saved.remove("metadata").unwrap_or_default(),
+        ),
+        bare => (bare, serde_json::Value::Null),
+    };
+    Ok((
+        serde_json::from_value(segments).map_err(err)?,
+        serde_json::from_value(metadata).map_err(err)?,
+    ))
+}
+
+/// `metadata` as a Python dict, via its JSON form.
+fn metadata_py(py: Python, metadata: Option<Metadata>) -> PyResult<Option<PyObject>> {
+    let Some(metadata) = metadata else {
+        return Ok(None);
+    };
+    let json = serde_json::to_string(&metadata).map_err(|e| CopyForwardError::new_err(e.to_string()))?;
+    Ok(Some(py.import("json")?.call_method1("loads", (json,))?.into_py(py)))
+}
+
+/// Python segments of one message. `source` gives the text of referenced
+/// messages for character offsets, or `None` once it is no longer kept.
+fn text_segments_py<'a>(
+    py: Python,
+    segments: &[Segment],
+    source: impl Fn(usize) -> Option<&'a str>,
+) -> Vec<PyObject> {
+    segments
+        .iter()
+        .map(|seg| match seg {
+            Segment::Literal(s) => PyLiteralSegment::new(s.to_string()).into_py(py),
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                let chars = source(*message_idx)
+                    .map(|text| (text[..*start].chars().count(), text[*start..start + len].chars().count()));
+                PyReferenceSegment::new(*message_idx, *start, *len, chars.map(|c| c.0), chars.map(|c| c.1))
+                    .into_py(py)
+            }
+        })
+        .collect()
+}
+
+fn token_segments_py(py: Python, segments: &[TokenSegment]) -> Vec<PyObject> {
+    segments
+        .iter()
+        .map(|seg| match seg {
+            TokenSegment::Literal(toks) => PyLiteralTokens::new(toks.clone()).into_py(py),
+            TokenSegment::Reference {
+                message_idx,
+                start,
+                len,
+            } => PyReferenceTokens::new(*message_idx, *start, *len).into_py(py),
+        })
+        .collect()
 }
 
 #[pyclass(name = "CopyForwardText")]
@@ -133,95 +443,114 @@ struct PyCopyForwardText {
 #[pymethods]
 impl PyCopyForwardText {
     #[classmethod]
-    #[pyo3(signature = (messages, *, exact_mode=true, min_match_len=4, lookback=None, cap_len=64, ncap=64))]
+    #[pyo3(signature = (messages, *, exact_mode=true, config=None, progress=None, progress_every=1000, **keywords))]
     fn from_texts(
         _cls: &pyo3::types::PyType,
-        messages: Vec<Option<String>>,
+        messages: &PyAny,
         exact_mode: bool,
-        min_match_len: usize,
-        lookback: Option<usize>,
-        cap_len: usize,
-        ncap: usize,
+        config: Option<PyConfig>,
+        progress: Option<PyObject>,
+        progress_every: usize,
+        keywords: Option<&PyDict>,
     ) -> PyResult<Self> {
-        let config = Config {
-            min_match_len,
-            lookback,
-            cap_len,
-            ncap,
-        };
-        let inner = if exact_mode {
-            TextAlg::Exact(exact(&messages, config))
-        } else {
-            TextAlg::Approx(approximate(&messages, config))
-        };
+        let py = messages.py();
+        let config = settings(py, config, keywords)?;
+        let messages: Vec<Option<String>> = extract_messages(messages)?;
+        let inner = with_progress(py, config, progress, progress_every, |config| {
+            if exact_mode {
+                TextAlg::Exact(exact(&messages, config))
+            } else {
+                TextAlg::Approx(approximate(&messages, config))
+            }
+        })?;
         Ok(PyCopyForwardText { inner })
     }
 
     fn segments(&self) -> PyResult<Vec<Vec<PyObject>>> {
         Python::with_gil(|py| {
-            let segs = match &self.inner {
-                TextAlg::Exact(inner) => CopyForward::segments(inner),
-                TextAlg::Approx(inner) => CopyForward::segments(inner),
-            };
-            Ok(segs
-                .into_iter()
-                .map(|v| {
-                    v.into_iter()
-                        .map(|seg| match seg {
-                            Segment::Literal(s) => PyLiteralSegment::new(s).into_py(py),
-                            Segment::Reference {
-                                message_idx,
-                                start,
-                                len,
-                            } => PyReferenceSegment::new(message_idx, start, len).into_py(py),
-                        })
-                        .collect()
-                })
+            let model = self.inner.model();
+            Ok((0..model.len())
+                .map(|idx| text_segments_py(py, model.segments_for(idx), |i| Some(model.message(i))))
                 .collect())
         })
     }
 
     fn render(&self, replacement: &str) -> Vec<Option<String>> {
-        let result = match &self.inner {
-            TextAlg::Exact(inner) => CopyForward::render_with_static(inner, replacement),
-            TextAlg::Approx(inner) => CopyForward::render_with_static(inner, replacement),
-        };
+        let result = self.inner.model().render_with_static(replacement);
         // Convert empty strings (from None entries) back to None for Python
         result.into_iter().map(|s| if s.is_empty() { None } else { Some(s) }).collect()
     }
 
+    /// Crate version, algorithm and config that produced the segments, as a
+    /// dict; `None` for results loaded without metadata.
+    fn metadata(&self, py: Python) -> PyResult<Option<PyObject>> {
+        metadata_py(py, self.inner.model().metadata())
+    }
+
     fn compression_ratio(&self) -> f64 {
-        let segs = match &self.inner {
-            TextAlg::Exact(inner) => CopyForward::segments(inner),
-            TextAlg::Approx(inner) => CopyForward::segments(inner),
-        };
-        let original: usize = match &self.inner {
-            TextAlg::Exact(inner) => {
-                CopyForward::render_with(inner, |_, _, _, text| text.to_string())
-            }
-            TextAlg::Approx(inner) => {
-                CopyForward::render_with(inner, |_, _, _, text| text.to_string())
-            }
-        }
-        .iter()
-        .map(|s| s.len())
-        .sum();
-        let compressed: usize = segs
-            .iter()
-            .map(|v| {
-                v.iter()
-                    .map(|seg| match seg {
-                        Segment::Literal(s) => s.len(),
-                        Segment::Reference { .. } => 1,
-                    })
-                    .sum::<usize>()
-            })
-            .sum();
-        if original == 0 {
-            1.0
-        } else {
-            compressed as f64 / original as f64
-        }
+        let segs = self.inner.model().segments();
+        Stats::from_segments(&segs).compression_ratio()
+    }
+
+    /// Segments as JSON, in the schema of the Rust `Segment` serde derive,
+    /// together with the metadata that produced them:
+    /// `{"metadata": ..., "segments": [...]}`.
+    fn to_json(&self) -> PyResult<String> {
+        let model = self.inner.model();
+        to_saved_json(model.metadata(), &model.segments())
+    }
+
+    /// Load a result written by `to_json`, or a bare segments array; original
+    /// texts are rebuilt from it.
+    #[classmethod]
+    fn from_json(_cls: &pyo3::types::PyType, data: &str) -> PyResult<Self> {
+        let (segments, metadata) = from_saved_json::<Segment>(data)?;
+        let loaded = Reconstructed::new(segments)
+            .map_err(|e| CopyForwardError::new_err(e.to_string()))?
+            .with_metadata(metadata);
+        Ok(PyCopyForwardText {
+            inner: TextAlg::Loaded(loaded),
+        })
+    }
+
+    /// Segments in the compact binary codec of `copyforward::codec`, with
+    /// checksums of the original messages and the metadata that produced them.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &encode_model(self.inner.model()))
+    }
+
+    /// Load a result written by `to_bytes`; original texts are rebuilt from it.
+    #[classmethod]
+    fn from_bytes(_cls: &pyo3::types::PyType, data: &[u8]) -> PyResult<Self> {
+        let segments = decode_segments(data).map_err(|e| CopyForwardError::new_err(e.to_string()))?;
+        let metadata = decode_metadata(data).map_err(|e| CopyForwardError::new_err(e.to_string()))?;
+        let loaded = Reconstructed::new(segments)
+            .map_err(|e| CopyForwardError::new_err(e.to_string()))?
+            .with_metadata(metadata);
+        Ok(PyCopyForwardText {
+            inner: TextAlg::Loaded(loaded),
+        })
+    }
+
+    /// Number of messages, including `None` entries.
+    fn __len__(&self) -> usize {
+        self.inner.model().len()
+    }
+
+    /// Segments of one message; negative indices count from the end.
+    fn __getitem__(&self, py: Python, idx: isize) -> PyResult<Vec<PyObject>> {
+        let model = self.inner.model();
+        let idx = message_index(idx, model.len())?;
+        Ok(text_segments_py(py, model.segments_for(idx), |i| Some(model.message(i))))
+    }
+
+    /// Iterate over the segment lists of all messages.
+    fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+        let model = self.inner.model();
+        let messages: Vec<Vec<PyObject>> = (0..model.len())
+            .map(|idx| text_segments_py(py, model.segments_for(idx), |i| Some(model.message(i))))
+            .collect();
+        Ok(PyList::new(py, messages).as_ref().iter()?.into_py(py))
     }
 }
 
@@ -234,27 +563,26 @@ struct PyCopyForwardTokens {
 #[pymethods]
 impl PyCopyForwardTokens {
     #[classmethod]
-    #[pyo3(signature = (messages, *, exact_mode=true, min_match_len=4, lookback=None, cap_len=64, ncap=64))]
+    #[pyo3(signature = (messages, *, exact_mode=true, config=None, progress=None, progress_every=1000, **keywords))]
     fn from_tokens(
         _cls: &pyo3::types::PyType,
-        messages: Vec<Option<Vec<u32>>>,
+        messages: &PyAny,
         exact_mode: bool,
-        min_match_len: usize,
-        lookback: Option<usize>,
-        cap_len: usize,
-        ncap: usize,
+        config: Option<PyConfig>,
+        progress: Option<PyObject>,
+        progress_every: usize,
+        keywords: Option<&PyDict>,
     ) -> PyResult<Self> {
-        let config = Config {
-            min_match_len,
-            lookback,
-            cap_len,
-            ncap,
-        };
-        let inner = if exact_mode {
-            TokensAlg::Exact(exact_tokens(&messages, config))
-        } else {
-            TokensAlg::Approx(approximate_tokens(&messages, config))
-        };
+        let py = messages.py();
+        let config = settings(py, config, keywords)?;
+        let messages: Vec<Option<Vec<u32>>> = extract_messages(messages)?;
+        let inner = with_progress(py, config, progress, progress_every, |config| {
+            if exact_mode {
+                TokensAlg::Exact(exact_tokens(&messages, config))
+            } else {
+                TokensAlg::Approx(approximate_tokens(&messages, config))
+            }
+        })?;
         Ok(PyCopyForwardTokens {
             inner,
             tokenizer: None,
@@ -263,30 +591,29 @@ impl PyCopyForwardTokens {
 
     /// Tokenizer opt-in: accept texts and a tokenizer name, return token-mode compressor.
     #[classmethod]
-    #[pyo3(signature = (messages, tokenizer, *, exact_mode=true, min_match_len=4, lookback=None, cap_len=64, ncap=64))]
+    #[pyo3(signature = (messages, tokenizer, *, exact_mode=true, config=None, progress=None, progress_every=1000, **keywords))]
     fn from_texts_with_tokenizer(
         _cls: &pyo3::types::PyType,
-        messages: Vec<Option<String>>,
+        messages: &PyAny,
         tokenizer: String,
         exact_mode: bool,
-        min_match_len: usize,
-        lookback: Option<usize>,
-        cap_len: usize,
-        ncap: usize,
+        config: Option<PyConfig>,
+        progress: Option<PyObject>,
+        progress_every: usize,
+        keywords: Option<&PyDict>,
     ) -> PyResult<Self> {
-        let config = Config {
-            min_match_len,
-            lookback,
-            cap_len,
-            ncap,
-        };
-        let mut tok = get_tokenizer(&tokenizer).map_err(PyTypeError::new_err)?;
+        let py = messages.py();
+        let config = settings(py, config, keywords)?;
+        let mut tok = get_tokenizer(&tokenizer).map_err(ConfigError::new_err)?;
+        let messages: Vec<Option<String>> = extract_messages(messages)?;
         let toks: Vec<Option<Vec<u32>>> = messages.into_iter().map(|opt_s| opt_s.map(|s| tok.encode(&s))).collect();
-        let inner = if exact_mode {
-            TokensAlg::Exact(exact_tokens(&toks, config))
-        } else {
-            TokensAlg::Approx(approximate_tokens(&toks, config))
-        };
+        let inner = with_progress(py, config, progress, progress_every, |config| {
+            if exact_mode {
+                TokensAlg::Exact(exact_tokens(&toks, config))
+            } else {
+                TokensAlg::Approx(approximate_tokens(&toks, config))
+            }
+        })?;
         Ok(PyCopyForwardTokens {
             inner,
             tokenizer: Some(tok),
@@ -295,10 +622,7 @@ impl PyCopyForwardTokens {
 
     fn segments(&self) -> PyResult<Vec<Vec<PyObject>>> {
         Python::with_gil(|py| {
-            let segs = match &self.inner {
-                TokensAlg::Exact(inner) => CopyForwardTokens::segments(inner),
-                TokensAlg::Approx(inner) => CopyForwardTokens::segments(inner),
-            };
+            let segs = self.inner.model().segments();
             Ok(segs
                 .into_iter()
                 .map(|v| {
@@ -317,6 +641,54 @@ impl PyCopyForwardTokens {
         })
     }
 
+    /// Segments as JSON, in the schema of the Rust `TokenSegment` serde
+    /// derive, together with the metadata that produced them.
+    fn to_json(&self) -> PyResult<String> {
+        let model = self.inner.model();
+        to_saved_json(model.metadata(), &model.segments())
+    }
+
+    /// Load a result written by `to_json`, or a bare segments array; token
+    /// sequences are rebuilt from it.
+    #[classmethod]
+    fn from_json(_cls: &pyo3::types::PyType, data: &str) -> PyResult<Self> {
+        let (segments, metadata) = from_saved_json::<TokenSegment>(data)?;
+        let loaded = ReconstructedTokens::new(segments)
+            .map_err(|e| CopyForwardError::new_err(e.to_string()))?
+            .with_metadata(metadata);
+        Ok(PyCopyForwardTokens {
+            inner: TokensAlg::Loaded(Box::new(loaded)),
+            tokenizer: None,
+        })
+    }
+
+    /// Crate version, algorithm and config that produced the segments, as a
+    /// dict; `None` for results loaded without metadata.
+    fn metadata(&self, py: Python) -> PyResult<Option<PyObject>> {
+        metadata_py(py, self.inner.model().metadata())
+    }
+
+    /// Number of (non-`None`) messages.
+    fn __len__(&self) -> usize {
+        self.inner.model().len()
+    }
+
+    /// Segments of one message; negative indices count from the end.
+    fn __getitem__(&self, py: Python, idx: isize) -> PyResult<Vec<PyObject>> {
+        let model = self.inner.model();
+        let idx = message_index(idx, model.len())?;
+        Ok(token_segments_py(py, model.segments_for(idx)))
+    }
+
+    /// Iterate over the segment lists of all messages.
+    fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+        let model = self.inner.model();
+        let messages: Vec<Vec<PyObject>> = (0..model.len())
+            .map(|idx| token_segments_py(py, model.segments_for(idx)))
+            .collect();
+        Ok(PyList::new(py, messages).as_ref().iter()?.into_py(py))
+    }
+
     #[pyo3(signature = (replacement, *, as_numpy=false))]
     fn render(&self, replacement: &PyAny, as_numpy: bool) -> PyResult<pyo3::PyObject> {
         Python::with_gil(|py| {
@@ -343,14 +715,7 @@ impl PyCopyForwardTokens {
                 ));
             };
             
-            let out_vecs: Vec<Vec<u32>> = match &self.inner {
-                TokensAlg::Exact(inner) => {
-                    CopyForwardTokens::render_with(inner, |_, _, _, _| repl_vec.clone())
-                }
-                TokensAlg::Approx(inner) => {
-                    CopyForwardTokens::render_with(inner, |_, _, _, _| repl_vec.clone())
-                }
-            };
+            let out_vecs: Vec<Vec<u32>> = self.inner.model().render_with_static(&repl_vec);
             if as_numpy {
                 let list = PyList::empty(py);
                 for v in out_vecs {
@@ -373,25 +738,148 @@ impl PyCopyForwardTokens {
             )
         })?;
         let repl_tokens = tok.encode(replacement);
-        let tokens: Vec<Vec<u32>> = match &self.inner {
-            TokensAlg::Exact(inner) => {
-                CopyForwardTokens::render_with(inner, |_, _, _, _| repl_tokens.clone())
-            }
-            TokensAlg::Approx(inner) => {
-                CopyForwardTokens::render_with(inner, |_, _, _, _| repl_tokens.clone())
-            }
-        };
+        let tokens: Vec<Vec<u32>> = self.inner.model().render_with_static(&repl_tokens);
         Ok(tokens.into_iter().map(|v| tok.decode(&v)).collect())
     }
 }
 
+/// Incremental copy-forward over a stream, referencing the last `window`
+/// messages.
+///
+/// `to_json` persists the window and `from_json` restores it, so a service
+/// can warm-start from a saved state instead of replaying its history.
+#[pyclass(name = "CopyForwardStream")]
+struct PyCopyForwardStream {
+    inner: RollingCopyForward,
+}
+
+#[pymethods]
+impl PyCopyForwardStream {
+    #[new]
+    #[pyo3(signature = (window, *, config=None))]
+    fn new(window: usize, config: Option<PyConfig>) -> Self {
+        let config = config.map_or_else(Config::default, |c| c.to_config());
+        PyCopyForwardStream {
+            inner: RollingCopyForward::new(window, config),
+        }
+    }
+
+    /// Segment `message` against the window and add it; returns its segments.
+    /// References to a message that left the window with this one have no
+    /// character offsets.
+    fn add_message(&mut self, py: Python, message: &str) -> Vec<PyObject> {
+        let segments = self.inner.push(message);
+        text_segments_py(py, &segments, |i| self.inner.message(i))
+    }
+
+    /// Text of message `idx`, or `None` once it has left the window.
+    fn message(&self, idx: usize) -> Option<String> {
+        self.inner.message(idx).map(str::to_string)
+    }
+
+    /// Number of messages added so far.
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// The window and its index state as JSON.
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.inner.snapshot())
+            .map_err(|e| CopyForwardError::new_err(e.to_string()))
+    }
+
+    /// Restore a stream saved with `to_json`.
+    #[classmethod]
+    fn from_json(_cls: &pyo3::types::PyType, data: &str) -> PyResult<Self> {
+        let snapshot: RollingSnapshot =
+            serde_json::from_str(data).map_err(|e| CopyForwardError::new_err(e.to_string()))?;
+        let inner =
+            RollingCopyForward::restore(snapshot).map_err(|e| CopyForwardError::new_err(e.to_string()))?;
+        Ok(PyCopyForwardStream { inner })
+    }
+}
+
+/// Compress every thread of a corpus independently, in parallel with the GIL
+/// released.
+///
+/// `threads` is an iterable of message lists or a pandas `SeriesGroupBy`
+/// (e.g. `df.groupby("thread")["body"]`). Returns the per-thread results in
+/// input order and a dict of aggregate statistics.
+#[pyfunction]
+#[pyo3(signature = (threads, *, exact_mode=true, config=None, **keywords))]
+fn compress_corpus(
+    py: Python,
+    threads: &PyAny,
+    exact_mode: bool,
+    config: Option<PyConfig>,
+    keywords: Option<&PyDict>,
+) -> PyResult<(Vec<PyCopyForwardText>, PyObject)> {
+    let config = settings(py, config, keywords)?;
+    let grouped = threads.hasattr("ngroups")?;
+    let mut corpus: Vec<Vec<Option<String>>> = Vec::new();
+    for item in threads.iter()? {
+        let item = item?;
+        let messages = if grouped { item.get_item(1)? } else { item };
+        corpus.push(extract_messages(messages)?);
+    }
+
+    let results = py.allow_threads(|| {
+        compress_threads(&corpus, |messages| {
+            if exact_mode {
+                TextAlg::Exact(exact(messages, config.clone()))
+            } else {
+                TextAlg::Approx(approximate(messages, config.clone()))
+            }
+        })
+    });
+    let total: Stats = results
+        .iter()
+        .map(|inner| Stats::from_segments(&inner.model().segments()))
+        .sum();
+    let stats = PyDict::new(py);
+    stats.set_item("messages", total.messages)?;
+    stats.set_item("original_bytes", total.original_bytes)?;
+    stats.set_item("literal_bytes", total.literal_bytes)?;
+    stats.set_item("references", total.references)?;
+    stats.set_item("compression_ratio", total.compression_ratio())?;
+    let results = results.into_iter().map(|inner| PyCopyForwardText { inner }).collect();
+    Ok((results, stats.into_py(py)))
+}
+
+/// Per-message MinHash signatures compatible with `datasketch.MinHash`.
+///
+/// With `shingle_size=None` each whitespace-separated word is one shingle;
+/// otherwise overlapping character n-grams of that size are used. Rebuild with
+/// `datasketch.MinHash(num_perm=num_perm, seed=seed, hashvalues=sig)`.
+#[pyfunction]
+#[pyo3(signature = (messages, *, num_perm=128, seed=1, shingle_size=None))]
+fn minhash_signatures(
+    messages: &PyAny,
+    num_perm: usize,
+    seed: u32,
+    shingle_size: Option<usize>,
+) -> PyResult<Vec<Vec<u64>>> {
+    let shingling = match shingle_size {
+        Some(n) => Shingling::Chars(n),
+        None => Shingling::Words,
+    };
+    let messages: Vec<Option<String>> = extract_messages(messages)?;
+    Ok(MinHasher::new(num_perm, seed).signatures(&messages, shingling))
+}
+
 #[pymodule]
-fn copyforward(_py: Python, m: &PyModule) -> PyResult<()> {
+fn copyforward(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyConfig>()?;
     m.add_class::<PyCopyForwardText>()?;
     m.add_class::<PyCopyForwardTokens>()?;
+    m.add_class::<PyCopyForwardStream>()?;
     m.add_class::<PyLiteralSegment>()?;
     m.add_class::<PyReferenceSegment>()?;
     m.add_class::<PyLiteralTokens>()?;
     m.add_class::<PyReferenceTokens>()?;
+    m.add_function(wrap_pyfunction!(compress_corpus, m)?)?;
+    m.add_function(wrap_pyfunction!(minhash_signatures, m)?)?;
+    m.add("CopyForwardError", py.get_type::<CopyForwardError>())?;
+    m.add("ConfigError", py.get_type::<ConfigError>())?;
     Ok(())
 }
@@ -0,0 +1,237 @@
+//! q-gram filter for approximate (edit-distance) matching against prior messages.
+//!
+//! Verifying an approximate match costs a dynamic-programming pass over the
+//! text, far too slow to run against every prior message. A [`QGramIndex`]
+//! narrows the search first, using the q-gram lemma: an occurrence of a
+//! pattern of `m` characters with at most `k` edits shares at least
+//! `m + 1 - (k + 1) * q` of the pattern's q-grams, all on diagonals within
+//! `2k` of each other. Only regions of prior messages whose diagonal band
+//! reaches that count become [`Candidate`]s, and only candidates are verified.
+//! The filter is lossless: every occurrence within `k` edits lies inside a
+//! candidate.
+//!
+//! # Example
+//! ```
+//! use copyforward::qgram::QGramIndex;
+//!
+//! let index = QGramIndex::new(&["The deploy window is Tuesday at 9am.", "Lunch?"], 3);
+//! let found = index.find("deploy windw is Tuesday", 2);
+//! assert_eq!(found.len(), 1);
+//! assert_eq!(found[0].message_idx, 0);
+//! assert_eq!(found[0].edits, 1);
+//! let occurrence = found[0].start..found[0].start + found[0].len;
+//! assert_eq!(&"The deploy window is Tuesday at 9am."[occurrence], "deploy window is Tuesday");
+//! ```
+
+use crate::hashing::{prefix_hashes_u32, range_hash};
+use crate::{MessageLike, compute_offsets, normalize};
+use ahash::AHashMap as HashMap;
+use std::collections::BTreeMap;
+
+const BASE: u64 = 257;
+
+/// Region of a prior message that may contain an approximate occurrence.
+///
+/// Offsets are in bytes, like [`crate::Segment::Reference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    /// Index of the prior message.
+    pub message_idx: usize,
+    /// Byte offset where the region starts.
+    pub start: usize,
+    /// Byte offset where the region ends (exclusive).
+    pub end: usize,
+}
+
+/// Verified approximate occurrence of a pattern in a prior message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApproxMatch {
+    /// Index of the prior message.
+    pub message_idx: usize,
+    /// Byte offset of the occurrence.
+    pub start: usize,
+    /// Byte length of the occurrence.
+    pub len: usize,
+    /// Edit distance between the pattern and the occurrence.
+    pub edits: usize,
+}
+
+struct Message {
+    codes: Vec<u32>,
+    offsets: Vec<usize>,
+}
+
+/// Index of every q-gram of the prior messages.
+pub struct QGramIndex {
+    q: usize,
+    messages: Vec<Message>,
+    table: HashMap<u64, Vec<(usize, usize)>>,
+}
+
+impl QGramIndex {
+    /// Index the q-grams of `messages`. `None` entries are indexed as empty
+    /// messages so indices stay aligned with the input.
+    pub fn new<M: MessageLike>(messages: &[M], q: usize) -> QGramIndex {
+        let q = q.max(1);
+        let messages: Vec<Message> = messages
+            .iter()
+            .map(|m| {
+                let text = m.as_message().unwrap_or("");
+                Message {
+                    codes: normalize::string_to_u32s(text),
+                    offsets: compute_offsets(text),
+                }
+            })
+            .collect();
+        let mut table: HashMap<u64, Vec<(usize, usize)>> = HashMap::new();
+        for (idx, m) in messages.iter().enumerate() {
+            if m.codes.len() >= q {
+                let (h, p) = prefix_hashes_u32(&m.codes, BASE);
+                for start in 0..=(m.codes.len() - q) {
+                    table
+                        .entry(range_hash(&h, &p, start, start + q))
+                        .or_default()
+                        .push((idx, start));
+                }
+            }
+        }
+        QGramIndex { q, messages, table }
+    }
+
+    /// Number of indexed messages.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Regions that may contain `pattern` within `max_edits` edits, ordered by
+    /// message and offset. When the pattern is too short for the q-gram lemma
+    /// to rule anything out, every non-empty message is one candidate.
+    pub fn candidates(&self, pattern: &str, max_edits: usize) -> Vec<Candidate> {
+        let codes = normalize::string_to_u32s(pattern);
+        self.char_candidates(&codes, max_edits)
+            .into_iter()
+            .map(|(message_idx, start, end)| {
+                let offsets = &self.messages[message_idx].offsets;
+                Candidate {
+                    message_idx,
+                    start: offsets[start],
+                    end: offsets[end],
+                }
+            })
+            .collect()
+    }
+
+    /// Best occurrence of `pattern` within `max_edits` edits in each candidate
+    /// region: fewest edits, then earliest end.
+    pub fn find(&self, pattern: &str, max_edits: usize) -> Vec<ApproxMatch> {
+        let codes = normalize::string_to_u32s(pattern);
+        if codes.is_empty() {
+            return Vec::new();
+        }
+        self.char_candidates(&codes, max_edits)
+            .into_iter()
+            .filter_map(|(message_idx, start, end)| {
+                let m = &self.messages[message_idx];
+                let (edits, from, to) = verify(&codes, &m.codes[start..end], max_edits)?;
+                let (from, to) = (m.offsets[start + from], m.offsets[start + to]);
+                Some(ApproxMatch {
+                    message_idx,
+                    start: from,
+                    len: to - from,
+                    edits,
+                })
+            })
+            .collect()
+    }
+
+    /// Candidate regions as `(message, start char, end char)`.
+    fn char_candidates(&self, pattern: &[u32], k: usize) -> Vec<(usize, usize, usize)> {
+        let (m, q) = (pattern.len(), self.q);
+        let threshold = (m + 1).saturating_sub((k + 1) * q);
+        if threshold == 0 {
+            return (self.messages.iter().enumerate())
+                .filter(|(_, msg)| !msg.codes.is_empty())
+                .map(|(idx, msg)| (idx, 0, msg.codes.len()))
+                .collect();
+        }
+
+        let (h, p) = prefix_hashes_u32(pattern, BASE);
+        let mut diagonals: BTreeMap<usize, Vec<isize>> = BTreeMap::new();
+        for i in 0..=(m - q) {
+            let key = range_hash(&h, &p, i, i + q);
+            for &(idx, j) in self.table.get(&key).into_iter().flatten() {
+                if self.messages[idx].codes[j..j + q] == pattern[i..i + q] {
+                    diagonals
+                        .entry(idx)
+                        .or_default()
+                        .push(j as isize - i as isize);
+                }
+            }
+        }
+
+        let (k, m) = (k as isize, m as isize);
+        let mut regions = Vec::new();
+        for (idx, mut diags) in diagonals {
+            diags.sort_unstable();
+            let len = self.messages[idx].codes.len() as isize;
+            let mut current: Option<(isize, isize)> = None;
+            let mut right = 0;
+            for left in 0..diags.len() {
+                while right < diags.len() && diags[right] <= diags[left] + 2 * k {
+                    right += 1;
+                }
+                if right - left < threshold {
+                    continue;
+                }
+                let start = (diags[left] - k).max(0);
+                let end = (diags[left] + 2 * k + m).min(len);
+                current = match current {
+                    Some((s, e)) if start <= e => Some((s, e.max(end))),
+                    Some((s, e)) => {
+                        regions.push((idx, s as usize, e as usize));
+                        Some((start, end))
+                    }
+                    None => Some((start, end)),
+                };
+            }
+            if let Some((s, e)) = current {
+                regions.push((idx, s as usize, e as usize));
+            }
+        }
+        regions
+    }
+}
+
+/// Best approximate occurrence of `pattern` in `text` with at most `k` edits,
+/// as `(edits, start, end)` in `text` positions (Sellers' algorithm).
+fn verify(pattern: &[u32], text: &[u32], k: usize) -> Option<(usize, usize, usize)> {
+    let m = pattern.len();
+    let mut cost: Vec<usize> = (0..=m).collect();
+    let mut start = vec![0; m + 1];
+    let mut best: Option<(usize, usize, usize)> = None;
+    for (pos, &t) in text.iter().enumerate() {
+        let (mut diag_cost, mut diag_start) = (cost[0], start[0]);
+        cost[0] = 0;
+        start[0] = pos + 1;
+        for i in 1..=m {
+            let (up_cost, up_start) = (cost[i], start[i]);
+            let mut next = (diag_cost + usize::from(pattern[i - 1] != t), diag_start);
+            if up_cost + 1 < next.0 {
+                next = (up_cost + 1, up_start);
+            }
+            if cost[i - 1] + 1 < next.0 {
+                next = (cost[i - 1] + 1, start[i - 1]);
+            }
+            (diag_cost, diag_start) = (up_cost, up_start);
+            (cost[i], start[i]) = next;
+        }
+        if cost[m] <= k && best.is_none_or(|(edits, _, _)| cost[m] < edits) {
+            best = Some((cost[m], start[m], pos + 1));
+        }
+    }
+    best
+}
@@ -0,0 +1,137 @@
+//! Measuring what a segmentation misses relative to another.
+//!
+//! [`compare`] lines up two segmentations of the same messages byte by byte
+//! and counts, per message and in total, the bytes one references and the
+//! other leaves literal. With [`exact()`](crate::exact()) as the baseline and
+//! [`approximate()`](crate::approximate()) as the candidate, the missed bytes
+//! are precisely the approximate algorithm's quality loss, independent of how
+//! either splits its references.
+//!
+//! # Example
+//! ```
+//! use copyforward::{approximate, exact, Config};
+//! use copyforward::quality::compare;
+//!
+//! let messages = ["Hello world, how are you?", "Fine.\n> Hello world, how are you?"];
+//! let baseline = exact(&messages, Config::default());
+//! let candidate = approximate(&messages, Config::default());
+//! let report = compare(&baseline, &candidate);
+//! assert_eq!(report.total.baseline_referenced, messages[0].len());
+//! assert_eq!(report.total.missed, 0);
+//! ```
+
+use crate::core::{CopyForward, Segment};
+use std::ops::Range;
+
+/// Byte counts for one message, or summed over all of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageQuality {
+    /// Bytes of message text.
+    pub bytes: usize,
+    /// Bytes the baseline covers with references.
+    pub baseline_referenced: usize,
+    /// Bytes the candidate covers with references.
+    pub candidate_referenced: usize,
+    /// Bytes the baseline references but the candidate leaves literal.
+    pub missed: usize,
+    /// Bytes the candidate references but the baseline leaves literal.
+    pub gained: usize,
+}
+
+impl MessageQuality {
+    /// Fraction of the baseline's referenced bytes the candidate missed; 0.0
+    /// when the baseline references nothing.
+    pub fn missed_fraction(&self) -> f64 {
+        if self.baseline_referenced == 0 {
+            0.0
+        } else {
+            self.missed as f64 / self.baseline_referenced as f64
+        }
+    }
+}
+
+/// Result of [`compare`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Quality {
+    /// One entry per message, in order.
+    pub messages: Vec<MessageQuality>,
+    /// Sum over all messages.
+    pub total: MessageQuality,
+}
+
+/// Compare `candidate` against `baseline`, two segmentations of the same
+/// messages.
+///
+/// # Panics
+/// If the two models do not render the same messages.
+pub fn compare(baseline: &dyn CopyForward, candidate: &dyn CopyForward) -> Quality {
+    assert_eq!(
+        baseline.len(),
+        candidate.len(),
+        "models hold different numbers of messages"
+    );
+    let mut quality = Quality::default();
+    for idx in 0..baseline.len() {
+        assert_eq!(
+            baseline.message(idx),
+            candidate.message(idx),
+            "models disagree on message {idx}"
+        );
+        let base = referenced(baseline.segments_for(idx));
+        let cand = referenced(candidate.segments_for(idx));
+        let shared = overlap(&base, &cand);
+        let baseline_referenced = total(&base);
+        let candidate_referenced = total(&cand);
+        let message = MessageQuality {
+            bytes: baseline.message(idx).len(),
+            baseline_referenced,
+            candidate_referenced,
+            missed: baseline_referenced - shared,
+            gained: candidate_referenced - shared,
+        };
+        let sum = &mut quality.total;
+        sum.bytes += message.bytes;
+        sum.baseline_referenced += message.baseline_referenced;
+        sum.candidate_referenced += message.candidate_referenced;
+        sum.missed += message.missed;
+        sum.gained += message.gained;
+        quality.messages.push(message);
+    }
+    quality
+}
+
+/// Byte ranges of a message covered by references, in order.
+fn referenced(segments: &[Segment]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut pos = 0;
+    for seg in segments {
+        match seg {
+            Segment::Literal(text) => pos += text.len(),
+            Segment::Reference { len, .. } => {
+                ranges.push(pos..pos + len);
+                pos += len;
+            }
+        }
+    }
+    ranges
+}
+
+fn total(ranges: &[Range<usize>]) -> usize {
+    ranges.iter().map(|r| r.len()).sum()
+}
+
+/// Bytes covered by both sorted, disjoint range lists.
+fn overlap(a: &[Range<usize>], b: &[Range<usize>]) -> usize {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].start.max(b[j].start);
+        let end = a[i].end.min(b[j].end);
+        shared += end.saturating_sub(start);
+        if a[i].end <= b[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    shared
+}
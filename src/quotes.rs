@@ -0,0 +1,168 @@
+//! Quote stripping for email-style threads.
+//!
+//! [`strip_quotes`] returns only the new content of each message by combining:
+//!
+//! 1. quote-marker handling: `>`-prefixed lines are dropped, along with the
+//!    `On ... wrote:` attribution line introducing them and everything after an
+//!    Outlook-style `-----Original Message-----` separator;
+//! 2. copy-forward matching on the marker-free text, which also removes
+//!    earlier text pasted without markers;
+//! 3. cleanup of the blank lines left behind.
+//!
+//! # Example
+//! ```
+//! use copyforward::quotes::strip_quotes;
+//!
+//! let thread = [
+//!     "Can we move the meeting to Thursday afternoon?",
+//!     "Thursday works for me.\n\nOn Mon, Ann wrote:\n> Can we move the meeting to Thursday afternoon?",
+//! ];
+//! assert_eq!(strip_quotes(&thread), ["Can we move the meeting to Thursday afternoon?", "Thursday works for me."]);
+//! ```
+
+use crate::core::{Config, CopyForward, Segment};
+use crate::{MessageLike, exact};
+
+/// Options for [`strip_quotes_with`].
+#[derive(Debug, Clone)]
+pub struct QuoteOptions {
+    /// Minimum length, in characters, of unmarked copied text to remove.
+    ///
+    /// **Default:** 32
+    pub min_match_len: usize,
+}
+
+impl Default for QuoteOptions {
+    fn default() -> Self {
+        QuoteOptions { min_match_len: 32 }
+    }
+}
+
+/// Extract the new content of each message in a thread using default options.
+///
+/// `None` entries yield empty strings.
+pub fn strip_quotes<M: MessageLike>(thread: &[M]) -> Vec<String> {
+    strip_quotes_with(thread, &QuoteOptions::default())
+}
+
+/// Extract the new content of each message in a thread.
+pub fn strip_quotes_with<M: MessageLike>(thread: &[M], options: &QuoteOptions) -> Vec<String> {
+    let parsed: Vec<Vec<Line>> = thread
+        .iter()
+        .map(|m| parse_lines(m.as_message().unwrap_or("")))
+        .collect();
+    let normalized: Vec<String> = parsed
+        .iter()
+        .map(|lines| lines.iter().map(|l| l.text).collect())
+        .collect();
+    let config = Config {
+        min_match_len: options.min_match_len.max(1),
+        ..Config::default()
+    };
+    let segments = exact(&normalized, config).segments();
+
+    parsed
+        .iter()
+        .zip(&normalized)
+        .zip(&segments)
+        .map(|((lines, text), segs)| {
+            let mut keep = vec![true; text.len()];
+            let mut pos = 0;
+            for seg in segs {
+                match seg {
+                    Segment::Literal(s) => pos += s.len(),
+                    Segment::Reference { len, .. } => {
+                        keep[pos..pos + len].fill(false);
+                        pos += len;
+                    }
+                }
+            }
+            let mut pos = 0;
+            for line in lines {
+                if line.drop {
+                    keep[pos..pos + line.text.len()].fill(false);
+                }
+                pos += line.text.len();
+            }
+            let kept: String = text
+                .char_indices()
+                .filter(|(i, _)| keep[*i])
+                .map(|(_, c)| c)
+                .collect();
+            tidy(&kept)
+        })
+        .collect()
+}
+
+struct Line<'a> {
+    /// Line content without quote markers, including its newline.
+    text: &'a str,
+    /// Quoted, attribution or trailing forwarded content.
+    drop: bool,
+}
+
+fn parse_lines(message: &str) -> Vec<Line<'_>> {
+    let mut lines: Vec<Line> = Vec::new();
+    let mut forwarded = false;
+    for raw in message.split_inclusive('\n') {
+        let (text, quoted) = strip_markers(raw);
+        if is_original_message_separator(text) {
+            forwarded = true;
+        }
+        lines.push(Line {
+            text,
+            drop: quoted || forwarded,
+        });
+    }
+    // Attribution lines directly introduce a quoted block (blank lines allowed).
+    for i in 0..lines.len() {
+        if lines[i].drop || !lines[i].text.trim_end().ends_with("wrote:") {
+            continue;
+        }
+        let next = lines[i + 1..].iter().find(|l| !l.text.trim().is_empty());
+        if next.is_some_and(|l| l.drop) {
+            lines[i].drop = true;
+        }
+    }
+    lines
+}
+
+/// Strip leading `>` markers (optionally separated by single spaces).
+fn strip_markers(line: &str) -> (&str, bool) {
+    let mut rest = line.trim_start_matches([' ', '\t']);
+    let mut quoted = false;
+    while let Some(r) = rest.strip_prefix('>') {
+        quoted = true;
+        rest = r.strip_prefix(' ').unwrap_or(r);
+    }
+    if quoted { (rest, true) } else { (line, false) }
+}
+
+fn is_original_message_separator(line: &str) -> bool {
+    let t = line.trim();
+    t.starts_with("-----")
+        && t.ends_with("-----")
+        && t.trim_matches('-')
+            .trim()
+            .eq_ignore_ascii_case("original message")
+}
+
+/// Trim each line's trailing whitespace, collapse runs of blank lines and trim
+/// the ends.
+fn tidy(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank_run = 0;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_run += 1;
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank_run > 0 { "\n\n" } else { "\n" });
+        }
+        out.push_str(line);
+        blank_run = 0;
+    }
+    out
+}
@@ -0,0 +1,673 @@
+//! Rendering helpers that operate on segments alone.
+//!
+//! A segmentation is self-contained: every reference points into an earlier
+//! message, so the original texts can be rebuilt without the inputs. This is
+//! what storage backends rely on when they persist only segments.
+
+use crate::compute_offsets;
+use crate::core::{
+    Config, CopyForward, CopyForwardTokens, DynReplacer, DynTokenReplacer, Segment, TokenSegment,
+};
+use crate::hashed_binary::HashedGreedyBinary;
+use crate::hashing::{prefix_hashes_u32, range_hash};
+use crate::metadata::Metadata;
+use crate::normalize::string_to_u32s;
+use crate::text::TextModel;
+use ahash::AHashMap as HashMap;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+/// Error returned when segments cannot be turned back into text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReconstructError {
+    /// A reference points at a later message.
+    ForwardReference { message: usize, segment: usize },
+    /// A reference span lies outside its source message (for a
+    /// self-reference, outside the text before it) or splits a UTF-8
+    /// character.
+    InvalidSpan { message: usize, segment: usize },
+}
+
+impl fmt::Display for ReconstructError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReconstructError::ForwardReference { message, segment } => write!(
+                f,
+                "segment {segment} of message {message} references a later message"
+            ),
+            ReconstructError::InvalidSpan { message, segment } => write!(
+                f,
+                "segment {segment} of message {message} references an invalid span"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReconstructError {}
+
+/// Rebuild the original messages from their segments.
+///
+/// # Example
+/// ```
+/// use copyforward::render::reconstruct;
+/// use copyforward::{exact, Config, CopyForward};
+///
+/// let messages = ["Hello world", "Hello world today"];
+/// let segments = exact(&messages, Config::default()).segments();
+/// assert_eq!(reconstruct(&segments).unwrap(), messages);
+/// ```
+pub fn reconstruct(segments: &[Vec<Segment>]) -> Result<Vec<String>, ReconstructError> {
+    let mut out: Vec<String> = Vec::with_capacity(segments.len());
+    for (message, segs) in segments.iter().enumerate() {
+        let mut text = String::new();
+        for (segment, seg) in segs.iter().enumerate() {
+            match seg {
+                Segment::Literal(s) => text.push_str(s),
+                Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => {
+                    let invalid = ReconstructError::InvalidSpan { message, segment };
+                    let range = start.checked_add(*len).map(|end| *start..end);
+                    if *message_idx == message {
+                        // A self-reference copies text rebuilt earlier in the message.
+                        let range = range.filter(|r| text.get(r.clone()).is_some());
+                        text.extend_from_within(range.ok_or(invalid)?);
+                    } else {
+                        let source = out
+                            .get(*message_idx)
+                            .ok_or(ReconstructError::ForwardReference { message, segment })?;
+                        text.push_str(range.and_then(|r| source.get(r)).ok_or(invalid)?);
+                    }
+                }
+            }
+        }
+        out.push(text);
+    }
+    Ok(out)
+}
+
+/// Rebuild the original token sequences from their segments.
+///
+/// Token counterpart of [`reconstruct`].
+pub fn reconstruct_tokens(
+    segments: &[Vec<TokenSegment>],
+) -> Result<Vec<Vec<u32>>, ReconstructError> {
+    let mut out: Vec<Vec<u32>> = Vec::with_capacity(segments.len());
+    for (message, segs) in segments.iter().enumerate() {
+        let mut tokens = Vec::new();
+        for (segment, seg) in segs.iter().enumerate() {
+            match seg {
+                TokenSegment::Literal(toks) => tokens.extend_from_slice(toks),
+                TokenSegment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => {
+                    let invalid = ReconstructError::InvalidSpan { message, segment };
+                    let range = start.checked_add(*len).map(|end| *start..end);
+                    if *message_idx == message {
+                        let range = range.filter(|r| r.end <= tokens.len());
+                        tokens.extend_from_within(range.ok_or(invalid)?);
+                    } else {
+                        let source = out
+                            .get(*message_idx)
+                            .ok_or(ReconstructError::ForwardReference { message, segment })?;
+                        tokens.extend_from_slice(range.and_then(|r| source.get(r)).ok_or(invalid)?);
+                    }
+                }
+            }
+        }
+        out.push(tokens);
+    }
+    Ok(out)
+}
+
+/// A [`CopyForward`] result rebuilt from stored segments.
+///
+/// Segments read back from JSON, the [binary codec](crate::codec) or a
+/// [store](crate::store) can be rendered, sliced and located like a freshly
+/// computed result, without the original messages.
+///
+/// # Example
+/// ```
+/// use copyforward::render::Reconstructed;
+/// use copyforward::{exact, Config, CopyForward};
+///
+/// let messages = ["Hello world", "Hello world today"];
+/// let segments = exact(&messages, Config::default()).segments();
+/// let loaded = Reconstructed::new(segments).unwrap();
+/// assert_eq!(loaded.message(1), "Hello world today");
+/// assert_eq!(loaded.render_with_static("[REF]")[1], "[REF] today");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Reconstructed {
+    model: TextModel,
+    metadata: Option<Metadata>,
+}
+
+impl Reconstructed {
+    /// Validate `segments` and rebuild the messages they describe.
+    pub fn new(segments: Vec<Vec<Segment>>) -> Result<Reconstructed, ReconstructError> {
+        let originals = reconstruct(&segments)?;
+        Ok(Reconstructed {
+            model: TextModel::from_parts(originals, segments),
+            metadata: None,
+        })
+    }
+
+    /// Report `metadata` from [`CopyForward::metadata`], typically as read
+    /// back with the segments, so re-encoding the result keeps it.
+    pub fn with_metadata(mut self, metadata: Option<Metadata>) -> Reconstructed {
+        self.metadata = metadata;
+        self
+    }
+}
+
+impl CopyForward for Reconstructed {
+    fn segments(&self) -> Vec<Vec<Segment>> {
+        self.model.segments()
+    }
+
+    fn segments_for(&self, idx: usize) -> &[Segment] {
+        self.model.segments_for(idx)
+    }
+
+    fn message(&self, idx: usize) -> &str {
+        self.model.message(idx)
+    }
+
+    fn len(&self) -> usize {
+        self.model.len()
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        self.metadata.clone()
+    }
+
+    fn render_with<F>(&self, replacer: F) -> Vec<String>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        self.model.render_with(replacer)
+    }
+
+    fn render_with_dyn(&self, replacer: &mut DynReplacer<'_>) -> Vec<String> {
+        self.model.render_with(replacer)
+    }
+
+    fn render_into_dyn(&self, idx: usize, buf: &mut String, replacer: &mut DynReplacer<'_>) {
+        self.model.render_into(idx, buf, replacer)
+    }
+
+    fn render_into<F>(&self, idx: usize, buf: &mut String, replacer: F)
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        self.model.render_into(idx, buf, replacer)
+    }
+
+    fn render_all_into<F>(&self, buf: &mut String, replacer: F) -> Vec<Range<usize>>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        self.model.render_all_into(buf, replacer)
+    }
+
+    fn render_cow<'a, F>(&'a self, replacer: F) -> Vec<Cow<'a, str>>
+    where
+        F: FnMut(usize, usize, usize, &'a str) -> Cow<'a, str>,
+    {
+        self.model.render_cow(replacer)
+    }
+}
+
+/// A [`CopyForwardTokens`] result rebuilt from stored token segments.
+#[derive(Debug, Clone)]
+pub struct ReconstructedTokens {
+    inner: HashedGreedyBinary,
+    metadata: Option<Metadata>,
+}
+
+impl ReconstructedTokens {
+    /// Validate `segments` and rebuild the token sequences they describe.
+    pub fn new(segments: Vec<Vec<TokenSegment>>) -> Result<ReconstructedTokens, ReconstructError> {
+        let messages = reconstruct_tokens(&segments)?;
+        Ok(ReconstructedTokens {
+            inner: HashedGreedyBinary::from_parts(segments, messages, Config::default()),
+            metadata: None,
+        })
+    }
+
+    /// Report `metadata` from [`CopyForwardTokens::metadata`]; see
+    /// [`Reconstructed::with_metadata`].
+    pub fn with_metadata(mut self, metadata: Option<Metadata>) -> ReconstructedTokens {
+        self.metadata = metadata;
+        self
+    }
+}
+
+impl CopyForwardTokens for ReconstructedTokens {
+    fn segments(&self) -> Vec<Vec<TokenSegment>> {
+        self.inner.segments()
+    }
+
+    fn segments_for(&self, idx: usize) -> &[TokenSegment] {
+        self.inner.segments_for(idx)
+    }
+
+    fn message(&self, idx: usize) -> &[u32] {
+        self.inner.message(idx)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        self.metadata.clone()
+    }
+
+    fn render_with<F>(&self, replacer: F) -> Vec<Vec<u32>>
+    where
+        F: FnMut(usize, usize, usize, &[u32]) -> Vec<u32>,
+    {
+        self.inner.render_with(replacer)
+    }
+
+    fn render_with_dyn(&self, replacer: &mut DynTokenReplacer<'_>) -> Vec<Vec<u32>> {
+        self.inner.render_with(replacer)
+    }
+}
+
+/// `segments` with reference spans converted from bytes to characters of
+/// their source, as given by `source`.
+pub(crate) fn char_segments<'a>(
+    segments: &[Segment],
+    source: impl Fn(usize) -> &'a str,
+) -> Vec<Segment> {
+    // Byte offset of every character boundary, per source message.
+    let mut boundaries: HashMap<usize, Vec<usize>> = HashMap::new();
+    segments
+        .iter()
+        .map(|seg| match seg {
+            Segment::Literal(_) => seg.clone(),
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                let offsets = boundaries
+                    .entry(*message_idx)
+                    .or_insert_with(|| compute_offsets(source(*message_idx)));
+                let char_at = |byte: usize| offsets.partition_point(|&b| b < byte);
+                let char_start = char_at(*start);
+                Segment::Reference {
+                    message_idx: *message_idx,
+                    start: char_start,
+                    len: char_at(start + len) - char_start,
+                }
+            }
+        })
+        .collect()
+}
+
+/// Render messages expanding reference chains only `max_depth` levels deep.
+///
+/// Text a message copies directly from an earlier one is at depth 1; text
+/// that the earlier message itself copied is at depth 2, and so on. Content
+/// deeper than `max_depth` is replaced by `placeholder`, once per contiguous
+/// run, which turns deeply nested quote pyramids into readable digests.
+/// `max_depth = 0` replaces every reference.
+///
+/// # Example
+/// ```
+/// use copyforward::render::render_depth_limited;
+/// use copyforward::{exact, Config, CopyForward};
+///
+/// let messages = [
+///     "Can we ship on Friday?",
+///     "Yes, if QA signs off.\n> Can we ship on Friday?",
+///     "QA signed off.\n> Yes, if QA signs off.\n> Can we ship on Friday?",
+/// ];
+/// let config = Config { min_match_len: 8, ..Config::default() };
+/// let segments = exact(&messages, config).segments();
+/// let digest = render_depth_limited(&segments, 1, "[…earlier quote…]").unwrap();
+/// assert_eq!(digest[2], "QA signed off.\n> Yes, if QA signs off.\n> […earlier quote…]");
+/// ```
+pub fn render_depth_limited(
+    segments: &[Vec<Segment>],
+    max_depth: usize,
+    placeholder: &str,
+) -> Result<Vec<String>, ReconstructError> {
+    let texts = reconstruct(segments)?;
+    let renderer = DepthRenderer {
+        segments,
+        texts: &texts,
+        max_depth,
+        placeholder,
+    };
+    Ok((0..segments.len())
+        .map(|i| {
+            let mut out = DepthOutput::default();
+            renderer.span(i, 0, texts[i].len(), 0, &mut out);
+            out.text
+        })
+        .collect())
+}
+
+struct DepthRenderer<'a> {
+    segments: &'a [Vec<Segment>],
+    texts: &'a [String],
+    max_depth: usize,
+    placeholder: &'a str,
+}
+
+#[derive(Default)]
+struct DepthOutput {
+    text: String,
+    in_placeholder: bool,
+}
+
+impl DepthRenderer<'_> {
+    /// Append bytes `start..end` of message `message`, whose own literals are
+    /// at `depth`.
+    fn span(&self, message: usize, start: usize, end: usize, depth: usize, out: &mut DepthOutput) {
+        let mut pos = 0;
+        for seg in &self.segments[message] {
+            let seg_len = match seg {
+                Segment::Literal(s) => s.len(),
+                Segment::Reference { len, .. } => *len,
+            };
+            let (lo, hi) = (start.max(pos), end.min(pos + seg_len));
+            if lo < hi {
+                match seg {
+                    Segment::Literal(_) => {
+                        out.text.push_str(&self.texts[message][lo..hi]);
+                        out.in_placeholder = false;
+                    }
+                    Segment::Reference {
+                        message_idx,
+                        start: ref_start,
+                        ..
+                    } => {
+                        if depth < self.max_depth {
+                            let offset = ref_start + (lo - pos);
+                            self.span(*message_idx, offset, offset + (hi - lo), depth + 1, out);
+                        } else if !out.in_placeholder {
+                            out.text.push_str(self.placeholder);
+                            out.in_placeholder = true;
+                        }
+                    }
+                }
+            }
+            pos += seg_len;
+            if pos >= end {
+                break;
+            }
+        }
+    }
+}
+
+/// Where a byte of a rendered message came from; see [`locate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// Index of the segment of the queried message containing the byte.
+    pub segment: usize,
+    /// Message where the byte was first written as literal text, after
+    /// following references transitively.
+    pub source_message: usize,
+    /// Byte offset of the byte within `source_message`.
+    pub source_offset: usize,
+    /// Largest span around `source_offset` that was copied along the same
+    /// chain of references from one literal of `source_message`.
+    pub source_span: Range<usize>,
+}
+
+/// Map byte `offset` of message `message` back to the segment producing it
+/// and its ultimate source.
+///
+/// Offsets are into the original message text, which is what `render_with`
+/// produces with an identity replacer. Returns `None` when `offset` is out of
+/// bounds or the segments are inconsistent.
+///
+/// # Example
+/// ```
+/// use copyforward::render::locate;
+/// use copyforward::{exact, Config, CopyForward};
+///
+/// let messages = ["Hello world", "Hello world today", "> Hello world today"];
+/// let segments = exact(&messages, Config::default()).segments();
+/// let origin = locate(&segments, 2, 8).unwrap();
+/// assert_eq!(origin.segment, 1);
+/// assert_eq!((origin.source_message, origin.source_offset), (0, 6));
+/// assert_eq!(origin.source_span, 0..11);
+/// ```
+pub fn locate(segments: &[Vec<Segment>], message: usize, offset: usize) -> Option<Origin> {
+    locate_with(|idx| segments.get(idx).map(Vec::as_slice), message, offset)
+}
+
+/// [`locate`] over any per-message segment lookup.
+pub(crate) fn locate_with<'a>(
+    segments_for: impl Fn(usize) -> Option<&'a [Segment]>,
+    message: usize,
+    offset: usize,
+) -> Option<Origin> {
+    let mut first_segment = None;
+    let (mut msg, mut at) = (message, offset);
+    let mut window = 0..usize::MAX;
+    loop {
+        let mut pos = 0;
+        let (index, seg) = segments_for(msg)?.iter().enumerate().find(|(_, seg)| {
+            let len = match seg {
+                Segment::Literal(s) => s.len(),
+                Segment::Reference { len, .. } => *len,
+            };
+            pos += len;
+            at < pos
+        })?;
+        first_segment.get_or_insert(index);
+        match seg {
+            Segment::Literal(s) => {
+                let seg_start = pos - s.len();
+                return Some(Origin {
+                    segment: first_segment?,
+                    source_message: msg,
+                    source_offset: at,
+                    source_span: window.start.max(seg_start)..window.end.min(pos),
+                });
+            }
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                let seg_start = pos - len;
+                if *message_idx > msg || (*message_idx == msg && start + len > seg_start) {
+                    return None;
+                }
+                let (lo, hi) = (window.start.max(seg_start), window.end.min(pos));
+                window = start + (lo - seg_start)..start + (hi - seg_start);
+                at = start + (at - seg_start);
+                msg = *message_idx;
+            }
+        }
+    }
+}
+
+/// One occurrence of a query string; see
+/// [`CopyForward::find_occurrences`](crate::CopyForward::find_occurrences).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    /// Message containing the occurrence.
+    pub message: usize,
+    /// Byte span of the occurrence in the message.
+    pub span: Range<usize>,
+    /// The span lies inside one reference, so it quotes an earlier message
+    /// rather than saying it first.
+    pub quoted: bool,
+}
+
+/// Base of the rolling hash [`longest_common`] compares windows with.
+const SEARCH_BASE: u64 = 257;
+
+/// Occurrences of non-empty `query` in `text`, message `message` with
+/// segments `segments`. Occurrences may overlap.
+pub(crate) fn occurrences_in(
+    message: usize,
+    text: &str,
+    segments: &[Segment],
+    query: &str,
+) -> Vec<Occurrence> {
+    let len = query.len();
+    let mut segs = segments.iter().map(|seg| match seg {
+        Segment::Literal(s) => (s.len(), false),
+        Segment::Reference { len, .. } => (*len, true),
+    });
+    let (mut seg_end, mut seg_is_ref) = (0, false);
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(found) = text[from..].find(query) {
+        let start = from + found;
+        // Search again from the next character, so overlapping matches count.
+        from = start + text[start..].chars().next().map_or(1, char::len_utf8);
+        while seg_end <= start {
+            let Some((seg_len, is_ref)) = segs.next() else {
+                break;
+            };
+            seg_end += seg_len;
+            seg_is_ref = is_ref;
+        }
+        out.push(Occurrence {
+            message,
+            span: start..start + len,
+            quoted: seg_is_ref && start + len <= seg_end,
+        });
+    }
+    out
+}
+
+/// A longest span two messages share; see
+/// [`CopyForward::lcs`](crate::CopyForward::lcs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedSpan {
+    /// Byte span in the first message.
+    pub first: Range<usize>,
+    /// Byte span of the same text in the second message.
+    pub second: Range<usize>,
+}
+
+/// Every distinct longest common substring of `a` and `b`, each at its first
+/// occurrence in both, ordered by position in `a`.
+pub(crate) fn longest_common(a: &str, b: &str) -> Vec<SharedSpan> {
+    let (ca, cb) = (string_to_u32s(a), string_to_u32s(b));
+    let (ha, pa) = prefix_hashes_u32(&ca, SEARCH_BASE);
+    let (hb, pb) = prefix_hashes_u32(&cb, SEARCH_BASE);
+    let common = |len: usize| {
+        let mut starts: HashMap<u64, Vec<usize>> = HashMap::new();
+        for s in 0..=ca.len() - len {
+            starts
+                .entry(range_hash(&ha, &pa, s, s + len))
+                .or_default()
+                .push(s);
+        }
+        let mut found: HashMap<&[u32], (usize, usize)> = HashMap::new();
+        for t in 0..=cb.len() - len {
+            let window = &cb[t..t + len];
+            if found.contains_key(window) {
+                continue;
+            }
+            let candidates = starts.get(&range_hash(&hb, &pb, t, t + len));
+            if let Some(&s) = candidates
+                .into_iter()
+                .flatten()
+                .find(|&&s| &ca[s..s + len] == window)
+            {
+                found.insert(window, (s, t));
+            }
+        }
+        let mut pairs: Vec<(usize, usize)> = found.into_values().collect();
+        pairs.sort_unstable();
+        pairs
+    };
+    // A common substring of length n contains common substrings of every
+    // shorter length, so the longest one can be binary searched.
+    let (mut lo, mut hi) = (0, ca.len().min(cb.len()));
+    while lo < hi {
+        let mid = (lo + hi).div_ceil(2);
+        if common(mid).is_empty() {
+            hi = mid - 1;
+        } else {
+            lo = mid;
+        }
+    }
+    if lo == 0 {
+        return Vec::new();
+    }
+    let (oa, ob) = (compute_offsets(a), compute_offsets(b));
+    common(lo)
+        .into_iter()
+        .map(|(s, t)| SharedSpan {
+            first: oa[s]..oa[s + lo],
+            second: ob[t]..ob[t + lo],
+        })
+        .collect()
+}
+
+/// Deepest reference chain under bytes `range` of message `message`: 0 when
+/// the range is all literal text, one more than the deepest text a reference
+/// copies otherwise.
+///
+/// Returns `None` when `range` is out of bounds or the segments are
+/// inconsistent.
+///
+/// # Example
+/// ```
+/// use copyforward::render::chain_depth;
+/// use copyforward::{exact, Config, CopyForward};
+///
+/// let messages = ["Hello world", "Hello world today", "> Hello world today"];
+/// let segments = exact(&messages, Config::default()).segments();
+/// assert_eq!(chain_depth(&segments, 2, 0..2), Some(0));
+/// assert_eq!(chain_depth(&segments, 2, 2..19), Some(2));
+/// assert_eq!(chain_depth(&segments, 2, 13..19), Some(1));
+/// ```
+pub fn chain_depth(
+    segments: &[Vec<Segment>],
+    message: usize,
+    range: Range<usize>,
+) -> Option<usize> {
+    let mut deepest = 0;
+    let mut pending = vec![(message, range, 0)];
+    while let Some((msg, range, depth)) = pending.pop() {
+        deepest = deepest.max(depth);
+        let mut pos = 0;
+        for seg in segments.get(msg)? {
+            let len = match seg {
+                Segment::Literal(s) => s.len(),
+                Segment::Reference { len, .. } => *len,
+            };
+            let (lo, hi) = (range.start.max(pos), range.end.min(pos + len));
+            if lo < hi
+                && let Segment::Reference {
+                    message_idx, start, ..
+                } = seg
+            {
+                if *message_idx > msg || (*message_idx == msg && start + len > pos) {
+                    return None;
+                }
+                pending.push((*message_idx, start + lo - pos..start + hi - pos, depth + 1));
+            }
+            pos += len;
+            if pos >= range.end {
+                break;
+            }
+        }
+        if pos < range.end {
+            return None;
+        }
+    }
+    Some(deepest)
+}
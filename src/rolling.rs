@@ -0,0 +1,344 @@
+//! Copy-forward over unbounded streams, keeping only a window of history.
+//!
+//! [`RollingCopyForward`] retains the text, hashes and k-mer index of the last
+//! `window` messages. Each pushed message is segmented against that window
+//! with the exact algorithm, then enters the window itself while the oldest
+//! message leaves it, so memory stays bounded however long the stream runs.
+//! This suits log and chat firehoses whose full history can never be kept.
+//!
+//! Messages are numbered in push order; references use those global indices,
+//! so segments stay valid after their sources leave the window, as long as
+//! the consumer keeps the sources itself.
+//!
+//! A [`RollingSnapshot`] captures the window so a service can persist it (with
+//! the `serde` feature) and [`restore`](RollingCopyForward::restore) it at
+//! startup instead of replaying the stream.
+//!
+//! # Example
+//! ```
+//! use copyforward::rolling::RollingCopyForward;
+//! use copyforward::{Config, Segment};
+//!
+//! let mut stream = RollingCopyForward::new(2, Config::default());
+//! stream.push("disk /dev/sda1 is 91% full");
+//! let segments = stream.push("disk /dev/sda1 is 95% full");
+//! assert!(matches!(segments[0], Segment::Reference { message_idx: 0, .. }));
+//! assert_eq!(stream.window(), 0..2);
+//!
+//! stream.push("backup finished");
+//! stream.push("backup finished");
+//! assert_eq!(stream.window(), 2..4);
+//! assert_eq!(stream.message(0), None);
+//! ```
+
+use crate::core::{Config, Segment, TokenSegment};
+use crate::engine::{extend_full, indexable_windows, message_hashes, room, stop_limits};
+use crate::hashing::PrefixHashes;
+use crate::{compute_offsets, normalize};
+use ahash::AHashMap as HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Candidates examined per lookup, as in the batch exact engine.
+const MAX_CANDIDATES: usize = 64;
+
+/// Streaming copy-forward compressor over a sliding window of messages.
+///
+/// [`Config::lookback`] is ignored; the window takes its place.
+#[derive(Debug, Clone)]
+pub struct RollingCopyForward {
+    config: Config,
+    window: usize,
+    pushed: usize,
+    retained: VecDeque<Retained>,
+    /// k-mer hash to `(global message index, start)` of retained messages.
+    table: HashMap<u64, Vec<(usize, usize)>>,
+}
+
+#[derive(Debug, Clone)]
+struct Retained {
+    text: String,
+    offsets: Vec<usize>,
+    prefixes: Arc<PrefixHashes>,
+    kmers: Arc<[u64]>,
+    limits: Option<Vec<Vec<usize>>>,
+    /// Segments in Unicode scalar values, which decide what gets indexed.
+    segments: Vec<TokenSegment>,
+}
+
+/// Window of a [`RollingCopyForward`], enough to rebuild its index.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RollingSnapshot {
+    /// Window size the stream was created with.
+    pub window: usize,
+    /// Messages pushed so far, including those that left the window.
+    pub pushed: usize,
+    /// Configuration the stream was created with.
+    pub config: Config,
+    /// Retained messages, oldest first, with their segments in Unicode
+    /// scalar values.
+    pub messages: Vec<(String, Vec<TokenSegment>)>,
+}
+
+/// Error returned by [`RollingCopyForward::restore`] for inconsistent snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// More messages than the window holds or than were pushed.
+    TooManyMessages,
+    /// A message's segments do not cover exactly its text.
+    SegmentMismatch { message: usize },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::TooManyMessages => {
+                write!(f, "snapshot holds more messages than its window")
+            }
+            SnapshotError::SegmentMismatch { message } => {
+                write!(f, "segments of message {message} do not match its text")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl RollingCopyForward {
+    /// Create a compressor that references at most the last `window` messages.
+    pub fn new(window: usize, config: Config) -> Self {
+        RollingCopyForward {
+            config,
+            window,
+            pushed: 0,
+            retained: VecDeque::with_capacity(window + 1),
+            table: HashMap::new(),
+        }
+    }
+
+    /// Segment `message` against the window and add it to the window.
+    ///
+    /// References point at global message indices within
+    /// [`window`](RollingCopyForward::window) as it was before this call.
+    pub fn push(&mut self, message: &str) -> Vec<Segment> {
+        let codes = normalize::string_to_u32s(message);
+        let mut entry = self.prepare(message, &codes);
+        entry.segments = self.segment(&entry, &codes);
+        let segments = self.to_segments(&entry);
+        self.admit(entry);
+        segments
+    }
+
+    /// Capture the window for persistence.
+    pub fn snapshot(&self) -> RollingSnapshot {
+        RollingSnapshot {
+            window: self.window,
+            pushed: self.pushed,
+            config: self.config.clone(),
+            messages: self
+                .retained
+                .iter()
+                .map(|r| (r.text.clone(), r.segments.clone()))
+                .collect(),
+        }
+    }
+
+    /// Rebuild a stream from a [`snapshot`](RollingCopyForward::snapshot);
+    /// it continues exactly as the original would have.
+    ///
+    /// # Example
+    /// ```
+    /// use copyforward::rolling::RollingCopyForward;
+    /// use copyforward::Config;
+    ///
+    /// let mut stream = RollingCopyForward::new(10, Config::default());
+    /// stream.push("disk /dev/sda1 is 91% full");
+    /// let mut restored = RollingCopyForward::restore(stream.snapshot()).unwrap();
+    /// assert_eq!(
+    ///     restored.push("disk /dev/sda1 is 95% full"),
+    ///     stream.push("disk /dev/sda1 is 95% full")
+    /// );
+    /// ```
+    pub fn restore(snapshot: RollingSnapshot) -> Result<RollingCopyForward, SnapshotError> {
+        let retained = snapshot.messages.len();
+        if retained > snapshot.window || retained > snapshot.pushed {
+            return Err(SnapshotError::TooManyMessages);
+        }
+        let first = snapshot.pushed - retained;
+        let mut stream = RollingCopyForward::new(snapshot.window, snapshot.config);
+        stream.pushed = first;
+        for (i, (text, segments)) in snapshot.messages.into_iter().enumerate() {
+            let codes = normalize::string_to_u32s(&text);
+            let covered: usize = segments
+                .iter()
+                .map(|seg| match seg {
+                    TokenSegment::Literal(toks) => toks.len(),
+                    TokenSegment::Reference { len, .. } => *len,
+                })
+                .sum();
+            if covered != codes.len() {
+                return Err(SnapshotError::SegmentMismatch { message: first + i });
+            }
+            let mut entry = stream.prepare(&text, &codes);
+            entry.segments = segments;
+            stream.admit(entry);
+        }
+        Ok(stream)
+    }
+
+    /// Hashes and offsets of a message, before segmentation.
+    fn prepare(&self, message: &str, codes: &[u32]) -> Retained {
+        let codes = [codes.to_vec()];
+        let (prefixes, kmers) = message_hashes(&codes, &self.config);
+        let limits = stop_limits(&codes, &self.config);
+        Retained {
+            text: message.to_string(),
+            offsets: compute_offsets(message),
+            prefixes: Arc::clone(&prefixes[0]),
+            kmers: Arc::clone(&kmers[0]),
+            limits,
+            segments: Vec::new(),
+        }
+    }
+
+    /// Index a segmented message and evict the oldest one if the window is full.
+    fn admit(&mut self, entry: Retained) {
+        let idx = self.pushed;
+        let indexable = indexable_windows(
+            &entry.kmers,
+            &entry.limits,
+            0,
+            &entry.segments,
+            &self.config,
+        );
+        for (start, &h) in entry.kmers.iter().enumerate() {
+            if indexable[start] {
+                self.table.entry(h).or_default().push((idx, start));
+            }
+        }
+        self.retained.push_back(entry);
+        self.pushed += 1;
+
+        if self.retained.len() > self.window {
+            let old = self.pushed - self.retained.len();
+            let evicted = self.retained.pop_front().expect("window is not empty");
+            for h in evicted.kmers.iter() {
+                if let Some(bucket) = self.table.get_mut(h) {
+                    bucket.retain(|&(m, _)| m != old);
+                    if bucket.is_empty() {
+                        self.table.remove(h);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of messages pushed so far.
+    pub fn len(&self) -> usize {
+        self.pushed
+    }
+
+    /// Whether no message has been pushed yet.
+    pub fn is_empty(&self) -> bool {
+        self.pushed == 0
+    }
+
+    /// Global indices of the messages currently retained.
+    pub fn window(&self) -> Range<usize> {
+        self.pushed - self.retained.len()..self.pushed
+    }
+
+    /// Text of message `idx`, if it is still in the window.
+    pub fn message(&self, idx: usize) -> Option<&str> {
+        self.get(idx).map(|r| r.text.as_str())
+    }
+
+    fn get(&self, idx: usize) -> Option<&Retained> {
+        let first = self.pushed - self.retained.len();
+        idx.checked_sub(first).and_then(|i| self.retained.get(i))
+    }
+
+    /// Greedy exact segmentation of `codes` against the window, in Unicode
+    /// scalar values.
+    fn segment(&self, entry: &Retained, codes: &[u32]) -> Vec<TokenSegment> {
+        let k = self.config.min_match_len;
+        let mut segs = Vec::new();
+        let mut cursor = 0;
+        let mut literal_start = 0;
+        while cursor < codes.len() {
+            let mut best: Option<(usize, usize, usize)> = None;
+            if let Some(h) = entry.kmers.get(cursor)
+                && room(&entry.limits, 0, cursor) >= k
+                && let Some(cands) = self.table.get(h)
+            {
+                for &(midx, ref_start) in cands.iter().take(MAX_CANDIDATES) {
+                    let source = self.get(midx).expect("indexed messages are retained");
+                    let limit =
+                        room(&entry.limits, 0, cursor).min(room(&source.limits, 0, ref_start));
+                    let match_len = extend_full(
+                        &entry.prefixes,
+                        &source.prefixes,
+                        cursor,
+                        ref_start,
+                        k,
+                        limit,
+                    );
+                    if best.is_none_or(|(l, _, _)| match_len > l) {
+                        best = Some((match_len, midx, ref_start));
+                    }
+                }
+            }
+            match best {
+                Some((match_len, midx, ref_start)) => {
+                    if literal_start < cursor {
+                        segs.push(TokenSegment::Literal(codes[literal_start..cursor].to_vec()));
+                    }
+                    segs.push(TokenSegment::Reference {
+                        message_idx: midx,
+                        start: ref_start,
+                        len: match_len,
+                    });
+                    cursor += match_len;
+                    literal_start = cursor;
+                }
+                None => cursor += 1,
+            }
+        }
+        if literal_start < codes.len() {
+            segs.push(TokenSegment::Literal(codes[literal_start..].to_vec()));
+        }
+        segs
+    }
+
+    /// Byte-offset segments of `entry` from its scalar-value segments.
+    fn to_segments(&self, entry: &Retained) -> Vec<Segment> {
+        let mut pos = 0;
+        entry
+            .segments
+            .iter()
+            .map(|seg| match seg {
+                TokenSegment::Literal(toks) => {
+                    let text = &entry.text[entry.offsets[pos]..entry.offsets[pos + toks.len()]];
+                    pos += toks.len();
+                    Segment::Literal(text.into())
+                }
+                TokenSegment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => {
+                    pos += len;
+                    let offs = &self.get(*message_idx).expect("source is retained").offsets;
+                    Segment::Reference {
+                        message_idx: *message_idx,
+                        start: offs[*start],
+                        len: offs[start + len] - offs[*start],
+                    }
+                }
+            })
+            .collect()
+    }
+}
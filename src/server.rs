@@ -0,0 +1,174 @@
+//! JSON-over-HTTP service behind the `copyforward-server` binary (requires the
+//! `server` feature).
+//!
+//! Endpoints:
+//!
+//! - `POST /segment` with a [`SegmentRequest`] body returns a [`SegmentResponse`].
+//! - `POST /render` with a [`RenderRequest`] body returns a [`RenderResponse`].
+//! - `GET /health` returns `{"status":"ok"}`.
+//!
+//! Errors are returned as `{"error": "..."}` with a 4xx status. Segments use the
+//! serde representation of [`Segment`]:
+//!
+//! ```json
+//! [{"literal": "Hello world"}, {"reference": {"message_idx": 0, "start": 0, "len": 5}}]
+//! ```
+//!
+//! Request handling is independent of the transport: [`handle`] maps a method,
+//! path and body to a [`Response`], and [`serve`] wires it to `tiny_http`.
+
+use crate::core::{Config, CopyForward, Segment};
+use crate::metadata::Metadata;
+use crate::render::reconstruct;
+use crate::stats::Stats;
+use crate::{approximate, exact};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Body of `POST /segment`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentRequest {
+    /// Messages in thread order; `null` entries are treated as empty.
+    pub messages: Vec<Option<String>>,
+    /// Use [`approximate()`] instead of [`exact()`].
+    #[serde(default)]
+    pub approximate: bool,
+    /// Algorithm configuration; missing fields take their defaults.
+    #[serde(default)]
+    pub config: Config,
+}
+
+/// Response of `POST /segment`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentResponse {
+    pub segments: Vec<Vec<Segment>>,
+    pub stats: Stats,
+    /// Crate version, algorithm and config that produced `segments`.
+    pub metadata: Metadata,
+}
+
+/// Body of `POST /render`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderRequest {
+    pub segments: Vec<Vec<Segment>>,
+    /// Replace every reference with this string instead of the referenced text.
+    #[serde(default)]
+    pub replacement: Option<String>,
+}
+
+/// Response of `POST /render`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderResponse {
+    pub messages: Vec<String>,
+}
+
+/// A JSON response: HTTP status code and body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json<T: Serialize>(value: &T) -> Response {
+        Response {
+            status: 200,
+            body: serde_json::to_string(value).expect("response types serialize"),
+        }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Response {
+        Response {
+            status,
+            body: serde_json::json!({ "error": message.to_string() }).to_string(),
+        }
+    }
+}
+
+/// Handle one request.
+///
+/// # Example
+/// ```
+/// use copyforward::server::handle;
+///
+/// let resp = handle("POST", "/segment", r#"{"messages": ["Hello world", "Hello world today"]}"#);
+/// assert_eq!(resp.status, 200);
+/// ```
+pub fn handle(method: &str, path: &str, body: &str) -> Response {
+    match (method, path) {
+        ("POST", "/segment") => match serde_json::from_str::<SegmentRequest>(body) {
+            Ok(req) => Response::json(&segment(req)),
+            Err(e) => Response::error(400, e),
+        },
+        ("POST", "/render") => match serde_json::from_str::<RenderRequest>(body) {
+            Ok(req) => match render(req) {
+                Ok(resp) => Response::json(&resp),
+                Err(e) => Response::error(422, e),
+            },
+            Err(e) => Response::error(400, e),
+        },
+        ("GET", "/health") => Response::json(&serde_json::json!({ "status": "ok" })),
+        (_, "/segment" | "/render" | "/health") => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    }
+}
+
+fn segment(req: SegmentRequest) -> SegmentResponse {
+    let model: Box<dyn CopyForward> = if req.approximate {
+        Box::new(approximate(&req.messages, req.config))
+    } else {
+        Box::new(exact(&req.messages, req.config))
+    };
+    let segments = model.segments();
+    let stats = Stats::from_segments(&segments);
+    let metadata = model
+        .metadata()
+        .expect("built models record their metadata");
+    SegmentResponse {
+        segments,
+        stats,
+        metadata,
+    }
+}
+
+fn render(req: RenderRequest) -> Result<RenderResponse, crate::render::ReconstructError> {
+    let mut messages = reconstruct(&req.segments)?;
+    if let Some(replacement) = req.replacement {
+        messages = req
+            .segments
+            .iter()
+            .map(|segs| {
+                segs.iter()
+                    .map(|seg| match seg {
+                        Segment::Literal(s) => s.as_str(),
+                        Segment::Reference { .. } => replacement.as_str(),
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+    Ok(RenderResponse { messages })
+}
+
+/// Serve requests on `addr` (e.g. `127.0.0.1:8080`) until the process exits.
+pub fn serve(addr: &str) -> io::Result<()> {
+    let server = tiny_http::Server::http(addr).map_err(io::Error::other)?;
+    let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json")
+        .expect("static header is valid");
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let resp = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => {
+                let path = request.url().split('?').next().unwrap_or_default();
+                handle(request.method().as_str(), path, &body)
+            }
+            Err(e) => Response::error(400, e),
+        };
+        let http = tiny_http::Response::from_string(resp.body)
+            .with_status_code(resp.status)
+            .with_header(content_type.clone());
+        // A client that disconnected early does not stop the server.
+        let _ = request.respond(http);
+    }
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! Sharded duplicate detection for corpora too large for a single index.
+//!
+//! Two passes over a corpus split into ordered shards:
+//!
+//! 1. Each shard builds a [`KmerIndex`] recording, for every k-mer, the first
+//!    document (by global position) containing it. Shards are independent, so
+//!    this runs in parallel; [`KmerIndex::merge`] combines them and is
+//!    associative and commutative, so partial indices can be merged in any
+//!    order or tree shape.
+//! 2. Each shard is scanned against the merged index: a k-mer whose first
+//!    occurrence is in an earlier document marks its span as duplicated. This
+//!    also runs in parallel and finds duplication across shard boundaries.
+//!
+//! Documents are numbered globally in shard order, so the result matches
+//! processing the concatenated corpus in one piece.
+//!
+//! # Example
+//! ```
+//! use copyforward::shard::find_duplicates_sharded;
+//!
+//! let shard_a = ["the quick brown fox", "unrelated"];
+//! let shard_b = ["a quick brown fox!"];
+//! let spans = find_duplicates_sharded(&[&shard_a[..], &shard_b[..]], 8);
+//! assert_eq!(spans[1][0], vec![1.."a quick brown fox".len()]);
+//! ```
+
+use crate::hashing::{prefix_hashes_u32, range_hash};
+use crate::{MessageLike, compute_offsets, normalize};
+use ahash::AHashMap as HashMap;
+use std::ops::Range;
+
+fn kmer_hashes(text: &str, k: usize) -> Vec<u64> {
+    let codes = normalize::string_to_u32s(text);
+    if codes.len() < k {
+        return Vec::new();
+    }
+    let (h, p) = prefix_hashes_u32(&codes, 257);
+    (0..=codes.len() - k)
+        .map(|s| range_hash(&h, &p, s, s + k))
+        .collect()
+}
+
+/// First-occurrence index of k-mers over a range of globally numbered documents.
+#[derive(Debug, Clone, Default)]
+pub struct KmerIndex {
+    k: usize,
+    first_seen: HashMap<u64, usize>,
+}
+
+impl KmerIndex {
+    /// Index `documents`, numbering them from `first_doc` in the global order.
+    ///
+    /// `k` is the minimum duplicate length in characters.
+    pub fn build<M: MessageLike>(documents: &[M], first_doc: usize, k: usize) -> KmerIndex {
+        let k = k.max(1);
+        let mut first_seen: HashMap<u64, usize> = HashMap::new();
+        for (i, doc) in documents.iter().enumerate() {
+            for h in kmer_hashes(doc.as_message().unwrap_or(""), k) {
+                first_seen.entry(h).or_insert(first_doc + i);
+            }
+        }
+        KmerIndex { k, first_seen }
+    }
+
+    /// Fold `other` into this index, keeping the earliest document per k-mer.
+    ///
+    /// # Panics
+    /// If the indices were built with different `k`.
+    pub fn merge(&mut self, other: KmerIndex) {
+        assert_eq!(
+            self.k, other.k,
+            "cannot merge k-mer indices with different k"
+        );
+        for (h, doc) in other.first_seen {
+            self.first_seen
+                .entry(h)
+                .and_modify(|d| *d = (*d).min(doc))
+                .or_insert(doc);
+        }
+    }
+
+    /// Number of distinct k-mers indexed.
+    pub fn len(&self) -> usize {
+        self.first_seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.first_seen.is_empty()
+    }
+
+    /// Byte ranges of each document that already occur in an earlier document.
+    ///
+    /// `documents` are numbered from `first_doc`, as in [`KmerIndex::build`].
+    pub fn duplicate_spans<M: MessageLike>(
+        &self,
+        documents: &[M],
+        first_doc: usize,
+    ) -> Vec<Vec<Range<usize>>> {
+        let k = self.k;
+        documents
+            .iter()
+            .enumerate()
+            .map(|(i, doc)| {
+                let text = doc.as_message().unwrap_or("");
+                let offsets = compute_offsets(text);
+                let mut spans: Vec<Range<usize>> = Vec::new();
+                for (s, h) in kmer_hashes(text, k).iter().enumerate() {
+                    if self.first_seen.get(h).is_none_or(|&d| d >= first_doc + i) {
+                        continue;
+                    }
+                    match spans.last_mut() {
+                        Some(last) if s <= last.end => last.end = s + k,
+                        _ => spans.push(s..s + k),
+                    }
+                }
+                spans
+                    .into_iter()
+                    .map(|r| offsets[r.start]..offsets[r.end])
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Find duplicated byte ranges in a corpus split into ordered shards, using one
+/// thread per shard for both passes.
+///
+/// Returns `spans[shard][document]`.
+pub fn find_duplicates_sharded<M: MessageLike + Sync>(
+    shards: &[&[M]],
+    k: usize,
+) -> Vec<Vec<Vec<Range<usize>>>> {
+    let starts: Vec<usize> = shards
+        .iter()
+        .scan(0, |next, shard| {
+            let start = *next;
+            *next += shard.len();
+            Some(start)
+        })
+        .collect();
+
+    let indices: Vec<KmerIndex> = std::thread::scope(|scope| {
+        let handles: Vec<_> = shards
+            .iter()
+            .zip(&starts)
+            .map(|(shard, &start)| scope.spawn(move || KmerIndex::build(shard, start, k)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("index thread panicked"))
+            .collect()
+    });
+    let mut merged = KmerIndex {
+        k: k.max(1),
+        ..KmerIndex::default()
+    };
+    for index in indices {
+        merged.merge(index);
+    }
+
+    let merged = &merged;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = shards
+            .iter()
+            .zip(&starts)
+            .map(|(shard, &start)| scope.spawn(move || merged.duplicate_spans(shard, start)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("scan thread panicked"))
+            .collect()
+    })
+}
@@ -0,0 +1,178 @@
+//! Provenance of rendered output, in the spirit of JavaScript source maps.
+//!
+//! A [`SourceMap`] lists, for every rendered message, which output byte
+//! ranges came from which byte ranges of which original message. With the
+//! `serde` feature it serializes to a self-describing JSON document, so
+//! external viewers can show provenance without linking this crate:
+//!
+//! ```json
+//! {"version": 1, "messages": [
+//!   {"length": 11, "mappings": [
+//!     {"output": {"start": 0, "end": 11}, "source_message": 0,
+//!      "source": {"start": 0, "end": 11}, "reference": false}]}]}
+//! ```
+//!
+//! # Example
+//! ```
+//! use copyforward::sourcemap::render_with_source_map;
+//! use copyforward::{exact, Config};
+//!
+//! let compressed = exact(&["Hello world", "Hello world today"], Config::default());
+//! let (rendered, map) = render_with_source_map(&compressed, |m, _, _, _| format!("[#{m}]"));
+//! assert_eq!(rendered[1], "[#0] today");
+//! let first = &map.messages[1].mappings[0];
+//! assert_eq!((first.output.clone(), first.source_message, first.source.clone()), (0..4, 0, 0..11));
+//! ```
+
+use crate::core::{CopyForward, Segment};
+use std::ops::Range;
+
+/// Current [`SourceMap::version`].
+pub const SOURCE_MAP_VERSION: u32 = 1;
+
+/// Provenance of every rendered message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceMap {
+    /// Format version, currently [`SOURCE_MAP_VERSION`].
+    pub version: u32,
+    /// One entry per message, in input order.
+    pub messages: Vec<MessageMap>,
+}
+
+/// Provenance of one rendered message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MessageMap {
+    /// Length in bytes of the rendered message.
+    pub length: usize,
+    /// Contiguous, ordered mappings covering the whole rendered message.
+    pub mappings: Vec<Mapping>,
+}
+
+/// One output range and where it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mapping {
+    /// Byte range in the rendered message.
+    pub output: Range<usize>,
+    /// Message the range was taken from: the rendered message itself for
+    /// literals, the referenced message for references.
+    pub source_message: usize,
+    /// Byte range in `source_message`. For replaced references its length may
+    /// differ from `output`.
+    pub source: Range<usize>,
+    /// Whether the output was produced by a reference.
+    pub reference: bool,
+}
+
+impl SourceMap {
+    /// Source map of the identity rendering of `segments`.
+    pub fn from_segments(segments: &[Vec<Segment>]) -> SourceMap {
+        let messages = segments
+            .iter()
+            .enumerate()
+            .map(|(idx, segs)| {
+                let mut builder = MessageMapBuilder::default();
+                for seg in segs {
+                    match seg {
+                        Segment::Literal(s) => builder.literal(idx, s.len()),
+                        Segment::Reference {
+                            message_idx,
+                            start,
+                            len,
+                        } => builder.reference(*message_idx, *start..start + len, *len),
+                    }
+                }
+                builder.finish()
+            })
+            .collect();
+        SourceMap {
+            version: SOURCE_MAP_VERSION,
+            messages,
+        }
+    }
+}
+
+/// Render like [`CopyForward::render_with`] and record where each output range
+/// came from.
+pub fn render_with_source_map<C, F>(cf: &C, mut replacer: F) -> (Vec<String>, SourceMap)
+where
+    C: CopyForward + ?Sized,
+    F: FnMut(usize, usize, usize, &str) -> String,
+{
+    let mut rendered = Vec::with_capacity(cf.len());
+    let mut messages = Vec::with_capacity(cf.len());
+    for idx in 0..cf.len() {
+        let mut out = String::new();
+        let mut builder = MessageMapBuilder::default();
+        for seg in cf.segments_for(idx) {
+            match seg {
+                Segment::Literal(s) => {
+                    out.push_str(s);
+                    builder.literal(idx, s.len());
+                }
+                Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => {
+                    let text = &cf.message(*message_idx)[*start..start + len];
+                    let replaced = replacer(*message_idx, *start, *len, text);
+                    out.push_str(&replaced);
+                    builder.reference(*message_idx, *start..start + len, replaced.len());
+                }
+            }
+        }
+        rendered.push(out);
+        messages.push(builder.finish());
+    }
+    let map = SourceMap {
+        version: SOURCE_MAP_VERSION,
+        messages,
+    };
+    (rendered, map)
+}
+
+#[derive(Default)]
+struct MessageMapBuilder {
+    /// Output bytes so far.
+    output: usize,
+    /// Bytes of the rendered message's own text so far.
+    own: usize,
+    mappings: Vec<Mapping>,
+}
+
+impl MessageMapBuilder {
+    fn literal(&mut self, idx: usize, len: usize) {
+        self.push(idx, self.own..self.own + len, len, false);
+    }
+
+    fn reference(&mut self, source_message: usize, source: Range<usize>, output_len: usize) {
+        self.push(source_message, source, output_len, true);
+    }
+
+    fn push(
+        &mut self,
+        source_message: usize,
+        source: Range<usize>,
+        output_len: usize,
+        reference: bool,
+    ) {
+        self.own += source.len();
+        self.output += output_len;
+        self.mappings.push(Mapping {
+            output: self.output - output_len..self.output,
+            source_message,
+            source,
+            reference,
+        });
+    }
+
+    fn finish(self) -> MessageMap {
+        MessageMap {
+            length: self.output,
+            mappings: self.mappings,
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! SQLite storage backend for segment archives (requires the `sqlite` feature).
+//!
+//! Threads are stored as segments only; message text is rebuilt on load with
+//! [`crate::render::reconstruct`]. The schema is stable and meant to be
+//! queried directly:
+//!
+//! ```sql
+//! CREATE TABLE store_metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+//! CREATE TABLE threads (
+//!     id   INTEGER PRIMARY KEY,
+//!     name TEXT NOT NULL UNIQUE
+//! );
+//! CREATE TABLE thread_metadata (
+//!     thread_id INTEGER NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+//!     key       TEXT NOT NULL,
+//!     value     TEXT NOT NULL,
+//!     PRIMARY KEY (thread_id, key)
+//! );
+//! CREATE TABLE messages (
+//!     id        INTEGER PRIMARY KEY,
+//!     thread_id INTEGER NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+//!     position  INTEGER NOT NULL,       -- index within the thread
+//!     len       INTEGER NOT NULL,       -- rendered length in bytes
+//!     UNIQUE (thread_id, position)
+//! );
+//! CREATE TABLE segments (
+//!     message_id        INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+//!     seq               INTEGER NOT NULL, -- order within the message
+//!     literal           TEXT,             -- set for literal segments
+//!     source_message_id INTEGER REFERENCES messages(id), -- set for references
+//!     start             INTEGER,          -- byte offset into the source message
+//!     len               INTEGER,          -- byte length of the referenced span
+//!     PRIMARY KEY (message_id, seq)
+//! );
+//! CREATE INDEX segments_by_source ON segments(source_message_id);
+//! ```
+//!
+//! `store_metadata` records `schema_version`; `thread_metadata` is free-form
+//! (for example the algorithm and config used to build a thread).
+
+use crate::core::Segment;
+use crate::render::ReconstructError;
+use crate::stats::Stats;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use std::path::Path;
+
+/// Version of the schema documented in the module docs.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS store_metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS threads (
+    id   INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS thread_metadata (
+    thread_id INTEGER NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+    key       TEXT NOT NULL,
+    value     TEXT NOT NULL,
+    PRIMARY KEY (thread_id, key)
+);
+CREATE TABLE IF NOT EXISTS messages (
+    id        INTEGER PRIMARY KEY,
+    thread_id INTEGER NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+    position  INTEGER NOT NULL,
+    len       INTEGER NOT NULL,
+    UNIQUE (thread_id, position)
+);
+CREATE TABLE IF NOT EXISTS segments (
+    message_id        INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    seq               INTEGER NOT NULL,
+    literal           TEXT,
+    source_message_id INTEGER REFERENCES messages(id),
+    start             INTEGER,
+    len               INTEGER,
+    PRIMARY KEY (message_id, seq)
+);
+CREATE INDEX IF NOT EXISTS segments_by_source ON segments(source_message_id);
+";
+
+/// A SQLite database holding compressed threads.
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Open (or create) a database file and ensure the schema exists.
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteStore> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Open a private in-memory database.
+    pub fn open_in_memory() -> Result<SqliteStore> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<SqliteStore> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO store_metadata (key, value) VALUES ('schema_version', ?1)",
+            params![SCHEMA_VERSION.to_string()],
+        )?;
+        Ok(SqliteStore { conn })
+    }
+
+    /// Underlying connection, for ad-hoc queries against the documented schema.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Store a thread's segments under `name`, replacing any previous version.
+    ///
+    /// Returns the thread's row id.
+    pub fn save_thread(&mut self, name: &str, segments: &[Vec<Segment>]) -> Result<i64> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM threads WHERE name = ?1", params![name])?;
+        tx.execute("INSERT INTO threads (name) VALUES (?1)", params![name])?;
+        let thread_id = tx.last_insert_rowid();
+
+        let mut message_ids: Vec<i64> = Vec::with_capacity(segments.len());
+        {
+            let mut insert_message =
+                tx.prepare("INSERT INTO messages (thread_id, position, len) VALUES (?1, ?2, ?3)")?;
+            let mut insert_segment = tx.prepare(
+                "INSERT INTO segments (message_id, seq, literal, source_message_id, start, len)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (position, segs) in segments.iter().enumerate() {
+                let len = Stats::from_segments(std::slice::from_ref(segs)).original_bytes;
+                insert_message.execute(params![thread_id, position as i64, len as i64])?;
+                let message_id = tx.last_insert_rowid();
+                message_ids.push(message_id);
+                for (seq, seg) in segs.iter().enumerate() {
+                    match seg {
+                        Segment::Literal(text) => insert_segment.execute(params![
+                            message_id,
+                            seq as i64,
+                            text.as_str(),
+                            None::<i64>,
+                            None::<i64>,
+                            None::<i64>
+                        ])?,
+                        Segment::Reference {
+                            message_idx,
+                            start,
+                            len,
+                        } => {
+                            let source =
+                                message_ids.get(*message_idx).copied().ok_or_else(|| {
+                                    rusqlite::Error::ToSqlConversionFailure(Box::new(
+                                        ReconstructError::ForwardReference {
+                                            message: position,
+                                            segment: seq,
+                                        },
+                                    ))
+                                })?;
+                            insert_segment.execute(params![
+                                message_id,
+                                seq as i64,
+                                None::<&str>,
+                                source,
+                                *start as i64,
+                                *len as i64
+                            ])?
+                        }
+                    };
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(thread_id)
+    }
+
+    /// Delete a stored thread; returns whether it existed.
+    pub fn delete_thread(&mut self, name: &str) -> Result<bool> {
+        let n = self
+            .conn
+            .execute("DELETE FROM threads WHERE name = ?1", params![name])?;
+        Ok(n > 0)
+    }
+
+    /// Names of all stored threads, in insertion order.
+    pub fn thread_names(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT name FROM threads ORDER BY id")?;
+        stmt.query_map([], |row| row.get(0))?.collect()
+    }
+
+    /// Load a thread's segments, or `None` if no thread has that name.
+    pub fn load_segments(&self, name: &str) -> Result<Option<Vec<Vec<Segment>>>> {
+        let Some(thread_id) = self.thread_id(name)? else {
+            return Ok(None);
+        };
+        let mut stmt = self.conn.prepare(
+            "SELECT m.position, s.literal, src.position, s.start, s.len
+             FROM messages m
+             LEFT JOIN segments s ON s.message_id = m.id
+             LEFT JOIN messages src ON src.id = s.source_message_id
+             WHERE m.thread_id = ?1
+             ORDER BY m.position, s.seq",
+        )?;
+        let mut rows = stmt.query(params![thread_id])?;
+        let mut segments: Vec<Vec<Segment>> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let position: i64 = row.get(0)?;
+            while segments.len() <= position as usize {
+                segments.push(Vec::new());
+            }
+            let literal: Option<String> = row.get(1)?;
+            let source: Option<i64> = row.get(2)?;
+            let seg = match (literal, source) {
+                (Some(text), _) => Segment::Literal(text.into()),
+                (None, Some(source)) => Segment::Reference {
+                    message_idx: source as usize,
+                    start: row.get::<_, i64>(3)? as usize,
+                    len: row.get::<_, i64>(4)? as usize,
+                },
+                // Message without segments (empty text).
+                (None, None) => continue,
+            };
+            segments[position as usize].push(seg);
+        }
+        Ok(Some(segments))
+    }
+
+    /// Row id of the message at `position` within thread `name`.
+    pub fn message_id(&self, name: &str, position: usize) -> Result<Option<i64>> {
+        self.conn
+            .query_row(
+                "SELECT m.id FROM messages m JOIN threads t ON t.id = m.thread_id
+                 WHERE t.name = ?1 AND m.position = ?2",
+                params![name, position as i64],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    /// Row ids of all messages containing a reference into message `message_id`.
+    pub fn messages_referencing(&self, message_id: i64) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT message_id FROM segments
+             WHERE source_message_id = ?1 ORDER BY message_id",
+        )?;
+        stmt.query_map(params![message_id], |row| row.get(0))?
+            .collect()
+    }
+
+    /// Set a free-form metadata entry on a stored thread.
+    ///
+    /// Returns `false` if no thread has that name.
+    pub fn set_thread_metadata(&self, name: &str, key: &str, value: &str) -> Result<bool> {
+        let Some(thread_id) = self.thread_id(name)? else {
+            return Ok(false);
+        };
+        self.conn.execute(
+            "INSERT OR REPLACE INTO thread_metadata (thread_id, key, value) VALUES (?1, ?2, ?3)",
+            params![thread_id, key, value],
+        )?;
+        Ok(true)
+    }
+
+    /// All metadata entries of a stored thread, sorted by key.
+    pub fn thread_metadata(&self, name: &str) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT md.key, md.value FROM thread_metadata md
+             JOIN threads t ON t.id = md.thread_id
+             WHERE t.name = ?1 ORDER BY md.key",
+        )?;
+        stmt.query_map(params![name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
+    fn thread_id(&self, name: &str) -> Result<Option<i64>> {
+        self.conn
+            .query_row(
+                "SELECT id FROM threads WHERE name = ?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+}
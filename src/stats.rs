@@ -0,0 +1,82 @@
+//! Summary statistics over a compressed segmentation.
+
+use crate::core::Segment;
+use std::iter::Sum;
+use std::ops::AddAssign;
+
+/// Size summary of a segmentation, computed purely from its segments.
+///
+/// # Example
+/// ```
+/// use copyforward::{exact, Config, CopyForward, Stats};
+///
+/// let compressed = exact(&["Hello world", "Hello world today"], Config::default());
+/// let stats = Stats::from_segments(&compressed.segments());
+/// assert_eq!(stats.original_bytes, 28);
+/// assert!(stats.references >= 1);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    /// Number of messages.
+    pub messages: usize,
+    /// Total bytes of the rendered (original) messages.
+    pub original_bytes: usize,
+    /// Bytes stored as literal text.
+    pub literal_bytes: usize,
+    /// Number of reference segments.
+    pub references: usize,
+}
+
+impl Stats {
+    /// Compute statistics for a segmentation.
+    pub fn from_segments(segments: &[Vec<Segment>]) -> Stats {
+        let mut stats = Stats {
+            messages: segments.len(),
+            ..Stats::default()
+        };
+        for seg in segments.iter().flatten() {
+            match seg {
+                Segment::Literal(s) => {
+                    stats.original_bytes += s.len();
+                    stats.literal_bytes += s.len();
+                }
+                Segment::Reference { len, .. } => {
+                    stats.original_bytes += len;
+                    stats.references += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    /// Compressed size relative to the original, counting each reference as one unit.
+    ///
+    /// Returns 1.0 for empty input.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.original_bytes == 0 {
+            1.0
+        } else {
+            (self.literal_bytes + self.references) as f64 / self.original_bytes as f64
+        }
+    }
+}
+
+/// Accumulate statistics of several segmentations, e.g. across threads.
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.messages += other.messages;
+        self.original_bytes += other.original_bytes;
+        self.literal_bytes += other.literal_bytes;
+        self.references += other.references;
+    }
+}
+
+impl Sum for Stats {
+    fn sum<I: Iterator<Item = Stats>>(iter: I) -> Stats {
+        iter.fold(Stats::default(), |mut total, stats| {
+            total += stats;
+            total
+        })
+    }
+}
@@ -0,0 +1,192 @@
+//! Pluggable persistence of per-thread segmentations.
+//!
+//! [`SegmentStore`] is a minimal key-value interface: one entry per thread,
+//! written and read independently, so a service that keeps ingesting threads
+//! only touches the threads that changed. Implementations:
+//!
+//! - [`MemoryStore`]: in-process map, useful for tests and caches.
+//! - `SledStore` (feature `sled`): embedded on-disk store using [`crate::codec`].
+//! - `SqliteStore` (feature `sqlite`): see [`crate::sqlite`].
+
+use crate::codec::DecodeError;
+use crate::core::Segment;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Error returned by [`SegmentStore`] operations.
+#[derive(Debug)]
+pub enum StoreError {
+    /// A stored entry could not be decoded.
+    Decode(DecodeError),
+    /// The storage backend failed.
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Decode(e) => write!(f, "corrupt stored segments: {e}"),
+            StoreError::Backend(e) => write!(f, "storage backend error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Decode(e) => Some(e),
+            StoreError::Backend(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<DecodeError> for StoreError {
+    fn from(e: DecodeError) -> Self {
+        StoreError::Decode(e)
+    }
+}
+
+/// Key-value persistence of segmentations, keyed by thread id.
+pub trait SegmentStore {
+    /// Store a thread's segments, replacing any previous entry.
+    fn put(&mut self, thread_id: &str, segments: &[Vec<Segment>]) -> Result<(), StoreError>;
+
+    /// Load a thread's segments, or `None` if the thread is unknown.
+    fn get(&self, thread_id: &str) -> Result<Option<Vec<Vec<Segment>>>, StoreError>;
+
+    /// Delete a thread; returns whether it existed.
+    fn remove(&mut self, thread_id: &str) -> Result<bool, StoreError>;
+
+    /// Ids of all stored threads.
+    fn thread_ids(&self) -> Result<Vec<String>, StoreError>;
+}
+
+/// In-memory [`SegmentStore`] ordered by thread id.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    threads: BTreeMap<String, Vec<Vec<Segment>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SegmentStore for MemoryStore {
+    fn put(&mut self, thread_id: &str, segments: &[Vec<Segment>]) -> Result<(), StoreError> {
+        self.threads
+            .insert(thread_id.to_string(), segments.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, thread_id: &str) -> Result<Option<Vec<Vec<Segment>>>, StoreError> {
+        Ok(self.threads.get(thread_id).cloned())
+    }
+
+    fn remove(&mut self, thread_id: &str) -> Result<bool, StoreError> {
+        Ok(self.threads.remove(thread_id).is_some())
+    }
+
+    fn thread_ids(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self.threads.keys().cloned().collect())
+    }
+}
+
+#[cfg(feature = "sled")]
+mod sled_impl {
+    use super::{SegmentStore, StoreError};
+    use crate::codec::{decode_segments, encode_segments};
+    use crate::core::Segment;
+    use std::path::Path;
+
+    fn backend(e: sled::Error) -> StoreError {
+        StoreError::Backend(Box::new(e))
+    }
+
+    /// [`SegmentStore`] backed by a sled tree; values use the binary codec.
+    pub struct SledStore {
+        tree: sled::Tree,
+    }
+
+    impl SledStore {
+        /// Open (or create) a sled database at `path` and use its default tree.
+        pub fn open(path: impl AsRef<Path>) -> Result<SledStore, StoreError> {
+            let db = sled::open(path).map_err(backend)?;
+            Ok(SledStore {
+                tree: (*db).clone(),
+            })
+        }
+
+        /// Use an existing tree, e.g. one of several named trees in a shared database.
+        pub fn from_tree(tree: sled::Tree) -> SledStore {
+            SledStore { tree }
+        }
+
+        /// Flush pending writes to disk.
+        pub fn flush(&self) -> Result<(), StoreError> {
+            self.tree.flush().map(|_| ()).map_err(backend)
+        }
+    }
+
+    impl SegmentStore for SledStore {
+        fn put(&mut self, thread_id: &str, segments: &[Vec<Segment>]) -> Result<(), StoreError> {
+            self.tree
+                .insert(thread_id.as_bytes(), encode_segments(segments))
+                .map(|_| ())
+                .map_err(backend)
+        }
+
+        fn get(&self, thread_id: &str) -> Result<Option<Vec<Vec<Segment>>>, StoreError> {
+            match self.tree.get(thread_id.as_bytes()).map_err(backend)? {
+                Some(bytes) => Ok(Some(decode_segments(&bytes)?)),
+                None => Ok(None),
+            }
+        }
+
+        fn remove(&mut self, thread_id: &str) -> Result<bool, StoreError> {
+            self.tree
+                .remove(thread_id.as_bytes())
+                .map(|old| old.is_some())
+                .map_err(backend)
+        }
+
+        fn thread_ids(&self) -> Result<Vec<String>, StoreError> {
+            self.tree
+                .iter()
+                .keys()
+                .map(|k| {
+                    let k = k.map_err(backend)?;
+                    Ok(String::from_utf8_lossy(&k).into_owned())
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(feature = "sled")]
+pub use sled_impl::SledStore;
+
+#[cfg(feature = "sqlite")]
+impl SegmentStore for crate::sqlite::SqliteStore {
+    fn put(&mut self, thread_id: &str, segments: &[Vec<Segment>]) -> Result<(), StoreError> {
+        self.save_thread(thread_id, segments)
+            .map(|_| ())
+            .map_err(|e| StoreError::Backend(Box::new(e)))
+    }
+
+    fn get(&self, thread_id: &str) -> Result<Option<Vec<Vec<Segment>>>, StoreError> {
+        self.load_segments(thread_id)
+            .map_err(|e| StoreError::Backend(Box::new(e)))
+    }
+
+    fn remove(&mut self, thread_id: &str) -> Result<bool, StoreError> {
+        self.delete_thread(thread_id)
+            .map_err(|e| StoreError::Backend(Box::new(e)))
+    }
+
+    fn thread_ids(&self) -> Result<Vec<String>, StoreError> {
+        self.thread_names()
+            .map_err(|e| StoreError::Backend(Box::new(e)))
+    }
+}
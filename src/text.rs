@@ -0,0 +1,380 @@
+//! Byte-level model shared by the text wrappers [`Exact`](crate::Exact) and
+//! [`Approximate`](crate::Approximate).
+//!
+//! The engines segment Unicode scalar values of the non-`None` messages; the
+//! model maps their output back to byte offsets in the original indices once,
+//! at construction, so rendering never revisits the token core.
+
+// Appending needs a greedy engine; without one that support goes unused.
+#![cfg_attr(
+    not(any(feature = "exact", feature = "approximate")),
+    allow(dead_code, unused_imports)
+)]
+
+use crate::core::{Config, Segment, TokenSegment, walk_segments};
+use crate::engine::Resume;
+use crate::literal::LiteralPool;
+use crate::peephole::inline_references;
+use crate::{MessageLike, compute_offsets, normalize};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+pub(crate) struct TextModel {
+    /// Input messages; `None` entries are stored as empty strings.
+    originals: Vec<String>,
+    /// Byte-offset segments per message.
+    segments: Vec<Vec<Segment>>,
+    /// Index of each message the engines see, that is of each non-`None` one.
+    valid_indices: Vec<usize>,
+    /// Shared text of the literals in `segments`, kept to intern those of
+    /// pushed messages.
+    literals: LiteralPool,
+}
+
+/// Messages prepared for the token engines: the non-`None` messages as
+/// Unicode scalar values, plus what is needed to map segments back to bytes.
+pub(crate) struct TextInput {
+    /// Input messages; `None` entries are stored as empty strings.
+    pub(crate) originals: Vec<String>,
+    /// Byte offset of every character boundary, per message.
+    offsets: Vec<Vec<usize>>,
+    /// Index in `originals` of each message the engines see.
+    pub(crate) valid_indices: Vec<usize>,
+    /// Unicode scalar values of each message the engines see.
+    codes: Vec<Vec<u32>>,
+}
+
+impl TextInput {
+    pub(crate) fn new<M: MessageLike>(messages: &[M]) -> TextInput {
+        TextInput::owned(messages.iter().map(|m| m.as_message().map(str::to_string)))
+    }
+
+    /// Input taking ownership of `messages`, whose strings become the
+    /// originals without a copy.
+    pub(crate) fn owned(messages: impl IntoIterator<Item = Option<String>>) -> TextInput {
+        let mut valid_indices = Vec::new();
+        let originals: Vec<String> = messages
+            .into_iter()
+            .enumerate()
+            .map(|(i, message)| {
+                if message.is_some() {
+                    valid_indices.push(i);
+                }
+                message.unwrap_or_default()
+            })
+            .collect();
+        let offsets: Vec<Vec<usize>> = originals.iter().map(|s| compute_offsets(s)).collect();
+        let codes: Vec<Vec<u32>> = valid_indices
+            .iter()
+            .map(|&i| normalize::string_to_u32s(&originals[i]))
+            .collect();
+        TextInput {
+            originals,
+            offsets,
+            valid_indices,
+            codes,
+        }
+    }
+
+    /// The messages the engines see, owned.
+    #[cfg_attr(not(feature = "exact"), allow(dead_code))]
+    pub(crate) fn codes(&self) -> &[Vec<u32>] {
+        &self.codes
+    }
+
+    /// The messages the engines see.
+    #[cfg_attr(not(feature = "optimal"), allow(dead_code))]
+    pub(crate) fn tokens(&self) -> Vec<&[u32]> {
+        self.codes.iter().map(|v| v.as_slice()).collect()
+    }
+
+    /// Byte-offset segments of the `valid`-th message the engines see, from
+    /// its token segments, with literals interned in `literals` and
+    /// references saving fewer than `min_gain` bytes spelled out.
+    pub(crate) fn byte_segments(
+        &self,
+        valid: usize,
+        segs: &[TokenSegment],
+        min_gain: Option<usize>,
+        literals: &mut LiteralPool,
+    ) -> Vec<Segment> {
+        let i = self.valid_indices[valid];
+        let segments = byte_segments(
+            &self.originals[i],
+            i,
+            segs,
+            &self.offsets,
+            &self.valid_indices,
+            literals,
+        );
+        match min_gain {
+            Some(min_gain) => inline_references(i, &segments, min_gain, |m| &self.originals[m]),
+            None => segments,
+        }
+    }
+}
+
+/// Byte-offset segments of message `i`, whose text is `text`, from its token
+/// segments, given every message's character offsets and the index of each
+/// message the engines see. Literals are interned in `literals`.
+fn byte_segments(
+    text: &str,
+    i: usize,
+    segs: &[TokenSegment],
+    offsets: &[Vec<usize>],
+    valid_indices: &[usize],
+    literals: &mut LiteralPool,
+) -> Vec<Segment> {
+    let offs = &offsets[i];
+    let mut pos = 0;
+    segs.iter()
+        .map(|seg| match seg {
+            TokenSegment::Literal(toks) => {
+                let literal = &text[offs[pos]..offs[pos + toks.len()]];
+                pos += toks.len();
+                Segment::Literal(literals.intern(literal))
+            }
+            TokenSegment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                pos += len;
+                let source = valid_indices[*message_idx];
+                let ref_offs = &offsets[source];
+                Segment::Reference {
+                    message_idx: source,
+                    start: ref_offs[*start],
+                    len: ref_offs[start + len] - ref_offs[*start],
+                }
+            }
+        })
+        .collect()
+}
+
+/// Engine state kept by a model that messages are pushed onto, with the
+/// character offsets of every message. Kept from the model's build; clones
+/// and models whose build was cancelled start without it and rebuild it when
+/// pushed to.
+pub(crate) struct Resumed<E> {
+    state: Option<Box<(E, Vec<Vec<usize>>)>>,
+}
+
+impl<E> Default for Resumed<E> {
+    fn default() -> Self {
+        Resumed { state: None }
+    }
+}
+
+impl<E> Clone for Resumed<E> {
+    fn clone(&self) -> Self {
+        Resumed::default()
+    }
+}
+
+impl<E> fmt::Debug for Resumed<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resumed")
+            .field("built", &self.state.is_some())
+            .finish()
+    }
+}
+
+impl TextModel {
+    /// Segment `input` with `engine`, which sees only the non-`None`
+    /// messages as Unicode scalar values, then spell out references saving
+    /// fewer than `min_gain` bytes.
+    #[cfg_attr(not(feature = "optimal"), allow(dead_code))]
+    pub(crate) fn build<E>(input: TextInput, min_gain: Option<usize>, engine: E) -> TextModel
+    where
+        E: FnOnce(&[&[u32]]) -> Vec<Vec<TokenSegment>>,
+    {
+        let token_segs = engine(&input.tokens());
+        TextModel::assemble(input, &token_segs, min_gain).0
+    }
+
+    /// [`build`](TextModel::build) with the resumable engine `E`, which is
+    /// kept in the returned [`Resumed`] so that pushed messages are matched
+    /// against its index as built, not a rebuilt one.
+    pub(crate) fn build_resumable<E: Resume>(
+        mut input: TextInput,
+        config: &Config,
+    ) -> (TextModel, Resumed<E>) {
+        let (token_segs, engine) = E::run(std::mem::take(&mut input.codes), config);
+        let (model, offsets) = TextModel::assemble(input, &token_segs, config.min_encoded_gain);
+        let state = engine.map(|engine| Box::new((engine, offsets)));
+        (model, Resumed { state })
+    }
+
+    /// Model of `input` from the engines' `token_segs`, with the character
+    /// offsets of every message.
+    fn assemble(
+        input: TextInput,
+        token_segs: &[Vec<TokenSegment>],
+        min_gain: Option<usize>,
+    ) -> (TextModel, Vec<Vec<usize>>) {
+        let mut literals = LiteralPool::default();
+        let mut segments = vec![Vec::new(); input.originals.len()];
+        for (valid, segs) in token_segs.iter().enumerate() {
+            segments[input.valid_indices[valid]] =
+                input.byte_segments(valid, segs, min_gain, &mut literals);
+        }
+        let model = TextModel {
+            originals: input.originals,
+            segments,
+            valid_indices: input.valid_indices,
+            literals,
+        };
+        (model, input.offsets)
+    }
+
+    /// Model over already-segmented messages; `segments` must reproduce
+    /// `originals`.
+    pub(crate) fn from_parts(originals: Vec<String>, segments: Vec<Vec<Segment>>) -> TextModel {
+        TextModel {
+            valid_indices: (0..originals.len()).collect(),
+            originals,
+            segments,
+            literals: LiteralPool::default(),
+        }
+    }
+
+    /// Segment `message` against every earlier message with the engine kept
+    /// in `resumed`, as a run of that engine over the whole thread would, and
+    /// append it. Without a kept engine, as after a clone or a cancelled
+    /// build, the first push reruns the engine over the model's messages.
+    pub(crate) fn push<E: Resume>(
+        &mut self,
+        resumed: &mut Resumed<E>,
+        config: &Config,
+        message: &str,
+    ) -> &[Segment] {
+        let (engine, offsets) = &mut **resumed.state.get_or_insert_with(|| {
+            let codes = self
+                .valid_indices
+                .iter()
+                .map(|&i| normalize::string_to_u32s(&self.originals[i]))
+                .collect();
+            let offsets = self.originals.iter().map(|s| compute_offsets(s)).collect();
+            // The model's config has no progress callback to cancel the run.
+            let engine = E::run(codes, config).1.expect("uncancelled run");
+            Box::new((engine, offsets))
+        });
+        let token_segs = engine.push(normalize::string_to_u32s(message));
+        let idx = self.originals.len();
+        offsets.push(compute_offsets(message));
+        self.valid_indices.push(idx);
+        let mut segments = byte_segments(
+            message,
+            idx,
+            &token_segs,
+            offsets,
+            &self.valid_indices,
+            &mut self.literals,
+        );
+        self.originals.push(message.to_string());
+        if let Some(min_gain) = config.min_encoded_gain {
+            segments = inline_references(idx, &segments, min_gain, |m| &self.originals[m]);
+        }
+        self.segments.push(segments);
+        &self.segments[idx]
+    }
+
+    pub(crate) fn segments(&self) -> Vec<Vec<Segment>> {
+        self.segments.clone()
+    }
+
+    pub(crate) fn segments_for(&self, idx: usize) -> &[Segment] {
+        &self.segments[idx]
+    }
+
+    pub(crate) fn message(&self, idx: usize) -> &str {
+        &self.originals[idx]
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.originals.len()
+    }
+
+    pub(crate) fn render_into<F>(&self, idx: usize, buf: &mut String, mut replacer: F)
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        let Ok(()) = walk_segments(
+            idx,
+            &self.segments[idx],
+            |m| self.originals[m].as_str(),
+            buf,
+            |r| Ok::<_, Infallible>(replacer(r.message_idx, r.start, r.len, r.text)),
+        );
+    }
+
+    pub(crate) fn render_all_into<F>(&self, buf: &mut String, mut replacer: F) -> Vec<Range<usize>>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        (0..self.segments.len())
+            .map(|idx| {
+                let start = buf.len();
+                self.render_into(idx, buf, &mut replacer);
+                start..buf.len()
+            })
+            .collect()
+    }
+
+    pub(crate) fn render_with<F>(&self, mut replacer: F) -> Vec<String>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        (0..self.segments.len())
+            .map(|idx| {
+                let mut s = String::new();
+                self.render_into(idx, &mut s, &mut replacer);
+                s
+            })
+            .collect()
+    }
+
+    pub(crate) fn render_cow<'a, F>(&'a self, mut replacer: F) -> Vec<Cow<'a, str>>
+    where
+        F: FnMut(usize, usize, usize, &'a str) -> Cow<'a, str>,
+    {
+        self.segments
+            .iter()
+            .enumerate()
+            .map(|(idx, segs)| {
+                let mut replaced: Vec<(Cow<'a, str>, bool)> = Vec::new();
+                for seg in segs {
+                    if let Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } = seg
+                    {
+                        let text = &self.originals[*message_idx][*start..start + len];
+                        let out = replacer(*message_idx, *start, *len, text);
+                        let unchanged = matches!(&out, Cow::Borrowed(b) if std::ptr::eq(*b, text));
+                        replaced.push((out, unchanged));
+                    }
+                }
+                if replaced.iter().all(|(_, unchanged)| *unchanged) {
+                    return Cow::Borrowed(self.originals[idx].as_str());
+                }
+                let mut out = String::new();
+                let mut replaced = replaced.into_iter();
+                for seg in segs {
+                    match seg {
+                        Segment::Literal(s) => out.push_str(s),
+                        Segment::Reference { .. } => {
+                            out.push_str(&replaced.next().expect("one replacement per reference").0)
+                        }
+                    }
+                }
+                Cow::Owned(out)
+            })
+            .collect()
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Minimal tokenizer trait for Python auto-tokenization.
 /// Implementations should provide deterministic encode/decode within an instance.
@@ -64,14 +65,61 @@ impl Tokenize for WhitespaceTokenizer {
     }
 }
 
+/// A lossless tokenizer splitting on Unicode word boundaries (UAX #29).
+/// - Words, punctuation and whitespace runs each become tokens, so text without
+///   spaces (CJK, mixed-script) is still split sensibly: one token per ideograph.
+/// - Assigns incremental u32 IDs to new tokens as encountered.
+/// - Decodes by concatenating tokens, reproducing the original text exactly.
+pub struct UnicodeWordTokenizer {
+    vocab: HashMap<String, u32>,
+    rev: Vec<String>,
+}
+
+impl UnicodeWordTokenizer {
+    pub fn new() -> Self {
+        Self {
+            vocab: HashMap::new(),
+            rev: Vec::new(),
+        }
+    }
+    fn id_for(&mut self, token: &str) -> u32 {
+        if let Some(&id) = self.vocab.get(token) {
+            return id;
+        }
+        let id = self.rev.len() as u32;
+        self.vocab.insert(token.to_string(), id);
+        self.rev.push(token.to_string());
+        id
+    }
+}
+
+impl Default for UnicodeWordTokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tokenize for UnicodeWordTokenizer {
+    fn encode(&mut self, text: &str) -> Vec<u32> {
+        text.split_word_bounds().map(|t| self.id_for(t)).collect()
+    }
+    fn decode(&self, ids: &[u32]) -> String {
+        ids.iter()
+            .map(|&id| self.rev.get(id as usize).map_or("<UNK>", String::as_str))
+            .collect()
+    }
+}
+
 /// Get a tokenizer by name.
 /// Currently supports:
 /// - "whitespace": simple whitespace tokenizer.
+/// - "unicode": lossless Unicode word-boundary tokenizer.
 ///
 /// Future: "hf:<model>" via the `tokenizers` feature.
 pub fn get_tokenizer(name: &str) -> Result<Box<dyn Tokenize + Send>, String> {
     match name {
         "whitespace" => Ok(Box::new(WhitespaceTokenizer::new())),
+        "unicode" => Ok(Box::new(UnicodeWordTokenizer::new())),
         _ if name.starts_with("hf:") => {
             let model = &name[3..];
             get_hf_tokenizer(model)
@@ -81,7 +129,7 @@ pub fn get_tokenizer(name: &str) -> Result<Box<dyn Tokenize + Send>, String> {
             get_file_tokenizer(path)
         }
         _ => Err(format!(
-            "unknown tokenizer '{}'. Available: 'whitespace', 'hf:<model>' (with feature), 'file:<path>' (with feature)",
+            "unknown tokenizer '{}'. Available: 'whitespace', 'unicode', 'hf:<model>' (with feature), 'file:<path>' (with feature)",
             name
         )),
     }
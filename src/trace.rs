@@ -0,0 +1,231 @@
+//! Decision traces explaining why the engines emitted what they did.
+//!
+//! Tuning [`Config`] heuristics means knowing which sources were found at a
+//! position, how far each matched, whether the candidate cap cut the search
+//! short and whether [`Config::shallow_preference`] or [`Config::tie_break`]
+//! overruled the first longest match. Set [`Config::trace`] to a
+//! [`Trace`], keep a clone, and read its [`Decision`]s once the model is
+//! built.
+//!
+//! The exact and approximate engines record one decision per reference,
+//! duplicate or literal they emit in their matching pass; the optimal engine
+//! records none. Later passes ([`Config::reextend`] and merging adjacent
+//! references) may still reshape the final segments. Message indices and
+//! positions are those the engine sees: for text input they count only the
+//! non-`None` messages and measure positions in Unicode scalar values, for
+//! token input they are token indices.
+//!
+//! # Example
+//! ```
+//! use copyforward::trace::Outcome;
+//! use copyforward::{exact, Config, Trace};
+//!
+//! let trace = Trace::new();
+//! let config = Config {
+//!     trace: Some(trace.clone()),
+//!     ..Config::default()
+//! };
+//! exact(&["Hello world", "> Hello world\nHi!"], config);
+//!
+//! let quote = trace
+//!     .decisions()
+//!     .into_iter()
+//!     .find(|d| matches!(d.outcome, Outcome::Reference { .. }))
+//!     .unwrap();
+//! assert_eq!((quote.message, quote.position), (1, 2));
+//! assert_eq!(quote.candidates[0].len, Some(11));
+//! ```
+
+#[cfg_attr(
+    not(any(feature = "exact", feature = "approximate")),
+    allow(unused_imports)
+)]
+use crate::core::Config;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Shared collector of [`Decision`]s, set as [`Config::trace`].
+///
+/// Clones share the same record, so a clone kept by the caller sees what the
+/// run recorded. Runs sharing a trace append to it; see
+/// [`clear`](Trace::clear).
+#[derive(Clone, Default)]
+pub struct Trace {
+    decisions: Arc<Mutex<Vec<Decision>>>,
+}
+
+impl Trace {
+    /// An empty trace.
+    pub fn new() -> Trace {
+        Trace::default()
+    }
+
+    /// Every decision recorded so far, in the order they were made.
+    pub fn decisions(&self) -> Vec<Decision> {
+        self.lock().clone()
+    }
+
+    /// Decisions recorded for engine message `message`.
+    pub fn decisions_for(&self, message: usize) -> Vec<Decision> {
+        self.lock()
+            .iter()
+            .filter(|d| d.message == message)
+            .cloned()
+            .collect()
+    }
+
+    /// Forget all recorded decisions.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Record the lookup at `position` of `message` and what came of it.
+    ///
+    /// [`Decision::tie_break`] is derived here: it is set when the chosen
+    /// source is not the first of the longest candidates, which is what a
+    /// plain longest-match rule would have picked.
+    #[cfg_attr(not(any(feature = "exact", feature = "approximate")), allow(dead_code))]
+    pub(crate) fn record(
+        &self,
+        message: usize,
+        position: usize,
+        candidates: Vec<Candidate>,
+        cap_hit: bool,
+        outcome: Outcome,
+    ) {
+        let tie_break = match outcome {
+            Outcome::Reference { source, start, .. } => {
+                let longest = candidates
+                    .iter()
+                    .filter(|c| c.len.is_some())
+                    .rev()
+                    .max_by_key(|c| c.len);
+                longest.is_some_and(|c| (c.message, c.start) != (source, start))
+            }
+            _ => false,
+        };
+        self.lock().push(Decision {
+            message,
+            position,
+            candidates,
+            cap_hit,
+            tie_break,
+            outcome,
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Decision>> {
+        self.decisions.lock().expect("trace lock")
+    }
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trace")
+            .field("decisions", &self.lock().len())
+            .finish()
+    }
+}
+
+/// One matching decision; see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    /// Engine index of the message being segmented.
+    pub message: usize,
+    /// Position in that message where the lookup started.
+    pub position: usize,
+    /// Sources examined, in the order the engine looked at them.
+    pub candidates: Vec<Candidate>,
+    /// Whether the candidate cap (64 for exact, [`Config::ncap`] for
+    /// approximate) stopped the search before every source was examined.
+    pub cap_hit: bool,
+    /// Whether [`Config::shallow_preference`] or [`Config::tie_break`] chose
+    /// a source other than the first of the longest candidates.
+    pub tie_break: bool,
+    /// What was emitted.
+    pub outcome: Outcome,
+}
+
+/// A source examined for a match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    /// Engine index of the source message.
+    pub message: usize,
+    /// Position of the match in the source.
+    pub start: usize,
+    /// Length it matched for, or `None` when the approximate engine rejected
+    /// it because its [`Config::cap_len`] window differs. Approximate lengths
+    /// stop at `cap_len`; only the winner is extended further.
+    pub len: Option<usize>,
+}
+
+/// What a [`Decision`] emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The whole message repeats an earlier one, found by
+    /// [`Config::dedup_messages`] without a search.
+    Duplicate {
+        /// Engine index of the repeated message.
+        source: usize,
+    },
+    /// A match against `source` from `start`, `len` units long.
+    Reference {
+        /// Engine index of the source message.
+        source: usize,
+        /// Position of the match in the source.
+        start: usize,
+        /// Full length of the match.
+        len: usize,
+        /// Whether [`Config::max_ref_depth`] split the match along its
+        /// source's segments instead of emitting one reference.
+        depth_bounded: bool,
+    },
+    /// No match; `len` units were emitted as a literal.
+    Literal {
+        /// Length of the literal.
+        len: usize,
+    },
+}
+
+/// Where an engine collects one lookup's candidates, only while
+/// [`Config::trace`] is set.
+#[cfg(any(feature = "exact", feature = "approximate"))]
+pub(crate) struct Lookup<'a> {
+    trace: Option<&'a Trace>,
+    candidates: Vec<Candidate>,
+    cap_hit: bool,
+}
+
+#[cfg(any(feature = "exact", feature = "approximate"))]
+impl<'a> Lookup<'a> {
+    pub(crate) fn new(config: &'a Config) -> Lookup<'a> {
+        Lookup {
+            trace: config.trace.as_ref(),
+            candidates: Vec::new(),
+            cap_hit: false,
+        }
+    }
+
+    /// Note a source examined at the current position.
+    pub(crate) fn candidate(&mut self, message: usize, start: usize, len: Option<usize>) {
+        if self.trace.is_some() {
+            self.candidates.push(Candidate {
+                message,
+                start,
+                len,
+            });
+        }
+    }
+
+    /// Note that the candidate cap ended the search.
+    pub(crate) fn cap_hit(&mut self) {
+        self.cap_hit = true;
+    }
+
+    /// Record the lookup at `position` of `message` with its outcome.
+    pub(crate) fn finish(self, message: usize, position: usize, outcome: Outcome) {
+        if let Some(trace) = self.trace {
+            trace.record(message, position, self.candidates, self.cap_hit, outcome);
+        }
+    }
+}
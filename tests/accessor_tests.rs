@@ -0,0 +1,249 @@
+use copyforward::{
+    Config, CopyForward, CopyForwardError, CopyForwardTokens, Segment, SegmentRef, approximate,
+    approximate_tokens, exact, exact_tokens,
+};
+
+#[test]
+fn test_text_accessors_match_segments() {
+    let messages = [Some("Hello world"), None, Some("Hello world again")];
+    let models: Vec<Box<dyn CopyForward>> = vec![
+        Box::new(exact(&messages, Config::default())),
+        Box::new(approximate(&messages, Config::default())),
+    ];
+    for cf in models {
+        assert_eq!(cf.len(), 3);
+        assert!(!cf.is_empty());
+        let all = cf.segments();
+        for (idx, message) in messages.iter().enumerate() {
+            assert_eq!(cf.segments_for(idx), all[idx].as_slice());
+            assert_eq!(cf.message(idx), message.unwrap_or(""));
+        }
+    }
+}
+
+#[test]
+fn test_empty_model_is_empty() {
+    let cf = exact(&[] as &[&str], Config::default());
+    assert_eq!(cf.len(), 0);
+    assert!(cf.is_empty());
+}
+
+#[test]
+fn test_token_accessors_skip_none() {
+    let tokens = [
+        Some(vec![1, 2, 3, 4, 5]),
+        None,
+        Some(vec![0, 1, 2, 3, 4, 5]),
+    ];
+    let models: Vec<Box<dyn CopyForwardTokens>> = vec![
+        Box::new(exact_tokens(&tokens, Config::default())),
+        Box::new(approximate_tokens(&tokens, Config::default())),
+    ];
+    for cf in models {
+        assert_eq!(cf.len(), 2);
+        assert_eq!(cf.message(1), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(cf.segments_for(1), cf.segments()[1].as_slice());
+    }
+}
+
+#[test]
+fn test_slice_segments_render_the_requested_range() {
+    let messages = [
+        "naïve café au lait",
+        "Order: naïve café au lait, s'il vous plaît",
+    ];
+    let cf = exact(&messages, Config::default());
+    let text = cf.message(1);
+    let bounds: Vec<usize> = (0..=text.len())
+        .filter(|&i| text.is_char_boundary(i))
+        .collect();
+    for &start in &bounds {
+        for &end in bounds.iter().filter(|&&e| e >= start) {
+            let rendered: String = cf
+                .slice_segments(1, start..end)
+                .iter()
+                .map(|seg| match seg {
+                    Segment::Literal(s) => s.as_str(),
+                    Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => &messages[*message_idx][*start..start + len],
+                })
+                .collect();
+            assert_eq!(rendered, &text[start..end]);
+        }
+    }
+    assert!(cf.slice_segments(1, 3..3).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "not a valid slice")]
+fn test_slice_segments_rejects_split_characters() {
+    let cf = exact(&["naïve"], Config::default());
+    cf.slice_segments(0, 0..3);
+}
+
+#[test]
+fn test_try_slice_segments_reports_invalid_ranges() {
+    let cf = exact(&["naïve"], Config::default());
+    assert_eq!(
+        cf.try_slice_segments(0, 0..3),
+        Err(CopyForwardError::NotCharBoundary {
+            message: 0,
+            offset: 3
+        })
+    );
+    assert_eq!(
+        cf.try_slice_segments(0, 2..9),
+        Err(CopyForwardError::OffsetOverflow {
+            message: 0,
+            offset: 9
+        })
+    );
+    assert_eq!(
+        cf.try_slice_segments(0, std::ops::Range { start: 4, end: 1 }),
+        Err(CopyForwardError::OffsetOverflow {
+            message: 0,
+            offset: 4
+        })
+    );
+    assert_eq!(
+        cf.try_slice_segments(0, 0..2),
+        Ok(vec![Segment::Literal("na".into())])
+    );
+}
+
+#[test]
+fn test_short_literals_are_stored_inline() {
+    let messages = [
+        "The deploy is blocked on review.",
+        "Ok, so the deploy is blocked on review.",
+    ];
+    let segments = exact(&messages, Config::default()).segments();
+    let short: Vec<_> = segments
+        .iter()
+        .flatten()
+        .filter_map(|seg| match seg {
+            Segment::Literal(s) if s.len() <= 8 => Some(s),
+            _ => None,
+        })
+        .collect();
+    assert!(!short.is_empty());
+    assert!(short.iter().all(|s| !s.is_heap_allocated()));
+}
+
+#[test]
+fn test_repeated_long_literals_share_storage() {
+    let paragraphs: Vec<String> = (0..6)
+        .map(|p| (0..16).map(|w| format!("p{p}w{w} ")).collect())
+        .collect();
+    let separator = "| forwarded from the planning channel |";
+    let mut messages = vec![paragraphs.join("\n")];
+    for pair in paragraphs.chunks(2) {
+        messages.push(format!("{}{separator}{}", pair[0], pair[1]));
+    }
+    let config = Config {
+        min_match_len: 64,
+        ..Config::default()
+    };
+    let segments = exact(&messages, config).segments();
+    let separators: Vec<_> = segments
+        .iter()
+        .flatten()
+        .filter_map(|seg| match seg {
+            Segment::Literal(s) if *s == separator => Some(s),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(separators.len(), 3);
+    assert!(separators.iter().all(|s| s.is_shared()));
+    assert!(
+        separators
+            .iter()
+            .all(|s| s.as_ptr() == separators[0].as_ptr())
+    );
+}
+
+#[test]
+fn test_char_segments_index_characters() {
+    let messages = [
+        "naïve café — olé, déjà vu 🎉",
+        "> naïve café — olé, déjà vu 🎉\nSí, señor 🎉",
+        "> Sí, señor 🎉 and naïve café — olé",
+    ];
+    let cf = exact(&messages, Config::default());
+    for idx in 0..messages.len() {
+        let bytes = cf.segments_for(idx);
+        let chars = cf.char_segments_for(idx);
+        assert_eq!(bytes.len(), chars.len());
+        for (b, c) in bytes.iter().zip(&chars) {
+            match (b, c) {
+                (
+                    Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    },
+                    Segment::Reference {
+                        message_idx: source,
+                        start: char_start,
+                        len: char_len,
+                    },
+                ) => {
+                    assert_eq!(message_idx, source);
+                    let text = messages[*message_idx];
+                    let expected: String = text.chars().skip(*char_start).take(*char_len).collect();
+                    assert_eq!(expected, text[*start..start + len]);
+                }
+                (Segment::Literal(a), Segment::Literal(b)) => assert_eq!(a, b),
+                _ => panic!("segment kinds differ: {b:?} vs {c:?}"),
+            }
+        }
+    }
+    assert!(
+        cf.segments_for(2)
+            .iter()
+            .any(|s| matches!(s, Segment::Reference { .. }))
+    );
+}
+
+#[test]
+fn test_segment_refs_borrow_from_model() {
+    let messages = [
+        "Hello world, see you soon",
+        "> Hello world, see you soon\nBye",
+    ];
+    let cf = exact(&messages, Config::default());
+    for (idx, message) in messages.iter().enumerate() {
+        let text: String = cf.segment_refs(idx).map(|seg| seg.text()).collect();
+        assert_eq!(text, *message);
+        for (seg, owned) in cf.segment_refs(idx).zip(cf.segments_for(idx)) {
+            match (seg, owned) {
+                (SegmentRef::Literal(lit), Segment::Literal(stored)) => {
+                    assert_eq!(lit.as_ptr(), stored.as_ptr());
+                }
+                (
+                    SegmentRef::Reference {
+                        message_idx,
+                        start,
+                        len,
+                        text,
+                    },
+                    Segment::Reference { .. },
+                ) => {
+                    assert_eq!(
+                        *owned,
+                        Segment::Reference {
+                            message_idx,
+                            start,
+                            len
+                        }
+                    );
+                    assert_eq!(text.as_ptr(), cf.message(message_idx)[start..].as_ptr());
+                }
+                other => panic!("mismatched segment kinds: {other:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use copyforward::archive::Archive;
+use copyforward::codec::DecodeError;
+use copyforward::fixture::generate_thread;
+use copyforward::render::reconstruct;
+use copyforward::{Config, CopyForward, Segment, approximate, exact};
+
+#[test]
+fn test_message_matches_full_decode() {
+    for seed in 0..4 {
+        let thread = generate_thread(seed, 30, 5);
+        for segments in [
+            exact(&thread, Config::default()).segments(),
+            approximate(&thread, Config::default()).segments(),
+        ] {
+            let archive = Archive::from_bytes(Archive::new(&segments).into_bytes()).unwrap();
+            assert_eq!(archive.len(), thread.len());
+            assert_eq!(archive.segments().unwrap(), segments);
+            let full = reconstruct(&archive.segments().unwrap()).unwrap();
+            for (i, text) in thread.iter().enumerate() {
+                assert_eq!(&archive.message(i).unwrap(), text);
+                assert_eq!(full[i], *text);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_message_only_decodes_its_reference_chain() {
+    let segments = vec![
+        vec![Segment::Literal("alpha".into())],
+        vec![Segment::Literal("unrelated".into())],
+        vec![
+            Segment::Reference {
+                message_idx: 0,
+                start: 0,
+                len: 5,
+            },
+            Segment::Literal("!".into()),
+        ],
+    ];
+    let mut bytes = Archive::new(&segments).into_bytes();
+    let at = bytes.windows(9).position(|w| w == b"unrelated").unwrap();
+    bytes[at - 2] = 7;
+    let archive = Archive::from_bytes(bytes).unwrap();
+    assert_eq!(archive.message(2).unwrap(), "alpha!");
+    assert_eq!(archive.message(1), Err(DecodeError::InvalidTag(7)));
+    assert_eq!(archive.segments(), Err(DecodeError::InvalidTag(7)));
+}
+
+#[test]
+fn test_empty_archive_round_trips() {
+    let archive = Archive::from_bytes(Archive::new(&[]).into_bytes()).unwrap();
+    assert!(archive.is_empty());
+    assert_eq!(archive.segments().unwrap(), Vec::<Vec<Segment>>::new());
+}
+
+#[test]
+fn test_from_bytes_rejects_bad_input() {
+    let thread = generate_thread(1, 5, 3);
+    let bytes = Archive::new(&exact(&thread, Config::default()).segments()).into_bytes();
+    assert_eq!(
+        Archive::from_bytes(b"CFSG\x01".to_vec()),
+        Err(DecodeError::BadMagic)
+    );
+    assert_eq!(bytes[4], 1);
+    let mut version_2 = bytes.clone();
+    version_2[4] = 2;
+    assert_eq!(
+        Archive::from_bytes(version_2),
+        Err(DecodeError::UnsupportedVersion(2))
+    );
+    assert_eq!(
+        Archive::from_bytes(bytes[..bytes.len() - 1].to_vec()),
+        Err(DecodeError::InvalidIndex)
+    );
+    assert_eq!(
+        Archive::from_bytes(bytes[..8].to_vec()),
+        Err(DecodeError::Truncated)
+    );
+    let mut wrong_count = bytes.clone();
+    let n = wrong_count.len();
+    wrong_count[n - 8] += 1;
+    assert_eq!(
+        Archive::from_bytes(wrong_count),
+        Err(DecodeError::InvalidIndex)
+    );
+}
+
+#[test]
+fn test_message_rejects_references_outside_their_source() {
+    let segments = vec![
+        vec![Segment::Literal("short".into())],
+        vec![Segment::Reference {
+            message_idx: 0,
+            start: 2,
+            len: 10,
+        }],
+    ];
+    let archive = Archive::new(&segments);
+    assert_eq!(
+        archive.message(1),
+        Err(DecodeError::ReferenceOutOfRange { message: 1 })
+    );
+}
+
+#[test]
+fn test_append_keeps_earlier_entries() {
+    let thread = generate_thread(5, 24, 4);
+    let (old, new) = thread.split_at(16);
+    let original = Archive::new(&exact(old, Config::default()).segments());
+    let mut archive = Archive::from_bytes(original.as_bytes().to_vec()).unwrap();
+    archive.append(&new[..3], Config::default()).unwrap();
+    archive.append(&new[3..], Config::default()).unwrap();
+    let entries = original.entries_len();
+    assert_eq!(
+        archive.as_bytes()[..entries],
+        original.as_bytes()[..entries]
+    );
+
+    let mut file = original.as_bytes()[..entries].to_vec();
+    file.extend_from_slice(&archive.as_bytes()[entries..]);
+    let reopened = Archive::from_bytes(file).unwrap();
+    assert_eq!(reopened.messages().unwrap(), thread);
+    for (i, text) in thread.iter().enumerate() {
+        assert_eq!(&reopened.message(i).unwrap(), text);
+    }
+    let appended = reopened.segments_for(20).unwrap();
+    assert!(
+        appended
+            .iter()
+            .any(|seg| matches!(seg, Segment::Reference { message_idx, .. } if *message_idx < 16))
+    );
+}
+
+#[test]
+fn test_push_segments_extends_an_empty_archive() {
+    let mut archive = Archive::new(&[]);
+    assert_eq!(
+        archive.push_segments(&[Segment::Literal("hi there".into())]),
+        Ok(0)
+    );
+    let quote = Segment::Reference {
+        message_idx: 0,
+        start: 3,
+        len: 5,
+    };
+    assert_eq!(archive.push_segments(&[quote]), Ok(1));
+    let archive = Archive::from_bytes(archive.into_bytes()).unwrap();
+    assert_eq!(archive.messages().unwrap(), ["hi there", "there"]);
+}
+
+#[test]
+#[should_panic(expected = "later message")]
+fn test_push_segments_rejects_forward_references() {
+    let mut archive = Archive::new(&[]);
+    let _ = archive.push_segments(&[Segment::Reference {
+        message_idx: 1,
+        start: 0,
+        len: 1,
+    }]);
+}
+
+#[test]
+fn test_reads_detect_corrupted_messages() {
+    let messages = [
+        "Ship it on Friday",
+        "> Ship it on Friday\nAgreed",
+        "Thanks!",
+    ];
+    let mut bytes = Archive::new(&exact(&messages, Config::default()).segments()).into_bytes();
+    let at = bytes.windows(5).position(|w| w == b"Agree").unwrap();
+    bytes[at] = b'a';
+    let archive = Archive::from_bytes(bytes).unwrap();
+    let corrupted = DecodeError::ChecksumMismatch { message: 1 };
+    assert_eq!(archive.message(1), Err(corrupted.clone()));
+    assert_eq!(archive.messages(), Err(corrupted.clone()));
+    assert_eq!(archive.segments(), Err(corrupted));
+    assert_eq!(archive.message(0).unwrap(), messages[0]);
+    assert_eq!(archive.message(2).unwrap(), messages[2]);
+}
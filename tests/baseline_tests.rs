@@ -0,0 +1,61 @@
+#![cfg(feature = "baseline")]
+
+use copyforward::Thread;
+use copyforward::baseline::{BaselineOptions, compare};
+use copyforward::fixture::generate_thread;
+
+fn corpus() -> Vec<Thread> {
+    (0..3)
+        .map(|seed| Thread::new(format!("t{seed}"), generate_thread(seed, 20, 4)))
+        .collect()
+}
+
+#[test]
+fn test_report_covers_every_method_and_message() {
+    let threads = corpus();
+    let report = compare(&threads, &BaselineOptions::default()).unwrap();
+    assert_eq!(report.threads, 3);
+    assert_eq!(report.messages, 60);
+    let original: usize = threads
+        .iter()
+        .flat_map(|t| &t.messages)
+        .map(String::len)
+        .sum();
+    assert_eq!(report.original_bytes, original);
+    let methods: Vec<&str> = report.results.iter().map(|m| m.method).collect();
+    assert_eq!(methods, ["copyforward", "copyforward+zstd", "zstd", "gzip"]);
+}
+
+#[test]
+fn test_every_method_shrinks_quoted_threads() {
+    for approximate in [false, true] {
+        let options = BaselineOptions {
+            approximate,
+            ..BaselineOptions::default()
+        };
+        let report = compare(&corpus(), &options).unwrap();
+        for m in &report.results {
+            assert!(report.ratio(m) < 1.0, "{} did not compress", m.method);
+        }
+        let copyforward = report.get("copyforward").unwrap();
+        assert!(report.get("copyforward+zstd").unwrap().elapsed >= copyforward.elapsed);
+    }
+}
+
+#[test]
+fn test_report_displays_as_a_table() {
+    let report = compare(&corpus(), &BaselineOptions::default()).unwrap();
+    let table = report.to_string();
+    let rows: Vec<&str> = table.lines().collect();
+    assert_eq!(rows[0], "method\tbytes\tratio\tseconds");
+    assert!(rows[1].starts_with(&format!("original\t{}\t", report.original_bytes)));
+    assert_eq!(rows.len(), 2 + report.results.len());
+    assert!(rows[3].starts_with("copyforward+zstd\t"));
+}
+
+#[test]
+fn test_empty_corpus_has_unit_ratio() {
+    let report = compare(&[], &BaselineOptions::default()).unwrap();
+    assert_eq!(report.original_bytes, 0);
+    assert!(report.results.iter().all(|m| report.ratio(m) == 1.0));
+}
@@ -0,0 +1,79 @@
+use copyforward::Thread;
+use copyforward::boilerplate::{BoilerplateOptions, analyze, analyze_threads};
+
+const DISCLAIMER: &str = "This e-mail and any attachments are confidential.";
+const FOOTER: &str = "Sent from my phone";
+
+#[test]
+fn test_spans_ranked_by_total_bytes() {
+    let docs: Vec<String> = (0..5)
+        .map(|i| {
+            let footer = if i < 4 { FOOTER } else { "" };
+            format!("Message number {i} with its own body.\n{DISCLAIMER}\n{footer}")
+        })
+        .collect();
+    let opts = BoilerplateOptions {
+        min_len: 10,
+        threshold: 3,
+    };
+    let report = analyze(&docs, &opts);
+    assert!(report.spans.len() >= 2);
+    assert!(report.spans[0].text.contains(DISCLAIMER));
+    assert_eq!(report.spans[0].documents, 4);
+    assert!(
+        report
+            .spans
+            .windows(2)
+            .all(|w| w[0].total_bytes >= w[1].total_bytes)
+    );
+    for (doc, stripped) in docs.iter().zip(report.strip(&docs)) {
+        assert!(!stripped.contains(DISCLAIMER));
+        assert!(stripped.len() < doc.len());
+    }
+}
+
+#[test]
+fn test_threshold_is_exclusive() {
+    let docs = [DISCLAIMER, DISCLAIMER, "unrelated text here"];
+    let at = analyze(
+        &docs,
+        &BoilerplateOptions {
+            min_len: 8,
+            threshold: 2,
+        },
+    );
+    assert!(at.spans.is_empty());
+    assert!(at.ranges.iter().all(|r| r.is_empty()));
+    let below = analyze(
+        &docs,
+        &BoilerplateOptions {
+            min_len: 8,
+            threshold: 1,
+        },
+    );
+    assert_eq!(below.spans[0].text, DISCLAIMER);
+    assert_eq!(below.ranges[0], vec![0..DISCLAIMER.len()]);
+}
+
+#[test]
+fn test_threads_count_once() {
+    let quoting = Thread::new(
+        "a",
+        vec![
+            DISCLAIMER.to_string(),
+            format!("> {DISCLAIMER}"),
+            format!("> > {DISCLAIMER}"),
+        ],
+    );
+    let other = Thread::new("b", vec![format!("Hi\n{DISCLAIMER}")]);
+    let opts = BoilerplateOptions {
+        min_len: 8,
+        threshold: 1,
+    };
+    let report = analyze_threads(&[quoting.clone(), other.clone()], &opts);
+    assert_eq!(report.ranges.len(), 4);
+    assert_eq!(report.spans[0].text, DISCLAIMER);
+    assert_eq!(report.spans[0].documents, 2);
+    assert_eq!(report.spans[0].occurrences, 4);
+    assert!(analyze_threads(&[quoting], &opts).spans.is_empty());
+}
@@ -0,0 +1,201 @@
+use copyforward::bridge::{FuzzySegment, Patch, bridge_fuzzy, bridge_literals};
+use copyforward::fixture::generate_thread;
+use copyforward::render::{Reconstructed, reconstruct};
+use copyforward::{Config, CopyForward, Segment, approximate, exact};
+
+const SOURCE: &str = "Alpha beta gamma";
+
+fn reference(message_idx: usize, start: usize, len: usize) -> Segment {
+    Segment::Reference {
+        message_idx,
+        start,
+        len,
+    }
+}
+
+fn literal(text: &str) -> Segment {
+    Segment::Literal(text.into())
+}
+
+/// Model whose message 1 quotes `SOURCE` as "Alpha" + `gap` + "gamma".
+fn quoted_with_gap(gap: &str, second_start: usize) -> Reconstructed {
+    Reconstructed::new(vec![
+        vec![literal(SOURCE)],
+        vec![
+            reference(0, 0, 5),
+            literal(gap),
+            reference(0, second_start, 5),
+        ],
+    ])
+    .unwrap()
+}
+
+fn unbridged(model: &dyn CopyForward, idx: usize) -> Vec<FuzzySegment> {
+    model
+        .segments_for(idx)
+        .iter()
+        .cloned()
+        .map(FuzzySegment::Exact)
+        .collect()
+}
+
+fn expanded(bridged: Vec<Vec<FuzzySegment>>) -> Vec<Vec<Segment>> {
+    bridged
+        .into_iter()
+        .map(|segs| {
+            segs.into_iter()
+                .flat_map(FuzzySegment::into_segments)
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_matching_gaps_are_bridged() {
+    let model = quoted_with_gap(" beta ", 11);
+    assert_eq!(bridge_literals(&model, 8)[1], [reference(0, 0, 16)]);
+    assert_eq!(
+        bridge_fuzzy(&model, 8)[1],
+        [FuzzySegment::Exact(reference(0, 0, 16))]
+    );
+}
+
+#[test]
+fn test_whitespace_only_differences_are_bridged_fuzzily() {
+    let model = quoted_with_gap("\n beta\t", 11);
+    assert_eq!(bridge_literals(&model, 8)[1], model.segments_for(1));
+    let bridged = bridge_fuzzy(&model, 8);
+    assert_eq!(
+        bridged[1],
+        [FuzzySegment::Fuzzy {
+            message_idx: 0,
+            start: 0,
+            len: 16,
+            patches: vec![Patch {
+                offset: 5,
+                len: 6,
+                text: "\n beta\t".into(),
+            }],
+        }]
+    );
+    assert_eq!(bridged[1][0].rendered_len(), model.message(1).len());
+    assert_eq!(expanded(bridged)[1], model.segments_for(1));
+}
+
+#[test]
+fn test_bridge_requires_matching_text_and_small_gaps() {
+    let model = quoted_with_gap(" beta ", 11);
+    assert_eq!(bridge_fuzzy(&model, 5)[1], unbridged(&model, 1));
+    let model = quoted_with_gap(" BETA ", 11);
+    assert_eq!(bridge_literals(&model, 8)[1], model.segments_for(1));
+    assert_eq!(bridge_fuzzy(&model, 8)[1], unbridged(&model, 1));
+    let model = quoted_with_gap(" beta", 11);
+    assert_eq!(bridge_fuzzy(&model, 8)[1], unbridged(&model, 1));
+}
+
+#[test]
+fn test_references_to_other_sources_are_not_bridged() {
+    let model = Reconstructed::new(vec![
+        vec![literal("Alpha one")],
+        vec![literal("Alpha two")],
+        vec![reference(0, 0, 5), literal(" "), reference(1, 6, 3)],
+    ])
+    .unwrap();
+    assert_eq!(bridge_literals(&model, 4)[2], model.segments_for(2));
+    assert_eq!(bridge_fuzzy(&model, 4)[2], unbridged(&model, 2));
+}
+
+#[test]
+fn test_self_references_are_not_bridged_past_their_position() {
+    // "abcabcab": bridging the two references would copy bytes 0..5 to
+    // position 3, before bytes 3..5 exist.
+    let model = Reconstructed::new(vec![vec![
+        literal("abc"),
+        reference(0, 0, 2),
+        literal("c"),
+        reference(0, 3, 2),
+    ]])
+    .unwrap();
+    assert_eq!(bridge_literals(&model, 4)[0], model.segments_for(0));
+    assert_eq!(bridge_fuzzy(&model, 4)[0], unbridged(&model, 0));
+}
+
+#[test]
+fn test_rewrapped_quotes_collapse_to_one_fuzzy_reference() {
+    let original = "Thanks for the update on the release. The staging cluster passed every smoke \
+                    test last night, so we can promote the build this afternoon unless QA objects.";
+    let rewrapped = original
+        .replace(". The", ".\nThe")
+        .replace("so we", "so\nwe");
+    let messages = [
+        original.to_string(),
+        format!("Replying inline:\n{rewrapped}"),
+    ];
+    let config = Config {
+        min_match_len: 8,
+        cap_len: 16,
+        ..Config::default()
+    };
+    let models: [Box<dyn CopyForward>; 2] = [
+        Box::new(exact(&messages, config.clone())),
+        Box::new(approximate(&messages, config)),
+    ];
+    for model in models {
+        assert!(model.segments_for(1).len() > 2);
+        let bridged = bridge_fuzzy(model.as_ref(), 2);
+        assert_eq!(bridged[1].len(), 2);
+        assert!(matches!(
+            &bridged[1][1],
+            FuzzySegment::Fuzzy { message_idx: 0, start: 0, len, patches }
+                if *len == original.len() && patches.len() == 2
+        ));
+        assert_eq!(reconstruct(&expanded(bridged)).unwrap(), messages);
+    }
+}
+
+#[test]
+fn test_bridged_threads_render_and_reconstruct() {
+    let original = "Ship the release on Friday once the staging soak is clean and QA signs off.";
+    let reply = format!("> {}\nAgreed.", original.replace("once the", "once\nthe"));
+    let messages = [
+        original.to_string(),
+        reply.clone(),
+        format!("Replying to: {reply}\nSee you then."),
+    ];
+    let config = Config {
+        min_match_len: 8,
+        ..Config::default()
+    };
+    let model = exact(&messages, config.clone());
+    assert!(
+        model
+            .segments_for(2)
+            .iter()
+            .any(|seg| matches!(seg, Segment::Reference { message_idx: 1, .. }))
+    );
+    assert!(
+        bridge_fuzzy(&model, 4)[1]
+            .iter()
+            .any(|seg| matches!(seg, FuzzySegment::Fuzzy { message_idx: 0, .. }))
+    );
+    let mut threads = vec![messages.to_vec()];
+    threads.extend((0..8).map(|seed| generate_thread(seed, 12, 4)));
+    for thread in threads {
+        let models: [Box<dyn CopyForward>; 2] = [
+            Box::new(exact(&thread, config.clone())),
+            Box::new(approximate(&thread, config.clone())),
+        ];
+        for model in models {
+            for max_gap in [1, 4, 16] {
+                let bridged = bridge_literals(model.as_ref(), max_gap);
+                assert_eq!(reconstruct(&bridged).unwrap(), thread);
+                let rendered = Reconstructed::new(bridged)
+                    .unwrap()
+                    .render_with_dyn(&mut |_, _, _, text| text.to_string());
+                assert_eq!(rendered, thread);
+                let fuzzy = expanded(bridge_fuzzy(model.as_ref(), max_gap));
+                assert_eq!(reconstruct(&fuzzy).unwrap(), thread);
+            }
+        }
+    }
+}
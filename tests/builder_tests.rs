@@ -0,0 +1,76 @@
+use copyforward::fixture::generate_thread;
+use copyforward::prelude::*;
+
+#[test]
+fn test_builder_matches_compress_with_the_same_settings() {
+    let thread = generate_thread(4, 20, 4);
+    for &algorithm in Algorithm::ALL {
+        let built = CopyForwardBuilder::new()
+            .algorithm(algorithm)
+            .min_match_len(6)
+            .lookback(5)
+            .cap_len(32)
+            .build(&thread)
+            .unwrap();
+        let config = Config {
+            min_match_len: 6,
+            lookback: Some(5),
+            cap_len: 32,
+            ..Config::default()
+        };
+        let expected = compress(&thread, algorithm, config.clone());
+        assert_eq!(built.segments(), expected.segments());
+        let metadata = built.metadata().unwrap();
+        assert_eq!(metadata.algorithm, algorithm.name());
+        assert_eq!(format!("{:?}", metadata.config), format!("{config:?}"));
+    }
+}
+
+#[test]
+fn test_default_builder_runs_the_first_algorithm_with_default_config() {
+    let thread = generate_thread(9, 10, 4);
+    let built = CopyForwardBuilder::new().build(&thread).unwrap();
+    let expected = compress(&thread, Algorithm::ALL[0], Config::default());
+    assert_eq!(built.segments(), expected.segments());
+}
+
+#[test]
+fn test_setters_apply_on_top_of_a_full_config() {
+    let config = Config {
+        dedup_messages: true,
+        min_match_len: 12,
+        ..Config::default()
+    };
+    let built = CopyForwardBuilder::new()
+        .config(config)
+        .min_match_len(5)
+        .build(&["Hello world", "Hello world"])
+        .unwrap();
+    let recorded = built.metadata().unwrap().config;
+    assert!(recorded.dedup_messages);
+    assert_eq!(recorded.min_match_len, 5);
+}
+
+#[test]
+fn test_builder_validates_and_verifies() {
+    let err = CopyForwardBuilder::new()
+        .cap_len(0)
+        .build(&["Hello world"])
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        CopyForwardError::InvalidConfig {
+            field: "cap_len",
+            ..
+        }
+    ));
+    let messages = ["Hello world, again", "Hello world, again and again"];
+    let verified = CopyForwardBuilder::new()
+        .verify(true)
+        .build(&messages)
+        .unwrap();
+    assert_eq!(
+        verified.render_with(|_, _, _, text| text.to_string()),
+        messages
+    );
+}
@@ -0,0 +1,123 @@
+use copyforward::{
+    ByteSegment, BytesModel, Config, CopyForward, CopyForwardBytes, approximate_bytes, exact,
+    exact_bytes, optimal_bytes,
+};
+
+const LATIN1: [&[u8]; 3] = [
+    b"Gr\xfc\xdfe aus M\xfcnchen, das Treffen ist auf Montag um zehn Uhr verschoben",
+    b"> Gr\xfc\xdfe aus M\xfcnchen, das Treffen ist auf Montag um zehn Uhr verschoben\nDanke",
+    b"\xff\xfe binary junk \xff\xfe binary junk",
+];
+
+fn identity(model: &BytesModel) -> Vec<Vec<u8>> {
+    model.render_with(|_, _, _, source| source.to_vec())
+}
+
+#[test]
+fn test_every_algorithm_round_trips_invalid_utf8() {
+    for model in [
+        exact_bytes(&LATIN1, Config::default()),
+        approximate_bytes(&LATIN1, Config::default()),
+        optimal_bytes(&LATIN1, Config::default()),
+    ] {
+        assert_eq!(identity(&model), LATIN1);
+        assert!(
+            model.segments_for(1)[1..]
+                .iter()
+                .any(|seg| matches!(seg, ByteSegment::Reference { message_idx: 0, .. }))
+        );
+        assert_eq!(model.len(), 3);
+        assert_eq!(model.message(2), LATIN1[2]);
+    }
+}
+
+#[test]
+fn test_references_may_split_multibyte_sequences() {
+    // "é" is two bytes; the shared prefix ends between them.
+    let messages: [&[u8]; 2] = ["résumé".as_bytes(), b"r\xc3\xa9sum\xc3 torn"];
+    let model = exact_bytes(
+        &messages,
+        Config {
+            min_match_len: 3,
+            ..Config::default()
+        },
+    );
+    assert_eq!(
+        model.segments_for(1)[0],
+        ByteSegment::Reference {
+            message_idx: 0,
+            start: 0,
+            len: 7
+        }
+    );
+    assert_eq!(identity(&model), messages);
+}
+
+#[test]
+fn test_none_messages_keep_their_index() {
+    let messages = [
+        Some(b"Hello world".to_vec()),
+        None,
+        Some(b"Hello world!".to_vec()),
+    ];
+    let model = exact_bytes(&messages, Config::default());
+    assert!(model.segments_for(1).is_empty());
+    assert_eq!(model.message(1), b"");
+    assert_eq!(
+        model.segments_for(2)[0],
+        ByteSegment::Reference {
+            message_idx: 0,
+            start: 0,
+            len: 11
+        }
+    );
+    assert_eq!(model.render_with_static(b"[REF]")[2], b"[REF]!");
+}
+
+#[test]
+fn test_valid_utf8_matches_text_mode_on_ascii() {
+    let messages = [
+        "Hello world, see you Monday",
+        "> Hello world, see you Monday\nOK",
+    ];
+    let bytes: Vec<&[u8]> = messages.iter().map(|m| m.as_bytes()).collect();
+    let text = exact(&messages, Config::default()).segments();
+    let model = exact_bytes(&bytes, Config::default());
+    let as_bytes: Vec<Vec<ByteSegment>> = text
+        .iter()
+        .map(|segs| {
+            segs.iter()
+                .map(|seg| match seg {
+                    copyforward::Segment::Literal(s) => ByteSegment::Literal(s.as_bytes().to_vec()),
+                    copyforward::Segment::Reference {
+                        message_idx,
+                        start,
+                        len,
+                    } => ByteSegment::Reference {
+                        message_idx: *message_idx,
+                        start: *start,
+                        len: *len,
+                    },
+                })
+                .collect()
+        })
+        .collect();
+    assert_eq!(model.segments(), as_bytes);
+}
+
+#[test]
+fn test_stop_phrases_match_bytes() {
+    let messages: [&[u8]; 2] = [b"Regards, \xe9quipe support", b"Regards, \xe9quipe support"];
+    let model = exact_bytes(
+        &messages,
+        Config {
+            stop_phrases: vec!["Regards,".into()],
+            ..Config::default()
+        },
+    );
+    assert_eq!(
+        model.segments_for(1)[0],
+        ByteSegment::Literal(b"Regards,".to_vec())
+    );
+    assert_eq!(model.metadata().unwrap().config.stop_phrases, ["Regards,"]);
+}
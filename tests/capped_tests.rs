@@ -1,5 +1,5 @@
 use copyforward::fixture::generate_thread;
-use copyforward::{Config, CopyForward, approximate};
+use copyforward::{Config, CopyForward, Segment, approximate};
 
 #[test]
 fn capped_preserves_rendering_small() {
@@ -54,3 +54,93 @@ fn capped_coalesces_adjacent_refs() {
     }
     assert!(found_ref, "expected a coalesced reference of length >= 64");
 }
+
+const MINUTES: &str =
+    "Minutes: the vendor review moved to Thursday, budget approval is pending finance.";
+const ACTIONS: &str =
+    " Action items: Priya drafts the RFP, Tom books the room, everyone reads the brief.";
+
+fn reextend_thread() -> Vec<String> {
+    vec![
+        MINUTES.to_string(),
+        format!("{MINUTES}{ACTIONS}"),
+        format!("> {MINUTES}{ACTIONS}"),
+    ]
+}
+
+#[test]
+fn test_reextend_swallows_references_split_between_sources() {
+    let msgs = reextend_thread();
+    let split = approximate(&msgs, Config::default());
+    assert_eq!(split.segments_for(2).len(), 3);
+
+    let config = Config {
+        reextend: true,
+        ..Config::default()
+    };
+    let merged = approximate(&msgs, config);
+    assert_eq!(
+        merged.segments_for(2),
+        [
+            Segment::Literal("> ".into()),
+            Segment::Reference {
+                message_idx: 1,
+                start: 0,
+                len: MINUTES.len() + ACTIONS.len(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_reextend_preserves_rendering() {
+    let config = Config {
+        reextend: true,
+        ..Config::default()
+    };
+    for seed in [1, 7, 23] {
+        let msgs = generate_thread(seed, 20, 6);
+        let plain = approximate(&msgs, Config::default());
+        let cf = approximate(&msgs, config.clone());
+        assert_eq!(cf.render_with(|_, _, _, s| s.to_string()), msgs);
+        for (with, without) in cf.segments().iter().zip(plain.segments()) {
+            assert!(with.len() <= without.len());
+        }
+    }
+}
+
+#[test]
+fn test_reextend_leaves_no_reference_extendable_into_literals() {
+    let config = Config {
+        reextend: true,
+        // A tiny candidate cap makes the first pass miss many quote starts.
+        ncap: 2,
+        ..Config::default()
+    };
+    for seed in [2, 5, 11] {
+        let msgs = generate_thread(seed, 25, 6);
+        let cf = approximate(&msgs, config.clone());
+        for idx in 0..cf.len() {
+            let segs = cf.segments_for(idx);
+            for (pos, seg) in segs.iter().enumerate() {
+                let &Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } = seg
+                else {
+                    continue;
+                };
+                let source = cf.message(message_idx);
+                if let Some(Segment::Literal(before)) = pos.checked_sub(1).map(|p| &segs[p]) {
+                    let back = source[..start].chars().next_back();
+                    assert_ne!(back, before.chars().next_back(), "message {idx}");
+                }
+                if let Some(Segment::Literal(after)) = segs.get(pos + 1) {
+                    let next = source[start + len..].chars().next();
+                    assert_ne!(next, after.chars().next(), "message {idx}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use copyforward::fixture::generate_thread;
+use copyforward::render::Reconstructed;
+use copyforward::{
+    Config, CopyForward, CopyForwardTokens, approximate, approximate_tokens, exact, exact_tokens,
+    optimal,
+};
+use std::sync::Arc;
+use std::thread;
+
+/// Everything a query thread reads from a model, for comparing threads.
+fn answers(model: &dyn CopyForward) -> Vec<String> {
+    let mut out = Vec::new();
+    for idx in 0..model.len() {
+        out.push(format!("{:?}", model.segments_for(idx)));
+        out.push(model.message(idx).to_string());
+        out.push(format!("{:?}", model.locate(idx, 0)));
+    }
+    out.extend(model.render_with_dyn(&mut |m, start, len, _| format!("[{m}:{start}+{len}]")));
+    out
+}
+
+#[test]
+fn test_models_answer_queries_from_many_threads() {
+    let messages = generate_thread(12, 24, 4);
+    let exact = exact(&messages, Config::default());
+    let models: Vec<Arc<dyn CopyForward>> = vec![
+        Arc::new(Reconstructed::new(exact.segments()).unwrap()),
+        Arc::new(exact),
+        Arc::new(approximate(&messages, Config::default())),
+        Arc::new(optimal(&messages, Config::default())),
+    ];
+    for model in models {
+        let expected = answers(model.as_ref());
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let model = Arc::clone(&model);
+                thread::spawn(move || answers(model.as_ref()))
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), expected);
+        }
+    }
+}
+
+#[test]
+fn test_token_models_answer_queries_from_many_threads() {
+    let messages: Vec<Vec<u32>> = vec![vec![1, 2, 3, 4, 5, 6], vec![9, 1, 2, 3, 4, 5, 6, 7]];
+    let models: Vec<Arc<dyn CopyForwardTokens>> = vec![
+        Arc::new(exact_tokens(&messages, Config::default())),
+        Arc::new(approximate_tokens(&messages, Config::default())),
+    ];
+    for model in models {
+        let segments = model.segments();
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    assert_eq!(
+                        model.render_with_dyn(&mut |_, _, _, t| t.to_vec()),
+                        messages
+                    );
+                    assert_eq!(model.segments_for(1), segments[1]);
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,55 @@
+use copyforward::contamination::{ContaminationIndex, ContaminationMatch};
+
+#[test]
+fn test_scan_reports_spans_per_document() {
+    let benchmarks = [
+        Some("Question: what is 2 + 2? Answer: 4"),
+        None,
+        Some("Translate 'bonjour' into English."),
+    ];
+    let index = ContaminationIndex::new(&benchmarks, 10);
+    assert_eq!(index.len(), 3);
+
+    let docs = [
+        "Here is a clean document with nothing in common.",
+        "prefix Translate 'bonjour' into English. and Question: what is 2 + 2?",
+    ];
+    let found = index.scan_all(&docs);
+    assert!(found[0].is_empty());
+    assert_eq!(found[1].len(), 2);
+    let first = found[1][0];
+    assert_eq!(first.reference, 2);
+    assert_eq!(
+        &docs[1][first.start..first.start + first.len],
+        "Translate 'bonjour' into English."
+    );
+    assert_eq!(first.reference_start, 0);
+    let second = found[1][1];
+    assert_eq!(second.reference, 0);
+    assert_eq!(
+        &docs[1][second.start..second.start + second.len],
+        "Question: what is 2 + 2?"
+    );
+}
+
+#[test]
+fn test_scan_uses_byte_offsets_for_multibyte_text() {
+    let index = ContaminationIndex::new(&["naïve café résumé"], 6);
+    let doc = "→ café résumé";
+    assert_eq!(
+        index.scan(doc),
+        vec![ContaminationMatch {
+            start: "→".len(),
+            len: " café résumé".len(),
+            reference: 0,
+            reference_start: "naïve".len(),
+        }]
+    );
+}
+
+#[test]
+fn test_short_overlaps_are_ignored() {
+    let index = ContaminationIndex::new(&["abcdefgh"], 5);
+    assert!(index.scan("xxabcdxx").is_empty());
+    assert_eq!(index.scan("xxabcdexx").len(), 1);
+}
@@ -0,0 +1,40 @@
+use copyforward::corpus::compress_threads;
+use copyforward::{Config, CopyForward, Stats, exact};
+
+#[test]
+fn test_compress_threads_preserves_input_order() {
+    let threads: Vec<Vec<String>> = (0..50)
+        .map(|i| {
+            vec![
+                format!("thread {i} opens"),
+                format!("thread {i} opens and continues"),
+            ]
+        })
+        .collect();
+    let results = compress_threads(&threads, |t| exact(t, Config::default()));
+    assert_eq!(results.len(), threads.len());
+    for (thread, result) in threads.iter().zip(&results) {
+        assert_eq!(
+            result.render_with(|_, _, _, text| text.to_string()),
+            *thread
+        );
+    }
+}
+
+#[test]
+fn test_compress_threads_handles_empty_corpus() {
+    let threads: Vec<Vec<&str>> = Vec::new();
+    assert!(compress_threads(&threads, |t| t.len()).is_empty());
+}
+
+#[test]
+fn test_stats_sum_across_threads() {
+    let a = Stats::from_segments(
+        &exact(&["Hello world", "Hello world!"], Config::default()).segments(),
+    );
+    let b = Stats::from_segments(&exact(&["abc"], Config::default()).segments());
+    let total: Stats = [a, b].into_iter().sum();
+    assert_eq!(total.messages, 3);
+    assert_eq!(total.original_bytes, a.original_bytes + b.original_bytes);
+    assert_eq!(total.references, a.references + b.references);
+}
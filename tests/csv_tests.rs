@@ -0,0 +1,57 @@
+use copyforward::csv::{Column, CsvOptions, parse_records, read_threads};
+
+#[test]
+fn test_parse_records_handles_quotes_and_crlf() {
+    let input = "id,body\r\n1,\"Hello, \"\"world\"\"\nsecond line\"\r\n2,plain\r\n";
+    let records = parse_records(input, b',').unwrap();
+    assert_eq!(
+        records,
+        vec![
+            vec!["id", "body"],
+            vec!["1", "Hello, \"world\"\nsecond line"],
+            vec!["2", "plain"],
+        ]
+    );
+}
+
+#[test]
+fn test_parse_records_rejects_unterminated_quote() {
+    assert!(parse_records("a,\"open\n", b',').is_err());
+}
+
+#[test]
+fn test_read_threads_groups_by_thread_column() {
+    let input = "ticket\tauthor\ttext\nT1\tann\tMy printer is broken\n\
+                 T2\tbob\tHow do I reset my password?\n\
+                 T1\tsupport\tSorry to hear that.\\n> My printer is broken\n";
+    let options = CsvOptions {
+        delimiter: b'\t',
+        text_column: Column::Name("text".to_string()),
+        thread_column: Some(Column::Index(0)),
+        ..CsvOptions::default()
+    };
+    let threads = read_threads(input.as_bytes(), &options).unwrap();
+    assert_eq!(threads.len(), 2);
+    assert_eq!(threads[0].id, "T1");
+    assert_eq!(threads[0].messages.len(), 2);
+    assert_eq!(threads[1].id, "T2");
+    assert_eq!(threads[1].messages, vec!["How do I reset my password?"]);
+}
+
+#[test]
+fn test_read_threads_without_headers_or_thread_column() {
+    let options = CsvOptions {
+        has_headers: false,
+        text_column: Column::Index(1),
+        ..CsvOptions::default()
+    };
+    let threads = read_threads("1,first\n2,second\n".as_bytes(), &options).unwrap();
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0].messages, vec!["first", "second"]);
+
+    let missing = CsvOptions {
+        text_column: Column::Name("body".to_string()),
+        ..CsvOptions::default()
+    };
+    assert!(read_threads("id,text\n1,x\n".as_bytes(), &missing).is_err());
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "server")]
+
+use copyforward::daemon::Daemon;
+use serde_json::Value;
+
+fn call(daemon: &mut Daemon, line: &str) -> Value {
+    serde_json::from_str(&daemon.handle_line(line)).unwrap()
+}
+
+#[test]
+fn test_thread_lifecycle() {
+    let mut d = Daemon::new();
+    let resp = call(
+        &mut d,
+        r#"{"id": 1, "cmd": "add_thread", "thread": "t1", "messages": ["Hello world"]}"#,
+    );
+    assert_eq!(resp["ok"], true);
+    assert_eq!(resp["id"], 1);
+    assert_eq!(resp["stats"]["messages"], 1);
+
+    let resp = call(
+        &mut d,
+        r#"{"cmd": "add_message", "thread": "t1", "message": "Hello world today"}"#,
+    );
+    assert_eq!(resp["index"], 1);
+    assert_eq!(resp["segments"][0]["reference"]["message_idx"], 0);
+
+    let resp = call(&mut d, r#"{"cmd": "render", "thread": "t1"}"#);
+    assert_eq!(
+        resp["messages"],
+        serde_json::json!(["Hello world", "Hello world today"])
+    );
+    let resp = call(
+        &mut d,
+        r#"{"cmd": "render", "thread": "t1", "replacement": "[..]"}"#,
+    );
+    assert_eq!(resp["messages"][1], "[..] today");
+    let resp = call(&mut d, r#"{"cmd": "query", "thread": "t1"}"#);
+    assert_eq!(resp["metadata"]["algorithm"], "exact");
+    assert_eq!(resp["metadata"]["crate_version"], env!("CARGO_PKG_VERSION"));
+
+    call(
+        &mut d,
+        r#"{"cmd": "add_message", "thread": "t2", "message": "other"}"#,
+    );
+    let resp = call(&mut d, r#"{"cmd": "query"}"#);
+    assert_eq!(resp["threads"], serde_json::json!(["t1", "t2"]));
+    assert_eq!(resp["stats"]["messages"], 3);
+}
+
+#[test]
+fn test_errors_keep_daemon_running() {
+    let mut d = Daemon::new();
+    let input = b"not json\n\n{\"cmd\": \"query\", \"thread\": \"missing\"}\n{\"cmd\": \"bogus\"}\n{\"cmd\": \"query\"}\n";
+    let mut out = Vec::new();
+    d.run(&input[..], &mut out).unwrap();
+    let lines: Vec<Value> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 4);
+    assert!(
+        lines[..3]
+            .iter()
+            .all(|l| l["ok"] == false && l["error"].is_string())
+    );
+    assert_eq!(lines[3]["ok"], true);
+}
@@ -0,0 +1,97 @@
+use copyforward::fixture::generate_thread;
+use copyforward::{Config, CopyForward, Segment, approximate, exact, optimal};
+
+const NOTICE: &str = "Reminder: the build server restarts tonight at 23:00 UTC.";
+
+fn models(messages: &[String], config: Config) -> Vec<Box<dyn CopyForward>> {
+    vec![
+        Box::new(exact(messages, config.clone())),
+        Box::new(approximate(messages, config.clone())),
+        Box::new(optimal(messages, config)),
+    ]
+}
+
+fn dedup_config() -> Config {
+    Config {
+        dedup_messages: true,
+        ..Config::default()
+    }
+}
+
+fn whole(message_idx: usize, text: &str) -> [Segment; 1] {
+    [Segment::Reference {
+        message_idx,
+        start: 0,
+        len: text.len(),
+    }]
+}
+
+#[test]
+fn test_repeated_messages_become_one_reference() {
+    let messages = [
+        NOTICE.to_string(),
+        format!("Ack, {NOTICE}"),
+        NOTICE.to_string(),
+        NOTICE.to_string(),
+    ];
+    for cf in models(&messages, dedup_config()) {
+        assert_eq!(cf.segments_for(2), whole(0, NOTICE));
+        assert_eq!(cf.segments_for(3), whole(0, NOTICE));
+        assert_eq!(
+            cf.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+            messages
+        );
+    }
+}
+
+#[test]
+fn test_repeats_outside_lookback_start_over() {
+    let messages = [
+        NOTICE.to_string(),
+        "First unrelated message in between.".to_string(),
+        "Second unrelated message in between.".to_string(),
+        NOTICE.to_string(),
+        NOTICE.to_string(),
+    ];
+    let config = Config {
+        lookback: Some(2),
+        ..dedup_config()
+    };
+    for cf in models(&messages, config) {
+        assert_eq!(cf.segments_for(3), [Segment::Literal(NOTICE.into())]);
+        assert_eq!(cf.segments_for(4), whole(3, NOTICE));
+    }
+}
+
+#[test]
+fn test_short_and_stop_phrase_messages_are_not_collapsed() {
+    let signed = "See you all tomorrow.\n-- \nAlex";
+    let messages = ["ok", signed, "ok", signed].map(String::from);
+    let config = Config {
+        stop_phrases: vec!["-- ".to_string()],
+        ..dedup_config()
+    };
+    for cf in models(&messages, config) {
+        assert_eq!(cf.segments_for(2), [Segment::Literal("ok".into())]);
+        assert_ne!(cf.segments_for(3), whole(1, signed));
+        assert_eq!(cf.message(3), signed);
+    }
+}
+
+#[test]
+fn test_dedup_messages_roundtrips_generated_threads() {
+    for seed in [4, 12] {
+        let mut messages = generate_thread(seed, 15, 3);
+        messages.extend_from_within(3..9);
+        let config = Config {
+            max_ref_depth: Some(2),
+            ..dedup_config()
+        };
+        for cf in models(&messages, config) {
+            assert_eq!(
+                cf.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+                messages
+            );
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use copyforward::dedup::{DedupMode, DedupOptions, TokenDedupOptions, dedup, dedup_tokens};
+
+const SIG: &str = "--\nSent from the mailing list. Unsubscribe at any time.";
+
+#[test]
+fn test_dedup_removes_repeated_spans() {
+    let docs = [
+        format!("First post about parsers.\n{SIG}"),
+        format!("Second post about lexers.\n{SIG}"),
+    ];
+    let opts = DedupOptions {
+        min_span_len: 20,
+        ..DedupOptions::default()
+    };
+    let out = dedup(&docs, &opts);
+    assert_eq!(out[0].text, docs[0]);
+    assert_eq!(out[0].duplicate_bytes, 0);
+    assert!(!out[1].text.contains("Unsubscribe"));
+    assert!(out[1].text.starts_with("Second post about lex"));
+    assert!(out[1].duplicate_bytes >= SIG.len());
+}
+
+#[test]
+fn test_dedup_masks_runs_once() {
+    let docs = [
+        Some("alpha beta gamma delta"),
+        None,
+        Some("alpha beta gamma delta!"),
+    ];
+    let opts = DedupOptions {
+        min_span_len: 4,
+        mode: DedupMode::Mask("[DUP]".to_string()),
+        ..DedupOptions::default()
+    };
+    let out = dedup(&docs, &opts);
+    assert_eq!(out[1].text, "");
+    assert_eq!(out[2].text, "[DUP]!");
+}
+
+#[test]
+fn test_dedup_drops_mostly_duplicate_documents() {
+    let docs = [
+        "a long original paragraph of text",
+        "a long original paragraph of text, ok",
+    ];
+    let opts = DedupOptions {
+        min_span_len: 8,
+        max_duplicate_fraction: 0.5,
+        ..DedupOptions::default()
+    };
+    let out = dedup(&docs, &opts);
+    assert!(!out[0].dropped);
+    assert!(out[1].dropped);
+    assert!(out[1].text.is_empty());
+    assert!(out[1].duplicate_fraction > 0.5);
+}
+
+#[test]
+fn test_short_repeats_are_kept() {
+    let docs = ["hello there", "hello again"];
+    let out = dedup(&docs, &DedupOptions::default());
+    assert_eq!(out[1].text, "hello again");
+}
+
+#[test]
+fn test_dedup_tokens_removes_token_spans() {
+    let docs: Vec<Option<Vec<u32>>> = vec![
+        Some((0..20).collect()),
+        None,
+        Some([100, 101].into_iter().chain(5..15).chain([102]).collect()),
+    ];
+    let opts = TokenDedupOptions {
+        min_span_len: 8,
+        ..TokenDedupOptions::default()
+    };
+    let out = dedup_tokens(&docs, &opts);
+    assert_eq!(out.len(), 3);
+    assert_eq!(out[0].tokens, (0..20).collect::<Vec<u32>>());
+    assert!(out[1].tokens.is_empty());
+    assert_eq!(out[2].tokens, [100, 101, 102]);
+    assert_eq!(out[2].duplicate_spans.len(), 1);
+    assert_eq!(out[2].duplicate_spans[0], 2..12);
+    assert_eq!(out[2].duplicate_tokens, 10);
+}
+
+#[test]
+fn test_dedup_tokens_min_span_is_in_tokens() {
+    let docs: Vec<Vec<u32>> = vec![vec![1, 2, 3, 4, 5], vec![9, 1, 2, 3, 4, 9]];
+    let short = TokenDedupOptions {
+        min_span_len: 5,
+        ..TokenDedupOptions::default()
+    };
+    assert_eq!(dedup_tokens(&docs, &short)[1].tokens, [9, 1, 2, 3, 4, 9]);
+    let drop = TokenDedupOptions {
+        min_span_len: 4,
+        max_duplicate_fraction: 0.5,
+        ..TokenDedupOptions::default()
+    };
+    let out = dedup_tokens(&docs, &drop);
+    assert!(out[1].dropped);
+    assert!(out[1].tokens.is_empty());
+}
@@ -0,0 +1,237 @@
+use copyforward::fixture::generate_thread;
+use copyforward::{Config, CopyForward, Segment, approximate, exact, optimal};
+
+const ORIGINAL: &str = "The quarterly report is attached; please send comments by Friday noon so we can \
+                        publish the final version on Monday.";
+
+fn models(messages: &[String], config: Config) -> Vec<Box<dyn CopyForward>> {
+    vec![
+        Box::new(exact(messages, config.clone())),
+        Box::new(approximate(messages, config)),
+    ]
+}
+
+/// A forward of the original, then a reply that quotes the original plus the
+/// first characters of the forward's footer.
+fn forwarded_thread() -> Vec<String> {
+    vec![
+        ORIGINAL.to_string(),
+        format!("{ORIGINAL} (fwd)"),
+        format!("Re: {ORIGINAL} (f"),
+    ]
+}
+
+#[test]
+fn test_longest_candidate_wins_by_default() {
+    let cf = exact(&forwarded_thread(), Config::default());
+    assert_eq!(
+        cf.segments_for(2),
+        [
+            Segment::Literal("Re: ".into()),
+            Segment::Reference {
+                message_idx: 1,
+                start: 0,
+                len: ORIGINAL.len() + 3,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_shallow_preference_points_at_the_original() {
+    let config = Config {
+        shallow_preference: Some(4),
+        ..Config::default()
+    };
+    for cf in models(&forwarded_thread(), config) {
+        assert_eq!(
+            cf.segments_for(2),
+            [
+                Segment::Literal("Re: ".into()),
+                Segment::Reference {
+                    message_idx: 0,
+                    start: 0,
+                    len: ORIGINAL.len(),
+                },
+                Segment::Literal(" (f".into()),
+            ]
+        );
+    }
+}
+
+#[test]
+fn test_shallow_preference_keeps_much_longer_matches() {
+    let config = Config {
+        shallow_preference: Some(2),
+        ..Config::default()
+    };
+    let cf = exact(&forwarded_thread(), config);
+    assert!(matches!(
+        cf.segments_for(2)[1],
+        Segment::Reference { message_idx: 1, .. }
+    ));
+}
+
+#[test]
+fn test_shallow_preference_preserves_rendering() {
+    let config = Config {
+        shallow_preference: Some(8),
+        ..Config::default()
+    };
+    for seed in [2, 9] {
+        let messages = generate_thread(seed, 25, 5);
+        for cf in models(&messages, config.clone()) {
+            assert_eq!(
+                cf.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+                messages
+            );
+        }
+    }
+}
+
+const PYRAMID: [&str; 4] = [
+    "Can we ship the billing migration on Friday afternoon?",
+    "Yes, if QA signs off by Thursday.\n> Can we ship the billing migration on Friday afternoon?",
+    "QA signed off this morning.\n> Yes, if QA signs off by Thursday.\n> Can we ship the billing \
+     migration on Friday afternoon?",
+    "Shipping now, thanks all.\n> QA signed off this morning.\n> Yes, if QA signs off by \
+     Thursday.\n> Can we ship the billing migration on Friday afternoon?",
+];
+
+/// Deepest reference chain in `segments`, measured per byte.
+fn chain_depth(segments: &[Vec<Segment>]) -> usize {
+    let mut depths: Vec<Vec<usize>> = Vec::new();
+    for segs in segments {
+        let mut bytes = Vec::new();
+        for seg in segs {
+            match seg {
+                Segment::Literal(text) => bytes.extend(std::iter::repeat_n(0, text.len())),
+                Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => bytes.extend(
+                    depths[*message_idx][*start..start + len]
+                        .iter()
+                        .map(|d| d + 1),
+                ),
+            }
+        }
+        depths.push(bytes);
+    }
+    depths.iter().flatten().copied().max().unwrap_or(0)
+}
+
+fn pyramid() -> Vec<String> {
+    PYRAMID.map(String::from).to_vec()
+}
+
+#[test]
+fn test_max_ref_depth_bounds_reference_chains() {
+    let unbounded = exact(&pyramid(), Config::default());
+    assert!(chain_depth(&unbounded.segments()) > 1);
+    for max in [1, 2] {
+        let config = Config {
+            max_ref_depth: Some(max),
+            ..Config::default()
+        };
+        for cf in models(&pyramid(), config) {
+            assert!(chain_depth(&cf.segments()) <= max);
+            assert_eq!(
+                cf.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+                PYRAMID
+            );
+        }
+    }
+}
+
+#[test]
+fn test_max_ref_depth_points_at_originals() {
+    let config = Config {
+        min_match_len: 16,
+        max_ref_depth: Some(1),
+        ..Config::default()
+    };
+    let cf = exact(&pyramid(), config);
+    let sources: Vec<usize> = cf
+        .segments_for(3)
+        .iter()
+        .filter_map(|seg| match seg {
+            Segment::Reference { message_idx, .. } => Some(*message_idx),
+            Segment::Literal(_) => None,
+        })
+        .collect();
+    assert_eq!(sources, [2, 1, 0]);
+}
+
+#[test]
+fn test_max_ref_depth_stays_inside_the_lookback_window() {
+    let config = Config {
+        min_match_len: 16,
+        lookback: Some(2),
+        max_ref_depth: Some(1),
+        ..Config::default()
+    };
+    for cf in models(&pyramid(), config) {
+        assert!(chain_depth(&cf.segments()) <= 1);
+        for (idx, segs) in cf.segments().iter().enumerate() {
+            for seg in segs {
+                if let Segment::Reference { message_idx, .. } = seg {
+                    assert!(idx - message_idx <= 2, "#{idx} references #{message_idx}");
+                }
+            }
+        }
+        assert_eq!(
+            cf.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+            PYRAMID
+        );
+    }
+}
+
+#[test]
+fn test_zero_max_ref_depth_emits_only_literals() {
+    let config = Config {
+        max_ref_depth: Some(0),
+        ..Config::default()
+    };
+    for cf in models(&pyramid(), config) {
+        for segs in cf.segments() {
+            assert!(segs.iter().all(|seg| matches!(seg, Segment::Literal(_))));
+        }
+        assert_eq!(
+            cf.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+            PYRAMID
+        );
+    }
+}
+
+#[test]
+fn test_max_ref_depth_holds_with_reextension() {
+    let config = Config {
+        max_ref_depth: Some(2),
+        reextend: true,
+        ..Config::default()
+    };
+    for seed in [4, 13] {
+        let messages = generate_thread(seed, 30, 4);
+        let cf = approximate(&messages, config.clone());
+        assert!(chain_depth(&cf.segments()) <= 2);
+        assert_eq!(cf.render_with(|_, _, _, text| text.to_string()), messages);
+    }
+}
+
+#[test]
+fn test_optimal_honors_max_ref_depth() {
+    for max in [0, 1, 2] {
+        let config = Config {
+            max_ref_depth: Some(max),
+            ..Config::default()
+        };
+        for seed in [4, 13] {
+            let messages = generate_thread(seed, 20, 4);
+            let cf = optimal(&messages, config.clone());
+            assert!(chain_depth(&cf.segments()) <= max);
+            assert_eq!(cf.render_with(|_, _, _, text| text.to_string()), messages);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use copyforward::{
+    Config, CopyForward, CopyForwardBytes, CopyForwardTokens, approximate, approximate_bytes,
+    approximate_tokens, exact, exact_bytes, exact_tokens, optimal, optimal_bytes,
+};
+
+const MESSAGES: [&str; 3] = ["Hello world", "Hello world today", "today and tomorrow"];
+
+fn text_algorithm(name: &str) -> Box<dyn CopyForward> {
+    match name {
+        "exact" => Box::new(exact(&MESSAGES, Config::default())),
+        "optimal" => Box::new(optimal(&MESSAGES, Config::default())),
+        _ => Box::new(approximate(&MESSAGES, Config::default())),
+    }
+}
+
+#[test]
+fn test_boxed_text_algorithms_render_like_concrete_types() {
+    for name in ["exact", "approximate", "optimal"] {
+        let cf = text_algorithm(name);
+        assert_eq!(cf.segments().len(), MESSAGES.len());
+        let rendered = cf.render_with_dyn(&mut |_, _, _, text| text.to_string());
+        assert_eq!(rendered, MESSAGES);
+        assert_eq!(
+            cf.render_with_static("[R]"),
+            cf.render_with_dyn(&mut |_, _, _, _| "[R]".to_string())
+        );
+
+        let mut buf = String::new();
+        cf.render_into_dyn(1, &mut buf, &mut |_, _, _, _| "[R]".to_string());
+        assert_eq!(buf, cf.render_with_static("[R]")[1]);
+    }
+}
+
+#[test]
+fn test_boxed_token_algorithms_render() {
+    let tokens: Vec<Vec<u32>> = vec![vec![1, 2, 3, 4, 5], vec![9, 1, 2, 3, 4, 5]];
+    let algorithms: Vec<Box<dyn CopyForwardTokens>> = vec![
+        Box::new(exact_tokens(&tokens, Config::default())),
+        Box::new(approximate_tokens(&tokens, Config::default())),
+    ];
+    for cf in algorithms {
+        assert_eq!(cf.render_with_dyn(&mut |_, _, _, t| t.to_vec()), tokens);
+        assert_eq!(cf.render_with_static(&[0])[1], [9, 0]);
+    }
+}
+
+#[test]
+fn test_boxed_byte_algorithms_render() {
+    let first = [b"Hello \xffworld, ".repeat(5).as_slice(), b"!"].concat();
+    let second = [first.as_slice(), b" today"].concat();
+    let messages = [first.as_slice(), second.as_slice()];
+    let algorithms: Vec<Box<dyn CopyForwardBytes>> = vec![
+        Box::new(exact_bytes(&messages, Config::default())),
+        Box::new(approximate_bytes(&messages, Config::default())),
+        Box::new(optimal_bytes(&messages, Config::default())),
+    ];
+    for cf in algorithms {
+        assert_eq!(cf.render_with_dyn(&mut |_, _, _, b| b.to_vec()), messages);
+        assert_eq!(cf.render_with_static(b"[R]")[1], b"[R] today");
+    }
+}
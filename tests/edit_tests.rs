@@ -0,0 +1,96 @@
+use copyforward::edit::{Edit, EditError, remap_after_edits};
+use copyforward::render::reconstruct;
+use copyforward::{Config, CopyForward, Segment, exact};
+
+const THREAD: [&str; 3] = [
+    "Release notes: faster startup, fewer crashes.",
+    "Release notes: faster startup, fewer crashes.\nShip it.",
+    "faster startup, fewer crashes.\nAgreed.",
+];
+
+fn segments() -> Vec<Vec<Segment>> {
+    let config = Config {
+        min_match_len: 8,
+        ..Config::default()
+    };
+    exact(&THREAD, config).segments()
+}
+
+#[test]
+fn test_undisturbed_references_shift_with_the_edit() {
+    let segs = vec![
+        vec![Segment::Literal("Release notes: faster startup.".into())],
+        vec![
+            Segment::Reference {
+                message_idx: 0,
+                start: 15,
+                len: 15,
+            },
+            Segment::Literal(" Yes.".into()),
+        ],
+    ];
+    let out = remap_after_edits(&segs, 0, &[Edit::replace(0..13, "Changes")]).unwrap();
+    assert_eq!(
+        out[1][0],
+        Segment::Reference {
+            message_idx: 0,
+            start: 9,
+            len: 15,
+        }
+    );
+    let texts = reconstruct(&out).unwrap();
+    assert_eq!(texts, ["Changes: faster startup.", "faster startup. Yes."]);
+}
+
+#[test]
+fn test_disturbed_references_are_materialized() {
+    let segs = segments();
+    let out = remap_after_edits(&segs, 0, &[Edit::replace(0..13, "Changes")]).unwrap();
+    let texts = reconstruct(&out).unwrap();
+    assert_eq!(texts[0], "Changes: faster startup, fewer crashes.");
+    assert_eq!(&texts[1..], &THREAD[1..]);
+    // Message 1 quoted all of message 0, so that reference becomes literal text.
+    assert_eq!(out[1], [Segment::Literal(THREAD[1].into())]);
+}
+
+#[test]
+fn test_edits_to_referenced_messages_keep_unaffected_references() {
+    let segs = segments();
+    let out = remap_after_edits(&segs, 1, &[Edit::insert(THREAD[1].len(), " Today.")]).unwrap();
+    let texts = reconstruct(&out).unwrap();
+    assert_eq!(texts[1], format!("{} Today.", THREAD[1]));
+    assert_eq!(texts[2], THREAD[2]);
+    assert_eq!(out[2], segs[2]);
+}
+
+#[test]
+fn test_edit_inside_reference_of_edited_message_splits_it() {
+    let segs = segments();
+    let out = remap_after_edits(&segs, 1, &[Edit::delete(15..22)]).unwrap();
+    let texts = reconstruct(&out).unwrap();
+    assert_eq!(texts[1], "Release notes: startup, fewer crashes.\nShip it.");
+    let references = out[1]
+        .iter()
+        .filter(|s| matches!(s, Segment::Reference { .. }))
+        .count();
+    assert_eq!(references, 2);
+    assert_eq!(texts[2], THREAD[2]);
+}
+
+#[test]
+fn test_invalid_edits_are_rejected() {
+    let segs = segments();
+    let overlapping = [Edit::delete(0..10), Edit::delete(5..12)];
+    assert_eq!(
+        remap_after_edits(&segs, 0, &overlapping),
+        Err(EditError::InvalidEdit { edit: 1 })
+    );
+    assert_eq!(
+        remap_after_edits(&segs, 0, &[Edit::insert(1000, "x")]),
+        Err(EditError::InvalidEdit { edit: 0 })
+    );
+    assert_eq!(
+        remap_after_edits(&segs, 7, &[]),
+        Err(EditError::UnknownMessage(7))
+    );
+}
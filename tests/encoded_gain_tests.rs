@@ -0,0 +1,116 @@
+use copyforward::codec::{decode_segments, encode_segments};
+use copyforward::fixture::generate_thread;
+use copyforward::render::reconstruct;
+use copyforward::{Config, CopyForward, Segment, TieBreak, approximate, exact, optimal};
+
+type Algorithm = fn(&[&str], Config) -> Box<dyn CopyForward>;
+
+fn algorithms() -> [(&'static str, Algorithm); 3] {
+    [
+        ("exact", |m, c| Box::new(exact(m, c))),
+        ("approximate", |m, c| Box::new(approximate(m, c))),
+        ("optimal", |m, c| Box::new(optimal(m, c))),
+    ]
+}
+
+fn with_gain(min_encoded_gain: Option<usize>) -> Config {
+    Config {
+        min_encoded_gain,
+        ..Config::default()
+    }
+}
+
+fn references(model: &dyn CopyForward) -> Vec<usize> {
+    (0..model.len())
+        .flat_map(|idx| model.segments_for(idx).to_vec())
+        .filter_map(|seg| match seg {
+            Segment::Reference { len, .. } => Some(len),
+            Segment::Literal(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_matches_saving_less_than_the_gain_become_literals() {
+    let messages = [
+        "The quarterly report is attached; numbers look fine overall.",
+        "Thanks. report is what I needed, will read tonight",
+    ];
+    let paying = exact(&messages, with_gain(Some(1)));
+    assert!(references(&paying).contains(&11));
+    let gained = exact(&messages, with_gain(Some(16)));
+    assert_eq!(
+        gained.segments_for(1),
+        [Segment::Literal(messages[1].into())]
+    );
+}
+
+#[test]
+fn test_long_quotes_are_kept() {
+    let quote = "Please review the attached deployment checklist before Friday's release window.";
+    let reply = format!("> {quote}\nLooks good to me.");
+    let messages = [quote, reply.as_str()];
+    for (name, algorithm) in algorithms() {
+        let model = algorithm(&messages, with_gain(Some(8)));
+        assert!(
+            references(model.as_ref()).contains(&quote.len()),
+            "{name} dropped a long quote"
+        );
+    }
+}
+
+#[test]
+fn test_gain_never_grows_the_encoding() {
+    let thread = generate_thread(7, 12, 4);
+    let messages: Vec<&str> = thread.iter().map(String::as_str).collect();
+    for (name, algorithm) in algorithms() {
+        let plain = algorithm(&messages, with_gain(None));
+        let gained = algorithm(&messages, with_gain(Some(1)));
+        assert!(
+            encode_segments(&gained.segments()).len() <= encode_segments(&plain.segments()).len(),
+            "{name} grew"
+        );
+        assert_eq!(reconstruct(&gained.segments()).unwrap(), messages);
+    }
+}
+
+#[test]
+fn test_larger_gains_keep_fewer_references() {
+    let thread = generate_thread(11, 12, 4);
+    let messages: Vec<&str> = thread.iter().map(String::as_str).collect();
+    let mut previous = usize::MAX;
+    for gain in [None, Some(1), Some(16), Some(64)] {
+        let model = exact(&messages, with_gain(gain));
+        let count = references(&model).len();
+        assert!(count <= previous, "{gain:?} kept more references");
+        previous = count;
+    }
+}
+
+#[test]
+fn test_push_message_applies_the_gain() {
+    let messages = [
+        "The quarterly report is attached; numbers look fine overall.",
+        "Thanks. report is what I needed, will read tonight",
+    ];
+    let config = with_gain(Some(16));
+    let mut model = exact(&messages[..1], config.clone());
+    model.push_message(messages[1]);
+    assert_eq!(model.segments(), exact(&messages, config).segments());
+}
+
+#[test]
+fn test_gained_self_references_survive_the_codec() {
+    let messages = ["xy日本日本日本"];
+    let config = Config {
+        min_match_len: 1,
+        self_references: true,
+        tie_break: TieBreak::PreferSameSourceAsPrevSegment,
+        min_encoded_gain: Some(1),
+        ..Config::default()
+    };
+    let model = exact(&messages, config);
+    assert_eq!(model.verify(), Ok(()));
+    let decoded = decode_segments(&encode_segments(&model.segments())).unwrap();
+    assert_eq!(decoded, model.segments());
+}
@@ -0,0 +1,85 @@
+use copyforward::fixture::generate_thread;
+use copyforward::flatten::{Flattened, resolve_to_origin};
+use copyforward::render::{ReconstructError, chain_depth, locate, reconstruct};
+use copyforward::{Config, CopyForward, Segment, approximate, exact};
+
+#[test]
+fn test_locate_matches_chain_walking() {
+    let messages = generate_thread(6, 25, 4);
+    for segments in [
+        exact(&messages, Config::default()).segments(),
+        approximate(&messages, Config::default()).segments(),
+    ] {
+        let flat = Flattened::new(&segments).unwrap();
+        for (m, text) in messages.iter().enumerate() {
+            assert_eq!(flat.message(m), text);
+            for offset in 0..=text.len() {
+                assert_eq!(flat.locate(m, offset), locate(&segments, m, offset));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_flattened_segments_are_one_level_deep() {
+    let messages = generate_thread(8, 25, 4);
+    let segments = exact(&messages, Config::default()).segments();
+    let flat = Flattened::new(&segments).unwrap();
+    let flattened = flat.segments();
+    assert_eq!(reconstruct(&flattened).unwrap(), messages);
+    for (m, text) in messages.iter().enumerate() {
+        assert!(chain_depth(&flattened, m, 0..text.len()).unwrap() <= 1);
+        let covered: usize = flat.pieces(m).iter().map(|p| p.output.len()).sum();
+        assert_eq!(covered, text.len());
+    }
+}
+
+#[test]
+fn test_render_with_passes_origins() {
+    let messages = [
+        "Can we ship?",
+        "Yes.\n> Can we ship?",
+        "Great\n> Yes.\n> Can we ship?",
+    ];
+    let flat = Flattened::new(&exact(&messages, Config::default()).segments()).unwrap();
+    let rendered = flat.render_with(|m, start, len, text| {
+        assert_eq!(&messages[m][start..start + len], text);
+        format!("<{m}>")
+    });
+    assert_eq!(rendered[0], messages[0]);
+    assert!(rendered[2].ends_with("<0>"));
+    assert!(!rendered[2].contains("Can we"));
+}
+
+#[test]
+fn test_new_rejects_invalid_segments() {
+    let forward = vec![vec![Segment::Reference {
+        message_idx: 1,
+        start: 0,
+        len: 1,
+    }]];
+    assert!(matches!(
+        Flattened::new(&forward),
+        Err(ReconstructError::ForwardReference { .. })
+    ));
+}
+
+#[test]
+fn test_resolve_to_origin_points_at_literals() {
+    let messages = generate_thread(9, 25, 4);
+    let segments = exact(&messages, Config::default()).segments();
+    let resolved = resolve_to_origin(&segments).unwrap();
+    assert_eq!(resolved, Flattened::new(&segments).unwrap().segments());
+    for segs in &resolved {
+        for seg in segs {
+            if let Segment::Reference {
+                message_idx, start, ..
+            } = seg
+            {
+                let origin = locate(&resolved, *message_idx, *start).unwrap();
+                assert_eq!(origin.source_message, *message_idx);
+            }
+        }
+    }
+    assert_eq!(reconstruct(&resolved).unwrap(), messages);
+}
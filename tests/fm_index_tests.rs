@@ -0,0 +1,145 @@
+use copyforward::Segment;
+use copyforward::fm_index::FmIndex;
+
+/// Deterministic pseudo-random text over a small alphabet so repeats are common.
+fn noisy_text(seed: u64, len: usize) -> String {
+    const ALPHABET: [char; 6] = ['a', 'b', 'c', ' ', 'é', '文'];
+    let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ALPHABET[(state >> 33) as usize % ALPHABET.len()]
+        })
+        .collect()
+}
+
+fn referenced<'a>(archive: &'a [String], segment: &Segment) -> &'a str {
+    match segment {
+        Segment::Reference {
+            message_idx,
+            start,
+            len,
+        } => &archive[*message_idx][*start..*start + *len],
+        Segment::Literal(text) => panic!("expected a reference, got literal {text:?}"),
+    }
+}
+
+/// Longest prefix of `pattern` (in chars) found in any archive message.
+fn brute_force_longest(archive: &[String], pattern: &str) -> usize {
+    let pattern: Vec<char> = pattern.chars().collect();
+    archive
+        .iter()
+        .flat_map(|message| {
+            let chars: Vec<char> = message.chars().collect();
+            (0..chars.len())
+                .map(|start| {
+                    chars[start..]
+                        .iter()
+                        .zip(&pattern)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                })
+                .collect::<Vec<_>>()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_longest_match_agrees_with_brute_force() {
+    let archive: Vec<String> = (0..4).map(|seed| noisy_text(seed, 300)).collect();
+    let index = FmIndex::new(&archive);
+    for seed in 10..40 {
+        let pattern = noisy_text(seed, 12);
+        let expected = brute_force_longest(&archive, &pattern);
+        match index.longest_match(&pattern) {
+            Some(segment) => {
+                let found = referenced(&archive, &segment);
+                assert_eq!(found.chars().count(), expected, "pattern {pattern:?}");
+                assert!(pattern.starts_with(found));
+            }
+            None => assert_eq!(expected, 0),
+        }
+    }
+}
+
+#[test]
+fn test_segment_reconstructs_query() {
+    let archive: Vec<String> = (0..3).map(|seed| noisy_text(seed, 500)).collect();
+    let index = FmIndex::new(&archive);
+    let quoted: String = archive[1].chars().skip(40).take(160).collect();
+    let query = format!("{quoted} {}", noisy_text(99, 80));
+    let segments = index.segment(&query, 6);
+    let rebuilt: String = segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Literal(text) => text.as_str(),
+            reference => referenced(&archive, reference),
+        })
+        .collect();
+    assert_eq!(rebuilt, query);
+    assert!(segments.iter().all(|segment| match segment {
+        Segment::Reference { len, .. } => *len >= 6,
+        Segment::Literal(_) => true,
+    }));
+}
+
+#[test]
+fn test_matches_never_span_messages() {
+    let index = FmIndex::new(&["hello", "world"]);
+    assert_eq!(
+        index.segment("helloworld", 3),
+        [
+            Segment::Reference {
+                message_idx: 0,
+                start: 0,
+                len: 5,
+            },
+            Segment::Reference {
+                message_idx: 1,
+                start: 0,
+                len: 5,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_multibyte_reference_uses_byte_offsets() {
+    let index = FmIndex::new(&["日本語のテキスト"]);
+    assert_eq!(
+        index.longest_match("のテキ!"),
+        Some(Segment::Reference {
+            message_idx: 0,
+            start: 9,
+            len: 9,
+        })
+    );
+}
+
+#[test]
+fn test_missing_messages_keep_their_index() {
+    let index = FmIndex::new(&[None, Some("shared text")]);
+    assert_eq!(index.len(), 2);
+    assert_eq!(
+        index.longest_match("shared"),
+        Some(Segment::Reference {
+            message_idx: 1,
+            start: 0,
+            len: 6,
+        })
+    );
+}
+
+#[test]
+fn test_unknown_characters_stay_literal() {
+    let index = FmIndex::new(&["abc"]);
+    assert_eq!(index.longest_match("xyz"), None);
+    assert_eq!(index.segment("xyz", 1), [Segment::Literal("xyz".into())]);
+
+    let empty = FmIndex::new::<&str>(&[]);
+    assert!(empty.is_empty());
+    assert_eq!(empty.segment("abc", 1), [Segment::Literal("abc".into())]);
+}
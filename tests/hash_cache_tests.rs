@@ -0,0 +1,94 @@
+use copyforward::hashing::HashCache;
+use copyforward::{Config, CopyForward, CopyForwardTokens, approximate, approximate_tokens, exact};
+
+const FOOTER: &str =
+    "-- \nThis message was sent from the shared support inbox. Please do not reply.";
+
+fn threads() -> Vec<Vec<String>> {
+    [
+        "Printer on floor 3 is jammed.",
+        "VPN drops every hour.",
+        "Badge reader is offline.",
+    ]
+    .iter()
+    .map(|opener| {
+        vec![
+            format!("{opener}\n{FOOTER}"),
+            format!("> {opener}\nLooking into it.\n{FOOTER}"),
+        ]
+    })
+    .collect()
+}
+
+#[test]
+fn test_cached_runs_match_uncached_runs() {
+    let cached = Config {
+        hash_cache: Some(HashCache::new(1_000)),
+        ..Config::default()
+    };
+    for thread in threads() {
+        assert_eq!(
+            exact(&thread, cached.clone()).segments(),
+            exact(&thread, Config::default()).segments()
+        );
+        assert_eq!(
+            approximate(&thread, cached.clone()).segments(),
+            approximate(&thread, Config::default()).segments()
+        );
+    }
+}
+
+#[test]
+fn test_cache_is_shared_across_runs_by_content() {
+    let cache = HashCache::new(1_000);
+    let config = Config {
+        hash_cache: Some(cache.clone()),
+        ..Config::default()
+    };
+    let thread = &threads()[0];
+    exact(thread, config.clone());
+    let after_first = cache.len();
+    exact(thread, config.clone());
+    approximate(thread, config.clone());
+    assert_eq!(cache.len(), after_first);
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_cache_stops_growing_at_capacity() {
+    let cache = HashCache::new(2);
+    let config = Config {
+        hash_cache: Some(cache.clone()),
+        ..Config::default()
+    };
+    let tokens: Vec<Vec<u32>> = (0..5)
+        .map(|i| vec![i, i + 1, i + 2, i + 3, i + 4])
+        .collect();
+    let rendered = approximate_tokens(&tokens, config).render_with(|_, _, _, t| t.to_vec());
+    assert_eq!(rendered, tokens);
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn test_cache_keeps_kmer_lengths_apart() {
+    let cache = HashCache::new(1_000);
+    let thread = &threads()[0];
+    for min_match_len in [4, 8, 4] {
+        let cached = Config {
+            min_match_len,
+            hash_cache: Some(cache.clone()),
+            ..Config::default()
+        };
+        let uncached = Config {
+            min_match_len,
+            ..Config::default()
+        };
+        assert_eq!(
+            exact(thread, cached).segments(),
+            exact(thread, uncached).segments()
+        );
+    }
+    assert_eq!(cache.len(), 2 * thread.len());
+}
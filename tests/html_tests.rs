@@ -0,0 +1,54 @@
+use copyforward::Thread;
+use copyforward::html::{render_index, render_thread};
+use copyforward::render::Reconstructed;
+use copyforward::{Config, Segment, exact};
+
+#[test]
+fn test_render_thread_links_quotes_to_their_sources() {
+    let model = Reconstructed::new(vec![
+        vec![Segment::Literal("Ship it on Friday".into())],
+        vec![
+            Segment::Literal("> ".into()),
+            Segment::Reference {
+                message_idx: 0,
+                start: 0,
+                len: 17,
+            },
+            Segment::Literal("\nAgreed".into()),
+        ],
+    ])
+    .unwrap();
+    let page = render_thread(&model, "Release");
+    assert!(page.starts_with("<!DOCTYPE html>"));
+    assert!(page.contains("<title>Release</title>"));
+    assert!(page.contains("<section id=\"m0\">"));
+    assert!(page.contains(
+        "<pre>&gt; <a class=\"quote\" href=\"#m0\" title=\"message 0, bytes 0..17\">\
+         Ship it on Friday</a>\nAgreed</pre>"
+    ));
+}
+
+#[test]
+fn test_render_thread_escapes_markup() {
+    let messages = ["<script>alert('x')</script> & more", "plain"];
+    let model = exact(&messages, Config::default());
+    let page = render_thread(&model, "a \"quoted\" <title>");
+    assert!(!page.contains("<script>"));
+    assert!(page.contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; more"));
+    assert!(page.contains("<title>a &quot;quoted&quot; &lt;title&gt;</title>"));
+}
+
+#[test]
+fn test_render_index_lists_every_page() {
+    let threads = [
+        Thread::new("a&b", vec!["one".into()]),
+        Thread::new("c", vec!["one".into(), "two".into()]),
+    ];
+    let pages = [
+        ("a_b.html".to_string(), &threads[0]),
+        ("c.html".to_string(), &threads[1]),
+    ];
+    let index = render_index("Threads", &pages);
+    assert!(index.contains("<li><a href=\"a_b.html\">a&amp;b</a> (1 message)</li>"));
+    assert!(index.contains("<li><a href=\"c.html\">c</a> (2 messages)</li>"));
+}
@@ -0,0 +1,70 @@
+use copyforward::Thread;
+use copyforward::jsonl::{JsonlOptions, read_threads, write_threads};
+
+fn by_thread() -> JsonlOptions {
+    JsonlOptions {
+        thread_field: Some("thread".to_string()),
+        ..JsonlOptions::default()
+    }
+}
+
+#[test]
+fn test_read_threads_groups_by_thread_field() {
+    let input = "{\"thread\": \"T1\", \"text\": \"My printer is broken\"}\n\
+                 {\"thread\": 2, \"text\": \"How do I reset my password?\"}\n\
+                 \n\
+                 {\"text\": \"Sorry.\\n> My printer is broken\", \"thread\": \"T1\"}\n";
+    let threads = read_threads(input.as_bytes(), &by_thread()).unwrap();
+    assert_eq!(threads.len(), 2);
+    assert_eq!(threads[0].id, "T1");
+    assert_eq!(
+        threads[0].messages,
+        ["My printer is broken", "Sorry.\n> My printer is broken"]
+    );
+    assert_eq!(threads[1].id, "2");
+}
+
+#[test]
+fn test_read_threads_decodes_escapes_and_skips_other_values() {
+    let input = r#"{"meta": {"tags": ["a}", "b]"], "ok": true, "n": null}, "text": "tab\there \"q\" é 😀 \/"}"#;
+    let threads = read_threads(input.as_bytes(), &JsonlOptions::default()).unwrap();
+    assert_eq!(threads[0].id, "all");
+    assert_eq!(threads[0].messages, ["tab\there \"q\" é 😀 /"]);
+}
+
+#[test]
+fn test_read_threads_reports_bad_lines() {
+    let cases = [
+        "{\"text\": \"ok\"}\n[1, 2]\n",
+        "{\"text\": \"unterminated}\n",
+        "{\"body\": \"no text field\"}\n",
+        "{\"text\": 5}\n",
+        "{\"text\": \"x\"} trailing\n",
+    ];
+    for input in cases {
+        let err = read_threads(input.as_bytes(), &JsonlOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "{input}");
+    }
+    let err = read_threads("{\"text\": \"x\"}\n".as_bytes(), &by_thread()).unwrap_err();
+    assert!(err.to_string().starts_with("line 1:"));
+}
+
+#[test]
+fn test_write_threads_round_trips() {
+    let threads = vec![
+        Thread::new(
+            "a \"1\"",
+            vec!["line\n\tbreak \\ \u{1} é".into(), "".into()],
+        ),
+        Thread::new("b", vec!["> quoted".into()]),
+    ];
+    let mut out = Vec::new();
+    write_threads(&mut out, &threads, &by_thread()).unwrap();
+    assert_eq!(String::from_utf8_lossy(&out).lines().count(), 3);
+    assert_eq!(read_threads(out.as_slice(), &by_thread()).unwrap(), threads);
+
+    let mut out = Vec::new();
+    write_threads(&mut out, &threads, &JsonlOptions::default()).unwrap();
+    let merged = read_threads(out.as_slice(), &JsonlOptions::default()).unwrap();
+    assert_eq!(merged[0].messages.len(), 3);
+}
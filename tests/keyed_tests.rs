@@ -0,0 +1,72 @@
+use copyforward::fixture::generate_thread;
+use copyforward::keyed::{KeyError, KeyedSegment, from_keyed, to_keyed};
+use copyforward::render::{ReconstructError, reconstruct};
+use copyforward::{Config, CopyForward, Segment, exact};
+
+#[test]
+fn test_keyed_round_trip_restores_segments() {
+    let messages = generate_thread(2, 20, 4);
+    let segments = exact(&messages, Config::default()).segments();
+    let keys: Vec<String> = (0..messages.len())
+        .map(|i| format!("<msg-{i}@example>"))
+        .collect();
+    let keyed = to_keyed(&segments, &keys).unwrap();
+    assert_eq!(from_keyed(&keyed, &keys).unwrap(), segments);
+}
+
+#[test]
+fn test_keyed_segments_survive_deletion() {
+    let messages = [
+        "The release branch is frozen until Friday.",
+        "Unrelated lunch plans",
+        "> The release branch is frozen until Friday.\nThanks!",
+    ];
+    let keys = [7u64, 8, 9];
+    let keyed = to_keyed(&exact(&messages, Config::default()).segments(), &keys).unwrap();
+
+    // Message 8 is deleted; message 9 moves to position 1.
+    let kept = [keyed[0].clone(), keyed[2].clone()];
+    let segments = from_keyed(&kept, &[7, 9]).unwrap();
+    assert!(segments[1].contains(&Segment::Reference {
+        message_idx: 0,
+        start: 0,
+        len: 42
+    }));
+    assert_eq!(reconstruct(&segments).unwrap(), [messages[0], messages[2]]);
+}
+
+#[test]
+fn test_from_keyed_rejects_deleted_sources() {
+    let keyed = [
+        vec![KeyedSegment::Literal("Hello world".into())],
+        vec![KeyedSegment::Reference {
+            message: "a",
+            start: 0,
+            len: 5,
+        }],
+    ];
+    assert_eq!(
+        from_keyed(&keyed[1..], &["b"]),
+        Err(KeyError::UnknownKey {
+            message: 0,
+            segment: 0
+        })
+    );
+    let reordered = from_keyed(&[keyed[1].clone(), keyed[0].clone()], &["b", "a"]).unwrap();
+    assert_eq!(
+        reconstruct(&reordered),
+        Err(ReconstructError::ForwardReference {
+            message: 0,
+            segment: 0
+        })
+    );
+}
+
+#[test]
+fn test_repeated_keys_are_rejected() {
+    let segments = exact(&["Hello world", "Hello world today"], Config::default()).segments();
+    assert_eq!(
+        to_keyed(&segments, &[1, 1]),
+        Err(KeyError::DuplicateKey { message: 1 })
+    );
+}
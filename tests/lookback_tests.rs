@@ -0,0 +1,101 @@
+use copyforward::fixture::generate_thread;
+use copyforward::{
+    Config, CopyForward, CopyForwardTokens, Segment, TokenSegment, approximate, approximate_tokens,
+    exact, exact_tokens, optimal,
+};
+
+fn models(messages: &[String], config: Config) -> Vec<Box<dyn CopyForward>> {
+    vec![
+        Box::new(exact(messages, config.clone())),
+        Box::new(approximate(messages, config.clone())),
+        Box::new(optimal(messages, config)),
+    ]
+}
+
+#[test]
+fn test_references_stay_inside_the_lookback_window() {
+    let messages = generate_thread(11, 24, 4);
+    for lookback in [0, 1, 3] {
+        let config = Config {
+            lookback: Some(lookback),
+            ..Config::default()
+        };
+        for cf in models(&messages, config) {
+            for (idx, segs) in cf.segments().iter().enumerate() {
+                for seg in segs {
+                    if let Segment::Reference { message_idx, .. } = seg {
+                        assert!(
+                            idx - message_idx <= lookback,
+                            "#{idx} references #{message_idx} with lookback {lookback}"
+                        );
+                    }
+                }
+            }
+            assert_eq!(
+                cf.render_with_dyn(&mut |_, _, _, t| t.to_string()),
+                messages
+            );
+        }
+    }
+}
+
+#[test]
+fn test_window_covering_the_thread_matches_unlimited() {
+    let messages = generate_thread(5, 12, 4);
+    let windowed = Config {
+        lookback: Some(messages.len()),
+        ..Config::default()
+    };
+    assert_eq!(
+        exact(&messages, windowed.clone()).segments(),
+        exact(&messages, Config::default()).segments()
+    );
+}
+
+#[test]
+fn test_text_repeated_inside_the_window_is_still_found() {
+    let messages = [
+        "Old announcement nobody quotes anymore.",
+        "The release branch is frozen until Friday.",
+        "Filler message one.",
+        "Reminder: the release branch is frozen until Friday.",
+    ];
+    let config = Config {
+        lookback: Some(2),
+        ..Config::default()
+    };
+    for cf in models(&messages.map(String::from), config) {
+        let segments = cf.segments();
+        assert!(segments[3].iter().any(|seg| matches!(
+            seg,
+            Segment::Reference { message_idx: 1, len, .. } if *len >= 30
+        )));
+    }
+}
+
+#[test]
+fn test_token_references_stay_inside_the_lookback_window() {
+    let messages: Vec<Vec<u32>> = generate_thread(11, 24, 4)
+        .iter()
+        .map(|m| m.chars().map(u32::from).collect())
+        .collect();
+    let refs: Vec<&[u32]> = messages.iter().map(Vec::as_slice).collect();
+    let config = Config {
+        lookback: Some(2),
+        ..Config::default()
+    };
+    let models: Vec<Box<dyn CopyForwardTokens>> = vec![
+        Box::new(exact_tokens(&refs, config.clone())),
+        Box::new(approximate_tokens(&refs, config)),
+    ];
+    for cf in models {
+        for (idx, segs) in cf.segments().iter().enumerate() {
+            for seg in segs {
+                if let TokenSegment::Reference { message_idx, .. } = seg {
+                    assert!(idx - message_idx <= 2, "#{idx} references #{message_idx}");
+                }
+            }
+        }
+        assert_eq!(cf.render_with_dyn(&mut |_, _, _, t| t.to_vec()), messages);
+    }
+}
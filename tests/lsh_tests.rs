@@ -0,0 +1,98 @@
+use copyforward::fixture::generate_thread;
+use copyforward::{Config, CopyForward, Lsh, Segment, approximate, exact};
+
+fn models(messages: &[String], config: Config) -> Vec<Box<dyn CopyForward>> {
+    vec![
+        Box::new(exact(messages, config.clone())),
+        Box::new(approximate(messages, config)),
+    ]
+}
+
+fn references(segments: &[Segment]) -> Vec<usize> {
+    segments
+        .iter()
+        .filter_map(|seg| match seg {
+            Segment::Reference { message_idx, .. } => Some(*message_idx),
+            Segment::Literal(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_lsh_roundtrips_generated_threads() {
+    for seed in [3, 17] {
+        let messages = generate_thread(seed, 30, 5);
+        for lsh in [Lsh::default(), Lsh { bands: 2, rows: 3 }] {
+            let config = Config {
+                lsh: Some(lsh),
+                ..Config::default()
+            };
+            for cf in models(&messages, config) {
+                assert_eq!(
+                    cf.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+                    messages
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_lsh_keeps_quotes_of_similar_messages() {
+    let original = "The migration to the new billing provider starts on Monday morning.";
+    let messages = [
+        original.to_string(),
+        "Unrelated: the cafeteria menu changes on Monday morning too.".to_string(),
+        format!("Sounds good.\n> {original}"),
+    ];
+    let config = Config {
+        min_match_len: 8,
+        lsh: Some(Lsh::default()),
+        ..Config::default()
+    };
+    for cf in models(&messages, config) {
+        assert_eq!(references(cf.segments_for(2)), [0]);
+    }
+}
+
+#[test]
+fn test_lsh_skips_dissimilar_messages() {
+    let messages = [
+        "Quarterly numbers: revenue grew, churn fell, and hiring stays frozen until May.",
+        "Garden club notes: tomatoes need staking, and hiring stays frozen until May.",
+    ];
+    let plain = Config {
+        min_match_len: 8,
+        ..Config::default()
+    };
+    let strict = Config {
+        lsh: Some(Lsh { bands: 1, rows: 8 }),
+        ..plain.clone()
+    };
+    for cf in models(&messages.map(String::from), plain) {
+        assert_eq!(references(cf.segments_for(1)), [0]);
+    }
+    for cf in models(&messages.map(String::from), strict) {
+        assert!(references(cf.segments_for(1)).is_empty());
+        assert_eq!(cf.message(1), messages[1]);
+    }
+}
+
+#[test]
+fn test_lsh_respects_lookback_eviction() {
+    let messages = generate_thread(5, 20, 3);
+    let config = Config {
+        lookback: Some(2),
+        lsh: Some(Lsh::default()),
+        ..Config::default()
+    };
+    for cf in models(&messages, config) {
+        for (idx, segs) in cf.segments().iter().enumerate() {
+            assert!(references(segs).iter().all(|&m| idx - m <= 2));
+        }
+        assert_eq!(
+            cf.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+            messages
+        );
+    }
+}
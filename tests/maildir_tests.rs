@@ -0,0 +1,74 @@
+use copyforward::maildir::{parse_message, read_threads};
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_maildir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "copyforward-maildir-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    for sub in ["cur", "new", "tmp"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_parse_message_headers_and_body() {
+    let raw = "Message-ID: <b@example.com>\r\n\
+               subject: Re: hello\r\n\
+               References: <a@example.com>\r\n\
+               \t<x@example.com>\r\n\
+               In-Reply-To: <a@example.com>\r\n\
+               \r\n\
+               Body line\r\n> quoted\r\n";
+    let msg = parse_message(raw);
+    assert_eq!(msg.message_id.as_deref(), Some("b@example.com"));
+    assert_eq!(msg.in_reply_to.as_deref(), Some("a@example.com"));
+    assert_eq!(msg.references, vec!["a@example.com", "x@example.com"]);
+    assert_eq!(msg.subject.as_deref(), Some("Re: hello"));
+    assert_eq!(msg.body, "Body line\n> quoted\n");
+}
+
+#[test]
+fn test_read_threads_groups_by_references() {
+    let dir = temp_maildir("threads");
+    fs::write(
+        dir.join("cur/1000.1.host:2,S"),
+        "Message-ID: <root@x>\n\nHello list, here is my question.\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("cur/1001.1.host:2,S"),
+        "Message-ID: <other@x>\n\nAn unrelated announcement.\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("new/1002.1.host"),
+        "Message-ID: <reply@x>\nIn-Reply-To: <root@x>\nReferences: <root@x>\n\n\
+         Answer.\n> Hello list, here is my question.\n",
+    )
+    .unwrap();
+    fs::write(dir.join("tmp/1003.1.host"), "Message-ID: <partial@x>\n\n").unwrap();
+
+    let threads = read_threads(&dir).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(threads.len(), 2);
+    assert_eq!(threads[0].id, "root@x");
+    assert_eq!(threads[0].messages.len(), 2);
+    assert!(threads[0].messages[1].starts_with("Answer."));
+    assert_eq!(threads[1].id, "other@x");
+    assert_eq!(threads[1].messages, vec!["An unrelated announcement.\n"]);
+}
+
+#[test]
+fn test_read_threads_rejects_non_maildir() {
+    let dir = std::env::temp_dir().join(format!("copyforward-not-maildir-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let result = read_threads(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(result.is_err());
+}
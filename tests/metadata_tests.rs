@@ -0,0 +1,129 @@
+use copyforward::archive::Archive;
+use copyforward::codec::{
+    DecodeError, decode_messages, decode_metadata, decode_segments, encode_model, encode_segments,
+};
+use copyforward::metadata::CRATE_VERSION;
+use copyforward::render::Reconstructed;
+use copyforward::{
+    Config, CopyForward, CopyForwardTokens, Lsh, TieBreak, approximate, approximate_tokens, exact,
+    exact_tokens, optimal,
+};
+
+fn tuned_config() -> Config {
+    Config {
+        min_match_len: 6,
+        lookback: Some(3),
+        cap_len: 32,
+        ncap: 16,
+        reextend: true,
+        stop_phrases: vec!["Best regards".into(), "—".into()],
+        kmer_density: 0.25,
+        skip_referenced_regions: true,
+        min_indexed_len: 2,
+        dedup_messages: true,
+        lsh: Some(Lsh { bands: 4, rows: 3 }),
+        shallow_preference: Some(5),
+        tie_break: TieBreak::PreferMostRecent,
+        max_ref_depth: Some(2),
+        ..Config::default()
+    }
+}
+
+fn assert_same_config(a: &Config, b: &Config) {
+    assert_eq!(format!("{a:?}"), format!("{b:?}"));
+}
+
+const MESSAGES: [&str; 3] = [
+    "Hello world, see you Monday",
+    "Hello world, see you Monday\nSure",
+    "> Hello world, see you Monday\n> Sure\nGreat",
+];
+
+#[test]
+fn test_models_report_their_algorithm_and_config() {
+    let config = tuned_config();
+    let models: [(&str, Box<dyn CopyForward>); 3] = [
+        ("exact", Box::new(exact(&MESSAGES, config.clone()))),
+        (
+            "approximate",
+            Box::new(approximate(&MESSAGES, config.clone())),
+        ),
+        ("optimal", Box::new(optimal(&MESSAGES, config.clone()))),
+    ];
+    for (algorithm, model) in models {
+        let metadata = model.metadata().unwrap();
+        assert_eq!(metadata.algorithm, algorithm);
+        assert_eq!(metadata.crate_version, CRATE_VERSION);
+        assert_same_config(&metadata.config, &config);
+    }
+    let tokens: [&[u32]; 2] = [&[1, 2, 3, 4], &[1, 2, 3, 4, 5]];
+    let config = Config::default();
+    let exact_metadata = exact_tokens(&tokens, config.clone()).metadata().unwrap();
+    assert_eq!(exact_metadata.algorithm, "exact");
+    let approximate_metadata = approximate_tokens(&tokens, config).metadata().unwrap();
+    assert_eq!(approximate_metadata.algorithm, "approximate");
+}
+
+#[test]
+fn test_codec_round_trips_metadata() {
+    let config = tuned_config();
+    let model = approximate(&MESSAGES, config.clone());
+    let bytes = encode_model(&model);
+    let metadata = decode_metadata(&bytes).unwrap().unwrap();
+    assert_eq!(metadata.algorithm, "approximate");
+    assert_eq!(metadata.crate_version, CRATE_VERSION);
+    assert_same_config(&metadata.config, &config);
+    assert_eq!(decode_segments(&bytes).unwrap(), model.segments());
+    assert_eq!(decode_messages(&bytes).unwrap(), MESSAGES);
+
+    let regenerated = approximate(&MESSAGES, metadata.config);
+    assert_eq!(encode_model(&regenerated), bytes);
+}
+
+#[test]
+fn test_segments_alone_carry_no_metadata() {
+    let segments = exact(&MESSAGES, Config::default()).segments();
+    assert!(
+        decode_metadata(&encode_segments(&segments))
+            .unwrap()
+            .is_none()
+    );
+    let loaded = Reconstructed::new(segments.clone()).unwrap();
+    assert!(loaded.metadata().is_none());
+    assert!(Archive::new(&segments).metadata().is_none());
+}
+
+#[test]
+fn test_reloaded_model_keeps_metadata() {
+    let bytes = encode_model(&exact(&MESSAGES, tuned_config()));
+    let loaded = Reconstructed::new(decode_segments(&bytes).unwrap())
+        .unwrap()
+        .with_metadata(decode_metadata(&bytes).unwrap());
+    assert_eq!(encode_model(&loaded), bytes);
+}
+
+#[test]
+fn test_archive_round_trips_metadata() {
+    let config = tuned_config();
+    let mut archive = Archive::from_model(&exact(&MESSAGES, config.clone()));
+    archive
+        .append(&["Great, thanks"], Config::default())
+        .unwrap();
+    let reopened = Archive::from_bytes(archive.into_bytes()).unwrap();
+    let metadata = reopened.metadata().unwrap();
+    assert_eq!(metadata.algorithm, "exact");
+    assert_same_config(&metadata.config, &config);
+    assert_eq!(reopened.message(2).unwrap(), MESSAGES[2]);
+    assert_eq!(reopened.message(3).unwrap(), "Great, thanks");
+}
+
+#[test]
+fn test_malformed_metadata_is_rejected() {
+    let mut bytes = encode_model(&exact(&MESSAGES, Config::default()));
+    bytes[5] = 7;
+    assert_eq!(
+        decode_metadata(&bytes).unwrap_err(),
+        DecodeError::InvalidMetadata
+    );
+    assert_eq!(decode_segments(&bytes), Err(DecodeError::InvalidMetadata));
+}
@@ -0,0 +1,53 @@
+use copyforward::{Config, CopyForward, Segment, approximate, exact};
+
+const THREAD: [&str; 4] = [
+    "Can someone restart the build agent?",
+    "thanks!",
+    "thanks! Can someone restart the build agent?",
+    "thanks!",
+];
+
+fn skipping_short(config: Config) -> Config {
+    Config {
+        min_indexed_len: 10,
+        ..config
+    }
+}
+
+#[test]
+fn test_short_messages_are_single_literals() {
+    for segments in [
+        exact(&THREAD, skipping_short(Config::default())).segments(),
+        approximate(&THREAD, skipping_short(Config::default())).segments(),
+    ] {
+        assert_eq!(segments[1], [Segment::Literal("thanks!".into())]);
+        assert_eq!(segments[3], [Segment::Literal("thanks!".into())]);
+    }
+}
+
+#[test]
+fn test_short_messages_are_never_referenced() {
+    let config = skipping_short(Config::default());
+    for cf in [
+        Box::new(exact(&THREAD, config.clone())) as Box<dyn CopyForward>,
+        Box::new(approximate(&THREAD, config.clone())),
+    ] {
+        let sources: Vec<usize> = cf
+            .segments()
+            .iter()
+            .flatten()
+            .filter_map(|seg| match seg {
+                Segment::Reference { message_idx, .. } => Some(*message_idx),
+                Segment::Literal(_) => None,
+            })
+            .collect();
+        assert!(!sources.contains(&1));
+        assert_eq!(cf.render_with_dyn(&mut |_, _, _, t| t.to_string()), THREAD);
+    }
+}
+
+#[test]
+fn test_short_messages_take_part_by_default() {
+    let segments = exact(&THREAD, Config::default()).segments();
+    assert!(matches!(segments[3][..], [Segment::Reference { .. }]));
+}
@@ -0,0 +1,35 @@
+use copyforward::minhash::{MinHasher, Shingling};
+
+// Expected values follow datasketch: MinHash(num_perm=4, seed=1) updated with
+// the same shingles via sha1_hash32.
+#[test]
+fn test_signatures_match_datasketch() {
+    let hasher = MinHasher::new(4, 1);
+    assert_eq!(
+        hasher.signature("the quick  brown\nfox", Shingling::Words),
+        [407326892, 34383241, 44059036, 1893391287]
+    );
+    assert_eq!(
+        hasher.signature("héllo", Shingling::Chars(3)),
+        [713706406, 261015590, 544299606, 565775851]
+    );
+}
+
+#[test]
+fn test_empty_and_none_signatures() {
+    let hasher = MinHasher::new(8, 1);
+    let sigs = hasher.signatures(&[None, Some(""), Some("ab")], Shingling::Chars(3));
+    let empty = vec![u32::MAX as u64; 8];
+    assert_eq!(sigs, [empty.clone(), empty.clone(), empty]);
+}
+
+#[test]
+fn test_jaccard_estimate() {
+    let hasher = MinHasher::new(256, 7);
+    let a = hasher.signature("a b c d e f g h", Shingling::Words);
+    let b = hasher.signature("a b c d e f g h", Shingling::Words);
+    let c = hasher.signature("a b c d w x y z", Shingling::Words);
+    assert_eq!(MinHasher::jaccard(&a, &b), 1.0);
+    let j = MinHasher::jaccard(&a, &c);
+    assert!((0.15..0.55).contains(&j), "{j}");
+}
@@ -0,0 +1,44 @@
+use copyforward::fixture::{generate_thread, minimize};
+use copyforward::{Config, CopyForward, approximate, exact};
+
+#[test]
+fn test_minimize_drops_messages_and_trims_text() {
+    let thread = generate_thread(3, 30, 4);
+    let fails =
+        |messages: &[String]| messages.iter().filter(|m| m.contains("sentence 2")).count() >= 2;
+    assert_eq!(minimize(&thread, fails), ["sentence 2", "sentence 2"]);
+}
+
+#[test]
+fn test_minimize_keeps_the_failure_reproducing() {
+    let count = |cf: &dyn CopyForward| cf.segments().iter().flatten().count();
+    let differs = |messages: &[String]| {
+        count(&exact(messages, Config::default()))
+            != count(&approximate(messages, Config::default()))
+    };
+    let thread = generate_thread(1, 20, 3);
+    let small = minimize(&thread, differs);
+    assert!(differs(&small));
+    let bytes = |messages: &[String]| messages.iter().map(String::len).sum::<usize>();
+    assert!(bytes(&small) < bytes(&thread) / 4);
+    for i in 0..small.len() {
+        let mut fewer = small.clone();
+        fewer.remove(i);
+        assert!(!differs(&fewer));
+    }
+}
+
+#[test]
+fn test_minimize_handles_multibyte_text() {
+    let thread = ["naïve café ☕ order".to_string(), "déjà vu".to_string()];
+    let small = minimize(&thread, |messages: &[String]| {
+        messages.iter().any(|m| m.contains('☕'))
+    });
+    assert_eq!(small, ["☕"]);
+}
+
+#[test]
+#[should_panic(expected = "does not reproduce")]
+fn test_minimize_rejects_passing_threads() {
+    minimize(&["fine".to_string()], |_: &[String]| false);
+}
@@ -0,0 +1,82 @@
+use copyforward::codec::encode_segments;
+use copyforward::fixture::generate_thread;
+use copyforward::{Config, CopyForward, Segment, approximate, exact, optimal};
+
+fn encoded_len(model: &dyn CopyForward) -> usize {
+    encode_segments(&model.segments()).len()
+}
+
+#[test]
+fn test_optimal_roundtrips_generated_threads() {
+    let configs = [
+        Config::default(),
+        Config {
+            lookback: Some(3),
+            ..Config::default()
+        },
+        Config {
+            max_ref_depth: Some(1),
+            ..Config::default()
+        },
+        Config {
+            stop_phrases: vec!["Thanks".to_string()],
+            ..Config::default()
+        },
+    ];
+    for seed in [2, 11] {
+        let messages = generate_thread(seed, 15, 3);
+        for config in configs.clone() {
+            let cf = optimal(&messages, config);
+            assert_eq!(cf.render_with(|_, _, _, text| text.to_string()), messages);
+        }
+    }
+}
+
+#[test]
+fn test_optimal_is_never_larger_than_greedy() {
+    for seed in [3, 7] {
+        let messages = generate_thread(seed, 20, 4);
+        let best = encoded_len(&optimal(&messages, Config::default()));
+        assert!(best <= encoded_len(&exact(&messages, Config::default())));
+        assert!(best <= encoded_len(&approximate(&messages, Config::default())));
+    }
+}
+
+#[test]
+fn test_optimal_spells_out_references_that_cost_more() {
+    let messages = ["abcdefgh", "xyz cdef!"];
+    let config = Config {
+        min_match_len: 4,
+        ..Config::default()
+    };
+    assert!(
+        exact(&messages, config.clone())
+            .segments_for(1)
+            .iter()
+            .any(|seg| matches!(seg, Segment::Reference { .. }))
+    );
+    assert_eq!(
+        optimal(&messages, config).segments_for(1),
+        [Segment::Literal("xyz cdef!".into())]
+    );
+}
+
+#[test]
+fn test_optimal_handles_missing_and_multibyte_messages() {
+    let messages = [
+        Some("Grüße aus München, bis später!"),
+        None,
+        Some("> Grüße aus München, bis später!\nDanke 🙂"),
+    ];
+    let cf = optimal(&messages, Config::default());
+    assert_eq!(cf.message(1), "");
+    assert_eq!(
+        cf.segments_for(2)[1],
+        Segment::Reference {
+            message_idx: 0,
+            start: 0,
+            len: messages[0].unwrap().len(),
+        }
+    );
+    assert_eq!(cf.message(2), messages[2].unwrap());
+}
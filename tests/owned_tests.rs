@@ -0,0 +1,48 @@
+use copyforward::fixture::generate_thread;
+use copyforward::{
+    Config, CopyForward, approximate, approximate_owned, exact, exact_owned, optimal, optimal_owned,
+};
+
+#[test]
+fn test_owned_constructors_match_borrowed_ones() {
+    let thread = generate_thread(6, 15, 4);
+    let config = Config::default();
+    assert_eq!(
+        exact_owned(thread.clone(), config.clone()).segments(),
+        exact(&thread, config.clone()).segments()
+    );
+    assert_eq!(
+        approximate_owned(thread.clone(), config.clone()).segments(),
+        approximate(&thread, config.clone()).segments()
+    );
+    assert_eq!(
+        optimal_owned(thread.clone(), config.clone()).segments(),
+        optimal(&thread, config).segments()
+    );
+}
+
+#[test]
+fn test_owned_none_messages_keep_their_index() {
+    let messages = vec![
+        Some("Hello world, see you Monday".to_string()),
+        None,
+        Some("> Hello world, see you Monday\nSure".to_string()),
+    ];
+    let owned = exact_owned(messages.clone(), Config::default());
+    assert_eq!(
+        owned.segments(),
+        exact(&messages, Config::default()).segments()
+    );
+    assert_eq!(owned.message(1), "");
+    assert!(owned.segments_for(1).is_empty());
+}
+
+#[test]
+fn test_owned_messages_are_moved_not_copied() {
+    let messages = vec!["Hello world".to_string(), "Hello world today".to_string()];
+    let addresses: Vec<*const u8> = messages.iter().map(|m| m.as_ptr()).collect();
+    let compressed = exact_owned(messages, Config::default());
+    for (idx, address) in addresses.into_iter().enumerate() {
+        assert_eq!(compressed.message(idx).as_ptr(), address);
+    }
+}
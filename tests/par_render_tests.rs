@@ -0,0 +1,17 @@
+#![cfg(feature = "rayon")]
+
+use copyforward::fixture::generate_thread;
+use copyforward::{Config, CopyForward, approximate, exact};
+
+#[test]
+fn test_par_render_matches_sequential_render() {
+    let messages = generate_thread(21, 64, 4);
+    let replacer = |m: usize, start: usize, len: usize, _: &str| format!("<{m}:{start}+{len}>");
+    let exact = exact(&messages, Config::default());
+    assert_eq!(exact.par_render_with(replacer), exact.render_with(replacer));
+    let approximate = approximate(&messages, Config::default());
+    assert_eq!(
+        approximate.par_render_with(|_, _, _, t| t.to_string()),
+        messages
+    );
+}
@@ -54,10 +54,9 @@ fn fixture_thread_is_deduped_substantially() {
         }
     }
 
-    let refs: Vec<&str> = msgs.iter().map(|s| s.as_str()).collect();
     let config = Config::default();
-    run_fixture_thread_is_deduped_substantially(exact(&refs, config.clone()), msgs.clone());
-    run_fixture_thread_is_deduped_substantially(approximate(&refs, config), msgs);
+    run_fixture_thread_is_deduped_substantially(exact(&msgs, config.clone()), msgs.clone());
+    run_fixture_thread_is_deduped_substantially(approximate(&msgs, config), msgs);
 }
 
 fn run_partial_overlaps_across_multiple_messages<C>(cf: C)
@@ -166,3 +165,23 @@ fn test_basic_compression() {
     assert_eq!(exact_rendered, msgs);
     assert_eq!(approx_rendered, msgs);
 }
+
+#[test]
+fn test_kmer_density_does_not_change_segments() {
+    let msgs = generate_thread(7, 16, 5);
+    let baseline = Config::default();
+    for kmer_density in [0.0, 0.1, 1.0, 3.0] {
+        let config = Config {
+            kmer_density,
+            ..Config::default()
+        };
+        assert_eq!(
+            exact(&msgs, config.clone()).segments(),
+            exact(&msgs, baseline.clone()).segments()
+        );
+        assert_eq!(
+            approximate(&msgs, config).segments(),
+            approximate(&msgs, baseline.clone()).segments()
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use copyforward::codec::encode_segments;
+use copyforward::fixture::generate_thread;
+use copyforward::peephole::{normalize_segments, optimize};
+use copyforward::render::{Reconstructed, reconstruct};
+use copyforward::{Config, CopyForward, Segment, approximate, exact};
+
+fn reference(message_idx: usize, start: usize, len: usize) -> Segment {
+    Segment::Reference {
+        message_idx,
+        start,
+        len,
+    }
+}
+
+fn literal(text: &str) -> Segment {
+    Segment::Literal(text.into())
+}
+
+#[test]
+fn test_adjacent_literals_and_empty_segments_are_merged() {
+    let model = Reconstructed::new(vec![vec![
+        literal("Hello"),
+        literal(""),
+        literal(", "),
+        literal("world"),
+    ]])
+    .unwrap();
+    assert_eq!(optimize(&model)[0], [literal("Hello, world")]);
+}
+
+#[test]
+fn test_short_references_become_literals() {
+    let model = Reconstructed::new(vec![
+        vec![literal("Hello world, how are you today?")],
+        vec![literal("Oh "), reference(0, 6, 5), literal("!")],
+    ])
+    .unwrap();
+    assert_eq!(optimize(&model)[1], [literal("Oh world!")]);
+}
+
+#[test]
+fn test_long_references_are_kept() {
+    let source = "Hello world, how are you today?";
+    let model = Reconstructed::new(vec![
+        vec![literal(source)],
+        vec![literal("> "), reference(0, 0, source.len())],
+    ])
+    .unwrap();
+    assert_eq!(optimize(&model)[1], model.segments_for(1));
+}
+
+#[test]
+fn test_self_references_are_not_merged_past_their_position() {
+    let source = "The deploy finished without errors at no";
+    let text = format!(
+        "{source}{}{}{}",
+        &source[..20],
+        &source[20..],
+        &source[..20]
+    );
+    let segments = vec![literal(source), reference(0, 0, 20), reference(0, 20, 40)];
+    let model = Reconstructed::new(vec![segments.clone()]).unwrap();
+    let optimized = optimize(&model);
+    assert_eq!(optimized[0], segments);
+    assert_eq!(reconstruct(&optimized).unwrap(), [text]);
+}
+
+#[test]
+fn test_references_point_at_the_original_span() {
+    let source = "The deploy finished without errors at noon.";
+    let model = Reconstructed::new(vec![
+        vec![literal(source)],
+        vec![literal("fwd: "), reference(0, 0, source.len())],
+        vec![reference(1, 9, 20), reference(0, 24, 19)],
+    ])
+    .unwrap();
+    assert_eq!(optimize(&model)[2], [reference(0, 4, 39)]);
+}
+
+#[test]
+fn test_optimize_preserves_rendering_and_never_grows() {
+    let config = Config {
+        min_match_len: 4,
+        ..Config::default()
+    };
+    for seed in [1, 8] {
+        let messages = generate_thread(seed, 30, 4);
+        let models: [Box<dyn CopyForward>; 2] = [
+            Box::new(exact(&messages, config.clone())),
+            Box::new(approximate(&messages, config.clone())),
+        ];
+        for model in models {
+            let optimized = optimize(model.as_ref());
+            assert!(encode_segments(&optimized).len() <= encode_segments(&model.segments()).len());
+            let rebuilt = Reconstructed::new(optimized).unwrap();
+            for (idx, message) in messages.iter().enumerate() {
+                assert_eq!(rebuilt.message(idx), message);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_normalize_merges_fragments_and_keeps_sources() {
+    let mut segments = vec![
+        vec![literal("Hello world, how are you today?")],
+        vec![literal("Hello world")],
+        vec![
+            literal(""),
+            reference(0, 0, 5),
+            reference(0, 5, 6),
+            reference(1, 11, 0),
+            reference(1, 0, 5),
+            literal(" and"),
+            literal(" more"),
+            reference(0, 12, 3),
+            reference(0, 16, 3),
+        ],
+    ];
+    normalize_segments(&mut segments);
+    assert_eq!(
+        segments[2],
+        [
+            reference(0, 0, 11),
+            reference(1, 0, 5),
+            literal(" and more"),
+            reference(0, 12, 3),
+            reference(0, 16, 3),
+        ]
+    );
+}
+
+#[test]
+fn test_normalize_merges_self_references_only_behind_their_position() {
+    let mut segments = vec![
+        vec![literal("abcdef"), reference(0, 0, 6), reference(0, 6, 6)],
+        vec![
+            literal("abcdef"),
+            literal("ghijkl"),
+            reference(1, 0, 6),
+            reference(1, 6, 6),
+        ],
+    ];
+    let rendered = reconstruct(&segments).unwrap();
+    normalize_segments(&mut segments);
+    assert_eq!(
+        segments,
+        [
+            vec![literal("abcdef"), reference(0, 0, 6), reference(0, 6, 6)],
+            vec![literal("abcdefghijkl"), reference(1, 0, 12)],
+        ]
+    );
+    assert_eq!(reconstruct(&segments).unwrap(), rendered);
+}
+
+#[test]
+fn test_normalize_preserves_rendering_and_is_idempotent() {
+    let messages = generate_thread(3, 20, 4);
+    let mut segments = exact(&messages, Config::default()).segments();
+    normalize_segments(&mut segments);
+    assert_eq!(reconstruct(&segments).unwrap(), messages);
+    let once = segments.clone();
+    normalize_segments(&mut segments);
+    assert_eq!(segments, once);
+}
@@ -0,0 +1,185 @@
+use copyforward::fixture::generate_thread;
+use copyforward::prelude::*;
+use copyforward::{Approximate, Exact, Optimal, approximate, exact, optimal};
+
+#[test]
+fn test_entry_functions_match_algorithms() {
+    for seed in 0..4 {
+        let thread = generate_thread(seed, 20, 4);
+        let config = Config::default();
+        let precise = copy_forward_exact(&thread, config.clone());
+        assert_eq!(
+            precise.segments(),
+            exact(&thread, config.clone()).segments()
+        );
+        assert_eq!(precise.metadata().unwrap().algorithm, "exact");
+        let fast = copy_forward_approx(&thread, config.clone());
+        assert_eq!(fast.segments(), approximate(&thread, config).segments());
+        assert_eq!(fast.metadata().unwrap().algorithm, "approximate");
+        for (i, text) in thread.iter().enumerate() {
+            assert_eq!(precise.message(i), text);
+            assert_eq!(fast.message(i), text);
+        }
+    }
+}
+
+#[test]
+fn test_results_share_one_type() {
+    let messages = [
+        Some("Hello world, see you Monday"),
+        None,
+        Some("Hello world, see you Monday\nSure"),
+    ];
+    let results: Vec<Compressed> = vec![
+        copy_forward_exact(&messages, Config::default()),
+        copy_forward_approx(&messages, Config::default()),
+        optimal(&messages, Config::default()).into(),
+    ];
+    for result in &results {
+        assert_eq!(result.len(), 3);
+        assert_eq!(result.message(1), "");
+        assert_eq!(
+            result.render_with(|_, _, _, text| text.to_string()),
+            [
+                "Hello world, see you Monday",
+                "",
+                "Hello world, see you Monday\nSure"
+            ]
+        );
+    }
+    assert_eq!(results[2].metadata().unwrap().algorithm, "optimal");
+}
+
+#[test]
+fn test_compress_dispatches_by_name() {
+    let thread = generate_thread(7, 12, 3);
+    let config = Config::default();
+    for name in ["exact", "approximate", "optimal"] {
+        let algorithm: Algorithm = name.parse().unwrap();
+        assert_eq!(algorithm.to_string(), name);
+        let compressed = compress(&thread, algorithm, config.clone());
+        assert_eq!(compressed.metadata().unwrap().algorithm, name);
+        let expected = match name {
+            "exact" => exact(&thread, config.clone()).segments(),
+            "approximate" => approximate(&thread, config.clone()).segments(),
+            _ => optimal(&thread, config.clone()).segments(),
+        };
+        assert_eq!(compressed.segments(), expected);
+    }
+    assert_eq!(Algorithm::ALL.len(), 3);
+}
+
+#[test]
+fn test_unknown_algorithm_lists_choices() {
+    let err = "capped".parse::<Algorithm>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown algorithm \"capped\"; expected one of: exact, approximate, optimal"
+    );
+}
+
+#[test]
+fn test_try_compress_rejects_unusable_config() {
+    let messages = ["Hello world", "Hello world today"];
+    let cases = [
+        (
+            Config {
+                min_match_len: 0,
+                ..Config::default()
+            },
+            "min_match_len",
+        ),
+        (
+            Config {
+                cap_len: 0,
+                ..Config::default()
+            },
+            "cap_len",
+        ),
+        (
+            Config {
+                ncap: 0,
+                ..Config::default()
+            },
+            "ncap",
+        ),
+        (
+            Config {
+                kmer_density: f64::NAN,
+                ..Config::default()
+            },
+            "kmer_density",
+        ),
+        (
+            Config {
+                lsh: Some(copyforward::Lsh { bands: 0, rows: 1 }),
+                ..Config::default()
+            },
+            "lsh.bands",
+        ),
+    ];
+    for (config, name) in cases {
+        match try_compress(&messages, Algorithm::Exact, config) {
+            Err(CopyForwardError::InvalidConfig { field, .. }) => assert_eq!(field, name),
+            other => panic!("{name} gave {:?}", other.err()),
+        }
+    }
+    let compressed = try_compress(&messages, Algorithm::Exact, Config::default()).unwrap();
+    assert_eq!(compressed.render_with_static("[REF]")[1], "[REF] today");
+}
+
+#[test]
+fn test_empty_input_is_rejected() {
+    let none: [&str; 0] = [];
+    for &algorithm in Algorithm::ALL {
+        assert_eq!(
+            try_compress(&none, algorithm, Config::default()).err(),
+            Some(CopyForwardError::EmptyInput)
+        );
+    }
+    assert_eq!(
+        CopyForwardError::EmptyInput.to_string(),
+        "no messages to compress"
+    );
+}
+
+#[test]
+fn test_try_with_config_matches_the_entry_functions() {
+    let messages = ["Hello world", "Hello world today"];
+    let config = Config::default();
+    assert_eq!(
+        Exact::try_with_config(&messages, config.clone())
+            .unwrap()
+            .segments(),
+        exact(&messages, config.clone()).segments()
+    );
+    assert_eq!(
+        Approximate::try_with_config(&messages, config.clone())
+            .unwrap()
+            .segments(),
+        approximate(&messages, config.clone()).segments()
+    );
+    assert_eq!(
+        Optimal::try_with_config(&messages, config.clone())
+            .unwrap()
+            .segments(),
+        optimal(&messages, config).segments()
+    );
+    let none: [&str; 0] = [];
+    assert_eq!(
+        Exact::try_with_config(&none, Config::default()).err(),
+        Some(CopyForwardError::EmptyInput)
+    );
+    let invalid = Config {
+        ncap: 0,
+        ..Config::default()
+    };
+    assert!(matches!(
+        Approximate::try_with_config(&messages, invalid.clone()),
+        Err(CopyForwardError::InvalidConfig { field: "ncap", .. })
+    ));
+    assert!(matches!(
+        Optimal::try_with_config(&messages, invalid),
+        Err(CopyForwardError::InvalidConfig { field: "ncap", .. })
+    ));
+}
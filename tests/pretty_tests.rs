@@ -0,0 +1,40 @@
+use copyforward::Segment;
+use copyforward::pretty::fmt_segments;
+
+#[test]
+fn test_previews_are_truncated_and_escaped() {
+    let segments = vec![vec![Segment::Literal("line one\nline two".into())]];
+    let dump = fmt_segments(&segments).preview_len(10).to_string();
+    assert_eq!(dump, "#0 (17 bytes)\n   0..17  lit  \"line one\\nl…\"\n");
+}
+
+#[test]
+fn test_offsets_align_across_messages() {
+    let long = "x".repeat(120);
+    let segments = vec![
+        vec![Segment::Literal(long.as_str().into())],
+        vec![
+            Segment::Literal("ab".into()),
+            Segment::Reference {
+                message_idx: 0,
+                start: 100,
+                len: 20,
+            },
+        ],
+    ];
+    let dump = fmt_segments(&segments).preview_len(3).to_string();
+    let lines: Vec<&str> = dump.lines().collect();
+    assert_eq!(lines[3], "    0..2    lit                \"ab\"");
+    assert_eq!(lines[4], "    2..22   ref  #0[100..120]  \"xxx…\"");
+}
+
+#[test]
+fn test_invalid_references_are_shown_without_preview() {
+    let segments = vec![vec![Segment::Reference {
+        message_idx: 3,
+        start: 0,
+        len: 4,
+    }]];
+    let dump = fmt_segments(&segments).to_string();
+    assert_eq!(dump, "#0 (4 bytes)\n  0..4  ref  #3[0..4]\n");
+}
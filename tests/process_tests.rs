@@ -0,0 +1,93 @@
+use copyforward::fixture::generate_thread;
+use copyforward::{Config, CopyForward, Progress, Segment, exact, process_iter};
+use std::sync::Mutex;
+use std::sync::mpsc::channel;
+
+#[test]
+fn test_yields_exact_segments_in_order() {
+    for seed in 0..4 {
+        let thread = generate_thread(seed, 30, 5);
+        let expected = exact(&thread, Config::default()).segments();
+        let streamed: Vec<Vec<Segment>> = process_iter(&thread, Config::default()).collect();
+        assert_eq!(streamed, expected);
+    }
+}
+
+#[test]
+fn test_applies_the_encoded_gain_like_exact() {
+    for min_encoded_gain in [Some(1), Some(16)] {
+        let config = Config {
+            min_encoded_gain,
+            ..Config::default()
+        };
+        for seed in [20, 45, 46] {
+            let thread = generate_thread(seed, 20, 4);
+            let expected = exact(&thread, config.clone()).segments();
+            let streamed: Vec<Vec<Segment>> = process_iter(&thread, config.clone()).collect();
+            assert_eq!(streamed, expected, "seed {seed}, gain {min_encoded_gain:?}");
+        }
+    }
+}
+
+#[test]
+fn test_none_messages_yield_empty_segments() {
+    let messages = [
+        None,
+        Some("Hello world, see you Monday"),
+        None,
+        None,
+        Some("> Hello world, see you Monday\nSure"),
+        None,
+    ];
+    let streamed: Vec<Vec<Segment>> = process_iter(&messages, Config::default()).collect();
+    assert_eq!(streamed, exact(&messages, Config::default()).segments());
+    assert_eq!(streamed.len(), 6);
+    assert!(streamed[5].is_empty());
+}
+
+#[test]
+fn test_message_is_available_before_later_ones_finish() {
+    let (release, gate) = channel::<()>();
+    let gate = Mutex::new(gate);
+    let config = Config {
+        // Hold the worker after the first message until the test has seen it.
+        progress: Some(Progress::new(1, move |done, _| {
+            if done == 1 {
+                gate.lock().unwrap().recv().unwrap();
+            }
+            true
+        })),
+        ..Config::default()
+    };
+    let messages = [
+        "Hello world, see you Monday",
+        "> Hello world, see you Monday",
+    ];
+    let mut iter = process_iter(&messages, config);
+    assert_eq!(
+        iter.next().unwrap(),
+        [Segment::Literal("Hello world, see you Monday".into())]
+    );
+    release.send(()).unwrap();
+    assert!(matches!(iter.next().unwrap()[1], Segment::Reference { .. }));
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_cancelled_run_matches_exact() {
+    let thread = generate_thread(7, 20, 4);
+    let config = || Config {
+        progress: Some(Progress::new(1, |done, _| done < 5)),
+        ..Config::default()
+    };
+    let streamed: Vec<Vec<Segment>> = process_iter(&thread, config()).collect();
+    assert_eq!(streamed, exact(&thread, config()).segments());
+}
+
+#[test]
+fn test_dropping_early_stops_the_worker() {
+    let thread = generate_thread(3, 200, 5);
+    let mut iter = process_iter(&thread, Config::default());
+    assert!(iter.next().is_some());
+    drop(iter);
+}
@@ -0,0 +1,80 @@
+use copyforward::{
+    Config, CopyForward, CopyForwardTokens, Progress, Segment, approximate, exact, exact_tokens,
+};
+use std::sync::{Arc, Mutex};
+
+type Calls = Arc<Mutex<Vec<(usize, usize)>>>;
+
+fn recording(every: usize, stop_at: usize) -> (Config, Calls) {
+    let calls: Calls = Arc::default();
+    let seen = Arc::clone(&calls);
+    let config = Config {
+        progress: Some(Progress::new(every, move |done, total| {
+            seen.lock().unwrap().push((done, total));
+            done < stop_at
+        })),
+        ..Config::default()
+    };
+    (config, calls)
+}
+
+#[test]
+fn test_progress_reports_every_n_and_the_last_message() {
+    let messages: Vec<String> = (0..7)
+        .map(|i| format!("status update number {i}"))
+        .collect();
+    let (config, calls) = recording(3, usize::MAX);
+    exact(&messages, config);
+    assert_eq!(*calls.lock().unwrap(), [(3, 7), (6, 7), (7, 7)]);
+
+    let (config, calls) = recording(3, usize::MAX);
+    approximate(&messages, config);
+    assert_eq!(*calls.lock().unwrap(), [(3, 7), (6, 7), (7, 7)]);
+}
+
+#[test]
+fn test_progress_counts_only_present_messages() {
+    let messages = [Some("Hello world"), None, Some("Hello world again")];
+    let (config, calls) = recording(1, usize::MAX);
+    exact(&messages, config);
+    assert_eq!(*calls.lock().unwrap(), [(1, 2), (2, 2)]);
+}
+
+#[test]
+fn test_cancelled_run_leaves_remaining_messages_literal() {
+    let messages = ["Hello world", "Hello world", "Hello world today", ""];
+    for approximate_mode in [false, true] {
+        let (config, calls) = recording(1, 2);
+        let compressed: Box<dyn CopyForward> = if approximate_mode {
+            Box::new(approximate(&messages, config))
+        } else {
+            Box::new(exact(&messages, config))
+        };
+        assert_eq!(calls.lock().unwrap().len(), 2);
+        assert!(matches!(
+            compressed.segments_for(1)[0],
+            Segment::Reference { .. }
+        ));
+        assert_eq!(
+            compressed.segments_for(2),
+            [Segment::Literal("Hello world today".into())]
+        );
+        assert!(compressed.segments_for(3).is_empty());
+        assert_eq!(
+            compressed.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+            messages
+        );
+    }
+}
+
+#[test]
+fn test_progress_applies_to_token_mode() {
+    let messages: [&[u32]; 3] = [&[1, 2, 3, 4], &[1, 2, 3, 4], &[5, 6]];
+    let (config, calls) = recording(2, usize::MAX);
+    let compressed = exact_tokens(&messages, config);
+    assert_eq!(*calls.lock().unwrap(), [(2, 3), (3, 3)]);
+    assert_eq!(
+        compressed.render_with_static(&[0]),
+        [vec![1, 2, 3, 4], vec![0], vec![5, 6]]
+    );
+}
@@ -0,0 +1,126 @@
+use copyforward::fixture::generate_thread;
+use copyforward::{Config, CopyForward, Lsh, Segment, approximate, exact};
+use std::time::{Duration, Instant};
+
+const THREAD: [&str; 6] = [
+    "Hi team, the release is planned for Friday afternoon.",
+    "> Hi team, the release is planned for Friday afternoon.\nCan we move it to Monday?",
+    "Monday works. Thanks, Ana",
+    "> Monday works. Thanks, Ana\n> > Can we move it to Monday?\nGreat, Monday it is.",
+    "Monday works. Thanks, Ana",
+    "> Great, Monday it is.\nI will update the calendar for the release on Monday.",
+];
+
+fn configs() -> Vec<Config> {
+    vec![
+        Config::default(),
+        Config {
+            lookback: Some(2),
+            dedup_messages: true,
+            ..Config::default()
+        },
+        Config {
+            stop_phrases: vec!["Thanks,".into()],
+            skip_referenced_regions: true,
+            reextend: true,
+            cap_len: 16,
+            ..Config::default()
+        },
+        Config {
+            shallow_preference: Some(4),
+            max_ref_depth: Some(1),
+            lsh: Some(Lsh::default()),
+            ..Config::default()
+        },
+    ]
+}
+
+#[test]
+fn test_exact_push_matches_batch() {
+    for config in configs() {
+        let batch = exact(&THREAD, config.clone());
+        for split in 0..THREAD.len() {
+            let mut model = exact(&THREAD[..split], config.clone());
+            for (idx, message) in THREAD.iter().enumerate().skip(split) {
+                assert_eq!(model.push_message(message), batch.segments_for(idx));
+            }
+            assert_eq!(model.segments(), batch.segments());
+            assert_eq!(model.message(THREAD.len() - 1), THREAD[THREAD.len() - 1]);
+        }
+    }
+}
+
+#[test]
+fn test_approximate_push_matches_batch() {
+    for config in configs() {
+        let batch = approximate(&THREAD, config.clone());
+        for split in 0..THREAD.len() {
+            let mut model = approximate(&THREAD[..split], config.clone());
+            for message in &THREAD[split..] {
+                model.push_message(message);
+            }
+            assert_eq!(model.segments(), batch.segments());
+        }
+    }
+}
+
+#[test]
+fn test_push_after_none_messages() {
+    let config = Config {
+        lookback: Some(1),
+        ..Config::default()
+    };
+    let messages = [Some(THREAD[0]), None, Some(THREAD[1])];
+    let mut model = exact(&messages, config.clone());
+    let pushed = model.push_message(THREAD[2]).to_vec();
+
+    let all = [Some(THREAD[0]), None, Some(THREAD[1]), Some(THREAD[2])];
+    let batch = exact(&all, config);
+    assert_eq!(pushed, batch.segments_for(3));
+    assert_eq!(model.segments(), batch.segments());
+    assert!(matches!(
+        model.segments_for(2)[1],
+        Segment::Reference { message_idx: 0, .. }
+    ));
+}
+
+#[test]
+fn test_clones_push_independently() {
+    let mut original = exact(&THREAD[..2], Config::default());
+    original.push_message(THREAD[2]);
+    let mut copy = original.clone();
+    copy.push_message(THREAD[3]);
+    original.push_message("Something else entirely");
+
+    let batch = exact(&THREAD[..4], Config::default());
+    assert_eq!(copy.segments(), batch.segments());
+    assert_eq!(original.len(), 4);
+    assert_eq!(original.message(3), "Something else entirely");
+}
+
+#[test]
+fn test_first_push_does_not_rebuild_the_index() {
+    // Rebuilding would make the first push cost as much as the build; a
+    // push onto the kept index costs about one message's share of it.
+    let thread = generate_thread(7, 200, 4);
+    let (history, next) = thread.split_at(thread.len() - 1);
+    let mut times = [(Duration::MAX, Duration::MAX); 2];
+    for _ in 0..3 {
+        let started = Instant::now();
+        let mut model = exact(history, Config::default());
+        times[0].0 = times[0].0.min(started.elapsed());
+        let started = Instant::now();
+        model.push_message(&next[0]);
+        times[0].1 = times[0].1.min(started.elapsed());
+
+        let started = Instant::now();
+        let mut model = approximate(history, Config::default());
+        times[1].0 = times[1].0.min(started.elapsed());
+        let started = Instant::now();
+        model.push_message(&next[0]);
+        times[1].1 = times[1].1.min(started.elapsed());
+    }
+    for (build, push) in times {
+        assert!(push * 10 < build, "push took {push:?}, build {build:?}");
+    }
+}
@@ -86,3 +86,200 @@ def test_from_tokens_list_and_numpy():
     assert all(isinstance(x, np.ndarray) for x in out2)
     # values match
     assert [list(x) for x in out2] == [[10,11,12,13],[999,14]]
+
+
+
+def test_results_support_container_protocols():
+    import copyforward
+
+    messages = ["Hello world from Alice", "Hello world from Alice again"]
+    cf = copyforward.CopyForwardText.from_texts(messages)
+    assert len(cf) == 2
+    assert [seg.message for seg in cf[1] if hasattr(seg, "message")] == [0]
+    assert cf[-1][0].message == cf[1][0].message
+    assert len(list(cf)) == 2
+    with pytest.raises(IndexError):
+        cf[2]
+
+    tokens = copyforward.CopyForwardTokens.from_tokens([[1, 2, 3, 4], [1, 2, 3, 4, 5]])
+    assert len(tokens) == 2
+    assert [seg.tokens for seg in tokens[0]] == [[1, 2, 3, 4]]
+    assert [len(segs) for segs in tokens] == [1, 2]
+    with pytest.raises(IndexError):
+        tokens[-3]
+
+
+def test_references_report_character_offsets():
+    import copyforward
+
+    messages = ["naïve café, déjà vu 🎉", "Re: naïve café, déjà vu 🎉"]
+    cf = copyforward.CopyForwardText.from_texts(messages)
+    ref = cf[1][1]
+    assert messages[0][ref.char_start : ref.char_start + ref.char_len] == messages[1][4:]
+    assert ref.len > ref.char_len
+
+
+def test_json_round_trip():
+    import json
+
+    import copyforward
+
+    messages = ["Hello world from Alice", None, "Hello world from Alice again"]
+    cf = copyforward.CopyForwardText.from_texts(messages)
+    data = cf.to_json()
+    saved = json.loads(data)
+    assert saved["segments"][2][0] == {"reference": {"message_idx": 0, "start": 0, "len": 22}}
+    assert saved["metadata"]["algorithm"] == "exact"
+    loaded = copyforward.CopyForwardText.from_json(data)
+    assert loaded.render("[REF]") == cf.render("[REF]")
+    assert loaded.metadata() == cf.metadata()
+    bare = copyforward.CopyForwardText.from_json(json.dumps(saved["segments"]))
+    assert bare.render("[REF]") == cf.render("[REF]")
+    assert bare.metadata() is None
+    with pytest.raises(copyforward.CopyForwardError):
+        copyforward.CopyForwardText.from_json('[[{"reference": {"message_idx": 1, "start": 0, "len": 1}}]]')
+
+    tokens = copyforward.CopyForwardTokens.from_tokens([[1, 2, 3, 4], [1, 2, 3, 4, 5]])
+    loaded_tokens = copyforward.CopyForwardTokens.from_json(tokens.to_json())
+    assert loaded_tokens.render([0]) == [[1, 2, 3, 4], [0, 5]]
+
+
+def test_bytes_round_trip():
+    import copyforward
+
+    messages = ["Hello world from Alice", None, "Hello world from Alice again"]
+    cf = copyforward.CopyForwardText.from_texts(messages)
+    data = cf.to_bytes()
+    assert data.startswith(b"CFSG")
+    assert len(data) < len(cf.to_json())
+    loaded = copyforward.CopyForwardText.from_bytes(data)
+    assert loaded.render("[REF]") == cf.render("[REF]")
+    assert loaded.metadata()["config"]["min_match_len"] == cf.metadata()["config"]["min_match_len"]
+    with pytest.raises(copyforward.CopyForwardError):
+        copyforward.CopyForwardText.from_bytes(data[:-1])
+
+
+def test_invalid_config_raises_config_error():
+    import copyforward
+
+    assert issubclass(copyforward.ConfigError, copyforward.CopyForwardError)
+    assert issubclass(copyforward.CopyForwardError, ValueError)
+    with pytest.raises(copyforward.ConfigError, match="min_match_len"):
+        copyforward.CopyForwardText.from_texts(["a", "a"], min_match_len=0)
+    with pytest.raises(copyforward.ConfigError, match="ncap"):
+        copyforward.CopyForwardTokens.from_tokens([[1], [1]], ncap=0)
+
+
+def test_compress_corpus_matches_per_thread_results():
+    import copyforward
+
+    threads = [
+        ["Hello world from Alice", "Hello world from Alice again"],
+        ["Lunch at noon?", None, "Lunch at noon works for me"],
+    ]
+    results, stats = copyforward.compress_corpus(threads, min_match_len=8)
+    assert len(results) == 2
+    for thread, result in zip(threads, results):
+        single = copyforward.CopyForwardText.from_texts(thread, min_match_len=8)
+        assert result.render("[REF]") == single.render("[REF]")
+    assert stats["messages"] == 5
+    assert stats["references"] == 2
+
+    pd = pytest.importorskip("pandas")
+    df = pd.DataFrame(
+        {"thread": ["a", "b", "a"], "body": ["Hello world from Alice", "Hi", "Hello world from Alice again"]}
+    )
+    grouped, _ = copyforward.compress_corpus(df.groupby("thread")["body"])
+    assert [len(r) for r in grouped] == [2, 1]
+
+
+def test_pandas_series_inputs_are_accepted_directly():
+    import copyforward
+
+    pd = pytest.importorskip("pandas")
+    messages = ["Hello world from Alice", None, "Hello world from Alice again"]
+    series = pd.Series(messages, index=[10, 20, 30])
+    expected = copyforward.CopyForwardText.from_texts(messages).render("[REF]")
+    assert copyforward.CopyForwardText.from_texts(series).render("[REF]") == expected
+
+    with_nan = pd.Series(["Hello world from Alice", float("nan"), "Hello world from Alice again"])
+    assert copyforward.CopyForwardText.from_texts(with_nan).render("[REF]") == expected
+    with_na = pd.Series(messages, dtype="string")
+    assert copyforward.CopyForwardText.from_texts(with_na).render("[REF]") == expected
+    assert copyforward.minhash_signatures(series) == copyforward.minhash_signatures(messages)
+
+
+def test_progress_callback_reports_and_cancels():
+    import copyforward
+
+    messages = [f"status update number {i}" for i in range(5)]
+    calls = []
+    copyforward.CopyForwardText.from_texts(
+        messages, progress=lambda done, total: calls.append((done, total)), progress_every=2
+    )
+    assert calls == [(2, 5), (4, 5), (5, 5)]
+
+    with pytest.raises(copyforward.CopyForwardError, match="cancelled"):
+        copyforward.CopyForwardTokens.from_tokens(
+            [[1, 2, 3]] * 4, progress=lambda done, total: False, progress_every=1
+        )
+
+    def boom(done, total):
+        raise RuntimeError("stop")
+
+    with pytest.raises(RuntimeError, match="stop"):
+        copyforward.CopyForwardText.from_texts(messages, progress=boom)
+
+
+def test_config_object_validates_and_applies():
+    import copyforward
+
+    config = copyforward.Config(min_match_len=8, stop_phrases=["Thanks,"])
+    assert config.min_match_len == 8
+    assert config.lookback is None
+    assert config == copyforward.Config(min_match_len=8, stop_phrases=["Thanks,"])
+
+    messages = ["Hello world from Alice", "Hello world from Alice again"]
+    with_config = copyforward.CopyForwardText.from_texts(messages, config=config)
+    with_keywords = copyforward.CopyForwardText.from_texts(messages, min_match_len=8, stop_phrases=["Thanks,"])
+    assert with_config.render("[REF]") == with_keywords.render("[REF]")
+
+    with pytest.raises(copyforward.ConfigError, match="kmer_density"):
+        copyforward.Config(kmer_density=0.0)
+    with pytest.raises(copyforward.ConfigError, match="not both"):
+        copyforward.CopyForwardText.from_texts(messages, config=config, lookback=3)
+
+
+def test_stream_warm_starts_from_saved_state():
+    import copyforward
+
+    stream = copyforward.CopyForwardStream(2, config=copyforward.Config(min_match_len=8))
+    stream.add_message("disk /dev/sda1 is 91% full")
+    stream.add_message("backup finished at 02:00")
+    saved = stream.to_json()
+
+    restored = copyforward.CopyForwardStream.from_json(saved)
+    assert len(restored) == 2
+    assert restored.message(0) == "disk /dev/sda1 is 91% full"
+    segments = restored.add_message("disk /dev/sda1 is 95% full")
+    assert segments[0].message == 0
+    assert [type(s) for s in segments] == [type(s) for s in stream.add_message("disk /dev/sda1 is 95% full")]
+    assert restored.message(0) is None
+
+    with pytest.raises(copyforward.CopyForwardError):
+        copyforward.CopyForwardStream.from_json("{}")
+
+def test_minhash_signatures_match_datasketch_layout():
+    import copyforward
+
+    sigs = copyforward.minhash_signatures(
+        ["the quick brown fox", None], num_perm=4, seed=1
+    )
+    assert sigs[0] == [407326892, 34383241, 44059036, 1893391287]
+    assert sigs[1] == [2**32 - 1] * 4
+
+    datasketch = pytest.importorskip("datasketch")
+    m = datasketch.MinHash(num_perm=4, seed=1)
+    for w in "the quick brown fox".split():
+        m.update(w.encode("utf8"))
+    assert list(m.hashvalues) == sigs[0]
@@ -0,0 +1,38 @@
+import ast
+import inspect
+from pathlib import Path
+
+STUB = Path(__file__).resolve().parents[2] / "copyforward.pyi"
+
+
+def stub_names():
+    tree = ast.parse(STUB.read_text())
+    names = {}
+    for node in tree.body:
+        if isinstance(node, ast.ClassDef):
+            names[node.name] = {
+                item.name for item in node.body if isinstance(item, ast.FunctionDef)
+            }
+        elif isinstance(node, ast.FunctionDef):
+            names[node.name] = set()
+    return names
+
+
+def test_stub_covers_every_public_name():
+    import copyforward
+
+    stubs = stub_names()
+    public = [name for name in dir(copyforward) if not name.startswith("_")]
+    missing = [name for name in public if name not in stubs]
+    assert missing == []
+
+
+def test_stub_covers_every_public_method():
+    import copyforward
+
+    for name, methods in stub_names().items():
+        obj = getattr(copyforward, name)
+        if not inspect.isclass(obj):
+            continue
+        public = {m for m in vars(obj) if not m.startswith("_")}
+        assert public <= methods, f"{name} is missing {sorted(public - methods)}"
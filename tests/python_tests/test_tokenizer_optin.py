@@ -42,10 +42,20 @@ def test_feature_errors_when_missing_tokenizers_feature():
 
     msgs = ["a", "b"]
     # These names require optional features; verify we emit a clear error.
-    with pytest.raises(TypeError) as e1:
+    with pytest.raises(copyforward.ConfigError) as e1:
         copyforward.CopyForwardTokens.from_texts_with_tokenizer(msgs, tokenizer="hf:distilbert-base-uncased")
     assert "requires building" in str(e1.value)
 
-    with pytest.raises(TypeError) as e2:
+    with pytest.raises(copyforward.ConfigError) as e2:
         copyforward.CopyForwardTokens.from_texts_with_tokenizer(msgs, tokenizer="file:/not/a/real/tokenizer.json")
     assert "requires building" in str(e2.value)
+
+
+def test_unicode_tokenizer_segments_cjk_and_round_trips():
+    import copyforward
+
+    messages = ["我们明天下午三点开会", "好的。我们明天下午三点开会"]
+    cf = copyforward.CopyForwardTokens.from_texts_with_tokenizer(
+        messages, tokenizer="unicode", exact_mode=True, min_match_len=4
+    )
+    assert cf.render_texts("[REF]") == ["我们明天下午三点开会", "好的。[REF]"]
@@ -0,0 +1,133 @@
+use copyforward::qgram::{ApproxMatch, Candidate, QGramIndex};
+
+/// Deterministic pseudo-random text over a small alphabet.
+fn noisy_text(seed: u64, len: usize) -> Vec<char> {
+    const ALPHABET: [char; 5] = ['a', 'b', 'c', 'd', 'é'];
+    let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(7);
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ALPHABET[(state >> 33) as usize % ALPHABET.len()]
+        })
+        .collect()
+}
+
+/// Smallest edit distance between `pattern` and any substring of `text`.
+fn brute_force_edits(pattern: &[char], text: &[char]) -> usize {
+    let mut cost: Vec<usize> = (0..=pattern.len()).collect();
+    let mut best = cost[pattern.len()];
+    for &t in text {
+        let mut diag = cost[0];
+        cost[0] = 0;
+        for i in 1..=pattern.len() {
+            let up = cost[i];
+            cost[i] = (diag + usize::from(pattern[i - 1] != t))
+                .min(up + 1)
+                .min(cost[i - 1] + 1);
+            diag = up;
+        }
+        best = best.min(cost[pattern.len()]);
+    }
+    best
+}
+
+#[test]
+fn test_filter_loses_no_occurrences() {
+    let archive: Vec<Vec<char>> = (0..6).map(|seed| noisy_text(seed, 200)).collect();
+    let messages: Vec<String> = archive.iter().map(|m| m.iter().collect()).collect();
+    let index = QGramIndex::new(&messages, 3);
+    for seed in 0..30u64 {
+        let source = &archive[seed as usize % archive.len()];
+        let at = (seed as usize * 37) % 150;
+        let mut pattern = source[at..at + 30].to_vec();
+        pattern[(seed as usize * 7) % 30] = 'x';
+        pattern.remove((seed as usize * 11) % 29);
+        let text: String = pattern.iter().collect();
+
+        for max_edits in [1, 2, 3] {
+            let found = index.find(&text, max_edits);
+            for (idx, message) in archive.iter().enumerate() {
+                let expected = brute_force_edits(&pattern, message);
+                let best = found
+                    .iter()
+                    .filter(|m| m.message_idx == idx)
+                    .map(|m| m.edits)
+                    .min();
+                if expected <= max_edits {
+                    assert_eq!(best, Some(expected), "message {idx}, pattern {text:?}");
+                } else {
+                    assert_eq!(best, None);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_candidates_skip_unrelated_messages() {
+    let messages = [
+        "Please review the quarterly budget before Friday.",
+        "Completely different content about gardening tips.",
+        "Please reveiw the quarterly budjet before Friday!",
+    ];
+    let index = QGramIndex::new(&messages, 4);
+    let candidates = index.candidates("review the quarterly budget", 3);
+    assert!(!candidates.is_empty());
+    assert!(candidates.iter().all(|c| c.message_idx != 1));
+
+    let found = index.find("review the quarterly budget", 3);
+    assert_eq!(
+        found,
+        [
+            ApproxMatch {
+                message_idx: 0,
+                start: 7,
+                len: 27,
+                edits: 0,
+            },
+            ApproxMatch {
+                message_idx: 2,
+                start: 7,
+                len: 27,
+                edits: 3,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_short_patterns_fall_back_to_whole_messages() {
+    let messages = [Some("abc"), None, Some("xyz")];
+    let index = QGramIndex::new(&messages, 3);
+    assert_eq!(index.len(), 3);
+    assert_eq!(
+        index.candidates("ab", 1),
+        [
+            Candidate {
+                message_idx: 0,
+                start: 0,
+                end: 3,
+            },
+            Candidate {
+                message_idx: 2,
+                start: 0,
+                end: 3,
+            },
+        ]
+    );
+    assert_eq!(index.find("ab", 0).len(), 1);
+    assert!(index.find("", 2).is_empty());
+}
+
+#[test]
+fn test_offsets_are_bytes_for_multibyte_text() {
+    let message = "→ naïve café résumé";
+    let index = QGramIndex::new(&[message], 2);
+    let found = index.find("cafe résumé", 1);
+    assert_eq!(found.len(), 1);
+    let occurrence = found[0].start..found[0].start + found[0].len;
+    assert_eq!(&message[occurrence], "café résumé");
+    assert_eq!(found[0].edits, 1);
+}
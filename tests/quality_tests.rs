@@ -0,0 +1,84 @@
+use copyforward::fixture::generate_thread;
+use copyforward::quality::compare;
+use copyforward::render::Reconstructed;
+use copyforward::{Config, Segment, approximate, exact};
+
+const SOURCE: &str = "The release notes are ready for review.";
+
+fn reference(message_idx: usize, start: usize, len: usize) -> Segment {
+    Segment::Reference {
+        message_idx,
+        start,
+        len,
+    }
+}
+
+fn literal(text: &str) -> Segment {
+    Segment::Literal(text.into())
+}
+
+fn model(reply: Vec<Segment>) -> Reconstructed {
+    Reconstructed::new(vec![vec![literal(SOURCE)], reply]).unwrap()
+}
+
+#[test]
+fn test_literal_bytes_the_baseline_referenced_are_missed() {
+    let baseline = model(vec![literal("> "), reference(0, 0, SOURCE.len())]);
+    let candidate = model(vec![literal(&format!("> {SOURCE}"))]);
+    let quality = compare(&baseline, &candidate);
+    assert_eq!(quality.messages[0].missed, 0);
+    assert_eq!(quality.messages[1].missed, SOURCE.len());
+    assert_eq!(quality.messages[1].gained, 0);
+    assert_eq!(quality.total.missed_fraction(), 1.0);
+
+    let reversed = compare(&candidate, &baseline);
+    assert_eq!(reversed.total.missed, 0);
+    assert_eq!(reversed.total.gained, SOURCE.len());
+}
+
+#[test]
+fn test_splitting_references_differently_costs_nothing() {
+    let baseline = model(vec![reference(0, 0, SOURCE.len())]);
+    let candidate = model(vec![
+        reference(0, 0, 11),
+        reference(0, 11, SOURCE.len() - 11),
+    ]);
+    let quality = compare(&baseline, &candidate);
+    assert_eq!(quality.total.missed, 0);
+    assert_eq!(quality.total.gained, 0);
+}
+
+#[test]
+fn test_partial_overlaps_count_only_the_uncovered_bytes() {
+    let baseline = model(vec![reference(0, 0, 20), literal(&SOURCE[20..])]);
+    let candidate = model(vec![
+        literal(&SOURCE[..8]),
+        reference(0, 8, 22),
+        literal(&SOURCE[30..]),
+    ]);
+    let quality = compare(&baseline, &candidate);
+    assert_eq!(quality.messages[1].missed, 8);
+    assert_eq!(quality.messages[1].gained, 10);
+}
+
+#[test]
+fn test_totals_sum_the_messages() {
+    let messages = generate_thread(6, 25, 4);
+    let baseline = exact(&messages, Config::default());
+    let candidate = approximate(&messages, Config::default());
+    let quality = compare(&baseline, &candidate);
+    assert_eq!(quality.messages.len(), messages.len());
+    let missed: usize = quality.messages.iter().map(|m| m.missed).sum();
+    let bytes: usize = quality.messages.iter().map(|m| m.bytes).sum();
+    assert_eq!(quality.total.missed, missed);
+    assert_eq!(bytes, messages.iter().map(String::len).sum::<usize>());
+    assert!(quality.total.missed <= quality.total.baseline_referenced);
+    assert_eq!(compare(&baseline, &baseline).total.missed, 0);
+}
+
+#[test]
+#[should_panic(expected = "disagree")]
+fn test_models_of_different_messages_are_rejected() {
+    let other = Reconstructed::new(vec![vec![literal("Something else")], vec![]]).unwrap();
+    compare(&model(vec![]), &other);
+}
@@ -0,0 +1,51 @@
+use copyforward::quotes::{QuoteOptions, strip_quotes, strip_quotes_with};
+
+const FIRST: &str = "Hi all,\n\nThe build is failing on the release branch since this morning.\nCan someone take a look?\n\nThanks,\nAnn";
+
+#[test]
+fn test_strips_marked_quotes_and_attribution() {
+    let reply = format!(
+        "Looking into it now.\n\nOn Tue, 3 Jun 2025, Ann wrote:\n{}",
+        FIRST
+            .lines()
+            .map(|l| format!("> {l}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    let nested = format!(
+        "Fixed in #42.\n\n{}",
+        reply
+            .lines()
+            .map(|l| format!(">{l}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    let out = strip_quotes(&[FIRST, reply.as_str(), nested.as_str()]);
+    assert_eq!(out[0], FIRST);
+    assert_eq!(out[1], "Looking into it now.");
+    assert_eq!(out[2], "Fixed in #42.");
+}
+
+#[test]
+fn test_strips_unmarked_copies_and_outlook_forwards() {
+    let pasted = format!("Same problem here.\n\n{FIRST}");
+    let outlook =
+        format!("Escalating.\n\n-----Original Message-----\nFrom: Ann\nSubject: build\n\n{FIRST}");
+    let out = strip_quotes(&[
+        Some(FIRST),
+        None,
+        Some(pasted.as_str()),
+        Some(outlook.as_str()),
+    ]);
+    assert_eq!(out[1], "");
+    assert_eq!(out[2], "Same problem here.");
+    assert_eq!(out[3], "Escalating.");
+}
+
+#[test]
+fn test_short_repeats_survive() {
+    let thread = ["Thanks,\nAnn", "Sounds good.\nThanks,\nAnn"];
+    assert_eq!(strip_quotes(&thread)[1], "Sounds good.\nThanks,\nAnn");
+    let aggressive = QuoteOptions { min_match_len: 4 };
+    assert_eq!(strip_quotes_with(&thread, &aggressive)[1], "Sounds good.");
+}
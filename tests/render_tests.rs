@@ -0,0 +1,475 @@
+use copyforward::fixture::generate_thread;
+use copyforward::render::{
+    self, ReconstructError, Reconstructed, ReconstructedTokens, render_depth_limited,
+};
+use copyforward::{
+    Config, CopyForward, CopyForwardTokens, Segment, TokenSegment, approximate, exact, exact_tokens,
+};
+use std::borrow::Cow;
+
+const PYRAMID: [&str; 4] = [
+    "Can we ship on Friday?",
+    "Yes, if QA signs off.\n> Can we ship on Friday?",
+    "QA signed off.\n> Yes, if QA signs off.\n> Can we ship on Friday?",
+    "Shipping now.\n> QA signed off.\n> Yes, if QA signs off.\n> Can we ship on Friday?",
+];
+
+fn segments() -> Vec<Vec<Segment>> {
+    let config = Config {
+        min_match_len: 16,
+        ..Config::default()
+    };
+    exact(&PYRAMID, config).segments()
+}
+
+#[test]
+fn test_depth_limited_render_elides_deep_quotes() {
+    let out = render_depth_limited(&segments(), 1, "[…]").unwrap();
+    assert_eq!(out[0], PYRAMID[0]);
+    assert_eq!(out[1], PYRAMID[1]);
+    assert_eq!(out[3], "Shipping now.\n> QA signed off.\n> […]");
+
+    let out = render_depth_limited(&segments(), 2, "[…]").unwrap();
+    assert_eq!(
+        out[3],
+        "Shipping now.\n> QA signed off.\n> Yes, if QA signs off.\n> […]"
+    );
+}
+
+#[test]
+fn test_depth_zero_replaces_every_reference() {
+    let out = render_depth_limited(&segments(), 0, "[…]").unwrap();
+    assert_eq!(out[1], "Yes, if QA signs off.\n> […]");
+    assert_eq!(out[3], "Shipping now.\n> […]");
+}
+
+#[test]
+fn test_deep_limit_reproduces_messages() {
+    let out = render_depth_limited(&segments(), usize::MAX, "[…]").unwrap();
+    assert_eq!(out, PYRAMID);
+}
+
+#[test]
+fn test_depth_limited_render_rejects_forward_references() {
+    let segs = vec![vec![Segment::Reference {
+        message_idx: 1,
+        start: 0,
+        len: 1,
+    }]];
+    assert_eq!(
+        render_depth_limited(&segs, 1, "…"),
+        Err(ReconstructError::ForwardReference {
+            message: 0,
+            segment: 0
+        })
+    );
+}
+
+#[test]
+fn test_render_into_reuses_buffer_and_matches_render_with() {
+    let messages = [Some("Hello world"), None, Some("Hello world, again")];
+    let cf = approximate(&messages, Config::default());
+    let expected = cf.render_with_static("[R]");
+
+    let mut buf = String::with_capacity(64);
+    for (idx, want) in expected.iter().enumerate() {
+        buf.clear();
+        cf.render_into(idx, &mut buf, |_, _, _, _| "[R]".to_string());
+        assert_eq!(&buf, want);
+    }
+}
+
+#[test]
+fn test_render_all_into_appends_with_ranges() {
+    let cf = exact(&PYRAMID, Config::default());
+    let mut buf = String::from("prefix:");
+    let ranges = cf.render_all_into(&mut buf, |_, _, _, text| text.to_string());
+    assert_eq!(ranges.len(), PYRAMID.len());
+    assert_eq!(ranges[0].start, "prefix:".len());
+    for (range, message) in ranges.iter().zip(PYRAMID) {
+        assert_eq!(&buf[range.clone()], message);
+    }
+    assert_eq!(ranges.last().unwrap().end, buf.len());
+}
+
+#[test]
+fn test_render_cow_borrows_unchanged_messages() {
+    let messages = ["Hello world", "Something new", "Hello world again"];
+    let cf = exact(&messages, Config::default());
+
+    let identity = cf.render_cow(|_, _, _, text| Cow::Borrowed(text));
+    assert!(identity.iter().all(|m| matches!(m, Cow::Borrowed(_))));
+    assert_eq!(identity, messages);
+
+    let redacted = cf.render_cow(|_, _, _, _| Cow::Borrowed("[R]"));
+    assert!(matches!(redacted[0], Cow::Borrowed(_)));
+    assert!(matches!(redacted[1], Cow::Borrowed(_)));
+    assert_eq!(redacted[2], "[R] again");
+    assert_eq!(redacted, cf.render_with_static("[R]"));
+}
+
+#[test]
+fn test_locate_follows_reference_chains_to_literals() {
+    let segs = segments();
+    let cf = exact(&PYRAMID, Config::default());
+    for (m, text) in PYRAMID.iter().enumerate() {
+        for offset in 0..text.len() {
+            let origin = render::locate(&segs, m, offset).unwrap();
+            let source = PYRAMID[origin.source_message];
+            assert_eq!(
+                source.as_bytes()[origin.source_offset],
+                text.as_bytes()[offset]
+            );
+            assert!(origin.source_span.contains(&origin.source_offset));
+            assert!(origin.source_message <= m);
+            assert!(cf.locate(m, offset).is_some());
+        }
+        assert_eq!(render::locate(&segs, m, text.len()), None);
+    }
+
+    let question = PYRAMID[3].rfind("Can we").unwrap();
+    let origin = render::locate(&segs, 3, question).unwrap();
+    assert_eq!(origin.source_message, 0);
+    assert_eq!(origin.source_offset, 0);
+    assert_eq!(origin.source_span, 0..PYRAMID[0].len());
+}
+
+#[test]
+fn test_chain_depth_counts_reference_levels() {
+    let segs = segments();
+    for (m, text) in PYRAMID.iter().enumerate() {
+        let question = text.rfind("Can we").unwrap();
+        assert_eq!(render::chain_depth(&segs, m, question..text.len()), Some(m));
+        assert_eq!(render::chain_depth(&segs, m, 0..1), Some(0));
+        assert_eq!(render::chain_depth(&segs, m, 0..text.len() + 1), None);
+    }
+    let forward = vec![vec![Segment::Reference {
+        message_idx: 0,
+        start: 0,
+        len: 1,
+    }]];
+    assert_eq!(render::chain_depth(&forward, 0, 0..1), None);
+}
+
+#[test]
+fn test_locate_rejects_cyclic_references() {
+    let segs = vec![vec![Segment::Reference {
+        message_idx: 0,
+        start: 0,
+        len: 4,
+    }]];
+    assert_eq!(render::locate(&segs, 0, 1), None);
+}
+
+#[test]
+fn test_reconstructed_renders_like_the_original_result() {
+    let original = exact(&PYRAMID, Config::default());
+    let loaded = Reconstructed::new(original.segments()).unwrap();
+    assert_eq!(loaded.len(), PYRAMID.len());
+    assert_eq!(loaded.message(3), PYRAMID[3]);
+    assert_eq!(
+        loaded.render_with(|m, _, _, _| format!("[#{m}]")),
+        original.render_with(|m, _, _, _| format!("[#{m}]"))
+    );
+    assert_eq!(loaded.segments_for(2), original.segments_for(2));
+}
+
+#[test]
+fn test_reconstructed_tokens_rebuild_token_sequences() {
+    let messages: [&[u32]; 2] = [&[1, 2, 3, 4, 5], &[9, 1, 2, 3, 4, 5]];
+    let original = exact_tokens(&messages, Config::default());
+    let loaded = ReconstructedTokens::new(original.segments()).unwrap();
+    assert_eq!(loaded.message(1), messages[1]);
+    assert_eq!(
+        loaded.render_with_static(&[0]),
+        original.render_with_static(&[0])
+    );
+}
+
+#[test]
+fn test_reconstructed_rejects_invalid_segments() {
+    let forward = vec![vec![Segment::Reference {
+        message_idx: 1,
+        start: 0,
+        len: 1,
+    }]];
+    assert!(matches!(
+        Reconstructed::new(forward),
+        Err(ReconstructError::ForwardReference {
+            message: 0,
+            segment: 0
+        })
+    ));
+
+    let out_of_range = vec![
+        vec![TokenSegment::Literal(vec![1, 2])],
+        vec![TokenSegment::Reference {
+            message_idx: 0,
+            start: 1,
+            len: 2,
+        }],
+    ];
+    assert!(matches!(
+        ReconstructedTokens::new(out_of_range),
+        Err(ReconstructError::InvalidSpan {
+            message: 1,
+            segment: 0
+        })
+    ));
+}
+
+#[test]
+fn test_find_occurrences_matches_brute_force() {
+    let messages = generate_thread(4, 20, 4);
+    let model = approximate(&messages, Config::default());
+    for query in ["sentence 1", "e", "sentence 3 of", "not there"] {
+        let mut expected = Vec::new();
+        for (i, text) in messages.iter().enumerate() {
+            for start in 0..text.len() {
+                if text[start..].starts_with(query) {
+                    expected.push((i, start..start + query.len()));
+                }
+            }
+        }
+        let found: Vec<_> = model
+            .find_occurrences(query)
+            .into_iter()
+            .map(|o| (o.message, o.span))
+            .collect();
+        assert_eq!(found, expected, "{query}");
+    }
+}
+
+#[test]
+fn test_find_occurrences_flags_quotes_and_overlaps() {
+    let model = Reconstructed::new(vec![
+        vec![Segment::Literal("née aaaa".into())],
+        vec![
+            Segment::Reference {
+                message_idx: 0,
+                start: 0,
+                len: 4,
+            },
+            Segment::Literal("!".into()),
+        ],
+    ])
+    .unwrap();
+    let places = |query| {
+        model
+            .find_occurrences(query)
+            .into_iter()
+            .map(|o| (o.message, o.span, o.quoted))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        places("aa"),
+        [(0, 5..7, false), (0, 6..8, false), (0, 7..9, false)]
+    );
+    assert_eq!(places("née"), [(0, 0..4, false), (1, 0..4, true)]);
+    assert_eq!(places("e!"), [(1, 3..5, false)]);
+    assert_eq!(places(""), []);
+}
+
+#[test]
+fn test_lcs_matches_brute_force() {
+    let messages = generate_thread(9, 6, 3);
+    let model = exact(&messages, Config::default());
+    let chars = |s: &str| s.chars().count();
+    for i in 0..messages.len() {
+        for j in 0..messages.len() {
+            let (a, b) = (&messages[i], &messages[j]);
+            let bounds: Vec<usize> = a.char_indices().map(|(p, _)| p).chain([a.len()]).collect();
+            let longest = bounds
+                .iter()
+                .flat_map(|&s| {
+                    bounds
+                        .iter()
+                        .filter(move |&&e| e > s)
+                        .map(move |&e| &a[s..e])
+                })
+                .filter(|sub| b.contains(sub))
+                .map(chars)
+                .max()
+                .unwrap_or(0);
+            let shared = model.lcs(i, j);
+            assert!(!shared.is_empty());
+            for span in &shared {
+                assert_eq!(chars(&a[span.first.clone()]), longest);
+                assert_eq!(a[span.first.clone()], b[span.second.clone()]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_lcs_reports_each_distinct_longest_span() {
+    let messages = ["ab£|xyz|ab£", "xyz-ab£-ab£", "qqq"];
+    let model = exact(&messages, Config::default());
+    let shared = model.lcs(0, 1);
+    let texts: Vec<&str> = shared
+        .iter()
+        .map(|s| &messages[0][s.first.clone()])
+        .collect();
+    assert_eq!(texts, ["ab£", "xyz"]);
+    assert_eq!(shared[0].first, 0..4);
+    assert_eq!(shared[0].second, 4..8);
+    assert_eq!(model.lcs(0, 2), []);
+    assert_eq!(model.lcs(2, 2)[0].first, 0..3);
+}
+
+#[test]
+fn test_references_land_on_char_boundaries() {
+    // Repeats that differ only inside a multi-byte character must not be
+    // matched through it.
+    let base = "日本語のテキスト🎉é ".repeat(12);
+    let messages = [
+        base.clone(),
+        format!("{base}🎊 ünïcödé"),
+        format!("ü{}🎉", base.replace('é', "è")),
+    ];
+    let config = Config {
+        min_match_len: 2,
+        ..Config::default()
+    };
+    let models: Vec<Box<dyn CopyForward>> = vec![
+        Box::new(exact(&messages, config.clone())),
+        Box::new(approximate(&messages, config.clone())),
+        Box::new(copyforward::optimal(&messages, config)),
+    ];
+    for model in models {
+        for segs in model.segments() {
+            for seg in segs {
+                if let Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } = seg
+                {
+                    let source = &messages[message_idx];
+                    assert!(source.is_char_boundary(start));
+                    assert!(source.is_char_boundary(start + len));
+                }
+            }
+        }
+        assert_eq!(
+            model.render_with_dyn(&mut |_, _, _, text| text.to_string()),
+            messages
+        );
+    }
+}
+
+#[test]
+fn test_render_message_matches_render_with() {
+    let thread = generate_thread(3, 30, 4);
+    let model = exact(&thread, Config::default());
+    let all = model.render_with(|m, s, l, _| format!("[{m}:{s}+{l}]"));
+    for (idx, expected) in all.iter().enumerate() {
+        let mut calls = 0;
+        let one = model.render_message(idx, |m, s, l, _| {
+            calls += 1;
+            format!("[{m}:{s}+{l}]")
+        });
+        assert_eq!(&one, expected);
+        let refs = model
+            .segments_for(idx)
+            .iter()
+            .filter(|s| matches!(s, Segment::Reference { .. }));
+        assert_eq!(calls, refs.count());
+    }
+}
+
+#[test]
+fn test_render_to_writes_what_render_all_into_builds() {
+    let thread = generate_thread(5, 25, 4);
+    let model = approximate(&thread, Config::default());
+    let mut expected = String::new();
+    let expected_ranges = model.render_all_into(&mut expected, |m, _, _, _| format!("<{m}>"));
+    let mut out = Vec::new();
+    let ranges = model
+        .render_to(&mut out, |m, _, _, _| format!("<{m}>"))
+        .unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+    assert_eq!(ranges, expected_ranges);
+}
+
+#[test]
+fn test_render_to_surfaces_write_errors() {
+    struct Full;
+    impl std::io::Write for Full {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::StorageFull.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let model = exact(&PYRAMID, Config::default());
+    let err = model
+        .render_to(Full, |_, _, _, text| text.to_string())
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::StorageFull);
+}
+
+#[test]
+fn test_try_render_with_matches_render_with_when_every_lookup_succeeds() {
+    let thread = generate_thread(8, 25, 4);
+    let model = exact(&thread, Config::default());
+    let expected = model.render_with(|m, s, l, _| format!("[{m}:{s}+{l}]"));
+    let rendered: Result<_, ()> = model.try_render_with(|m, s, l, _| Ok(format!("[{m}:{s}+{l}]")));
+    assert_eq!(rendered.unwrap(), expected);
+}
+
+#[test]
+fn test_try_render_with_stops_at_the_first_error() {
+    let model = exact(&PYRAMID, Config::default());
+    let mut calls = 0;
+    let rendered = model.try_render_with(|m, _, _, _| {
+        calls += 1;
+        if m == 1 {
+            Err(format!("lookup of message {m} failed"))
+        } else {
+            Ok(String::new())
+        }
+    });
+    assert_eq!(rendered.unwrap_err(), "lookup of message 1 failed");
+    let before_failure = (0..model.len())
+        .flat_map(|idx| model.segments_for(idx))
+        .take_while(|seg| !matches!(seg, Segment::Reference { message_idx: 1, .. }))
+        .filter(|seg| matches!(seg, Segment::Reference { .. }))
+        .count();
+    assert_eq!(calls, before_failure + 1);
+}
+
+#[test]
+fn test_render_with_context_reports_target_and_output_offset() {
+    let thread = generate_thread(2, 25, 4);
+    let model = exact(&thread, Config::default());
+    let mut seen = Vec::new();
+    let rendered = model.render_with_context(|r| {
+        seen.push((r.target, r.output_offset, r.message_idx, r.start, r.len));
+        assert_eq!(r.text, &thread[r.message_idx][r.start..r.start + r.len]);
+        format!("<{}>", r.message_idx)
+    });
+    assert_eq!(rendered, model.render_with(|m, _, _, _| format!("<{m}>")));
+    let mut expected = Vec::new();
+    for (target, message) in rendered.iter().enumerate() {
+        let mut offset = 0;
+        for seg in model.segments_for(target) {
+            match seg {
+                Segment::Literal(text) => offset += text.len(),
+                &Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => {
+                    let marker = format!("<{message_idx}>");
+                    assert_eq!(&message[offset..offset + marker.len()], marker);
+                    expected.push((target, offset, message_idx, start, len));
+                    offset += marker.len();
+                }
+            }
+        }
+    }
+    assert!(!expected.is_empty());
+    assert_eq!(seen, expected);
+}
@@ -0,0 +1,99 @@
+use copyforward::fixture::generate_thread;
+use copyforward::render::reconstruct;
+use copyforward::rolling::{RollingCopyForward, SnapshotError};
+use copyforward::{Config, CopyForward, TokenSegment, exact};
+
+#[test]
+fn test_rolling_matches_exact_with_the_same_lookback() {
+    let messages = generate_thread(3, 30, 4);
+    for window in [1, 4, 100] {
+        let mut stream = RollingCopyForward::new(window, Config::default());
+        let streamed: Vec<_> = messages.iter().map(|m| stream.push(m)).collect();
+        let config = Config {
+            lookback: Some(window),
+            ..Config::default()
+        };
+        assert_eq!(streamed, exact(&messages, config).segments());
+    }
+}
+
+#[test]
+fn test_rolling_retains_only_the_window() {
+    let messages = generate_thread(9, 20, 3);
+    let mut stream = RollingCopyForward::new(3, Config::default());
+    let mut segments = Vec::new();
+    for (i, m) in messages.iter().enumerate() {
+        segments.push(stream.push(m));
+        assert_eq!(stream.window(), i.saturating_sub(2)..i + 1);
+        assert_eq!(stream.message(i), Some(m.as_str()));
+    }
+    assert_eq!(stream.len(), messages.len());
+    assert_eq!(stream.message(0), None);
+    assert_eq!(reconstruct(&segments).unwrap(), messages);
+}
+
+#[test]
+fn test_rolling_handles_multibyte_text() {
+    let mut stream = RollingCopyForward::new(2, Config::default());
+    let messages = [
+        "Grüße aus Köln, bis später!",
+        "» Grüße aus Köln, bis später! «",
+    ];
+    let segments: Vec<_> = messages.iter().map(|m| stream.push(m)).collect();
+    assert_eq!(reconstruct(&segments).unwrap(), messages);
+    assert!(segments[1].len() > 1);
+}
+
+#[test]
+fn test_zero_window_never_references() {
+    let mut stream = RollingCopyForward::new(0, Config::default());
+    stream.push("same text twice");
+    let segments = stream.push("same text twice");
+    assert_eq!(
+        segments,
+        [copyforward::Segment::Literal("same text twice".into())]
+    );
+    assert_eq!(stream.window(), 2..2);
+}
+
+#[test]
+fn test_restored_stream_continues_like_the_original() {
+    let messages = generate_thread(5, 24, 4);
+    for skip_referenced_regions in [false, true] {
+        let config = Config {
+            skip_referenced_regions,
+            ..Config::default()
+        };
+        let mut stream = RollingCopyForward::new(4, config);
+        for m in &messages[..10] {
+            stream.push(m);
+        }
+        let mut restored = RollingCopyForward::restore(stream.snapshot()).unwrap();
+        assert_eq!(restored.window(), stream.window());
+        assert_eq!(restored.message(9), stream.message(9));
+        for m in &messages[10..] {
+            assert_eq!(restored.push(m), stream.push(m));
+        }
+    }
+}
+
+#[test]
+fn test_restore_rejects_inconsistent_snapshots() {
+    let mut stream = RollingCopyForward::new(2, Config::default());
+    stream.push("first message");
+    stream.push("second message");
+
+    let mut oversized = stream.snapshot();
+    oversized.window = 1;
+    assert!(matches!(
+        RollingCopyForward::restore(oversized),
+        Err(SnapshotError::TooManyMessages)
+    ));
+
+    let mut mismatched = stream.snapshot();
+    mismatched.messages[1].1 = vec![TokenSegment::Literal(vec![1, 2, 3])];
+    assert!(matches!(
+        RollingCopyForward::restore(mismatched),
+        Err(SnapshotError::SegmentMismatch { message: 1 })
+    ));
+}
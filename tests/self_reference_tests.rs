@@ -0,0 +1,144 @@
+use copyforward::codec::{decode_segments, encode_segments};
+use copyforward::edit::{Edit, remap_after_edits};
+use copyforward::fixture::generate_thread;
+use copyforward::flatten::Flattened;
+use copyforward::render::{locate, reconstruct, reconstruct_tokens};
+use copyforward::{
+    Config, CopyForward, CopyForwardTokens, Segment, TokenSegment, exact, exact_tokens,
+};
+
+const SIGNATURE: &str = "--\nJane Doe, Release Manager, ACME Corp.";
+
+fn self_referencing() -> Config {
+    Config {
+        self_references: true,
+        ..Config::default()
+    }
+}
+
+/// References of message `idx` into itself, as `(position, start, len)`.
+fn self_refs(segments: &[Segment], idx: usize) -> Vec<(usize, usize, usize)> {
+    let mut pos = 0;
+    let mut out = Vec::new();
+    for seg in segments {
+        match seg {
+            Segment::Literal(s) => pos += s.len(),
+            Segment::Reference {
+                message_idx,
+                start,
+                len,
+            } => {
+                if *message_idx == idx {
+                    out.push((pos, *start, *len));
+                }
+                pos += len;
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn test_repetition_inside_one_message_is_referenced() {
+    let message = format!("Status: green.\n{SIGNATURE}\nForgot one thing: ship it.\n{SIGNATURE}");
+    let cf = exact(&[message.as_str()], self_referencing());
+    let refs = self_refs(cf.segments_for(0), 0);
+    assert_eq!(refs.len(), 1);
+    let (pos, start, len) = refs[0];
+    assert!(start + len <= pos);
+    assert!(len >= SIGNATURE.len());
+    assert_eq!(cf.render_with(|_, _, _, text| text.to_string()), [message]);
+}
+
+#[test]
+fn test_self_references_are_off_by_default() {
+    let message = format!("{SIGNATURE} {SIGNATURE}");
+    let cf = exact(&[message.as_str()], Config::default());
+    assert_eq!(
+        cf.segments()[0],
+        [Segment::Literal(message.as_str().into())]
+    );
+}
+
+#[test]
+fn test_earlier_messages_win_ties() {
+    let messages = [SIGNATURE.to_string(), format!("{SIGNATURE}\n{SIGNATURE}")];
+    let cf = exact(&messages, self_referencing());
+    for seg in cf.segments_for(1) {
+        if let Segment::Reference { message_idx, .. } = seg {
+            assert_eq!(*message_idx, 0);
+        }
+    }
+}
+
+#[test]
+fn test_segments_with_self_references_round_trip() {
+    let messages: Vec<String> = generate_thread(3, 15, 4)
+        .into_iter()
+        .map(|m| format!("{m}\n{SIGNATURE}\n{m}"))
+        .collect();
+    let cf = exact(&messages, self_referencing());
+    let segments = cf.segments();
+    assert!((0..messages.len()).any(|i| !self_refs(&segments[i], i).is_empty()));
+    assert_eq!(reconstruct(&segments).unwrap(), messages);
+    assert_eq!(
+        decode_segments(&encode_segments(&segments)).unwrap(),
+        segments
+    );
+
+    let flat = Flattened::new(&segments).unwrap();
+    for (m, text) in messages.iter().enumerate() {
+        for offset in 0..text.len() {
+            let origin = locate(&segments, m, offset).unwrap();
+            assert_eq!(flat.locate(m, offset), Some(origin.clone()));
+            let source = &messages[origin.source_message];
+            assert_eq!(
+                source.as_bytes()[origin.source_offset],
+                text.as_bytes()[offset]
+            );
+        }
+    }
+}
+
+#[test]
+fn test_token_messages_reference_themselves() {
+    let tokens: Vec<u32> = [1, 2, 3, 4, 5, 9, 1, 2, 3, 4, 5].to_vec();
+    let cf = exact_tokens(&[tokens.as_slice()], self_referencing());
+    assert_eq!(
+        cf.segments()[0],
+        [
+            TokenSegment::Literal(vec![1, 2, 3, 4, 5, 9]),
+            TokenSegment::Reference {
+                message_idx: 0,
+                start: 0,
+                len: 5
+            },
+        ]
+    );
+    assert_eq!(reconstruct_tokens(&cf.segments()).unwrap(), [tokens]);
+}
+
+#[test]
+fn test_depth_bounds_disable_self_references() {
+    let message = format!("{SIGNATURE} {SIGNATURE}");
+    let config = Config {
+        max_ref_depth: Some(1),
+        ..self_referencing()
+    };
+    let cf = exact(&[message.as_str()], config);
+    assert!(self_refs(cf.segments_for(0), 0).is_empty());
+}
+
+#[test]
+fn test_edits_spell_out_self_references_of_the_source() {
+    let messages = [
+        format!("Hi.\n{SIGNATURE}\nAlso:\n{SIGNATURE}"),
+        format!("> Hi.\n{SIGNATURE}"),
+    ];
+    let segments = exact(&messages, self_referencing()).segments();
+    assert!(!self_refs(&segments[0], 0).is_empty());
+    let remapped = remap_after_edits(&segments, 0, &[Edit::insert(0, "Hello! ")]).unwrap();
+    let texts = reconstruct(&remapped).unwrap();
+    assert_eq!(texts[0], format!("Hello! {}", messages[0]));
+    assert_eq!(texts[1], messages[1]);
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "server")]
+
+use copyforward::server::{RenderResponse, SegmentResponse, handle};
+
+#[test]
+fn test_segment_then_render_round_trip() {
+    let resp = handle(
+        "POST",
+        "/segment",
+        r#"{"messages": ["Hello world", null, "Hello world today"], "config": {"min_match_len": 5}}"#,
+    );
+    assert_eq!(resp.status, 200, "{}", resp.body);
+    let segmented: SegmentResponse = serde_json::from_str(&resp.body).unwrap();
+    assert_eq!(segmented.stats.messages, 3);
+    assert!(segmented.stats.references >= 1);
+    assert_eq!(segmented.metadata.algorithm, "exact");
+    assert_eq!(segmented.metadata.config.min_match_len, 5);
+
+    let body = serde_json::json!({ "segments": segmented.segments }).to_string();
+    let resp = handle("POST", "/render", &body);
+    assert_eq!(resp.status, 200, "{}", resp.body);
+    let rendered: RenderResponse = serde_json::from_str(&resp.body).unwrap();
+    assert_eq!(rendered.messages, ["Hello world", "", "Hello world today"]);
+}
+
+#[test]
+fn test_render_with_replacement() {
+    let body = r#"{"segments": [[{"literal": "Hello world"}],
+        [{"reference": {"message_idx": 0, "start": 0, "len": 5}}, {"literal": "!"}]],
+        "replacement": "[..]"}"#;
+    let resp = handle("POST", "/render", body);
+    let rendered: RenderResponse = serde_json::from_str(&resp.body).unwrap();
+    assert_eq!(rendered.messages, ["Hello world", "[..]!"]);
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(handle("POST", "/segment", "not json").status, 400);
+    assert_eq!(handle("GET", "/segment", "").status, 405);
+    assert_eq!(handle("POST", "/nope", "{}").status, 404);
+    let forward = r#"{"segments": [[{"reference": {"message_idx": 1, "start": 0, "len": 1}}]]}"#;
+    let resp = handle("POST", "/render", forward);
+    assert_eq!(resp.status, 422);
+    assert!(resp.body.contains("error"));
+}
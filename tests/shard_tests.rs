@@ -0,0 +1,34 @@
+use copyforward::fixture::generate_thread;
+use copyforward::shard::{KmerIndex, find_duplicates_sharded};
+
+#[test]
+fn test_sharding_matches_single_pass() {
+    let docs = generate_thread(11, 30, 6);
+    let whole = find_duplicates_sharded(&[&docs[..]], 12);
+    let sharded = find_duplicates_sharded(&[&docs[..7], &docs[7..18], &docs[18..]], 12);
+    let flattened: Vec<_> = sharded.into_iter().flatten().collect();
+    assert_eq!(flattened, whole[0]);
+    assert!(flattened.iter().skip(1).any(|spans| !spans.is_empty()));
+}
+
+#[test]
+fn test_cross_shard_duplicates_found() {
+    let a = [Some("an original sentence that will be copied later"), None];
+    let b = [Some(
+        "intro: an original sentence that will be copied later",
+    )];
+    let spans = find_duplicates_sharded(&[&a[..], &b[..]], 10);
+    assert!(spans[0].iter().all(|s| s.is_empty()));
+    assert_eq!(spans[1][0], vec!["intro: ".len()..b[0].unwrap().len()]);
+}
+
+#[test]
+fn test_merge_keeps_earliest_document() {
+    let docs = ["shared text here", "shared text here"];
+    let mut late = KmerIndex::build(&docs[1..], 1, 6);
+    let early = KmerIndex::build(&docs[..1], 0, 6);
+    late.merge(early);
+    let spans = late.duplicate_spans(&docs, 0);
+    assert!(spans[0].is_empty());
+    assert_eq!(spans[1], vec![0..docs[1].len()]);
+}
@@ -0,0 +1,74 @@
+use copyforward::{Config, CopyForward, Segment, approximate, exact};
+
+fn quoted_thread() -> Vec<String> {
+    let mut thread =
+        vec!["The staging database ran out of disk during the nightly import.".to_string()];
+    for reply in [
+        "Which volume?",
+        "The data volume, 200 GB.",
+        "Resizing it now.",
+    ] {
+        let quoted = thread.last().unwrap().replace('\n', "\n> ");
+        thread.push(format!("> {quoted}\n{reply}"));
+    }
+    thread
+}
+
+fn skipping() -> Config {
+    Config {
+        skip_referenced_regions: true,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn test_skipping_referenced_regions_round_trips() {
+    let thread = quoted_thread();
+    for cf in [
+        Box::new(exact(&thread, skipping())) as Box<dyn CopyForward>,
+        Box::new(approximate(&thread, skipping())),
+    ] {
+        assert_eq!(cf.render_with_dyn(&mut |_, _, _, t| t.to_string()), thread);
+    }
+}
+
+/// Byte ranges of `segs` that are references.
+fn referenced_ranges(segs: &[Segment]) -> Vec<std::ops::Range<usize>> {
+    let mut pos = 0;
+    let mut out = Vec::new();
+    for seg in segs {
+        match seg {
+            Segment::Literal(s) => pos += s.len(),
+            Segment::Reference { len, .. } => {
+                out.push(pos..pos + len);
+                pos += len;
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn test_no_reference_starts_inside_a_quoted_region() {
+    let thread = quoted_thread();
+    let k = skipping().min_match_len;
+    for segments in [
+        exact(&thread, skipping()).segments(),
+        approximate(&thread, skipping()).segments(),
+    ] {
+        for seg in segments.iter().flatten() {
+            if let Segment::Reference {
+                message_idx, start, ..
+            } = seg
+            {
+                let quoted = referenced_ranges(&segments[*message_idx]);
+                assert!(
+                    !quoted
+                        .iter()
+                        .any(|r| r.start <= *start && start + k <= r.end),
+                    "reference into #{message_idx} at {start} starts inside a quoted region"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use copyforward::sourcemap::{SourceMap, render_with_source_map};
+use copyforward::{Config, CopyForward, approximate, exact};
+
+const THREAD: [&str; 3] = [
+    "Are the logs rotated nightly?",
+    "Are the logs rotated nightly? I think weekly.",
+    "Weekly. > Are the logs rotated nightly? I think weekly.",
+];
+
+#[test]
+fn test_mappings_cover_output_and_point_at_matching_text() {
+    let cf = approximate(&THREAD, Config::default());
+    let (rendered, map) = render_with_source_map(&cf, |_, _, _, text| text.to_string());
+    assert_eq!(rendered, THREAD);
+    assert_eq!(map, SourceMap::from_segments(&cf.segments()));
+    for (idx, message) in map.messages.iter().enumerate() {
+        assert_eq!(message.length, THREAD[idx].len());
+        let mut end = 0;
+        for m in &message.mappings {
+            assert_eq!(m.output.start, end);
+            end = m.output.end;
+            assert_eq!(
+                &rendered[idx][m.output.clone()],
+                &THREAD[m.source_message][m.source.clone()]
+            );
+            assert_eq!(m.reference, m.source_message != idx);
+        }
+        assert_eq!(end, message.length);
+    }
+}
+
+#[test]
+fn test_replaced_references_map_whole_source_range() {
+    let cf = exact(&THREAD, Config::default());
+    let (rendered, map) = render_with_source_map(&cf, |_, _, _, _| "<q>".to_string());
+    for (idx, message) in map.messages.iter().enumerate() {
+        assert_eq!(message.length, rendered[idx].len());
+        for m in message.mappings.iter().filter(|m| m.reference) {
+            assert_eq!(&rendered[idx][m.output.clone()], "<q>");
+            assert!(m.source.len() >= Config::default().min_match_len);
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn test_source_map_json_round_trip() {
+    let cf = exact(&THREAD, Config::default());
+    let map = SourceMap::from_segments(&cf.segments());
+    let json = serde_json::to_value(&map).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(
+        json["messages"][0]["mappings"][0]["output"]["end"],
+        THREAD[0].len()
+    );
+    let back: SourceMap = serde_json::from_value(json).unwrap();
+    assert_eq!(back, map);
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "sqlite")]
+
+use copyforward::render::reconstruct;
+use copyforward::sqlite::SqliteStore;
+use copyforward::{Config, CopyForward, exact};
+
+#[test]
+fn test_save_and_load_round_trip() {
+    let messages = [
+        "Hello world from Alice",
+        "",
+        "Hello world from Alice, again",
+    ];
+    let segments = exact(&messages, Config::default()).segments();
+
+    let mut store = SqliteStore::open_in_memory().unwrap();
+    store.save_thread("t1", &segments).unwrap();
+    let loaded = store.load_segments("t1").unwrap().unwrap();
+    assert_eq!(loaded, segments);
+    assert_eq!(reconstruct(&loaded).unwrap(), messages);
+    assert!(store.load_segments("missing").unwrap().is_none());
+}
+
+#[test]
+fn test_messages_referencing() {
+    let messages = [
+        "The quick brown fox",
+        "unrelated",
+        "The quick brown fox jumps",
+    ];
+    let segments = exact(&messages, Config::default()).segments();
+
+    let mut store = SqliteStore::open_in_memory().unwrap();
+    store.save_thread("t1", &segments).unwrap();
+    let first = store.message_id("t1", 0).unwrap().unwrap();
+    let third = store.message_id("t1", 2).unwrap().unwrap();
+    assert_eq!(store.messages_referencing(first).unwrap(), vec![third]);
+}
+
+#[test]
+fn test_resave_replaces_thread_and_keeps_metadata_per_thread() {
+    let mut store = SqliteStore::open_in_memory().unwrap();
+    let v1 = exact(&["one message"], Config::default()).segments();
+    let v2 = exact(&["one message", "one message again"], Config::default()).segments();
+    store.save_thread("t", &v1).unwrap();
+    assert!(
+        store
+            .set_thread_metadata("t", "algorithm", "exact")
+            .unwrap()
+    );
+    store.save_thread("t", &v2).unwrap();
+
+    assert_eq!(store.thread_names().unwrap(), vec!["t"]);
+    assert_eq!(store.load_segments("t").unwrap().unwrap(), v2);
+    assert!(store.thread_metadata("t").unwrap().is_empty());
+    assert!(!store.set_thread_metadata("missing", "k", "v").unwrap());
+}
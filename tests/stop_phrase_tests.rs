@@ -0,0 +1,64 @@
+use copyforward::{Config, CopyForward, Segment, approximate, exact};
+
+const MESSAGES: [&str; 3] = [
+    "Hi team,\nThe deploy finished without errors.\nThanks,\nAnn",
+    "Hi team,\nThe deploy finished without errors.\nThanks,\nAnn\nAlso the docs are live.",
+    "Hi Bob,\nThanks,\nAnn",
+];
+
+fn config() -> Config {
+    Config {
+        min_match_len: 4,
+        stop_phrases: vec!["Hi ".to_string(), "Thanks,".to_string()],
+        ..Config::default()
+    }
+}
+
+/// Text of every referenced span.
+fn referenced(segments: &[Vec<Segment>]) -> Vec<&'static str> {
+    let mut out = Vec::new();
+    for (i, segs) in segments.iter().enumerate() {
+        let mut pos = 0;
+        for seg in segs {
+            match seg {
+                Segment::Literal(s) => pos += s.len(),
+                Segment::Reference { len, .. } => {
+                    out.push(&MESSAGES[i][pos..pos + len]);
+                    pos += len;
+                }
+            }
+        }
+    }
+    out
+}
+
+fn check(segments: &[Vec<Segment>]) {
+    for covered in referenced(segments) {
+        assert!(!covered.contains("Thanks,"), "{covered:?}");
+        assert!(!covered.contains("Hi "), "{covered:?}");
+    }
+}
+
+#[test]
+fn test_stop_phrases_never_referenced() {
+    let cf = exact(&MESSAGES, config());
+    check(&cf.segments());
+    assert_eq!(cf.render_with(|_, _, _, t| t.to_string()), MESSAGES);
+
+    let cf = approximate(&MESSAGES, config());
+    check(&cf.segments());
+    assert_eq!(cf.render_with(|_, _, _, t| t.to_string()), MESSAGES);
+}
+
+#[test]
+fn test_text_between_stop_phrases_still_matches() {
+    let spans = referenced(&exact(&MESSAGES, config()).segments());
+    assert!(spans.contains(&"team,\nThe deploy finished without errors.\n"));
+
+    let unrestricted = Config {
+        stop_phrases: Vec::new(),
+        ..config()
+    };
+    let spans = referenced(&exact(&MESSAGES, unrestricted).segments());
+    assert!(spans.iter().any(|s| s.contains("Thanks,")));
+}
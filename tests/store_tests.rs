@@ -0,0 +1,146 @@
+use copyforward::codec::{
+    DecodeError, checksum, decode_messages, decode_segments, encode_model, encode_segments,
+};
+use copyforward::fixture::generate_thread;
+use copyforward::store::{MemoryStore, SegmentStore};
+use copyforward::{Config, CopyForward, Segment, approximate, exact};
+
+#[test]
+fn test_codec_round_trip_fixture_thread() {
+    let msgs = generate_thread(7, 40, 8);
+    for segments in [
+        exact(&msgs, Config::default()).segments(),
+        approximate(&msgs, Config::default()).segments(),
+    ] {
+        let bytes = encode_segments(&segments);
+        assert_eq!(decode_segments(&bytes).unwrap(), segments);
+    }
+}
+
+#[test]
+fn test_codec_rejects_malformed_input() {
+    let segments = vec![
+        vec![Segment::Literal("héllo wörld".into())],
+        vec![Segment::Reference {
+            message_idx: 0,
+            start: 0,
+            len: 6,
+        }],
+    ];
+    let bytes = encode_segments(&segments);
+    assert_eq!(decode_segments(b"nope"), Err(DecodeError::BadMagic));
+    assert_eq!(
+        decode_segments(&bytes[..bytes.len() - 1]),
+        Err(DecodeError::Truncated)
+    );
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(decode_segments(&trailing), Err(DecodeError::TrailingBytes));
+    let mut version = bytes;
+    version[4] = 99;
+    assert_eq!(
+        decode_segments(&version),
+        Err(DecodeError::UnsupportedVersion(99))
+    );
+}
+
+#[test]
+fn test_codec_detects_corrupted_messages() {
+    let messages = ["Ship it on Friday", "> Ship it on Friday\nAgreed"];
+    let model = exact(&messages, Config::default());
+    let bytes = encode_model(&model);
+    assert_eq!(decode_segments(&bytes).unwrap(), model.segments());
+    assert_eq!(decode_messages(&bytes).unwrap(), messages);
+
+    let mut corrupted = bytes.clone();
+    let at = corrupted.windows(5).position(|w| w == b"Agree").unwrap();
+    corrupted[at] = b'a';
+    let mismatch = DecodeError::ChecksumMismatch { message: 1 };
+    assert_eq!(decode_segments(&corrupted), Err(mismatch.clone()));
+    assert_eq!(decode_messages(&corrupted), Err(mismatch));
+
+    let out_of_range = vec![
+        vec![Segment::Literal("short".into())],
+        vec![Segment::Reference {
+            message_idx: 0,
+            start: 3,
+            len: 9,
+        }],
+    ];
+    assert_eq!(
+        decode_segments(&encode_segments(&out_of_range)),
+        Err(DecodeError::ReferenceOutOfRange { message: 1 })
+    );
+}
+
+#[test]
+fn test_codec_accepts_only_version_1() {
+    let bytes = encode_segments(&[vec![Segment::Literal("hello".into())]]);
+    assert_eq!(bytes[4], 1);
+    for version in [0, 2, 3] {
+        let mut other = bytes.clone();
+        other[4] = version;
+        let unsupported = DecodeError::UnsupportedVersion(version);
+        assert_eq!(decode_segments(&other), Err(unsupported.clone()));
+        assert_eq!(decode_messages(&other), Err(unsupported));
+    }
+}
+
+#[test]
+fn test_checksum_is_crc32() {
+    assert_eq!(checksum(""), 0);
+    assert_eq!(checksum("123456789"), 0xCBF4_3926);
+}
+
+fn exercise_store(store: &mut dyn SegmentStore) {
+    let t1 = exact(&["Hello world", "Hello world today"], Config::default()).segments();
+    let t2 = exact(&["Another thread"], Config::default()).segments();
+    store.put("t1", &t1).unwrap();
+    store.put("t2", &t2).unwrap();
+    assert_eq!(store.get("t1").unwrap(), Some(t1));
+    assert_eq!(store.get("missing").unwrap(), None);
+
+    let mut ids = store.thread_ids().unwrap();
+    ids.sort();
+    assert_eq!(ids, vec!["t1", "t2"]);
+
+    // Incremental update: only t2 is rewritten.
+    let t2b = exact(
+        &["Another thread", "Another thread, continued"],
+        Config::default(),
+    )
+    .segments();
+    store.put("t2", &t2b).unwrap();
+    assert_eq!(store.get("t2").unwrap(), Some(t2b));
+
+    assert!(store.remove("t1").unwrap());
+    assert!(!store.remove("t1").unwrap());
+    assert_eq!(store.thread_ids().unwrap(), vec!["t2"]);
+}
+
+#[test]
+fn test_memory_store() {
+    exercise_store(&mut MemoryStore::new());
+}
+
+#[cfg(feature = "sled")]
+#[test]
+fn test_sled_store() {
+    let dir = std::env::temp_dir().join(format!("copyforward-sled-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    {
+        let mut store = copyforward::store::SledStore::open(&dir).unwrap();
+        exercise_store(&mut store);
+        store.flush().unwrap();
+    }
+    let reopened = copyforward::store::SledStore::open(&dir).unwrap();
+    assert_eq!(reopened.thread_ids().unwrap(), vec!["t2"]);
+    drop(reopened);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_sqlite_segment_store() {
+    exercise_store(&mut copyforward::sqlite::SqliteStore::open_in_memory().unwrap());
+}
@@ -76,6 +76,31 @@ fn test_mixed_string_types() {
     assert!(compressed3.segments().len() == 2);
 }
 
+#[test]
+fn test_borrowed_and_shared_string_types() {
+    use copyforward::CompactString;
+    use std::borrow::Cow;
+    use std::sync::Arc;
+
+    let owned = ["hello world".to_string(), "skip".to_string(), "hello world again".to_string()];
+    let expected = exact(&["hello world", "hello world again"], Config::default()).segments();
+
+    let kept: Vec<&String> = owned.iter().filter(|m| *m != "skip").collect();
+    assert_eq!(exact(&kept, Config::default()).segments(), expected);
+    let cows: Vec<Cow<str>> = kept.iter().map(|m| Cow::Borrowed(m.as_str())).collect();
+    assert_eq!(exact(&cows, Config::default()).segments(), expected);
+    let boxed: Vec<Box<str>> = kept.iter().map(|m| m.as_str().into()).collect();
+    assert_eq!(exact(&boxed, Config::default()).segments(), expected);
+    let shared: Vec<Arc<str>> = kept.iter().map(|m| m.as_str().into()).collect();
+    assert_eq!(exact(&shared, Config::default()).segments(), expected);
+    let compact: Vec<CompactString> = kept.iter().map(|m| m.as_str().into()).collect();
+    assert_eq!(exact(&compact, Config::default()).segments(), expected);
+
+    let optional = [Some("hello world".to_string()), None];
+    let borrowed: Vec<&Option<String>> = optional.iter().collect();
+    assert_eq!(exact(&borrowed, Config::default()).segments(), exact(&optional, Config::default()).segments());
+}
+
 #[test]
 fn test_mixed_token_types() {
     // Test that our TokenLike trait works with different token types
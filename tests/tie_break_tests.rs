@@ -0,0 +1,56 @@
+use copyforward::render::reconstruct;
+use copyforward::{Config, CopyForward, Segment, TieBreak, exact};
+
+fn with_tie_break(tie_break: TieBreak) -> Config {
+    Config {
+        tie_break,
+        ..Config::default()
+    }
+}
+
+fn sources(model: &dyn CopyForward, idx: usize) -> Vec<usize> {
+    model
+        .segments_for(idx)
+        .iter()
+        .filter_map(|seg| match seg {
+            Segment::Reference { message_idx, .. } => Some(*message_idx),
+            Segment::Literal(_) => None,
+        })
+        .collect()
+}
+
+const NOTICE: &str =
+    "The build server will be offline on Saturday from nine to noon while the disks are replaced.";
+
+#[test]
+fn test_default_prefers_the_earliest_source() {
+    let reply = format!("> {NOTICE}\nNoted.");
+    let messages = [NOTICE, NOTICE, reply.as_str()];
+    let default = exact(&messages, Config::default());
+    let earliest = exact(&messages, with_tie_break(TieBreak::PreferEarliest));
+    assert_eq!(sources(&default, 2), [0]);
+    assert_eq!(default.segments(), earliest.segments());
+}
+
+#[test]
+fn test_most_recent_prefers_the_latest_source() {
+    let reply = format!("> {NOTICE}\nNoted.");
+    let messages = [NOTICE, NOTICE, reply.as_str()];
+    let model = exact(&messages, with_tie_break(TieBreak::PreferMostRecent));
+    assert_eq!(sources(&model, 2), [1]);
+}
+
+#[test]
+fn test_same_source_keeps_to_the_previous_reference() {
+    let question =
+        "Could someone confirm whether the staging database is included in this maintenance?";
+    let thread = format!("{question}\n{NOTICE}");
+    let reply = format!("> {question}\n(see below)\n> {NOTICE}\nYes, it is.");
+    let messages = [NOTICE, thread.as_str(), reply.as_str()];
+    let earliest = exact(&messages, Config::default());
+    assert_eq!(sources(&earliest, 2), [1, 0]);
+    let config = with_tie_break(TieBreak::PreferSameSourceAsPrevSegment);
+    let same = exact(&messages, config);
+    assert_eq!(sources(&same, 2), [1, 1]);
+    assert_eq!(reconstruct(&same.segments()).unwrap(), messages);
+}
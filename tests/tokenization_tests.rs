@@ -0,0 +1,34 @@
+use copyforward::tokenization::{Tokenize, UnicodeWordTokenizer, get_tokenizer};
+use copyforward::{Config, CopyForwardTokens, exact_tokens};
+
+#[test]
+fn test_unicode_tokenizer_splits_cjk_and_round_trips() {
+    let mut tok = UnicodeWordTokenizer::new();
+    let text = "会议改到周四, ok?  Thanks!";
+    let ids = tok.encode(text);
+    assert_eq!(tok.decode(&ids), text);
+
+    let pieces: Vec<String> = ids.iter().map(|&id| tok.decode(&[id])).collect();
+    assert_eq!(&pieces[..6], ["会", "议", "改", "到", "周", "四"]);
+    assert!(pieces.contains(&"Thanks".to_string()));
+    assert!(pieces.contains(&"  ".to_string()));
+}
+
+#[test]
+fn test_unicode_tokenizer_finds_repeats_in_unspaced_text() {
+    let mut tok = get_tokenizer("unicode").unwrap();
+    let messages = ["我们明天下午三点开会", "好的。我们明天下午三点开会"];
+    let ids: Vec<Vec<u32>> = messages.iter().map(|m| tok.encode(m)).collect();
+    let config = Config {
+        min_match_len: 4,
+        ..Config::default()
+    };
+    let rendered = exact_tokens(&ids, config).render_with(|_, _, _, _| tok.encode("[REF]"));
+    assert_eq!(tok.decode(&rendered[1]), "好的。[REF]");
+}
+
+#[test]
+fn test_unknown_tokenizer_lists_unicode() {
+    let err = get_tokenizer("nope").err().unwrap();
+    assert!(err.contains("'unicode'"));
+}
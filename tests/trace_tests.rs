@@ -0,0 +1,193 @@
+use copyforward::trace::{Candidate, Decision, Outcome};
+use copyforward::{Config, CopyForward, Trace, approximate, exact, exact_tokens, optimal};
+
+fn traced(config: Config) -> (Trace, Config) {
+    let trace = Trace::new();
+    let config = Config {
+        trace: Some(trace.clone()),
+        ..config
+    };
+    (trace, config)
+}
+
+fn references(decisions: &[Decision]) -> Vec<&Decision> {
+    decisions
+        .iter()
+        .filter(|d| matches!(d.outcome, Outcome::Reference { .. }))
+        .collect()
+}
+
+#[test]
+fn test_trace_does_not_change_segments() {
+    let messages = [
+        "Hello world, see you Monday",
+        "> Hello world, see you Monday\nSure",
+        "> > Hello world, see you Monday\n> Sure\nGreat",
+    ];
+    let (trace, config) = traced(Config::default());
+    assert_eq!(
+        exact(&messages, config.clone()).segments(),
+        exact(&messages, Config::default()).segments()
+    );
+    assert_eq!(
+        approximate(&messages, config).segments(),
+        approximate(&messages, Config::default()).segments()
+    );
+    assert!(!trace.decisions().is_empty());
+}
+
+#[test]
+fn test_exact_trace_covers_every_unit() {
+    let messages = ["Hello world", "> Hello world\nHi!"];
+    let (trace, config) = traced(Config::default());
+    exact(&messages, config);
+
+    let second = trace.decisions_for(1);
+    let covered: usize = second
+        .iter()
+        .map(|d| match d.outcome {
+            Outcome::Reference { len, .. } | Outcome::Literal { len } => len,
+            Outcome::Duplicate { .. } => unreachable!(),
+        })
+        .sum();
+    assert_eq!(covered, messages[1].chars().count());
+    assert_eq!(
+        second[1],
+        Decision {
+            message: 1,
+            position: 2,
+            candidates: vec![Candidate {
+                message: 0,
+                start: 0,
+                len: Some(11),
+            }],
+            cap_hit: false,
+            tie_break: false,
+            outcome: Outcome::Reference {
+                source: 0,
+                start: 0,
+                len: 11,
+                depth_bounded: false,
+            },
+        }
+    );
+    assert_eq!(trace.decisions_for(0), [trace.decisions()[0].clone()]);
+}
+
+#[test]
+fn test_trace_reports_cap_hits() {
+    let repeated = "a".repeat(100);
+    let messages = [repeated.as_str(), "aaaa"];
+    let (trace, config) = traced(Config::default());
+    exact(&messages, config);
+    let lookup = &trace.decisions_for(1)[0];
+    assert!(lookup.cap_hit);
+    assert_eq!(lookup.candidates.len(), 64);
+
+    let (trace, config) = traced(Config {
+        ncap: 2,
+        ..Config::default()
+    });
+    approximate(&messages, config);
+    let lookup = &trace.decisions_for(1)[0];
+    assert!(lookup.cap_hit);
+    assert_eq!(lookup.candidates.len(), 2);
+}
+
+#[test]
+fn test_trace_reports_shallow_tie_breaks() {
+    let original = "The quarterly numbers are in and look good";
+    let quote = format!("{original} indeed");
+    let messages = [original, quote.as_str(), quote.as_str()];
+    let tokens: Vec<Vec<u32>> = messages
+        .iter()
+        .map(|m| m.chars().map(u32::from).collect())
+        .collect();
+
+    let (trace, config) = traced(Config::default());
+    exact_tokens(&tokens, config);
+    assert!(
+        references(&trace.decisions_for(2))
+            .iter()
+            .all(|d| !d.tie_break)
+    );
+
+    let (trace, config) = traced(Config {
+        shallow_preference: Some(10),
+        ..Config::default()
+    });
+    exact_tokens(&tokens, config);
+    let decisions = trace.decisions_for(2);
+    let chosen = references(&decisions)[0];
+    assert!(chosen.tie_break);
+    assert!(matches!(
+        chosen.outcome,
+        Outcome::Reference { source: 0, .. }
+    ));
+}
+
+#[test]
+fn test_trace_reports_duplicates_and_depth_bounds() {
+    let messages = [
+        "Meeting moved to Friday afternoon",
+        "Meeting moved to Friday afternoon",
+    ];
+    let (trace, config) = traced(Config {
+        dedup_messages: true,
+        ..Config::default()
+    });
+    exact(&messages, config);
+    assert_eq!(
+        trace.decisions_for(1)[0].outcome,
+        Outcome::Duplicate { source: 0 }
+    );
+
+    let messages = [
+        "Meeting moved to Friday afternoon",
+        "> Meeting moved to Friday afternoon",
+        "> > Meeting moved to Friday afternoon",
+    ];
+    let (trace, config) = traced(Config {
+        max_ref_depth: Some(1),
+        ..Config::default()
+    });
+    approximate(&messages, config);
+    let decisions = trace.decisions_for(2);
+    assert!(references(&decisions).iter().any(|d| matches!(
+        d.outcome,
+        Outcome::Reference {
+            source: 1,
+            depth_bounded: true,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn test_approximate_trace_lists_rejected_candidates() {
+    let messages = [
+        "Hello world, how are you",
+        "Hello world, how is it",
+        "Hello world, how are we",
+    ];
+    let (trace, config) = traced(Config {
+        cap_len: 20,
+        ..Config::default()
+    });
+    approximate(&messages, config);
+    let first = &trace.decisions_for(2)[0];
+    assert!(first.candidates.iter().any(|c| c.len.is_none()));
+}
+
+#[test]
+fn test_trace_is_shared_and_clearable() {
+    let (trace, config) = traced(Config::default());
+    exact(&["Hello world"], config.clone());
+    let once = trace.decisions().len();
+    exact(&["Hello world"], config.clone());
+    assert_eq!(trace.decisions().len(), 2 * once);
+    trace.clear();
+    assert!(trace.decisions().is_empty());
+    optimal(&["Hello world", "Hello world"], config);
+    assert!(trace.decisions().is_empty());
+}
@@ -0,0 +1,308 @@
+use copyforward::fixture::generate_thread;
+use copyforward::prelude::*;
+use copyforward::{DynReplacer, exact};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Model holding whatever segments it is given, right or wrong.
+struct Stored {
+    originals: Vec<String>,
+    segments: Vec<Vec<Segment>>,
+}
+
+impl CopyForward for Stored {
+    fn segments(&self) -> Vec<Vec<Segment>> {
+        self.segments.clone()
+    }
+
+    fn segments_for(&self, idx: usize) -> &[Segment] {
+        &self.segments[idx]
+    }
+
+    fn message(&self, idx: usize) -> &str {
+        &self.originals[idx]
+    }
+
+    fn len(&self) -> usize {
+        self.originals.len()
+    }
+
+    fn render_with<F>(&self, mut replacer: F) -> Vec<String>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        (0..self.len())
+            .map(|idx| self.render_message(idx, &mut replacer))
+            .collect()
+    }
+
+    fn render_with_dyn(&self, replacer: &mut DynReplacer<'_>) -> Vec<String> {
+        self.render_with(replacer)
+    }
+
+    fn render_into_dyn(&self, idx: usize, buf: &mut String, replacer: &mut DynReplacer<'_>) {
+        self.render_into(idx, buf, replacer)
+    }
+
+    fn render_into<F>(&self, idx: usize, buf: &mut String, mut replacer: F)
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        for seg in &self.segments[idx] {
+            match seg {
+                Segment::Literal(text) => buf.push_str(text),
+                &Segment::Reference {
+                    message_idx,
+                    start,
+                    len,
+                } => {
+                    let text = &self.originals[message_idx][start..start + len];
+                    buf.push_str(&replacer(message_idx, start, len, text));
+                }
+            }
+        }
+    }
+
+    fn render_all_into<F>(&self, buf: &mut String, mut replacer: F) -> Vec<Range<usize>>
+    where
+        F: FnMut(usize, usize, usize, &str) -> String,
+    {
+        (0..self.len())
+            .map(|idx| {
+                let start = buf.len();
+                self.render_into(idx, buf, &mut replacer);
+                start..buf.len()
+            })
+            .collect()
+    }
+
+    fn render_cow<'a, F>(&'a self, mut replacer: F) -> Vec<Cow<'a, str>>
+    where
+        F: FnMut(usize, usize, usize, &'a str) -> Cow<'a, str>,
+    {
+        (0..self.len())
+            .map(|idx| {
+                let mut out = String::new();
+                for seg in &self.segments[idx] {
+                    match seg {
+                        Segment::Literal(text) => out.push_str(text),
+                        &Segment::Reference {
+                            message_idx,
+                            start,
+                            len,
+                        } => {
+                            let text = &self.originals[message_idx][start..start + len];
+                            out.push_str(&replacer(message_idx, start, len, text));
+                        }
+                    }
+                }
+                Cow::Owned(out)
+            })
+            .collect()
+    }
+}
+
+fn stored(second: Vec<Segment>) -> Stored {
+    Stored {
+        originals: vec!["Hello world".into(), "Hello there".into()],
+        segments: vec![vec![Segment::Literal("Hello world".into())], second],
+    }
+}
+
+#[test]
+fn test_every_algorithm_verifies() {
+    for seed in 0..3 {
+        let thread = generate_thread(seed, 20, 4);
+        for &algorithm in Algorithm::ALL {
+            let compressed = compress_verified(&thread, algorithm, Config::default()).unwrap();
+            assert_eq!(
+                compressed.segments(),
+                compress(&thread, algorithm, Config::default()).segments()
+            );
+        }
+    }
+    assert_eq!(
+        exact(&[Some("Hello"), None], Config::default()).verify(),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_verify_reports_the_message_that_does_not_round_trip() {
+    // As if a hash collision had matched "world" against "there".
+    let collided = stored(vec![
+        Segment::Literal("Hello ".into()),
+        Segment::Reference {
+            message_idx: 0,
+            start: 6,
+            len: 5,
+        },
+    ]);
+    let err = collided.verify().unwrap_err();
+    assert_eq!(err, CopyForwardError::RoundTrip { message: 1 });
+    assert_eq!(
+        err.to_string(),
+        "message 1 does not render back to its input"
+    );
+}
+
+#[test]
+fn test_verify_rejects_references_outside_their_source() {
+    for reference in [
+        Segment::Reference {
+            message_idx: 0,
+            start: 6,
+            len: 50,
+        },
+        Segment::Reference {
+            message_idx: 7,
+            start: 0,
+            len: 5,
+        },
+    ] {
+        let broken = stored(vec![reference, Segment::Literal(" there".into())]);
+        assert!(broken.verify().is_err());
+    }
+    let past_end = stored(vec![Segment::Reference {
+        message_idx: 0,
+        start: 6,
+        len: 50,
+    }]);
+    assert_eq!(
+        past_end.verify(),
+        Err(CopyForwardError::OffsetOverflow {
+            message: 0,
+            offset: 56
+        })
+    );
+}
+
+#[test]
+fn test_verify_rejects_references_splitting_characters() {
+    let split = Stored {
+        originals: vec!["naïve".into(), "na".into()],
+        segments: vec![
+            vec![Segment::Literal("naïve".into())],
+            vec![Segment::Reference {
+                message_idx: 0,
+                start: 0,
+                len: 3,
+            }],
+        ],
+    };
+    assert_eq!(
+        split.verify(),
+        Err(CopyForwardError::NotCharBoundary {
+            message: 0,
+            offset: 3
+        })
+    );
+}
+
+#[test]
+fn test_try_render_message_reports_bad_references() {
+    let reference = |message_idx, start, len| Segment::Reference {
+        message_idx,
+        start,
+        len,
+    };
+    let cases = [
+        (
+            reference(0, 6, 50),
+            CopyForwardError::OffsetOverflow {
+                message: 0,
+                offset: 56,
+            },
+        ),
+        (
+            reference(0, usize::MAX, 2),
+            CopyForwardError::OffsetOverflow {
+                message: 0,
+                offset: usize::MAX,
+            },
+        ),
+        (
+            reference(7, 0, 5),
+            CopyForwardError::OffsetOverflow {
+                message: 7,
+                offset: 0,
+            },
+        ),
+    ];
+    for (reference, expected) in cases {
+        let broken = stored(vec![reference]);
+        assert_eq!(
+            broken.try_render_message(1, |_, _, _, text| text.to_string()),
+            Err(expected)
+        );
+    }
+    let split = Stored {
+        originals: vec!["naïve".into(), "a".into()],
+        segments: vec![
+            vec![Segment::Literal("naïve".into())],
+            vec![reference(0, 3, 1)],
+        ],
+    };
+    let err = split
+        .try_render_message(1, |_, _, _, text| text.to_string())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        CopyForwardError::NotCharBoundary {
+            message: 0,
+            offset: 3
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "byte offset 3 of message 0 is not a char boundary"
+    );
+    let model = exact(&["Hello world", "Hello world today"], Config::default());
+    assert_eq!(
+        model.try_render_message(1, |_, _, _, _| "[REF]".to_string()),
+        Ok("[REF] today".to_string())
+    );
+}
+
+#[test]
+fn test_verify_rejects_references_that_do_not_rebuild() {
+    let reference = |message_idx, start, len| Segment::Reference {
+        message_idx,
+        start,
+        len,
+    };
+    // Each renders right from the originals, but the self-reference copies
+    // text after its own position and the other names a later message.
+    let overlapping = Stored {
+        originals: vec!["Hello".into(), "abababab".into()],
+        segments: vec![
+            vec![Segment::Literal("Hello".into())],
+            vec![Segment::Literal("ab".into()), reference(1, 0, 6)],
+        ],
+    };
+    assert_eq!(
+        overlapping.verify(),
+        Err(CopyForwardError::RoundTrip { message: 1 })
+    );
+    let forward = Stored {
+        originals: vec!["Hello".into(), "Hello".into()],
+        segments: vec![
+            vec![reference(1, 0, 5)],
+            vec![Segment::Literal("Hello".into())],
+        ],
+    };
+    assert_eq!(
+        forward.verify(),
+        Err(CopyForwardError::RoundTrip { message: 0 })
+    );
+}
+
+#[test]
+fn test_compress_verified_checks_the_config_first() {
+    let config = Config {
+        min_match_len: 0,
+        ..Config::default()
+    };
+    let err = compress_verified(&["Hello world"], Algorithm::ALL[0], config).unwrap_err();
+    assert!(matches!(err, CopyForwardError::InvalidConfig { .. }));
+}